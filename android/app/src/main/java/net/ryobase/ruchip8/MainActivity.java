@@ -0,0 +1,97 @@
+package net.ryobase.ruchip8;
+
+import android.app.Activity;
+import android.content.Intent;
+import android.net.Uri;
+import android.os.Bundle;
+import android.webkit.ValueCallback;
+import android.webkit.WebChromeClient;
+import android.webkit.WebResourceRequest;
+import android.webkit.WebResourceResponse;
+import android.webkit.WebSettings;
+import android.webkit.WebView;
+import android.webkit.WebViewClient;
+
+import androidx.webkit.WebViewAssetLoader;
+
+/**
+ * The browser build of the emulator in a full screen WebView.
+ *
+ * The page and the wasm come from the app's assets, served under an
+ * https origin so ES modules and localStorage, where savestates go, work as
+ * they do on the web. A file input on the page opens the system file
+ * chooser.
+ */
+public class MainActivity extends Activity {
+    private static final String PAGE = "https://appassets.androidplatform.net/assets/index.html";
+    private static final int PICK_ROM = 1;
+
+    private WebView web;
+    /** Where the chosen ROM goes, while the file chooser is open. */
+    private ValueCallback<Uri[]> picking;
+
+    @Override
+    protected void onCreate(Bundle savedInstanceState) {
+        super.onCreate(savedInstanceState);
+        final WebViewAssetLoader assets = new WebViewAssetLoader.Builder()
+                .addPathHandler("/assets/", new WebViewAssetLoader.AssetsPathHandler(this))
+                .build();
+
+        web = new WebView(this);
+        WebSettings settings = web.getSettings();
+        settings.setJavaScriptEnabled(true);
+        settings.setDomStorageEnabled(true);
+        settings.setAllowFileAccess(false);
+        web.setWebViewClient(new WebViewClient() {
+            @Override
+            public WebResourceResponse shouldInterceptRequest(WebView view, WebResourceRequest request) {
+                return assets.shouldInterceptRequest(request.getUrl());
+            }
+        });
+        web.setWebChromeClient(new WebChromeClient() {
+            @Override
+            public boolean onShowFileChooser(WebView view, ValueCallback<Uri[]> callback, FileChooserParams params) {
+                if (picking != null) {
+                    picking.onReceiveValue(null);
+                }
+                picking = callback;
+                // ROMs have no MIME type of their own, any file will do.
+                Intent intent = new Intent(Intent.ACTION_OPEN_DOCUMENT);
+                intent.addCategory(Intent.CATEGORY_OPENABLE);
+                intent.setType("*/*");
+                startActivityForResult(intent, PICK_ROM);
+                return true;
+            }
+        });
+        setContentView(web);
+        web.loadUrl(PAGE);
+    }
+
+    @Override
+    protected void onActivityResult(int requestCode, int resultCode, Intent data) {
+        if (requestCode == PICK_ROM && picking != null) {
+            picking.onReceiveValue(WebChromeClient.FileChooserParams.parseResult(resultCode, data));
+            picking = null;
+            return;
+        }
+        super.onActivityResult(requestCode, resultCode, data);
+    }
+
+    @Override
+    protected void onPause() {
+        super.onPause();
+        web.onPause();
+    }
+
+    @Override
+    protected void onResume() {
+        super.onResume();
+        web.onResume();
+    }
+
+    @Override
+    protected void onDestroy() {
+        web.destroy();
+        super.onDestroy();
+    }
+}
@@ -0,0 +1,75 @@
+#[macro_use]
+extern crate criterion;
+extern crate ruchip8;
+
+use criterion::{black_box, Criterion};
+use ruchip8::{decode, decode_match, Chip8, Display};
+
+/// A loop mixing the instructions real ROMs spend most of their time in:
+/// loads, ALU ops, skips, a BCD/register dump, a draw and a jump back.
+static MIX: [u16; 16] = [
+    0x6005, // V0 = 5
+    0x7101, // V1 += 1
+    0x8014, // V0 += V1
+    0x8125, // V1 -= V2
+    0x8206, // V2 >>= 1
+    0x3000, // skip if V0 == 0
+    0x4000, // skip if V0 != 0
+    0x6300, // V3 = 0
+    0xA300, // I = 0x300
+    0xF333, // BCD V3
+    0xF265, // V0..V2 = [I]
+    0xA000, // I = font '0'
+    0xD015, // draw 5 rows at V0,V1
+    0x9010, // skip if V0 != V1
+    0x6300, // V3 = 0
+    0x1200, // jump back to the start
+];
+
+fn mix_rom() -> Vec<u8> {
+    MIX.iter().flat_map(|op| vec![(op >> 8) as u8, *op as u8]).collect()
+}
+
+fn execute_cycle(c: &mut Criterion) {
+    let mut chip = Chip8::new();
+    chip.load_rom(&mix_rom()).unwrap();
+
+    c.bench_function("execute_cycle mix", |b| {
+        b.iter(|| chip.execute_cycle().unwrap())
+    });
+}
+
+fn decoding(c: &mut Criterion) {
+    c.bench_function("decode mix table", |b| {
+        b.iter(|| for &op in MIX.iter() { black_box(decode(black_box(op))); })
+    });
+    c.bench_function("decode mix match", |b| {
+        b.iter(|| for &op in MIX.iter() { black_box(decode_match(black_box(op))); })
+    });
+}
+
+fn draw(c: &mut Criterion) {
+    let sprite = [0xFF; 15];
+    let mut display = Display::new();
+
+    c.bench_function("draw 8x15 aligned", |b| {
+        b.iter(|| display.draw(black_box(8), black_box(8), &sprite))
+    });
+    c.bench_function("draw 8x15 unaligned", |b| {
+        b.iter(|| display.draw(black_box(27), black_box(9), &sprite))
+    });
+    c.bench_function("draw 8x15 clipped", |b| {
+        b.iter(|| display.draw(black_box(60), black_box(25), &sprite))
+    });
+
+    let large = [0xA5; 32];
+    display.set_hires(true);
+    c.bench_function("draw 16x16 hires unaligned", |b| {
+        b.iter(|| display.draw_large(black_box(37), black_box(20), &large))
+    });
+    // Unpacks the frame, once every 60Hz tick.
+    c.bench_function("present hires", |b| b.iter(|| display.present()));
+}
+
+criterion_group!(benches, execute_cycle, decoding, draw);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+#[macro_use]
+extern crate criterion;
+extern crate ruchip8;
+
+use criterion::Criterion;
+use ruchip8::jit::Jit;
+use ruchip8::Chip8;
+
+/// Register work with a jump back every 16 instructions, the best case for
+/// compiled blocks.
+static ALU: [u16; 16] = [
+    0x6005, 0x7101, 0x8014, 0x8125, 0x8206, 0x8317, 0x843E, 0x8012,
+    0x8123, 0x8231, 0xA300, 0xF01E, 0xF129, 0x6300, 0x7F01, 0x1200,
+];
+
+fn rom() -> Vec<u8> {
+    ALU.iter().flat_map(|op| vec![(op >> 8) as u8, *op as u8]).collect()
+}
+
+fn interpreter_vs_jit(c: &mut Criterion) {
+    let mut chip = Chip8::new();
+    chip.load_rom(&rom()).unwrap();
+    c.bench_function("interpreter alu 1600", |b| {
+        b.iter(|| for _ in 0..1600 { chip.execute_cycle().unwrap() })
+    });
+
+    let mut chip = Chip8::new();
+    chip.load_rom(&rom()).unwrap();
+    let mut jit = Jit::new().unwrap();
+    c.bench_function("jit alu 1600", |b| {
+        b.iter(|| jit.run(&mut chip, 1600).unwrap())
+    });
+}
+
+criterion_group!(benches, interpreter_vs_jit);
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+//! An arcade cabinet with the machine on its screen, in 3D.
+//!
+//! `cargo run --example cabinet -- game.ch8` plays the ROM given from the
+//! keyboard, or, with none, shows the key pressed last.
+
+use std::env;
+use std::fs;
+
+use bevy::prelude::*;
+use bevy_ruchip8::{Chip8Machine, Chip8Plugin};
+
+/// Shows the key pressed last.
+const ROM: &[u8] = &[
+    0x00, 0xE0, // CLS
+    0xF0, 0x0A, // LD V0, K
+    0xF0, 0x29, // LD F, V0
+    0x61, 0x1C, // LD V1, 1C
+    0x62, 0x0D, // LD V2, 0D
+    0xD1, 0x25, // DRW V1, V2, 5
+    0x12, 0x00, // JP 200
+];
+
+fn setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let rom = match env::args().nth(1) {
+        Some(path) => fs::read(&path).unwrap_or_else(|e| panic!("cannot read {}: {}", path, e)),
+        None => ROM.to_vec(),
+    };
+    let machine = Chip8Machine::new(&rom, &mut images).expect("the ROM is too big");
+
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(Cuboid::new(1.2, 2.0, 1.0)),
+        material: materials.add(Color::srgb(0.15, 0.1, 0.3)),
+        transform: Transform::from_xyz(0.0, 0.0, -0.01),
+        ..default()
+    });
+    // The screen, 2:1 like the machine's, lit by itself.
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(Rectangle::new(1.0, 0.5)),
+        material: materials.add(StandardMaterial {
+            base_color_texture: Some(machine.image.clone()),
+            unlit: true,
+            ..default()
+        }),
+        transform: Transform::from_xyz(0.0, 0.4, 0.5),
+        ..default()
+    });
+    commands.spawn(PointLightBundle { transform: Transform::from_xyz(2.0, 3.0, 3.0), ..default() });
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(0.8, 0.8, 2.2).looking_at(Vec3::new(0.0, 0.3, 0.0), Vec3::Y),
+        ..default()
+    });
+    commands.insert_resource(machine);
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, Chip8Plugin))
+        .add_systems(Startup, setup)
+        .run();
+}
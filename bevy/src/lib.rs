@@ -0,0 +1,199 @@
+//! ruchip8 inside a Bevy game, an arcade machine in the game world say.
+//!
+//! The machine is a resource, `Chip8Machine`, and its screen an `Image`
+//! that goes on anything an image goes on, a sprite or the material of a
+//! cabinet's screen mesh. `Chip8Plugin` runs it at 60 frames a second of
+//! game time and keeps the image up to date:
+//!
+//! ```no_run
+//! use bevy::prelude::*;
+//! use bevy_ruchip8::{Chip8Machine, Chip8Plugin};
+//!
+//! fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+//!     let rom = std::fs::read("pong.ch8").unwrap();
+//!     let machine = Chip8Machine::new(&rom, &mut images).unwrap();
+//!     commands.spawn(Camera2dBundle::default());
+//!     commands.spawn(SpriteBundle {
+//!         texture: machine.image.clone(),
+//!         sprite: Sprite { custom_size: Some(Vec2::new(512.0, 256.0)), ..default() },
+//!         ..default()
+//!     });
+//!     commands.insert_resource(machine);
+//! }
+//!
+//! App::new().add_plugins((DefaultPlugins, Chip8Plugin)).add_systems(Startup, setup).run();
+//! ```
+//!
+//! With the `keyboard` feature, on by default, the keyboard plays the
+//! machine while `Chip8Machine::keyboard` is set, the left hand block of a
+//! QWERTY keyboard onto the hex keypad as the native build maps it. Games
+//! that take input their own way, or only when the player stands at the
+//! machine, call `key_down` and `key_up` instead. Removing the resource
+//! turns the machine off.
+
+extern crate bevy;
+extern crate ruchip8;
+
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+use ruchip8::palette::Palette;
+use ruchip8::{Chip8, HIRES_HEIGHT, HIRES_WIDTH, KEY_COUNT};
+
+/// Seconds in a frame of the machine.
+const FRAME: f32 = 1.0 / 60.0;
+
+/// How far the machine catches up after the game stalls, in seconds.
+const MAX_BEHIND: f32 = 4.0 * FRAME;
+
+/// Bevy key codes in the order of the keypad keys they stand for.
+#[cfg(feature = "keyboard")]
+const KEYMAP: [KeyCode; KEY_COUNT] = [
+    KeyCode::KeyX,
+    KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3,
+    KeyCode::KeyQ, KeyCode::KeyW, KeyCode::KeyE,
+    KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD,
+    KeyCode::KeyZ, KeyCode::KeyC,
+    KeyCode::Digit4, KeyCode::KeyR, KeyCode::KeyF, KeyCode::KeyV,
+];
+
+/// A CHIP-8 machine with a ROM loaded and the image it draws on.
+#[derive(Resource)]
+pub struct Chip8Machine {
+    pub chip: Chip8,
+    /// The screen, 128x64 in both resolutions, lores pixels doubled, in
+    /// RGBA and sampled nearest so the pixels stay sharp.
+    pub image: Handle<Image>,
+    /// The colors the image is drawn in.
+    pub palette: Palette,
+    /// Stops the machine, and its timers, while set.
+    pub paused: bool,
+    /// Whether the keyboard plays the machine.
+    #[cfg(feature = "keyboard")]
+    pub keyboard: bool,
+    /// What stopped the program, if it faulted. The machine stays put
+    /// until `reset`.
+    pub error: Option<ruchip8::Error>,
+    rom: Vec<u8>,
+    /// Game time not run yet, in seconds.
+    behind: f32,
+    /// The palette the image is in, `None` until it is first drawn.
+    drawn: Option<Palette>,
+}
+
+impl Chip8Machine {
+    /// Loads `rom` into a new machine and adds the image it draws on to
+    /// `images`. Fails if the ROM does not fit.
+    pub fn new(rom: &[u8], images: &mut Assets<Image>) -> Result<Chip8Machine, ruchip8::Error> {
+        let mut chip = Chip8::new();
+        chip.load_rom(rom)?;
+        let size = Extent3d { width: HIRES_WIDTH as u32, height: HIRES_HEIGHT as u32, depth_or_array_layers: 1 };
+        let mut image = Image::new_fill(size, TextureDimension::D2, &[0, 0, 0, 0xFF], TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default());
+        image.sampler = ImageSampler::nearest();
+        Ok(Chip8Machine {
+            chip,
+            image: images.add(image),
+            palette: Palette::default(),
+            paused: false,
+            #[cfg(feature = "keyboard")]
+            keyboard: true,
+            error: None,
+            rom: rom.to_vec(),
+            behind: 0.0,
+            drawn: None,
+        })
+    }
+
+    /// Presses keypad key `k`, 0 to 15. Presses and releases are queued,
+    /// so a tap between two frames still counts.
+    pub fn key_down(&mut self, k: u8) {
+        if (k as usize) < KEY_COUNT {
+            self.chip.push_key(k, true);
+        }
+    }
+
+    /// Releases keypad key `k`.
+    pub fn key_up(&mut self, k: u8) {
+        if (k as usize) < KEY_COUNT {
+            self.chip.push_key(k, false);
+        }
+    }
+
+    /// Whether the machine is beeping, for the game to play a sound.
+    pub fn sound(&self) -> bool {
+        self.chip.sound_timer() > 0
+    }
+
+    /// Starts the ROM over on cleared memory, after a fault too.
+    pub fn reset(&mut self) {
+        self.chip.hard_reset(&self.rom).expect("the ROM fitted before");
+        self.error = None;
+        self.drawn = None;
+    }
+
+    /// Draws the screen on `image`.
+    fn draw(&mut self, image: &mut Image) {
+        self.chip.display().frame().scaled_rgba(&self.palette, &mut image.data);
+        self.drawn = Some(self.palette);
+    }
+}
+
+/// Runs the `Chip8Machine` resource, when there is one, every `Update`.
+pub struct Chip8Plugin;
+
+impl Plugin for Chip8Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, run);
+        #[cfg(feature = "keyboard")]
+        app.add_systems(Update, keyboard.before(run));
+    }
+}
+
+/// Runs as many frames as game time has passed, drawing the last.
+fn run(time: Res<Time>, machine: Option<ResMut<Chip8Machine>>, mut images: ResMut<Assets<Image>>) {
+    let Some(mut machine) = machine else { return };
+    let machine = &mut *machine;
+    if !machine.paused && machine.error.is_none() {
+        machine.behind = (machine.behind + time.delta_seconds()).min(MAX_BEHIND);
+        let mut ready = false;
+        while machine.behind >= FRAME {
+            machine.behind -= FRAME;
+            let keys = machine.chip.keys();
+            match machine.chip.run_frame(keys) {
+                Ok(output) => ready |= output.frame_ready,
+                Err(e) => {
+                    error!("CHIP-8 machine stopped: {}", e);
+                    machine.error = Some(e);
+                    break;
+                },
+            }
+        }
+        if ready {
+            machine.drawn = None;
+        }
+    }
+    if machine.drawn != Some(machine.palette) {
+        if let Some(image) = images.get_mut(&machine.image) {
+            machine.draw(image);
+        }
+    }
+}
+
+/// Feeds the keyboard to the machine.
+#[cfg(feature = "keyboard")]
+fn keyboard(input: Res<ButtonInput<KeyCode>>, machine: Option<ResMut<Chip8Machine>>) {
+    let Some(mut machine) = machine else { return };
+    if !machine.keyboard {
+        return;
+    }
+    for (key, &code) in KEYMAP.iter().enumerate() {
+        if input.just_pressed(code) {
+            machine.key_down(key as u8);
+        }
+        if input.just_released(code) {
+            machine.key_up(key as u8);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! ruchip8 on a Raspberry Pi Pico.
+//!
+//! The screen is a 128x64 SSD1306 on I2C0 (SDA on GP4, SCL on GP5), each
+//! CHIP-8 pixel drawn as a 2x2 block. The keypad is a 4x4 matrix with its
+//! rows on GP6-GP9 and its columns on GP10-GP13. The onboard LED lights up
+//! when the program stops on an error.
+
+#![no_std]
+#![no_main]
+
+use embedded_graphics_core::pixelcolor::BinaryColor;
+use embedded_hal::digital::{InputPin, OutputPin};
+use panic_halt as _;
+use rp_pico::entry;
+use rp_pico::hal::{self, fugit::RateExtU32, gpio, pac, rosc::RingOscillator};
+use ssd1306::mode::BufferedGraphicsMode;
+use ssd1306::prelude::*;
+use ssd1306::{I2CDisplayInterface, Ssd1306};
+
+use ruchip8::graphics::Renderer;
+use ruchip8::host::{Clock, Host, Keypad, Screen};
+use ruchip8::{Chip8, Display};
+
+/// Shows the key pressed last. Swap in `include_bytes!("game.ch8")`.
+const ROM: &[u8] = &[
+    0x00, 0xE0, // CLS
+    0xF0, 0x0A, // LD V0, K
+    0xF0, 0x29, // LD F, V0
+    0x61, 0x1C, // LD V1, 1C
+    0x62, 0x0D, // LD V2, 0D
+    0xD1, 0x25, // DRW V1, V2, 5
+    0x12, 0x00, // JP 200
+];
+
+struct Oled<DI> {
+    driver: Ssd1306<DI, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>,
+    renderer: Renderer<BinaryColor>,
+}
+
+impl<DI: WriteOnlyDataCommand> Screen for Oled<DI> {
+    fn present(&mut self, display: &Display) {
+        // A missed frame is not worth stopping for.
+        let _ = self.renderer.draw(display, &mut self.driver);
+        let _ = self.driver.flush();
+    }
+}
+
+type RowPin = gpio::Pin<gpio::DynPinId, gpio::FunctionSioOutput, gpio::PullDown>;
+type ColumnPin = gpio::Pin<gpio::DynPinId, gpio::FunctionSioInput, gpio::PullUp>;
+
+/// The usual COSMAC VIP layout.
+const LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+struct Matrix {
+    rows: [RowPin; 4],
+    columns: [ColumnPin; 4],
+}
+
+impl Keypad for Matrix {
+    fn keys(&mut self) -> u16 {
+        let mut keys = 0;
+        for (row, pin) in self.rows.iter_mut().enumerate() {
+            let _ = pin.set_low();
+            // Let the line settle before reading the columns.
+            cortex_m::asm::delay(100);
+            for (column, input) in self.columns.iter_mut().enumerate() {
+                if input.is_low().unwrap_or(false) {
+                    keys |= 1 << LAYOUT[row][column];
+                }
+            }
+            let _ = pin.set_high();
+        }
+        keys
+    }
+}
+
+struct Micros(hal::Timer);
+
+impl Clock for Micros {
+    fn micros(&mut self) -> u64 {
+        self.0.get_counter().ticks()
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+    let clocks = hal::clocks::init_clocks_and_plls(
+        rp_pico::XOSC_CRYSTAL_FREQ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    ).ok().unwrap();
+
+    let sio = hal::Sio::new(pac.SIO);
+    let pins = rp_pico::Pins::new(pac.IO_BANK0, pac.PADS_BANK0, sio.gpio_bank0, &mut pac.RESETS);
+
+    let sda: gpio::Pin<_, gpio::FunctionI2C, gpio::PullUp> = pins.gpio4.reconfigure();
+    let scl: gpio::Pin<_, gpio::FunctionI2C, gpio::PullUp> = pins.gpio5.reconfigure();
+    let i2c = hal::I2C::i2c0(pac.I2C0, sda, scl, 400.kHz(), &mut pac.RESETS, &clocks.system_clock);
+    let mut oled = Ssd1306::new(I2CDisplayInterface::new(i2c), DisplaySize128x64, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode();
+    oled.init().unwrap();
+
+    let keypad = Matrix {
+        rows: [
+            pins.gpio6.into_push_pull_output().into_dyn_pin(),
+            pins.gpio7.into_push_pull_output().into_dyn_pin(),
+            pins.gpio8.into_push_pull_output().into_dyn_pin(),
+            pins.gpio9.into_push_pull_output().into_dyn_pin(),
+        ],
+        columns: [
+            pins.gpio10.into_pull_up_input().into_dyn_pin(),
+            pins.gpio11.into_pull_up_input().into_dyn_pin(),
+            pins.gpio12.into_pull_up_input().into_dyn_pin(),
+            pins.gpio13.into_pull_up_input().into_dyn_pin(),
+        ],
+    };
+    let mut led = pins.led.into_push_pull_output();
+
+    // The ring oscillator jitters enough to seed CXNN.
+    let rosc = RingOscillator::new(pac.ROSC).initialize();
+    let seed = (0..64).fold(0u64, |seed, _| seed << 1 | rosc.get_random_bit() as u64);
+
+    let mut chip = Chip8::new();
+    chip.set_seed(seed);
+    chip.load_rom(ROM).unwrap();
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    let screen = Oled {
+        driver: oled,
+        renderer: Renderer { scale: 2, ..Renderer::new(BinaryColor::On, BinaryColor::Off) },
+    };
+    let mut host = Host::new(chip, screen, keypad, Micros(timer));
+
+    while host.poll().is_ok() {}
+    let _ = led.set_high();
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
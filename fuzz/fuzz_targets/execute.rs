@@ -0,0 +1,44 @@
+//! Runs an arbitrary memory image for an arbitrary number of cycles.
+//!
+//! The first two bytes of the input pick the memory size, 4K or the 64K of
+//! XO-CHIP in the top bit, and the cycle count in the rest, which is copied
+//! over the start of memory (font included). Jumps in 64K reach code at the
+//! top of the address space. The machine must report every problem through
+//! `Error` rather than panicking.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate ruchip8;
+
+use ruchip8::{Chip8, MEMORY_SIZE, XO_MEMORY_SIZE};
+
+/// Keeps a single run short enough for the fuzzer to stay productive.
+const MAX_CYCLES: usize = 4096;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let xo = data[0] & 0x80 != 0;
+    let cycles = (data[0] as usize & 0x7F) << 8 | data[1] as usize;
+    let size = if xo { XO_MEMORY_SIZE } else { MEMORY_SIZE };
+    let image = &data[2..data.len().min(size + 2)];
+
+    let mut chip = if xo {
+        Chip8::builder().memory_size(XO_MEMORY_SIZE).build().unwrap()
+    } else {
+        Chip8::new()
+    };
+    chip.memory_mut()[..image.len()].copy_from_slice(image);
+
+    for _ in 0..cycles.min(MAX_CYCLES) {
+        if chip.execute_cycle().is_err() {
+            break;
+        }
+        // Release any FX0A wait so execution keeps going.
+        if chip.is_waiting_for_key() {
+            chip.set_key(cycles as u8 & 0xF, true);
+        }
+    }
+});
@@ -0,0 +1,161 @@
+//! ruchip8 on macroquad, one small program for Windows, macOS, Linux and
+//! the browser.
+//!
+//! `ruchip8-macroquad GAME.ch8` plays the ROM in a window, `rom.ch8` when
+//! none is given; in the browser, where there are no arguments, `rom.ch8`
+//! is fetched from next to the page. The screen is a texture drawn as
+//! large as the window takes at 2:1, with black bars around it, so any
+//! window size works.
+//!
+//! The keys map the usual way, 1234 QWER ASDF ZXCV onto the hex keypad.
+//! Escape quits where there is something to quit.
+
+use std::env;
+
+use macroquad::audio::{self, PlaySoundParams, Sound};
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+
+use ruchip8::audio::BEEP_FREQUENCY;
+use ruchip8::palette::Palette;
+use ruchip8::{Chip8, HIRES_HEIGHT, HIRES_WIDTH};
+
+/// Seconds in a frame of the machine.
+const FRAME: f32 = 1.0 / 60.0;
+
+/// How far the machine catches up after the window stalls, in seconds.
+const MAX_BEHIND: f32 = 4.0 * FRAME;
+
+/// macroquad key codes in the order of the keypad keys they stand for.
+const KEYMAP: [KeyCode; 16] = [
+    KeyCode::X,
+    KeyCode::Key1, KeyCode::Key2, KeyCode::Key3,
+    KeyCode::Q, KeyCode::W, KeyCode::E,
+    KeyCode::A, KeyCode::S, KeyCode::D,
+    KeyCode::Z, KeyCode::C,
+    KeyCode::Key4, KeyCode::R, KeyCode::F, KeyCode::V,
+];
+
+const SAMPLE_RATE: u32 = 44100;
+
+/// A tenth of a second of the beep as a WAV, a whole number of periods
+/// so it loops without a click.
+fn beep() -> Vec<u8> {
+    let samples = SAMPLE_RATE / 10;
+    let mut wav = Vec::with_capacity(44 + samples as usize * 2);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + samples * 2).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, mono, 16 bits.
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(samples * 2).to_le_bytes());
+    for n in 0..samples {
+        let high = (n as f32 * BEEP_FREQUENCY / SAMPLE_RATE as f32).fract() < 0.5;
+        let sample: i16 = if high { 4000 } else { -4000 };
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// Shows `message` until the window closes.
+async fn fail(message: String) {
+    loop {
+        clear_background(BLACK);
+        draw_text(&message, 20.0, 40.0, 30.0, WHITE);
+        if is_key_pressed(KeyCode::Escape) {
+            return;
+        }
+        next_frame().await
+    }
+}
+
+fn window() -> Conf {
+    Conf {
+        window_title: "ruchip8".to_owned(),
+        window_width: HIRES_WIDTH as i32 * 8,
+        window_height: HIRES_HEIGHT as i32 * 8,
+        ..Default::default()
+    }
+}
+
+#[macroquad::main(window)]
+async fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| "rom.ch8".to_owned());
+    let rom = match macroquad::file::load_file(&path).await {
+        Ok(rom) => rom,
+        Err(e) => return fail(format!("cannot read {}: {}", path, e)).await,
+    };
+    let mut chip = Chip8::new();
+    if let Err(e) = chip.load_rom(&rom) {
+        return fail(format!("{}: {}", path, e)).await;
+    }
+    chip.set_seed((date::now() * 1000.0) as u64);
+    let palette = Palette::default();
+    // Some browsers have no audio until the page is clicked, play silently.
+    let beep: Option<Sound> = audio::load_sound_from_bytes(&beep()).await.ok();
+    let mut beeping = false;
+
+    let mut pixels = vec![0; HIRES_WIDTH * HIRES_HEIGHT * 4];
+    chip.display().frame().scaled_rgba(&palette, &mut pixels);
+    let texture = Texture2D::from_rgba8(HIRES_WIDTH as u16, HIRES_HEIGHT as u16, &pixels);
+    texture.set_filter(FilterMode::Nearest);
+
+    let mut behind = 0.0;
+    loop {
+        if is_key_pressed(KeyCode::Escape) {
+            return;
+        }
+        for (key, &code) in KEYMAP.iter().enumerate() {
+            if is_key_pressed(code) {
+                chip.push_key(key as u8, true);
+            }
+            if is_key_released(code) {
+                chip.push_key(key as u8, false);
+            }
+        }
+
+        behind = (behind + get_frame_time()).min(MAX_BEHIND);
+        let mut ready = false;
+        while behind >= FRAME {
+            behind -= FRAME;
+            let keys = chip.keys();
+            match chip.run_frame(keys) {
+                Ok(output) => ready |= output.frame_ready,
+                Err(e) => {
+                    if let Some(beep) = &beep {
+                        audio::stop_sound(beep);
+                    }
+                    return fail(e.to_string()).await;
+                },
+            }
+        }
+        if ready {
+            chip.display().frame().scaled_rgba(&palette, &mut pixels);
+            texture.update_from_bytes(HIRES_WIDTH as u32, HIRES_HEIGHT as u32, &pixels);
+        }
+        if let Some(beep) = &beep {
+            let sound = chip.sound_timer() > 0;
+            if sound && !beeping {
+                audio::play_sound(beep, PlaySoundParams { looped: true, volume: 0.5 });
+            } else if !sound && beeping {
+                audio::stop_sound(beep);
+            }
+            beeping = sound;
+        }
+
+        // As large as fits at 2:1, centered.
+        let scale = (screen_width() / HIRES_WIDTH as f32).min(screen_height() / HIRES_HEIGHT as f32);
+        let size = vec2(HIRES_WIDTH as f32 * scale, HIRES_HEIGHT as f32 * scale);
+        clear_background(BLACK);
+        draw_texture_ex(&texture, (screen_width() - size.x) / 2.0, (screen_height() - size.y) / 2.0, WHITE,
+            DrawTextureParams { dest_size: Some(size), ..Default::default() });
+        next_frame().await
+    }
+}
@@ -0,0 +1,172 @@
+//! HTTP control API.
+//!
+//! | Method | Path                        | Action                                 |
+//! |--------|-----------------------------|----------------------------------------|
+//! | GET    | `/registers`                | machine state as JSON                  |
+//! | GET    | `/memory?addr=A&len=N`      | N bytes of memory from A as JSON       |
+//! | GET    | `/stats`                    | frame, instruction and cycle counts    |
+//! | GET    | `/screen.png?scale=S`       | the screen as a PNG, S times larger    |
+//! | GET    | `/screen.pbm?scale=S`       | the same as plain PBM text             |
+//! | GET    | `/screen.xbm?scale=S`       | the same as XBM, a C array             |
+//! | POST   | `/rom`                      | load the request body as a new ROM     |
+//! | POST   | `/reset`                    | restart the program, keeping memory    |
+//! | POST   | `/hard-reset`               | restart the ROM on cleared memory      |
+//! | POST   | `/pause`, `/resume`         | stop or restart the frame loop         |
+//! | POST   | `/step?n=N`                 | pause, then execute N instructions     |
+//!
+//! Numbers may be given in decimal or as `0x` prefixed hex. State changing
+//! requests answer with the resulting machine state.
+
+use std::io;
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use ruchip8::screenshot;
+use json::Json;
+use session::{Remote, Session};
+
+/// Default screenshot magnification.
+const SCREEN_SCALE: usize = 10;
+
+type HttpResponse = Response<io::Cursor<Vec<u8>>>;
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes(&b"Content-Type"[..], value.as_bytes()).expect("valid header")
+}
+
+fn json(status: u16, body: String) -> HttpResponse {
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type("application/json"))
+}
+
+fn error(status: u16, message: &str) -> HttpResponse {
+    json(status, Json::object(vec![("error", message.into())]).to_string())
+}
+
+fn parse_number(text: &str) -> Option<usize> {
+    if text.starts_with("0x") || text.starts_with("0X") {
+        usize::from_str_radix(&text[2..], 16).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// Looks up a numeric query parameter, falling back to `default` when absent.
+fn query_param(query: &str, name: &str, default: usize) -> Result<usize, String> {
+    for pair in query.split('&') {
+        let mut kv = pair.splitn(2, '=');
+        if kv.next() == Some(name) {
+            let value = kv.next().unwrap_or("");
+            return parse_number(value).ok_or_else(|| format!("invalid {} '{}'", name, value));
+        }
+    }
+    Ok(default)
+}
+
+fn handle(request: &mut Request, session: &mut Session) -> Result<HttpResponse, HttpResponse> {
+    let url = request.url().to_owned();
+    let mut parts = url.splitn(2, '?');
+    let path = parts.next().unwrap_or("");
+    let query = parts.next().unwrap_or("");
+    let bad_request = |e: String| error(400, &e);
+
+    match (request.method(), path) {
+        (&Method::Get, "/registers") => {},
+        (&Method::Get, "/memory") => {
+            let addr = query_param(query, "addr", 0).map_err(bad_request)?;
+            let len = query_param(query, "len", 16).map_err(bad_request)?;
+            if addr.checked_add(len).is_none_or(|end| end > session.chip.memory().len()) {
+                return Err(error(400, "range outside of memory"));
+            }
+            let bytes: Vec<Json> = session.chip.memory()[addr..addr + len]
+                .iter().map(|&b| (b as u64).into()).collect();
+            return Ok(json(200, Json::object(vec![("addr", addr.into()), ("bytes", bytes.into())]).to_string()));
+        },
+        (&Method::Get, "/stats") => return Ok(json(200, session.stats_json())),
+        (&Method::Get, "/screen.png") => {
+            let scale = query_param(query, "scale", SCREEN_SCALE).map_err(bad_request)?;
+            let png = screenshot::to_png_in(session.chip.display(), scale.min(64), &session.palette);
+            return Ok(Response::from_data(png).with_header(content_type("image/png")));
+        },
+        (&Method::Get, "/screen.pbm") => {
+            let scale = query_param(query, "scale", SCREEN_SCALE).map_err(bad_request)?;
+            let pbm = screenshot::to_pbm(session.chip.display(), scale.min(64));
+            return Ok(Response::from_string(pbm).with_header(content_type("image/x-portable-bitmap")));
+        },
+        (&Method::Get, "/screen.xbm") => {
+            let scale = query_param(query, "scale", SCREEN_SCALE).map_err(bad_request)?;
+            let xbm = screenshot::to_xbm(session.chip.display(), scale.min(64), "screen");
+            return Ok(Response::from_string(xbm).with_header(content_type("image/x-xbitmap")));
+        },
+        (&Method::Post, "/rom") => {
+            let mut rom = Vec::new();
+            request.as_reader().read_to_end(&mut rom)
+                .map_err(|e| error(400, &e.to_string()))?;
+            session.load(&rom).map_err(|e| error(400, &e.to_string()))?;
+            session.rom_path = None;
+            session.patches.clear();
+        },
+        (&Method::Post, "/reset") => session.soft_reset(),
+        (&Method::Post, "/hard-reset") => session.hard_reset(),
+        (&Method::Post, "/pause") => session.paused = true,
+        (&Method::Post, "/resume") => session.paused = false,
+        (&Method::Post, "/step") => {
+            let n = query_param(query, "n", 1).map_err(bad_request)?;
+            session.paused = true;
+            if session.step(n.min(u32::MAX as usize) as u32).is_err() {
+                return Err(json(409, session.state_json()));
+            }
+        },
+        (_, "/registers") | (_, "/memory") | (_, "/stats") | (_, "/screen.png") | (_, "/screen.pbm") |
+        (_, "/screen.xbm") | (_, "/rom") |
+        (_, "/reset") | (_, "/hard-reset") | (_, "/pause") | (_, "/resume") | (_, "/step") =>
+            return Err(error(405, "method not allowed")),
+        _ => return Err(error(404, "not found")),
+    }
+
+    Ok(json(200, session.state_json()))
+}
+
+pub struct ApiServer {
+    server: Server,
+}
+
+impl ApiServer {
+    /// Listens on localhost only, the API has no authentication.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let server = Server::http(("127.0.0.1", port))
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        info!("listening on http://127.0.0.1:{}", port);
+        Ok(ApiServer { server })
+    }
+}
+
+impl Remote for ApiServer {
+    fn poll(&mut self, session: &mut Session) {
+        while let Ok(Some(mut request)) = self.server.try_recv() {
+            let response = match handle(&mut request, session) {
+                Ok(response) | Err(response) => response,
+            };
+            if let Err(e) = request.respond(response) {
+                warn!("failed to respond: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_numbers_from_the_query() {
+        assert_eq!(query_param("addr=0x200&len=16", "len", 1), Ok(16));
+        assert_eq!(query_param("addr=0x200&len=16", "addr", 0), Ok(0x200));
+        assert_eq!(query_param("", "n", 1), Ok(1));
+        assert_eq!(query_param("n=4294967296", "n", 1), Ok(1 << 32));
+        assert_eq!(query_param("n=", "n", 1), Err("invalid n ''".to_owned()));
+        assert_eq!(query_param("n=0xZ", "n", 1), Err("invalid n '0xZ'".to_owned()));
+        assert_eq!(query_param("n=-1", "n", 1), Err("invalid n '-1'".to_owned()));
+    }
+}
@@ -0,0 +1,214 @@
+//! Sound synthesis for audio backends.
+//!
+//! `Synth` turns the machine's sound state into samples and is meant to be
+//! called from an audio callback with the latest machine. While the sound
+//! timer runs it plays the XO-CHIP pattern buffer as a 1 bit waveform read
+//! at the FX3A rate, or a plain square wave beep when the program never
+//! loaded a pattern.
+//!
+//! The waveform position carries over from one call to the next and pitch
+//! changes only change how fast it moves, so notes glide without clicks.
+//! Each sample averages the pattern bits it covers, which smooths bit
+//! edges and keeps high rates from aliasing.
+//!
+//! Sounds fade in and out over `Synth::ramp` instead of popping on and off
+//! at full volume, and play for at least `Synth::min_beep`, so a ROM
+//! setting the sound timer to 1 again and again is heard as beeps rather
+//! than clicks. Setting both to 0 gives the bare square edges and timer
+//! length of a real machine.
+//!
+//! `Latency` picks how far ahead of the speaker a backend schedules the
+//! sound: a short lead answers key presses sooner, a long one keeps a slow
+//! machine from running dry and crackling.
+
+use chip8::Chip8;
+
+/// Size of the XO-CHIP audio pattern buffer, in bytes.
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+/// The pitch a machine starts with, playing the pattern at 4000 bits a
+/// second.
+pub const DEFAULT_PITCH: u8 = 64;
+/// Frequency of the beep played without a pattern by default, in Hz.
+pub const BEEP_FREQUENCY: f32 = 440.0;
+
+/// How long sounds take to fade in and out by default, in seconds.
+pub const RAMP: f32 = 0.002;
+/// The shortest sound played by default, in seconds, two frames.
+pub const MIN_BEEP: f32 = 2.0 / 60.0;
+
+/// Bits in the pattern buffer.
+const PATTERN_BITS: f32 = (AUDIO_PATTERN_SIZE * 8) as f32;
+
+/// 2^(n/48) for n in 0..48, a pitch step being a 48th of an octave, in
+/// 16.16 fixed point.
+const STEPS: [u32; 48] = [
+    65536, 66489, 67456, 68438, 69433, 70443, 71468, 72507,
+    73562, 74632, 75717, 76819, 77936, 79069, 80220, 81386,
+    82570, 83771, 84990, 86226, 87480, 88752, 90043, 91353,
+    92682, 94030, 95398, 96785, 98193, 99621, 101070, 102540,
+    104032, 105545, 107080, 108638, 110218, 111821, 113448, 115098,
+    116772, 118470, 120194, 121942, 123715, 125515, 127341, 129193,
+];
+
+/// The pattern playback rate for a pitch, 4000 * 2^((pitch - 64) / 48)
+/// bits a second.
+pub fn playback_rate(pitch: u8) -> f32 {
+    let steps = pitch as i32 - DEFAULT_PITCH as i32;
+    let (octaves, step) = (steps.div_euclid(48), steps.rem_euclid(48));
+    let rate = 4000.0 * STEPS[step as usize] as f32 / 65536.0;
+    if octaves >= 0 {
+        rate * (1u32 << octaves) as f32
+    } else {
+        rate / (1u32 << -octaves) as f32
+    }
+}
+
+/// How far ahead of the speaker the audio runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Latency {
+    /// Around 5ms, for rhythm games on a fast machine.
+    Low,
+    /// Around 20ms, fine for most games and machines.
+    #[default]
+    Balanced,
+    /// Around 50ms, for machines that crackle otherwise.
+    Safe,
+    /// A target in milliseconds.
+    Millis(u32),
+}
+
+impl Latency {
+    /// Parses `low`, `balanced`, `safe` or milliseconds.
+    pub fn parse(text: &str) -> Option<Latency> {
+        match text {
+            "low" => Some(Latency::Low),
+            "balanced" => Some(Latency::Balanced),
+            "safe" => Some(Latency::Safe),
+            _ => text.parse().ok().filter(|&ms| ms > 0).map(Latency::Millis),
+        }
+    }
+
+    pub fn millis(&self) -> u32 {
+        match *self {
+            Latency::Low => 5,
+            Latency::Balanced => 20,
+            Latency::Safe => 50,
+            Latency::Millis(ms) => ms,
+        }
+    }
+}
+
+/// Renders the machine's sound into samples.
+pub struct Synth {
+    sample_rate: f32,
+    /// Position in the waveform, in pattern bits or beep periods.
+    phase: f32,
+    /// Peak amplitude of the samples, between 0 and 1.
+    pub volume: f32,
+    /// Frequency of the beep played without a pattern, in Hz.
+    pub frequency: f32,
+    /// Seconds a sound takes to fade in or out, 0 to start and stop at
+    /// once.
+    pub ramp: f32,
+    /// Seconds a sound plays at least, however short the sound timer.
+    pub min_beep: f32,
+    /// How far the sound has faded in, from 0 to 1.
+    gain: f32,
+    /// Samples the sound plays for at least, whatever the sound timer.
+    hold: u32,
+    /// Whether the sound timer ran as of the last call.
+    sounding: bool,
+}
+
+impl Synth {
+    pub fn new(sample_rate: u32) -> Self {
+        Synth {
+            sample_rate: sample_rate as f32,
+            phase: 0.0,
+            volume: 0.25,
+            frequency: BEEP_FREQUENCY,
+            ramp: RAMP,
+            min_beep: MIN_BEEP,
+            gain: 0.0,
+            hold: 0,
+            sounding: false,
+        }
+    }
+
+    /// Whether `render` has anything to play for `chip`, the sound timer
+    /// running or a sound still fading out.
+    pub fn is_playing(&self, chip: &Chip8) -> bool {
+        chip.sound_timer() > 0 || self.gain > 0.0 || self.hold > 0
+    }
+
+    /// Fills `out` with the next samples, between `-volume` and `volume`.
+    /// Silence is all zeroes.
+    pub fn render(&mut self, chip: &Chip8, out: &mut [f32]) {
+        let sounding = chip.sound_timer() > 0;
+        if sounding && !self.sounding {
+            self.hold = (self.min_beep * self.sample_rate) as u32;
+        }
+        self.sounding = sounding;
+        if !self.is_playing(chip) {
+            for sample in out.iter_mut() {
+                *sample = 0.0;
+            }
+            // The next sound starts at the beginning of its waveform.
+            self.phase = 0.0;
+            return;
+        }
+
+        let fade = if self.ramp > 0.0 { 1.0 / (self.ramp * self.sample_rate) } else { 1.0 };
+        for sample in out.iter_mut() {
+            let on = sounding || self.hold > 0;
+            self.hold = self.hold.saturating_sub(1);
+            self.gain = if on { (self.gain + fade).min(1.0) } else { (self.gain - fade).max(0.0) };
+            if self.gain == 0.0 {
+                *sample = 0.0;
+                self.phase = 0.0;
+                continue;
+            }
+            *sample = self.gain * self.volume * self.wave(chip);
+        }
+    }
+
+    /// The next sample of the waveform at full volume, between -1 and 1.
+    fn wave(&mut self, chip: &Chip8) -> f32 {
+        match chip.audio_pattern() {
+            Some(pattern) => {
+                let step = playback_rate(chip.pitch()) / self.sample_rate;
+                let level = coverage(pattern, self.phase, step);
+                self.phase = (self.phase + step) % PATTERN_BITS;
+                2.0 * level - 1.0
+            },
+            None => {
+                self.phase %= 1.0;
+                let level = if self.phase < 0.5 {1.0} else {-1.0};
+                self.phase += self.frequency / self.sample_rate;
+                level
+            },
+        }
+    }
+}
+
+/// The share of set bits in the `len` bits of `pattern` from `start`.
+fn coverage(pattern: &[u8; AUDIO_PATTERN_SIZE], start: f32, len: f32) -> f32 {
+    let bit = |pos: f32| {
+        let n = pos as usize % (AUDIO_PATTERN_SIZE * 8);
+        pattern[n / 8] & (0x80 >> (n % 8)) != 0
+    };
+    if len <= 0.0 {
+        return if bit(start) {1.0} else {0.0};
+    }
+
+    let end = start + len;
+    let (mut pos, mut set) = (start, 0.0);
+    while pos < end {
+        let next = ((pos as u32 + 1) as f32).min(end);
+        if bit(pos) {
+            set += next - pos;
+        }
+        pos = next;
+    }
+    set / len
+}
@@ -0,0 +1,110 @@
+//! Memory freezing cheats.
+//!
+//! A cheat file lists one cheat per line as `ADDR VALUE [NAME]`, both in
+//! hexadecimal, with `#` starting a comment:
+//!
+//! ```text
+//! # Blinky
+//! 3F0 09 lives
+//! 3F2 00 timer
+//! ```
+//!
+//! Every enabled cheat writes its value back after each frame, so the game
+//! never sees the byte change for long.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ruchip8::{Chip8, XO_MEMORY_SIZE};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    pub addr: usize,
+    pub value: u8,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Cheats {
+    pub list: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut list = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut words = line.splitn(3, char::is_whitespace);
+            let (addr, value) = match (words.next(), words.next()) {
+                (Some(addr), Some(value)) => (addr, value.trim()),
+                _ => return Err(format!("line {}: expected ADDR VALUE [NAME]", n + 1)),
+            };
+            let addr = usize::from_str_radix(addr, 16).ok()
+                .filter(|&addr| addr < XO_MEMORY_SIZE)
+                .ok_or_else(|| format!("line {}: invalid address '{}'", n + 1, addr))?;
+            let value = u8::from_str_radix(value, 16)
+                .map_err(|_| format!("line {}: invalid value '{}'", n + 1, value))?;
+            let name = words.next().map_or_else(|| format!("{:04X}", addr), |name| name.trim().to_owned());
+            list.push(Cheat { name, addr, value, enabled: true });
+        }
+        Ok(Cheats { list })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        Cheats::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// The cheats stored next to `rom`, with its extension replaced by
+    /// `.cht`, or none when there is no such file.
+    pub fn for_rom(rom: &Path) -> Result<Self, String> {
+        let path = rom.with_extension("cht");
+        match fs::metadata(&path) {
+            Ok(_) => Cheats::load(&path),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Cheats::default()),
+            Err(e) => Err(format!("cannot read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Writes every enabled cheat into memory. Cheats past the end of a
+    /// smaller memory do nothing. Runs every frame, so only bytes that
+    /// changed are written, keeping the rest of the decoded instructions.
+    pub fn apply(&self, chip: &mut Chip8) {
+        for cheat in self.list.iter().filter(|cheat| cheat.enabled) {
+            if chip.memory().get(cheat.addr).is_some_and(|&byte| byte != cheat.value) {
+                let _ = chip.write_memory(cheat.addr, &[cheat.value]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cheat_files() {
+        let cheats = Cheats::parse("# Blinky\n3F0 09 lives\n\n3f2 0  # no name\n").unwrap();
+        assert_eq!(cheats.list, vec![
+            Cheat { name: "lives".to_owned(), addr: 0x3F0, value: 9, enabled: true },
+            Cheat { name: "03F2".to_owned(), addr: 0x3F2, value: 0, enabled: true },
+        ]);
+        assert_eq!(Cheats::parse("3F0").unwrap_err(), "line 1: expected ADDR VALUE [NAME]");
+        assert_eq!(Cheats::parse("\n10000 1").unwrap_err(), "line 2: invalid address '10000'");
+        assert_eq!(Cheats::parse("3F0 100").unwrap_err(), "line 1: invalid value '100'");
+    }
+
+    #[test]
+    fn applies_enabled_cheats_that_fit() {
+        let mut cheats = Cheats::parse("300 AA\n301 BB\nFFFF CC").unwrap();
+        cheats.list[1].enabled = false;
+        let mut chip = Chip8::new();
+        cheats.apply(&mut chip);
+        assert_eq!(&chip.memory()[0x300..0x302], &[0xAA, 0x00]);
+    }
+}
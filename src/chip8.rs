@@ -0,0 +1,1344 @@
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use core::mem;
+use core::ops::Range;
+
+#[cfg(feature = "std")]
+use rand;
+
+use audio::{AUDIO_PATTERN_SIZE, DEFAULT_PITCH};
+use display::Display;
+use font::{Font, BIG_FONT_SET, BIG_FONT_START, FONT_SIZE};
+use error::Error;
+use instruction::{decode_long, Instruction, LONG_PREFIX};
+use keys::{KeyEvent, KeyQueue};
+use observer::EmuObserver;
+use rng::Rng;
+#[cfg(feature = "std")]
+use snapshot::Snapshot;
+use timing::Timing;
+use {FLAG, KEY_COUNT, MEMORY_SIZE, PROGRAM_START, REGISTER_SIZE, STACK_SIZE};
+#[cfg(feature = "std")]
+use {DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH, XO_MEMORY_SIZE};
+
+/// The largest memory a machine can have: `XO_MEMORY_SIZE` with `std`,
+/// `MEMORY_SIZE` without an allocator.
+#[cfg(feature = "std")]
+pub const MAX_MEMORY_SIZE: usize = XO_MEMORY_SIZE;
+#[cfg(not(feature = "std"))]
+pub const MAX_MEMORY_SIZE: usize = MEMORY_SIZE;
+
+macro_rules! opcode_not_implemented {
+    ($op: expr, $pc: expr) => (
+        return Err(Error::UnknownOpcode { opcode: $op, pc: $pc })
+    )
+}
+
+
+/// What to do when a program calls a machine language routine with 0NNN.
+/// Hybrid COSMAC VIP programs ship routines of their own that the machine
+/// can't run, so they misbehave whatever the choice.
+#[derive(Debug, Clone, Copy)]
+pub enum SysPolicy {
+    /// Carry on with the next instruction.
+    Ignore,
+    /// Like `Ignore`, logging a warning the first time each routine is
+    /// called. The same as `Ignore` without `std`.
+    Warn,
+    /// Stop with `Error::MachineCall`.
+    Halt,
+    /// Hand NNN to a function standing in for the routine. PC has already
+    /// moved on to the next instruction.
+    Call(fn(&mut Chip8, u16) -> Result<(), Error>),
+}
+
+impl SysPolicy {
+    /// Parses `ignore`, `warn` or `halt`.
+    pub fn parse(text: &str) -> Option<SysPolicy> {
+        match text {
+            "ignore" => Some(SysPolicy::Ignore),
+            "warn" => Some(SysPolicy::Warn),
+            "halt" => Some(SysPolicy::Halt),
+            _ => None,
+        }
+    }
+}
+
+/// What to do when a program writes below `PROGRAM_START`, over the
+/// interpreter area where the font lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteProtect {
+    /// Let the write through.
+    Off,
+    /// Let the write through, logging a warning the first time each
+    /// instruction does it. The same as `Off` without `std`.
+    Warn,
+    /// Stop with `Error::ProtectedWrite` before anything is written.
+    Trap,
+}
+
+impl WriteProtect {
+    /// Parses `off`, `warn` or `trap`.
+    pub fn parse(text: &str) -> Option<WriteProtect> {
+        match text {
+            "off" => Some(WriteProtect::Off),
+            "warn" => Some(WriteProtect::Warn),
+            "trap" => Some(WriteProtect::Trap),
+            _ => None,
+        }
+    }
+}
+
+/// When FX0A takes the key it waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyWait {
+    /// As soon as a key goes down.
+    Press,
+    /// When a key that went down during the wait comes back up, as on the
+    /// COSMAC VIP.
+    Release,
+}
+
+impl KeyWait {
+    /// Parses `press` or `release`.
+    pub fn parse(text: &str) -> Option<KeyWait> {
+        match text {
+            "press" => Some(KeyWait::Press),
+            "release" => Some(KeyWait::Release),
+            _ => None,
+        }
+    }
+}
+
+/// Memory allocated for the size the machine is built with. Without an
+/// allocator it is always `MEMORY_SIZE` long, with only the configured
+/// size in use.
+#[cfg(feature = "std")]
+type Memory<T> = Vec<T>;
+#[cfg(not(feature = "std"))]
+type Memory<T> = [T; MEMORY_SIZE];
+
+#[cfg(feature = "std")]
+fn memory_of<T: Copy>(size: usize, fill: T) -> Memory<T> {
+    vec![fill; size]
+}
+
+#[cfg(not(feature = "std"))]
+fn memory_of<T: Copy>(_size: usize, fill: T) -> Memory<T> {
+    [fill; MEMORY_SIZE]
+}
+
+/// What a `run_frame` left for the frontend.
+#[derive(Clone, Copy)]
+pub struct FrameOutput<'a> {
+    pub display: &'a Display,
+    /// Whether the frame drew anything, the screen needs redrawing then.
+    pub frame_ready: bool,
+    /// Whether the buzzer sounds until the next frame.
+    pub sound: bool,
+    /// Instructions executed and the cycles they took under the timing.
+    pub instructions: u32,
+    pub cycles: u32,
+    pub halted: bool,
+    pub waiting_for_key: bool,
+}
+
+/// Configures a machine before it is built.
+///
+/// ```
+/// # use ruchip8::{Chip8, XO_MEMORY_SIZE};
+/// let chip = Chip8::builder().memory_size(XO_MEMORY_SIZE).build().unwrap();
+/// assert_eq!(chip.memory().len(), XO_MEMORY_SIZE);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Chip8Builder {
+    memory_size: usize,
+    font: [u8; FONT_SIZE],
+    timing: Timing,
+}
+
+impl Chip8Builder {
+    /// Bytes of memory, `MEMORY_SIZE` by default. Anything from just past
+    /// `PROGRAM_START` up to `MAX_MEMORY_SIZE` works: less for teaching,
+    /// `XO_MEMORY_SIZE` for XO-CHIP programs.
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.memory_size = size;
+        self
+    }
+
+    /// The 4x5 digits at the bottom of memory, `Font::Default` by default.
+    /// Bundled sets come from `Font::glyphs`.
+    pub fn font(mut self, glyphs: &[u8; FONT_SIZE]) -> Self {
+        self.font = *glyphs;
+        self
+    }
+
+    /// The pace `run_frame` runs at, `Timing::default()` by default.
+    pub fn timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    pub fn build(self) -> Result<Chip8, Error> {
+        let size = self.memory_size;
+        if size <= PROGRAM_START || size > MAX_MEMORY_SIZE {
+            return Err(Error::InvalidMemorySize { size });
+        }
+        let mut chip = Chip8::build(size, self.font);
+        chip.timing = self.timing;
+        Ok(chip)
+    }
+}
+
+/// CHIP-8 machine struct.
+pub struct Chip8 {
+    /// Index register
+    i: usize,
+    /// Program counter
+    pc: usize,
+    /// Registers refer to as V0 to VF where VF is used primarily for carry
+    v: [u8; REGISTER_SIZE],
+    /// Stack, with `sp` entries in use
+    stack: [u16; STACK_SIZE],
+    sp: usize,
+    /// Machine memory, of which the first `memory_size` bytes are in use
+    memory: Memory<u8>,
+    memory_size: usize,
+    /// The 4x5 digits loaded at address 0
+    font: [u8; FONT_SIZE],
+    /// Delay timer
+    delay_timer: u8,
+    /// Sound timer
+    sound_timer: u8,
+    /// Keypad state, true when the key is held down
+    keys: [bool; KEY_COUNT],
+    /// Events from `push_key` not applied yet.
+    key_queue: KeyQueue,
+    /// Keys the queue pressed that no EX9E, EXA1 or FX0A has looked at
+    /// yet, one bit each. Their release waits.
+    unseen_keys: u16,
+    /// The frame the queue last pressed each key in.
+    pressed_at: [u64; KEY_COUNT],
+    /// Frames `tick_timers` ran since the machine was built.
+    frames: u64,
+    /// Wait for key press
+    wait_for_key: (bool, u8),
+    /// The key that went down during the wait, for `KeyWait::Release`.
+    wait_pressed: Option<u8>,
+    /// When FX0A is done waiting.
+    key_wait: KeyWait,
+    /// The key EX9E or EXA1 last looked at.
+    last_polled: Option<u8>,
+    /// Set by 00FD. The machine stays put until reset.
+    halted: bool,
+    /// SCHIP RPL user flags, written by FX75 and read by FX85. Survive reset.
+    rpl: [u8; REGISTER_SIZE],
+    /// XO-CHIP audio pattern loaded by F002, played while the sound timer
+    /// runs. `None` until a program loads one, the plain beep plays then.
+    pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    /// XO-CHIP playback rate of the pattern, set by FX3A.
+    pitch: u8,
+    /// Handling of 0NNN.
+    sys_policy: SysPolicy,
+    /// Routines `SysPolicy::Warn` already warned about, one bit per NNN.
+    sys_warned: [u32; 0x1000 / 32],
+    /// Handling of writes below `PROGRAM_START`.
+    write_protect: WriteProtect,
+    /// Addresses `WriteProtect::Warn` already warned about, one bit each.
+    protect_warned: [u32; PROGRAM_START / 32],
+    /// Original implementation requires VY to be shifted instead of VX.
+    /// Though many many ROM assume otherwise.
+    shift_vy: bool,
+    /// Budget and costs of a `run_frame`.
+    timing: Timing,
+    /// Screen
+    display: Display,
+    /// Source of CXNN random numbers
+    rng: Rng,
+    /// Instructions decoded so far, by address. Writes to memory drop the
+    /// entries they overlap, which keeps self-modifying programs working.
+    cache: Memory<Option<Instruction>>,
+    /// Told about frames, draws, sound and key waits.
+    #[cfg(feature = "std")]
+    observers: Vec<Box<dyn EmuObserver + Send>>,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Chip8::new()
+    }
+}
+
+impl Chip8 {
+    /// A machine with `MEMORY_SIZE` bytes of memory.
+    pub fn new() -> Self {
+        Chip8::build(MEMORY_SIZE, *Font::Default.glyphs())
+    }
+
+    /// A machine configured other than by default.
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder { memory_size: MEMORY_SIZE, font: *Font::Default.glyphs(), timing: Timing::default() }
+    }
+
+    fn build(size: usize, font: [u8; FONT_SIZE]) -> Self {
+        let mut memory = memory_of(size, 0);
+        memory[..FONT_SIZE].copy_from_slice(&font);
+        memory[BIG_FONT_START..BIG_FONT_START + BIG_FONT_SET.len()].copy_from_slice(&BIG_FONT_SET);
+
+        Chip8 {
+            i: 0,
+            pc: PROGRAM_START,
+            v: [0; REGISTER_SIZE],
+            stack: [0; STACK_SIZE],
+            sp: 0,
+            memory,
+            memory_size: size,
+            font,
+            delay_timer: 0,
+            sound_timer: 0,
+            keys: [false; KEY_COUNT],
+            key_queue: KeyQueue::new(),
+            unseen_keys: 0,
+            pressed_at: [0; KEY_COUNT],
+            frames: 0,
+            wait_for_key: (false, 0),
+            wait_pressed: None,
+            key_wait: KeyWait::Press,
+            last_polled: None,
+            halted: false,
+            rpl: [0; REGISTER_SIZE],
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+            sys_policy: SysPolicy::Halt,
+            sys_warned: [0; 0x1000 / 32],
+            write_protect: WriteProtect::Off,
+            protect_warned: [0; PROGRAM_START / 32],
+            shift_vy: false,
+            timing: Timing::default(),
+            display: Display::new(),
+            rng: Rng::new(Chip8::initial_seed()),
+            cache: memory_of(size, None),
+            #[cfg(feature = "std")]
+            observers: Vec::new(),
+        }
+    }
+
+    /// A random seed where the OS can provide one. Without `std` every
+    /// machine starts from the same seed, hosts should call `set_seed` with
+    /// something like a timer reading.
+    #[cfg(feature = "std")]
+    fn initial_seed() -> u64 {
+        rand::random()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn initial_seed() -> u64 {
+        0
+    }
+
+    /// Copies a program into memory at `PROGRAM_START`.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Error> {
+        let end = PROGRAM_START + rom.len();
+        if end > self.memory_size {
+            return Err(Error::RomTooLarge { size: rom.len() });
+        }
+        self.memory[PROGRAM_START..end].copy_from_slice(rom);
+        self.invalidate(PROGRAM_START..end);
+        Ok(())
+    }
+
+    /// Starts the program over: clears the registers, stack, timers and
+    /// screen modes, keeping memory as the program left it.
+    pub fn soft_reset(&mut self) {
+        self.v = [0; REGISTER_SIZE];
+        self.sp = 0;
+        self.pc = PROGRAM_START;
+        self.i = 0;
+        self.delay_timer = 0;
+        self.set_sound(0);
+        self.wait_for_key = (false, 0);
+        self.wait_pressed = None;
+        self.last_polled = None;
+        self.halted = false;
+        self.pattern = None;
+        self.pitch = DEFAULT_PITCH;
+        self.display.set_hires(false);
+    }
+
+    /// Starts `rom` from a machine as good as new: memory cleared, the
+    /// fonts and `rom` copied in again and the screen blank, then a soft
+    /// reset. Quirks, RPL flags and observers stay as they are.
+    pub fn hard_reset(&mut self, rom: &[u8]) -> Result<(), Error> {
+        if PROGRAM_START + rom.len() > self.memory_size {
+            return Err(Error::RomTooLarge { size: rom.len() });
+        }
+        self.memory[..self.memory_size].fill(0);
+        self.memory[..FONT_SIZE].copy_from_slice(&self.font);
+        self.memory[BIG_FONT_START..BIG_FONT_START + BIG_FONT_SET.len()].copy_from_slice(&BIG_FONT_SET);
+        self.invalidate(0..self.memory_size);
+        self.soft_reset();
+        self.display.clear();
+        self.display.present();
+        self.load_rom(rom)
+    }
+
+    /// Fetches and executes one instruction. On error the machine state is
+    /// left as it was right before the faulting instruction.
+    pub fn execute_cycle(&mut self) -> Result<(), Error> {
+        self.execute_timed(Timing::default()).map(|_| ())
+    }
+
+    /// Like `execute_cycle`, returning the cycles the instruction took under
+    /// `timing`. A machine waiting on FX0A spends the cost of FX0A, a halted
+    /// one that of 00FD.
+    pub fn execute_timed(&mut self, timing: Timing) -> Result<u32, Error> {
+        if self.halted {
+            return Ok(timing.cost(Instruction::Exit));
+        }
+        if !self.key_queue.is_empty() {
+            self.take_key_events();
+        }
+        // FX0A stalls the machine until `set_key` reports a press.
+        if let (true, x) = self.wait_for_key {
+            return Ok(timing.cost(Instruction::WaitKey { x }));
+        }
+        let instruction = self.fetch()?;
+        #[cfg(feature = "std")]
+        trace!(target: "ruchip8::cpu", "{:04X}: {:04X}  {}", self.pc, instruction.encode(), instruction);
+        let pc = self.pc;
+        self.execute(instruction)?;
+        self.notify(|observer, chip| observer.on_instruction(chip, pc, instruction));
+        Ok(timing.cost(instruction))
+    }
+
+    /// Runs one frame: sets the keys held in `keys`, one bit per key with
+    /// key 0 in bit 0, executes instructions until they use up the budget of
+    /// `timing()` and ticks the timers. Call at `TIMERS_CLOCK`.
+    ///
+    /// A machine that halts or starts waiting on FX0A idles for the rest of
+    /// the frame instead of spinning through its budget. A key pressed in a
+    /// later frame's `keys` releases the wait. On error the timers are left
+    /// as they were and the machine as it was right before the faulting
+    /// instruction.
+    pub fn run_frame(&mut self, keys: u16) -> Result<FrameOutput<'_>, Error> {
+        for key in 0..KEY_COUNT as u8 {
+            let pressed = keys & (1 << key) != 0;
+            if self.keys[key as usize] != pressed {
+                self.set_key(key, pressed);
+            }
+        }
+        let (mut cycles, mut instructions) = (0, 0);
+        while cycles < self.timing.budget() && !self.halted && !self.wait_for_key.0 {
+            cycles += self.execute_timed(self.timing)?;
+            instructions += 1;
+        }
+        self.tick_timers();
+        let frame_ready = self.display.take_frame_ready();
+        Ok(FrameOutput {
+            display: &self.display,
+            frame_ready,
+            sound: self.sound_timer > 0,
+            instructions,
+            cycles,
+            halted: self.halted,
+            waiting_for_key: self.wait_for_key.0,
+        })
+    }
+
+    /// Counts both timers down by one and presents what was drawn since the
+    /// last tick. Call at `TIMERS_CLOCK`.
+    pub fn tick_timers(&mut self) {
+        self.frames += 1;
+        self.take_key_events();
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.set_sound(self.sound_timer.saturating_sub(1));
+        self.display.present();
+        self.notify(|observer, chip| observer.on_frame(chip));
+    }
+
+    /// Index register.
+    pub fn i(&self) -> usize {
+        self.i
+    }
+
+    pub fn set_i(&mut self, i: usize) {
+        self.i = i;
+    }
+
+    /// Program counter.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    /// Value of register VN.
+    pub fn v(&self, n: u8) -> u8 {
+        self.v[n as usize]
+    }
+
+    pub fn set_v(&mut self, n: u8, val: u8) {
+        self.v[n as usize] = val;
+    }
+
+    /// Return addresses, oldest first.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.memory_size]
+    }
+
+    /// The 4x5 digits the machine was built with.
+    pub fn font(&self) -> &[u8; FONT_SIZE] {
+        &self.font
+    }
+
+    /// Write access to memory. Drops every decoded instruction, since any
+    /// byte may change.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        for slot in self.cache.iter_mut() {
+            *slot = None;
+        }
+        &mut self.memory[..self.memory_size]
+    }
+
+    /// Writes `bytes` over memory from `addr`, from outside the program as
+    /// a debugger would, so observers are not told. Only the decoded
+    /// instructions the bytes overlap are dropped.
+    pub fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Result<(), Error> {
+        let end = addr.checked_add(bytes.len()).filter(|&end| end <= self.memory_size)
+            // The first address past the end of memory.
+            .ok_or(Error::MemoryOutOfBounds { addr: addr.max(self.memory_size), pc: self.pc })?;
+        self.memory[addr..end].copy_from_slice(bytes);
+        self.invalidate(addr..end);
+        Ok(())
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn set_delay_timer(&mut self, val: u8) {
+        self.delay_timer = val;
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn set_sound_timer(&mut self, val: u8) {
+        self.set_sound(val);
+    }
+
+    /// Sets the sound timer, telling the observers when it starts or stops.
+    fn set_sound(&mut self, val: u8) {
+        let was_running = self.sound_timer != 0;
+        self.sound_timer = val;
+        match (was_running, val != 0) {
+            (false, true) => self.notify(|observer, chip| observer.on_sound_start(chip)),
+            (true, false) => self.notify(|observer, chip| observer.on_sound_stop(chip)),
+            _ => {},
+        }
+    }
+
+    /// Registers an observer, which is told about events from then on.
+    #[cfg(feature = "std")]
+    pub fn add_observer(&mut self, observer: Box<dyn EmuObserver + Send>) {
+        self.observers.push(observer);
+    }
+
+    /// Whether any observer is registered.
+    #[cfg(feature = "std")]
+    pub fn has_observers(&self) -> bool {
+        !self.observers.is_empty()
+    }
+
+    /// Unregisters every observer and returns them, to move them to another
+    /// machine.
+    #[cfg(feature = "std")]
+    pub fn take_observers(&mut self) -> Vec<Box<dyn EmuObserver + Send>> {
+        mem::take(&mut self.observers)
+    }
+
+    /// Calls `event` on every observer.
+    #[cfg(feature = "std")]
+    fn notify<F: FnMut(&mut dyn EmuObserver, &Chip8)>(&mut self, mut event: F) {
+        if self.observers.is_empty() {
+            return;
+        }
+        // Out of the machine while they run, so they can look at it.
+        let mut observers = mem::take(&mut self.observers);
+        for observer in observers.iter_mut() {
+            event(observer.as_mut(), self);
+        }
+        self.observers = observers;
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn notify<F: FnMut(&mut dyn EmuObserver, &Chip8)>(&mut self, _event: F) {}
+
+    pub fn shift_vy(&self) -> bool {
+        self.shift_vy
+    }
+
+    pub fn set_shift_vy(&mut self, shift_vy: bool) {
+        self.shift_vy = shift_vy;
+    }
+
+    pub fn sys_policy(&self) -> SysPolicy {
+        self.sys_policy
+    }
+
+    /// Picks how 0NNN is handled, `SysPolicy::Halt` by default.
+    pub fn set_sys_policy(&mut self, policy: SysPolicy) {
+        self.sys_policy = policy;
+    }
+
+    pub fn write_protect(&self) -> WriteProtect {
+        self.write_protect
+    }
+
+    /// Picks how program writes below `PROGRAM_START` are handled,
+    /// `WriteProtect::Off` by default. Writes through `memory_mut` are
+    /// never checked.
+    pub fn set_write_protect(&mut self, protect: WriteProtect) {
+        self.write_protect = protect;
+    }
+
+    /// Reseeds the CXNN random number generator. Machines running the same
+    /// program with the same seed and inputs stay in lockstep.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// The pace `run_frame` runs at.
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// Whether `tick_timers` presented a frame since the last call.
+    pub fn take_frame_ready(&mut self) -> bool {
+        self.display.take_frame_ready()
+    }
+
+    /// The state a savestate needs, see `Snapshot`.
+    #[cfg(feature = "std")]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            i: self.i,
+            pc: self.pc,
+            v: self.v,
+            stack: self.stack().to_vec(),
+            memory: self.memory().to_vec(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            key_wait: self.key_wait_register(),
+            last_polled: self.last_polled,
+            halted: self.halted,
+            rpl: self.rpl,
+            pattern: self.pattern,
+            pitch: self.pitch,
+            hires: self.display.is_hires(),
+            screen: self.display.screen().collect(),
+            rng: self.rng.state(),
+        }
+    }
+
+    /// Puts the machine back in the state of `snapshot` and presents its
+    /// screen. Held keys, quirks and observers stay as they are. Fails with
+    /// `Error::InvalidSnapshot`, changing nothing, when the snapshot is for
+    /// another memory size or does not hold together.
+    #[cfg(feature = "std")]
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+        let screen_size = if snapshot.hires { HIRES_WIDTH * HIRES_HEIGHT } else { DISPLAY_WIDTH * DISPLAY_HEIGHT };
+        if snapshot.memory.len() != self.memory_size || snapshot.stack.len() > STACK_SIZE
+            || snapshot.screen.len() != screen_size || snapshot.key_wait.is_some_and(|x| x as usize >= REGISTER_SIZE)
+            || snapshot.last_polled.is_some_and(|key| key as usize >= KEY_COUNT) {
+            return Err(Error::InvalidSnapshot);
+        }
+        self.i = snapshot.i;
+        self.pc = snapshot.pc;
+        self.v = snapshot.v;
+        self.sp = snapshot.stack.len();
+        self.stack[..self.sp].copy_from_slice(&snapshot.stack);
+        self.memory.copy_from_slice(&snapshot.memory);
+        self.invalidate(0..self.memory_size);
+        self.delay_timer = snapshot.delay_timer;
+        self.set_sound(snapshot.sound_timer);
+        self.wait_for_key = snapshot.key_wait.map_or((false, 0), |x| (true, x));
+        self.wait_pressed = None;
+        self.last_polled = snapshot.last_polled;
+        self.halted = snapshot.halted;
+        self.rpl = snapshot.rpl;
+        self.pattern = snapshot.pattern;
+        self.pitch = snapshot.pitch;
+        self.display.restore(snapshot.hires, &snapshot.screen);
+        self.rng = Rng::from_state(snapshot.rng);
+        Ok(())
+    }
+
+    /// SCHIP RPL flags. Hosts may persist them, some games keep high scores
+    /// there.
+    pub fn rpl_flags(&self) -> &[u8] {
+        &self.rpl
+    }
+
+    /// Restores RPL flags, as many as `flags` holds.
+    pub fn set_rpl_flags(&mut self, flags: &[u8]) {
+        let len = flags.len().min(REGISTER_SIZE);
+        self.rpl[..len].copy_from_slice(&flags[..len]);
+    }
+
+    /// The audio pattern the program loaded with F002, if any.
+    pub fn audio_pattern(&self) -> Option<&[u8; AUDIO_PATTERN_SIZE]> {
+        self.pattern.as_ref()
+    }
+
+    /// The pattern playback rate set by FX3A, 64 when the program set none.
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Whether the program ran 00FD. The CPU no longer runs and the screen
+    /// keeps its last frame until a reset.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Whether FX0A is holding the machine until a key is pressed.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.wait_for_key.0
+    }
+
+    /// The register FX0A stores the key in while it waits.
+    pub fn key_wait_register(&self) -> Option<u8> {
+        match self.wait_for_key {
+            (true, x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The key EX9E or EXA1 last tested, since the last reset.
+    pub fn last_polled_key(&self) -> Option<u8> {
+        self.last_polled
+    }
+
+    pub fn is_key_down(&self, key: u8) -> bool {
+        self.keys[key as usize]
+    }
+
+    /// The keys held, one bit per key with key 0 in bit 0.
+    pub fn keys(&self) -> u16 {
+        self.keys.iter().rev().fold(0, |keys, &down| keys << 1 | down as u16)
+    }
+
+    pub fn key_wait(&self) -> KeyWait {
+        self.key_wait
+    }
+
+    /// Picks when FX0A is done waiting, `KeyWait::Press` by default.
+    pub fn set_key_wait(&mut self, key_wait: KeyWait) {
+        self.key_wait = key_wait;
+    }
+
+    /// Frames `tick_timers` ran since the machine was built, which stamps
+    /// `KeyEvent`s.
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// Queues a key going down or up, see `KeyEvent`. Unlike `set_key` the
+    /// event takes effect as the machine runs, and a press the program has
+    /// not looked at yet stays down until it has or a frame went by.
+    pub fn push_key(&mut self, key: u8, pressed: bool) {
+        if self.key_queue.is_full() {
+            if let Some(event) = self.key_queue.pop() {
+                self.apply_key_event(event);
+            }
+        }
+        self.key_queue.push(KeyEvent { key: key & 0xF, pressed, frame: self.frames });
+    }
+
+    /// Whether `push_key` events are waiting for the machine to take them.
+    pub fn has_queued_keys(&self) -> bool {
+        !self.key_queue.is_empty()
+    }
+
+    /// Applies the queued events that are due, in order.
+    fn take_key_events(&mut self) {
+        while let Some(event) = self.key_queue.peek() {
+            let unseen = self.unseen_keys & (1 << event.key) != 0;
+            if !event.pressed && unseen && self.pressed_at[event.key as usize] == self.frames {
+                break;
+            }
+            self.key_queue.pop();
+            self.apply_key_event(event);
+        }
+    }
+
+    fn apply_key_event(&mut self, event: KeyEvent) {
+        if event.pressed {
+            self.unseen_keys |= 1 << event.key;
+            self.pressed_at[event.key as usize] = self.frames;
+        } else {
+            self.unseen_keys &= !(1 << event.key);
+        }
+        self.set_key(event.key, event.pressed);
+        self.notify(|observer, chip| observer.on_key(chip, event));
+    }
+
+    /// Updates the state of a key. A press, or with `KeyWait::Release` the
+    /// release of the key pressed first, ends a pending FX0A wait.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.keys[key as usize] = pressed;
+
+        let (waiting, x) = self.wait_for_key;
+        if !waiting {
+            return;
+        }
+        if pressed {
+            self.unseen_keys &= !(1 << key);
+        }
+        let done = match self.key_wait {
+            KeyWait::Press => pressed,
+            KeyWait::Release if pressed => {
+                self.wait_pressed = self.wait_pressed.or(Some(key));
+                false
+            },
+            KeyWait::Release => self.wait_pressed == Some(key),
+        };
+        if done {
+            self.v[x as usize] = key;
+            self.wait_for_key = (false, 0);
+            self.wait_pressed = None;
+            self.pc += 2;
+        }
+    }
+
+    /// Fetches 2 bytes
+    fn get_opcode(&self) -> Result<u16, Error> {
+        self.word_at(self.pc)
+    }
+
+    /// The big endian word at `addr`.
+    fn word_at(&self, addr: usize) -> Result<u16, Error> {
+        let bytes = self.mem_range(addr, 2)?;
+        Ok((self.memory[bytes.start] as u16) << 8 | (self.memory[bytes.start+1] as u16))
+    }
+
+    /// Checks that `len` bytes starting at `addr` lie within memory.
+    fn mem_range(&self, addr: usize, len: usize) -> Result<Range<usize>, Error> {
+        match addr.checked_add(len) {
+            Some(end) if end <= self.memory_size => Ok(addr..end),
+            _ => Err(Error::MemoryOutOfBounds { addr, pc: self.pc }),
+        }
+    }
+
+    /// Like `mem_range`, for a program about to write to the range.
+    fn mem_range_mut(&mut self, addr: usize, len: usize) -> Result<Range<usize>, Error> {
+        let range = self.mem_range(addr, len)?;
+        if range.start >= PROGRAM_START {
+            return Ok(range);
+        }
+        match self.write_protect {
+            WriteProtect::Off => {},
+            WriteProtect::Warn => {
+                let (word, bit) = (addr / 32, 1 << (addr % 32));
+                if self.protect_warned[word] & bit == 0 {
+                    self.protect_warned[word] |= bit;
+                    #[cfg(feature = "std")]
+                    warn!("{:05X}: write to {:05X}, below the program", self.pc, addr);
+                }
+            },
+            WriteProtect::Trap => return Err(Error::ProtectedWrite { addr, pc: self.pc }),
+        }
+        Ok(range)
+    }
+
+    /// The instruction at PC, from the cache when it was decoded before.
+    fn fetch(&mut self) -> Result<Instruction, Error> {
+        if let Some(&Some(instruction)) = self.cache.get(self.pc) {
+            return Ok(instruction);
+        }
+        let ops = self.get_opcode()?;
+        let next = if ops == LONG_PREFIX { self.word_at(self.pc + 2)? } else { 0 };
+        match decode_long(ops, next) {
+            Some(instruction) => {
+                self.cache[self.pc] = Some(instruction);
+                Ok(instruction)
+            },
+            None => opcode_not_implemented!(ops, self.pc),
+        }
+    }
+
+    /// Forgets the decoded instructions overlapping `range`, including those
+    /// starting up to 3 bytes before it, the size of F000 NNNN.
+    fn invalidate(&mut self, range: Range<usize>) {
+        let start = range.start.saturating_sub(3);
+        for slot in &mut self.cache[start..range.end] {
+            *slot = None;
+        }
+    }
+
+    /// Executes one decoded instruction as if it had been fetched from PC.
+    pub fn execute(&mut self, instruction: Instruction) -> Result<(), Error> {
+        use instruction::Instruction::*;
+
+        // Every instruction is two bytes long, so everything but jumps,
+        // calls, returns and 00FD moves the program counter on by two.
+        match instruction {
+            Cls => self.cls(),
+            Ret => self.ret()?,
+            ScrollDown { n } => self.scroll(|display| display.scroll_down(n as usize)),
+            ScrollUp { n } => self.scroll(|display| display.scroll_up(n as usize)),
+            ScrollRight => self.scroll(|display| display.scroll_right(4)),
+            ScrollLeft => self.scroll(|display| display.scroll_left(4)),
+            Sys { addr } => self.sys(addr)?,
+            Exit => self.halted = true,
+            LowRes => self.set_hires(false),
+            HighRes => self.set_hires(true),
+            Jump { addr } => self.jump_addr(addr),
+            Call { addr } => self.call_sub(addr)?,
+            SkipEqImm { x, nn } => self.se_vx(x, nn),
+            SkipNeImm { x, nn } => self.sne_vx(x, nn),
+            SkipEqReg { x, y } => self.se_vx_vy(x, y),
+            LoadImm { x, nn } => self.set_reg_vn(x, nn),
+            AddImm { x, nn } => {
+                // Adds the value NN to register VX.
+                let vx = self.read_reg_vn(x);
+                self.set_reg_vn(x, vx.wrapping_add(nn));
+            },
+            Move { x, y } => {
+                // Stores the value of register VY in register VX.
+                let vy = self.read_reg_vn(y);
+                self.set_reg_vn(x, vy);
+            },
+            Or { x, y } => self.or_vx_vy(x, y),
+            And { x, y } => self.and_vx_vy(x, y),
+            Xor { x, y } => self.xor_vx_vy(x, y),
+            Add { x, y } => self.add_vx_vy(x, y),
+            Sub { x, y } => self.sub_vx_vy(x, y),
+            Shr { x, y } => self.rshft_vx_vy(x, y),
+            SubN { x, y } => self.subn_vx_vy(x, y),
+            Shl { x, y } => self.lshft_vx_vy(x, y),
+            SkipNeReg { x, y } => self.skip_ne_vx_vy(x, y),
+            LoadIndex { addr } => self.set_i_addr(addr),
+            LongIndex { addr } => self.set_i_long(addr),
+            JumpV0 { addr } => {
+                // Jumps to address NNN + V0.
+                let v0 = self.read_reg_vn(0) as u16;
+                self.jump_addr(addr + v0);
+            },
+            Random { x, nn } => self.rnd_vx_nn(x, nn),
+            Draw { x, y, n } => self.draw_vx_vy(x, y, n)?,
+            SkipKey { x } => self.skip_vx(x),
+            SkipNotKey { x } => self.skipn_vx(x),
+            ReadDelay { x } => self.set_delay(x),
+            WaitKey { x } => self.wait_vx(x),
+            SetDelay { x } => self.set_vx_delay(x),
+            SetSound { x } => self.set_vx_sound(x),
+            AddIndex { x } => self.add_vx_to_i(x),
+            Font { x } => self.set_i_sprite(x),
+            BigFont { x } => self.set_i_big_sprite(x),
+            Bcd { x } => self.set_bcd_vx(x)?,
+            Store { x } => self.set_mem_regs(x)?,
+            StoreRange { x, y } => self.store_range(x, y)?,
+            LoadRange { x, y } => self.load_range(x, y)?,
+            Load { x } => self.fill_regs_mem(x)?,
+            Audio => self.load_pattern()?,
+            Pitch { x } => self.set_pitch_vx(x),
+            SaveFlags { x } => self.save_flags(x),
+            LoadFlags { x } => self.load_flags(x),
+        }
+
+        Ok(())
+    }
+
+    /// Set V at index N to a specific value.
+    fn set_reg_vn(&mut self, n: u8, val: u8) {
+        self.v[n as usize] = val;
+        self.pc += 2;
+    }
+
+    /// Read a value of V at index N.
+    fn read_reg_vn(&mut self, n: u8) -> u8 {
+        self.v[n as usize]
+    }
+
+    /// Clears the display
+    fn cls(&mut self) {
+        self.display.clear();
+        self.pc += 2;
+    }
+
+    /// Runs 0NNN according to the policy.
+    fn sys(&mut self, addr: u16) -> Result<(), Error> {
+        match self.sys_policy {
+            SysPolicy::Ignore => {},
+            SysPolicy::Warn => {
+                let (word, bit) = (addr as usize / 32, 1 << (addr % 32));
+                if self.sys_warned[word] & bit == 0 {
+                    self.sys_warned[word] |= bit;
+                    #[cfg(feature = "std")]
+                    warn!("{:05X}: ignoring call to machine language routine {:03X}", self.pc, addr);
+                }
+            },
+            SysPolicy::Halt => return Err(Error::MachineCall { addr, pc: self.pc }),
+            SysPolicy::Call(routine) => {
+                self.pc += 2;
+                return routine(self, addr);
+            },
+        }
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Scrolls the display. Amounts are in pixels of the current mode, the
+    /// way later SCHIP versions work, where SCHIP 1.1 moved half as far in
+    /// 64x32.
+    fn scroll<F: FnOnce(&mut Display)>(&mut self, scroll: F) {
+        scroll(&mut self.display);
+        self.pc += 2;
+    }
+
+    /// Switches the display mode, which also clears it.
+    fn set_hires(&mut self, hires: bool) {
+        self.display.set_hires(hires);
+        self.pc += 2;
+    }
+
+    /// Returns from the subroutine, by setting the program counter
+    /// to the address from the top of stack.
+    fn ret(&mut self) -> Result<(), Error> {
+        if self.sp == 0 {
+            return Err(Error::StackUnderflow { pc: self.pc });
+        }
+        self.sp -= 1;
+        let addr = self.stack[self.sp];
+        self.jump_addr(addr);
+        Ok(())
+    }
+
+    /// Jumps program counter to a specified address
+    fn jump_addr(&mut self, addr: u16) {
+        self.pc = addr as usize;
+    }
+
+    /// Calls a subroutine by pushing the address of the next instruction
+    /// to the stack, then jumps to the given address.
+    fn call_sub(&mut self, addr: u16) -> Result<(), Error> {
+        if self.sp == STACK_SIZE {
+            return Err(Error::StackOverflow { pc: self.pc });
+        }
+        // A CALL in the last word of 64K memory has nowhere to return to.
+        let next = self.pc + 2;
+        self.stack[self.sp] = u16::try_from(next).map_err(|_| Error::MemoryOutOfBounds { addr: next, pc: self.pc })?;
+        self.sp += 1;
+        self.jump_addr(addr);
+        Ok(())
+    }
+
+    /// Moves past the following instruction when `skip`, past this one
+    /// otherwise. F000 NNNN is skipped whole.
+    fn skip_if(&mut self, skip: bool) {
+        self.pc += 2;
+        if skip {
+            self.pc += if self.word_at(self.pc) == Ok(LONG_PREFIX) {4} else {2};
+        }
+    }
+
+    /// Skips the following instruction if the value of register VX equals NN.
+    fn se_vx(&mut self, x: u8, nn: u8) {
+        self.skip_if(self.v[x as usize] == nn);
+    }
+
+    /// Skips the following instruction if the value of register VX is not equal to NN.
+    fn sne_vx(&mut self, x: u8, nn: u8) {
+        self.skip_if(self.v[x as usize] != nn);
+    }
+
+    /// Skips the following instruction if the value of
+    /// register VX is equal to the value of register VY.
+    fn se_vx_vy(&mut self, x: u8, y: u8) {
+        self.skip_if(self.v[x as usize] == self.v[y as usize]);
+    }
+
+    fn or_vx_vy(&mut self, x: u8, y: u8) {
+        self.v[x as usize] |= self.v[y as usize];
+        self.pc += 2;
+    }
+
+    fn and_vx_vy(&mut self, x: u8, y: u8) {
+        self.v[x as usize] &= self.v[y as usize];
+        self.pc += 2;
+    }
+
+    fn xor_vx_vy(&mut self, x: u8, y: u8) {
+        self.v[x as usize] ^= self.v[y as usize];
+        self.pc += 2;
+    }
+
+    fn add_vx_vy(&mut self, x: u8, y: u8) {
+        let sum: u16 = self.v[x as usize] as u16 + self.v[y as usize] as u16;
+        self.v[x as usize] = sum as u8;
+        self.v[FLAG] = if sum > 0xFF {0x1} else {0x0};
+        self.pc += 2;
+    }
+
+    /// Subtract the value of register VY from register VX
+    /// Set VF to 00 if a borrow occurs
+    /// Set VF to 01 if a borrow does not occur.
+    fn sub_vx_vy(&mut self, x: u8, y: u8) {
+        let (vx, vy) = (self.v[x as usize], self.v[y as usize]);
+        self.v[x as usize] = vx.wrapping_sub(vy);
+        self.v[FLAG] = if vx < vy {0x0} else {0x1};
+        self.pc += 2;
+    }
+
+    /// Set register VX to the value of VY minus VX
+    /// Set VF to 00 if a borrow occurs
+    /// Set VF to 01 if a borrow does not occur.
+    fn subn_vx_vy(&mut self, x: u8, y: u8) {
+        let (vx, vy) = (self.v[x as usize], self.v[y as usize]);
+        self.v[x as usize] = vy.wrapping_sub(vx);
+        self.v[FLAG] = if vy < vx {0x0} else {0x1};
+        self.pc += 2;
+    }
+
+    /// Store the value of register VY shifted right one bit in register VX
+    /// Set register VF to the least significant bit prior to the shift.
+    fn rshft_vx_vy(&mut self, x: u8, y: u8) {
+        let n = if self.shift_vy {y} else {x};
+        let val = self.v[n as usize];
+        self.v[x as usize] = val >> 1;
+        self.v[FLAG] = val & 0x01; // 0000 0001
+        self.pc += 2;
+    }
+
+    /// Store the value of register VY shifted left one bit in register VX
+    /// Set register VF to the most significant bit prior to the shift.
+    fn lshft_vx_vy(&mut self, x: u8, y: u8) {
+        let n = if self.shift_vy {y} else {x};
+        let val = self.v[n as usize];
+        self.v[x as usize] = val << 1;
+        self.v[FLAG] = (val & 0x80) >> 7; // 1000 0000
+        self.pc += 2;
+    }
+
+    /// Skips the following instruction if the value of register VX is not equal
+    /// to the value of register VY.
+    fn skip_ne_vx_vy(&mut self, x: u8, y: u8) {
+        self.skip_if(self.v[x as usize] != self.v[y as usize]);
+    }
+
+    /// Stores memory address NNN in register I.
+    fn set_i_addr(&mut self, addr: u16) {
+        self.i = addr as usize;
+        self.pc += 2;
+    }
+
+    /// Stores the address NNNN following F000 in register I.
+    fn set_i_long(&mut self, addr: u16) {
+        self.i = addr as usize;
+        self.pc += 4;
+    }
+
+    /// Sets VX to a random number with a mask of NN.
+    fn rnd_vx_nn(&mut self, x: u8, nn: u8) {
+        self.v[x as usize] = self.rng.next_u8() & nn;
+        self.pc += 2;
+    }
+
+    /// Draws a sprite at position VX, VY with N bytes of sprite data starting at the address stored in I
+    /// Set VF to 01 if any set pixels are changed to unset, and 00 otherwise.
+    /// In 128x64 mode, N = 0 draws a 16x16 sprite from 32 bytes instead.
+    fn draw_vx_vy(&mut self, x: u8, y: u8, n: u8) -> Result<(), Error> {
+        let pos_x = self.v[x as usize] as usize;
+        let pos_y = self.v[y as usize] as usize;
+        let large = n == 0 && self.display.is_hires();
+        let sprite = self.mem_range(self.i, if large { 32 } else { n as usize })?;
+        let collision = if large {
+            self.display.draw_large(pos_x, pos_y, &self.memory[sprite.clone()])
+        } else {
+            self.display.draw(pos_x, pos_y, &self.memory[sprite.clone()])
+        };
+        self.notify(|observer, chip| observer.on_read(chip, sprite.clone()));
+        self.v[FLAG] = if collision {0x1} else {0x0};
+        self.pc += 2;
+        // The coordinates as they were, VF may have just been the flag.
+        self.notify(|observer, chip| observer.on_draw(chip, pos_x as u8, pos_y as u8, n, collision));
+        Ok(())
+    }
+
+    /// Skips the following instruction if the key corresponding to the hex value
+    /// currently stored in register VX is pressed.
+    fn skip_vx(&mut self, x: u8) {
+        let key = self.v[x as usize] & 0xF;
+        self.last_polled = Some(key);
+        self.unseen_keys &= !(1 << key);
+        self.skip_if(self.keys[key as usize]);
+    }
+
+    /// Skips the following instruction if the key corresponding to the hex value
+    /// currently stored in register VX is not pressed.
+    fn skipn_vx(&mut self, x: u8) {
+        let key = self.v[x as usize] & 0xF;
+        self.last_polled = Some(key);
+        self.unseen_keys &= !(1 << key);
+        self.skip_if(!self.keys[key as usize]);
+    }
+
+    /// Stores the current value of the delay timer in register VX.
+    fn set_delay(&mut self, x: u8) {
+        self.v[x as usize] = self.delay_timer;
+        self.pc += 2;
+    }
+
+    /// Waits for a keypress and store the result in register VX.
+    fn wait_vx(&mut self, x: u8) {
+        self.wait_for_key = (true, x);
+        self.wait_pressed = None;
+        self.notify(|observer, chip| observer.on_key_wait(chip, x));
+    }
+
+    /// Sets the delay timer to the value of register VX.
+    fn set_vx_delay(&mut self, x: u8) {
+        self.delay_timer = self.v[x as usize];
+        self.pc += 2;
+    }
+
+    /// Sets the sound timer to the value of register VX.
+    fn set_vx_sound(&mut self, x: u8) {
+        self.set_sound(self.v[x as usize]);
+        self.pc += 2;
+    }
+
+    /// Adds the value stored in register VX to register I.
+    fn add_vx_to_i(&mut self, x: u8) {
+        self.i += self.v[x as usize] as usize;
+        self.pc += 2;
+    }
+
+    /// Sets I to the memory address of the sprite data corresponding to the hexadecimal digit
+    /// stored in register VX.
+    fn set_i_sprite(&mut self, x: u8) {
+        // Multiply by 5 because a sprite has 5 lines, a line equates to one byte.
+        self.i = (self.v[x as usize] & 0xF) as usize * 5;
+        self.pc += 2;
+    }
+
+    /// Sets I to the location of the SCHIP 8x10 sprite for the character
+    /// in register VX.
+    fn set_i_big_sprite(&mut self, x: u8) {
+        self.i = BIG_FONT_START + (self.v[x as usize] & 0xF) as usize * 10;
+        self.pc += 2;
+    }
+
+    /// Stores the binary-coded decimal equivalent of the value stored in register VX at addresses I, I+1, and I+2
+    fn set_bcd_vx(&mut self, x: u8) -> Result<(), Error> {
+        let vx = self.read_reg_vn(x);
+        let i = self.mem_range_mut(self.i, 3)?.start;
+        self.memory[i] = vx / 100;
+        self.memory[i+1] = (vx / 10) % 10;
+        self.memory[i+2] = (vx % 100) % 10;
+        self.invalidate(i..i + 3);
+        self.notify(|observer, chip| observer.on_write(chip, i..i + 3));
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Stores the values of registers V0 to VX inclusive in memory starting at address I
+    /// I is set to I + X + 1 after operation.
+    fn set_mem_regs(&mut self, x: u8) -> Result<(), Error> {
+        let len = x as usize + 1;
+        let dest = self.mem_range_mut(self.i, len)?;
+        self.memory[dest.clone()].copy_from_slice(&self.v[..len]);
+        self.invalidate(dest.clone());
+        self.notify(|observer, chip| observer.on_write(chip, dest.clone()));
+        self.i += len;
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Fills registers V0 to VX inclusive with the values stored in memory starting at address I
+    /// I is set to I + X + 1 after operation.
+    fn fill_regs_mem(&mut self, x: u8) -> Result<(), Error> {
+        let len = x as usize + 1;
+        let src = self.mem_range(self.i, len)?;
+        self.v[..len].copy_from_slice(&self.memory[src.clone()]);
+        self.notify(|observer, chip| observer.on_read(chip, src.clone()));
+        self.i += len;
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Stores registers VX to VY in memory starting at address I, in reverse
+    /// order when X > Y. I is unchanged.
+    fn store_range(&mut self, x: u8, y: u8) -> Result<(), Error> {
+        let (x, y) = (x as usize, y as usize);
+        let dest = self.mem_range_mut(self.i, x.max(y) - x.min(y) + 1)?;
+        for (offset, addr) in dest.clone().enumerate() {
+            self.memory[addr] = self.v[if x <= y {x + offset} else {x - offset}];
+        }
+        self.invalidate(dest.clone());
+        self.notify(|observer, chip| observer.on_write(chip, dest.clone()));
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Loads registers VX to VY from memory starting at address I, in
+    /// reverse order when X > Y. I is unchanged.
+    fn load_range(&mut self, x: u8, y: u8) -> Result<(), Error> {
+        let (x, y) = (x as usize, y as usize);
+        let src = self.mem_range(self.i, x.max(y) - x.min(y) + 1)?;
+        for (offset, addr) in src.clone().enumerate() {
+            self.v[if x <= y {x + offset} else {x - offset}] = self.memory[addr];
+        }
+        self.notify(|observer, chip| observer.on_read(chip, src.clone()));
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Loads the audio pattern from the 16 bytes at I.
+    fn load_pattern(&mut self) -> Result<(), Error> {
+        let src = self.mem_range(self.i, AUDIO_PATTERN_SIZE)?;
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        pattern.copy_from_slice(&self.memory[src.clone()]);
+        self.notify(|observer, chip| observer.on_read(chip, src.clone()));
+        self.pattern = Some(pattern);
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Sets the audio pattern playback rate to the value of register VX.
+    fn set_pitch_vx(&mut self, x: u8) {
+        self.pitch = self.v[x as usize];
+        self.pc += 2;
+    }
+
+    /// Stores the values of registers V0 to VX inclusive in the RPL flags.
+    fn save_flags(&mut self, x: u8) {
+        let len = x as usize + 1;
+        self.rpl[..len].copy_from_slice(&self.v[..len]);
+        self.pc += 2;
+    }
+
+    /// Fills registers V0 to VX inclusive from the RPL flags.
+    fn load_flags(&mut self, x: u8) {
+        let len = x as usize + 1;
+        self.v[..len].copy_from_slice(&self.rpl[..len]);
+        self.pc += 2;
+    }
+}
@@ -0,0 +1,253 @@
+//! Runs several machines side by side, e.g. to compare quirk settings.
+//!
+//! `ruchip8 compare [--frames N] [--seed N] SPEC SPEC...` where each SPEC is
+//! a ROM path, optionally followed by quirks as in `game.ch8:shift-vy`. All
+//! machines share the seed and the frame clock, so any divergence comes from
+//! the ROMs or the quirks. The first frame each machine's screen differs from
+//! the first machine's is reported, followed by the final screens.
+//!
+//! `ruchip8 quirkdiff [--instructions N] [--seed N] ROM QUIRK,... QUIRK,...`
+//! goes finer: it runs two machines in lockstep, instruction by
+//! instruction, and reports the first one after which their states differ,
+//! to tell which quirk a misbehaving ROM depends on. `none` stands for the
+//! default quirks.
+//!
+//! Quirks are `shift-vy` or `shift-vx`, which register 8XY6 and 8XYE shift,
+//! and `key-press` or `key-release`, when FX0A lets go.
+
+use std::fs;
+
+use ruchip8::{Chip8, KeyWait, REGISTER_SIZE};
+
+use difftest::{self, Stepper};
+use session::Session;
+
+const USAGE: &str = "usage: ruchip8 compare [--frames N] [--seed N] ROM[:QUIRK,...] ROM[:QUIRK,...]...";
+const QUIRKDIFF_USAGE: &str = "usage: ruchip8 quirkdiff [--instructions N] [--seed N] ROM QUIRK,... QUIRK,...";
+
+struct Machine {
+    name: String,
+    session: Session,
+    /// The first frame the screen differed from the first machine's.
+    diverged: Option<u32>,
+    /// The frame the machine stopped on an error.
+    stopped: Option<u32>,
+}
+
+fn machine(spec: &str, seed: u64) -> Result<Machine, String> {
+    Ok(Machine { name: spec.to_owned(), session: session(spec, seed)?, diverged: None, stopped: None })
+}
+
+/// A session running the ROM of `spec`, `ROM[:QUIRK,...]`, seeded with
+/// `seed`.
+pub fn session(spec: &str, seed: u64) -> Result<Session, String> {
+    let mut parts = spec.splitn(2, ':');
+    let path = parts.next().unwrap_or(spec);
+    let mut chip = Chip8::new();
+    for quirk in parts.next().into_iter().flat_map(|quirks| quirks.split(',')).filter(|quirk| !quirk.is_empty()) {
+        match quirk {
+            "shift-vy" => chip.set_shift_vy(true),
+            "shift-vx" => chip.set_shift_vy(false),
+            "key-press" => chip.set_key_wait(KeyWait::Press),
+            "key-release" => chip.set_key_wait(KeyWait::Release),
+            _ => return Err(format!("unknown quirk '{}', expected shift-vy, shift-vx, key-press or key-release", quirk)),
+        }
+    }
+
+    let rom = fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let mut session = Session::new(chip);
+    session.seed = Some(seed);
+    session.load(&rom).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(session)
+}
+
+/// Draws a screen with half blocks, two pixel rows per line.
+fn render(session: &Session) -> Vec<String> {
+    let display = session.chip.display();
+    (0..display.height() / 2).map(|row| {
+        (0..display.width()).map(|x| {
+            match (display.pixel(x, row * 2), display.pixel(x, row * 2 + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            }
+        }).collect()
+    }).collect()
+}
+
+pub fn command(args: &[String]) -> Result<(), String> {
+    let mut frames = 600u32;
+    let mut seed = None;
+    let mut specs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let n = args.next().ok_or("--frames needs a count")?;
+                frames = n.parse().map_err(|_| format!("invalid frame count '{}'", n))?;
+            },
+            "--seed" => {
+                let n = args.next().ok_or("--seed needs a number")?;
+                seed = Some(n.parse().map_err(|_| format!("invalid seed '{}'", n))?);
+            },
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'\n{}", flag, USAGE)),
+            spec => specs.push(spec),
+        }
+    }
+    if specs.len() < 2 {
+        return Err(USAGE.to_owned());
+    }
+
+    let seed = seed.unwrap_or_else(rand::random);
+    println!("seed {}, {} frames", seed, frames);
+    let mut machines = specs.iter()
+        .map(|spec| machine(spec, seed))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for frame in 0..frames {
+        for machine in machines.iter_mut() {
+            machine.session.run_frame();
+            if machine.stopped.is_none() && machine.session.error.is_some() {
+                machine.stopped = Some(frame);
+            }
+        }
+        let (first, rest) = machines.split_at_mut(1);
+        let reference = first[0].session.chip.display().rows();
+        for machine in rest.iter_mut().filter(|m| m.diverged.is_none()) {
+            if machine.session.chip.display().rows() != reference {
+                machine.diverged = Some(frame);
+            }
+        }
+    }
+
+    for machine in &machines[1..] {
+        match machine.diverged {
+            Some(frame) => println!("{}: screen differs from {} from frame {}", machine.name, machines[0].name, frame),
+            None => println!("{}: screen matches {}", machine.name, machines[0].name),
+        }
+    }
+    for machine in &machines {
+        if let (Some(frame), Some(e)) = (machine.stopped, machine.session.error) {
+            println!("{}: stopped at frame {}: {}", machine.name, frame, e);
+        }
+    }
+
+    println!();
+    let names: Vec<String> = machines.iter()
+        .map(|m| format!("{:<width$.width$}", m.name, width = m.session.chip.display().width()))
+        .collect();
+    println!("{}", names.join("  "));
+    let screens: Vec<Vec<String>> = machines.iter().map(|m| render(&m.session)).collect();
+    let rows = screens.iter().map(Vec::len).max().unwrap_or(0);
+    for row in 0..rows {
+        // Machines in 64x32 run out of rows before those in 128x64.
+        let line: Vec<String> = machines.iter().zip(&screens).map(|(m, screen)| {
+            let width = m.session.chip.display().width();
+            screen.get(row).cloned().unwrap_or_else(|| " ".repeat(width))
+        }).collect();
+        println!("{}", line.join("  "));
+    }
+    Ok(())
+}
+
+/// What differs between the states of two machines, `None` if nothing.
+fn state_difference(a: &Session, b: &Session) -> Option<String> {
+    let (a, b) = (&a.chip, &b.chip);
+    if a.pc() != b.pc() {
+        return Some(format!("PC {:04X} against {:04X}", a.pc(), b.pc()));
+    }
+    if let Some(n) = (0..REGISTER_SIZE as u8).find(|&n| a.v(n) != b.v(n)) {
+        return Some(format!("V{:X} {:02X} against {:02X}", n, a.v(n), b.v(n)));
+    }
+    if a.i() != b.i() {
+        return Some(format!("I {:04X} against {:04X}", a.i(), b.i()));
+    }
+    if (a.delay_timer(), a.sound_timer()) != (b.delay_timer(), b.sound_timer()) {
+        return Some(format!("timers DT={:02X} ST={:02X} against DT={:02X} ST={:02X}",
+                            a.delay_timer(), a.sound_timer(), b.delay_timer(), b.sound_timer()));
+    }
+    if a.stack() != b.stack() {
+        return Some("the stack".to_owned());
+    }
+    if let Some(addr) = a.memory().iter().zip(b.memory()).position(|(x, y)| x != y) {
+        return Some(format!("memory at {:04X}, {:02X} against {:02X}", addr, a.memory()[addr], b.memory()[addr]));
+    }
+    if a.display().rows() != b.display().rows() {
+        return Some("the screen".to_owned());
+    }
+    if a.is_halted() != b.is_halted() {
+        return Some("one machine exited".to_owned());
+    }
+    None
+}
+
+/// The quirk an opcode depends on, when it is known to.
+fn hint(opcode: &str) -> Option<&'static str> {
+    let opcode = opcode.to_ascii_uppercase();
+    match opcode.as_bytes() {
+        [b'8', _, _, b'6'] | [b'8', _, _, b'E'] => Some("shift-vy against shift-vx"),
+        [b'F', _, b'0', b'A'] => Some("key-press against key-release"),
+        _ => None,
+    }
+}
+
+/// `ruchip8 quirkdiff`, see the module documentation.
+pub fn lockstep(args: &[String]) -> Result<(), String> {
+    let mut instructions = 100_000u64;
+    let mut seed = None;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--instructions" => {
+                let n = args.next().ok_or("--instructions needs a count")?;
+                instructions = n.parse().map_err(|_| format!("invalid instruction count '{}'", n))?;
+            },
+            "--seed" => {
+                let n = args.next().ok_or("--seed needs a number")?;
+                seed = Some(n.parse().map_err(|_| format!("invalid seed '{}'", n))?);
+            },
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'\n{}", flag, QUIRKDIFF_USAGE)),
+            arg => rest.push(arg),
+        }
+    }
+    let (rom, profiles) = match rest[..] {
+        [rom, a, b] => (rom, [a, b]),
+        _ => return Err(QUIRKDIFF_USAGE.to_owned()),
+    };
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut machines = Vec::new();
+    for &quirks in &profiles {
+        let quirks = if quirks == "none" { "" } else { quirks };
+        machines.push(Stepper::new(session(&format!("{}:{}", rom, quirks), seed)?));
+    }
+    println!("seed {}, {} against {}", seed, profiles[0], profiles[1]);
+
+    for n in 1..=instructions {
+        if machines.iter().all(|machine| machine.session.chip.is_halted()) {
+            println!("both exited after {} instructions without diverging", n - 1);
+            return Ok(());
+        }
+        let mut records = Vec::new();
+        for (machine, quirks) in machines.iter_mut().zip(&profiles) {
+            let record = machine.step().map_err(|e| format!("instruction {} failed under {}: {}", n, quirks, e))?;
+            records.push(record);
+        }
+        if let Some(difference) = state_difference(&machines[0].session, &machines[1].session) {
+            println!("diverged after instruction {}: {}\n", n, difference);
+            for (record, quirks) in records.iter().zip(&profiles) {
+                println!("{:<16} {}", quirks, difftest::line(record));
+            }
+            for (machine, quirks) in machines.iter().zip(&profiles) {
+                println!("\n{}\n{}", quirks, difftest::registers(&machine.session));
+            }
+            if let Some(quirk) = hint(&records[0].opcode) {
+                println!("\n{} runs differently under {}", records[0].mnemonic, quirk);
+            }
+            return Ok(());
+        }
+    }
+    println!("no divergence in {} instructions", instructions);
+    Ok(())
+}
@@ -0,0 +1,56 @@
+//! Buffered, non-blocking connections to the clients of the servers.
+//!
+//! The control socket, the debugger and the debug adapter all poll their
+//! clients between frames: whatever arrived is read into `input`, answers
+//! queue in `output` and go out as fast as the other end takes them, and
+//! nothing ever waits on a slow client.
+
+use std::io::{self, Read, Write};
+
+/// A stream set to not block and the bytes in flight either way.
+pub struct Connection<S> {
+    stream: S,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+}
+
+impl<S: Read + Write> Connection<S> {
+    pub fn new(stream: S) -> Self {
+        Connection { stream, input: Vec::new(), output: Vec::new() }
+    }
+
+    /// Pulls in pending bytes. Returns false once the peer hung up.
+    pub fn read(&mut self) -> bool {
+        let mut buf = [0; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    /// Pushes out as much queued output as the stream takes.
+    pub fn flush(&mut self) -> bool {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return false,
+                Ok(n) => { self.output.drain(..n); },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+        self.stream.flush().is_ok()
+    }
+
+    /// The next line in `input`, its newline included.
+    pub fn line(&mut self) -> Option<String> {
+        let end = self.input.iter().position(|&b| b == b'\n')?;
+        let line: Vec<u8> = self.input.drain(..=end).collect();
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+}
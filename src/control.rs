@@ -0,0 +1,367 @@
+//! A control protocol for scripts, on stdin and stdout or a unix socket.
+//!
+//! `--stdio` reads one JSON command per line and answers each with one
+//! line of JSON, so any language that can start a process can drive the
+//! emulator. Log lines go to stderr and stay out of the way.
+//! `--control-socket PATH` speaks the same to every client of a unix
+//! socket, for instances that run for long:
+//!
+//! ```text
+//! echo '{"cmd":"save-state","slot":1}' | nc -U /tmp/ruchip8.sock
+//! ```
+//!
+//! Commands:
+//!
+//! ```text
+//! {"cmd":"load","path":"pong.ch8"}          or "rom" with the ROM as base64
+//! {"cmd":"pause"}  {"cmd":"resume"}
+//! {"cmd":"reset"}                           memory kept, "hard":true reloads the ROM
+//! {"cmd":"step","n":100}                    pause, run 100 instructions
+//! {"cmd":"frames","n":60}                   run 60 frames right away
+//! {"cmd":"key","key":5,"pressed":true}
+//! {"cmd":"read-mem","addr":512,"len":16}
+//! {"cmd":"write-mem","addr":768,"bytes":[170,187]}  while paused
+//! {"cmd":"screenshot","scale":4}            the screen as a base64 PNG,
+//!                                           "format":"pbm" or "xbm" as text
+//! {"cmd":"save-state","slot":1}             slots 1 to 10, as F1 to F10
+//! {"cmd":"load-state","slot":1}
+//! {"cmd":"state"}
+//! {"cmd":"quit"}
+//! ```
+//!
+//! Answers carry `"ok":true` and the machine state, with `bytes`, `png` or `text`
+//! added for those commands, or `"ok":false` and an `error`. An `id` given
+//! in a command comes back in its answer:
+//!
+//! ```text
+//! {"id":1,"ok":true,"state":{"paused":true,"halted":false,"error":null,"pc":712,..}}
+//! ```
+//!
+//! With `--stdio` the emulator quits when stdin closes.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use ruchip8::{screenshot, KEY_COUNT};
+
+#[cfg(unix)]
+use connection::Connection;
+use json::{base64, from_base64, Json};
+use savestate;
+use session::{Remote, Session};
+
+/// Screenshots are pixel for pixel unless asked otherwise.
+const SCREEN_SCALE: u64 = 1;
+
+pub struct StdioServer {
+    /// Lines read, `None` once stdin closed.
+    lines: Receiver<Option<String>>,
+}
+
+impl StdioServer {
+    pub fn new() -> Self {
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) => if tx.send(Some(line)).is_err() { return },
+                    Err(_) => break,
+                }
+            }
+            let _ = tx.send(None);
+        });
+        StdioServer { lines }
+    }
+}
+
+fn number(command: &Json, name: &str, default: u64) -> Result<u64, String> {
+    match command.get(name) {
+        None => Ok(default),
+        Some(value) => value.as_u64().ok_or_else(|| format!("'{}' must be a whole number", name)),
+    }
+}
+
+/// Runs `command`, returning what to add to the answer besides the state.
+fn execute(command: &Json, session: &mut Session) -> Result<Vec<(&'static str, Json)>, String> {
+    let name = command.get("cmd").and_then(Json::as_str).ok_or("a command needs a 'cmd'")?;
+    match name {
+        "load" => {
+            let rom = match (command.get("path").and_then(Json::as_str), command.get("rom").and_then(Json::as_str)) {
+                (Some(path), _) => fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?,
+                (None, Some(rom)) => from_base64(rom).ok_or("'rom' is not base64")?,
+                (None, None) => return Err("load needs a 'path' or a 'rom'".to_owned()),
+            };
+            session.load(&rom).map_err(|e| e.to_string())?;
+            session.rom_path = command.get("path").and_then(Json::as_str).map(PathBuf::from);
+            session.patches.clear();
+        },
+        "pause" => session.paused = true,
+        "resume" => session.paused = false,
+        "reset" => match command.get("hard").and_then(Json::as_bool) {
+            Some(true) => session.hard_reset(),
+            _ => session.soft_reset(),
+        },
+        "step" => {
+            let n = number(command, "n", 1)?;
+            session.paused = true;
+            // Stopping early leaves the reason in the state.
+            let _ = session.step(n.min(u32::MAX as u64) as u32);
+        },
+        "frames" => {
+            let paused = session.paused;
+            session.paused = false;
+            for _ in 0..number(command, "n", 1)? {
+                session.run_frame();
+                if session.paused {
+                    break;
+                }
+            }
+            session.paused |= paused;
+        },
+        "key" => {
+            let key = number(command, "key", u64::MAX)?;
+            if key >= KEY_COUNT as u64 {
+                return Err("'key' must be from 0 to 15".to_owned());
+            }
+            let pressed = command.get("pressed").and_then(Json::as_bool).ok_or("key needs 'pressed'")?;
+            session.set_key(key as u8, pressed);
+        },
+        "read-mem" => {
+            let addr = number(command, "addr", 0)? as usize;
+            let len = number(command, "len", 16)? as usize;
+            let memory = session.chip.memory();
+            let bytes = addr.checked_add(len).and_then(|end| memory.get(addr..end)).ok_or("range outside of memory")?;
+            return Ok(vec![("bytes", Json::Array(bytes.iter().map(|&b| (b as u64).into()).collect()))]);
+        },
+        "write-mem" => {
+            let addr = number(command, "addr", 0)? as usize;
+            let bytes = command.get("bytes").and_then(Json::as_array).ok_or("write-mem needs 'bytes'")?
+                .iter()
+                .map(|byte| byte.as_u64().filter(|&byte| byte <= 0xFF).map(|byte| byte as u8))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or("'bytes' must be numbers from 0 to 255")?;
+            session.poke(addr, &bytes)?;
+        },
+        "screenshot" => {
+            let scale = number(command, "scale", SCREEN_SCALE)?.clamp(1, 64) as usize;
+            let display = session.chip.display();
+            return match command.get("format").and_then(Json::as_str).unwrap_or("png") {
+                "png" => Ok(vec![("png", base64(&screenshot::to_png_in(display, scale, &session.palette)).into())]),
+                "pbm" => Ok(vec![("text", screenshot::to_pbm(display, scale).into())]),
+                "xbm" => Ok(vec![("text", screenshot::to_xbm(display, scale, "screen").into())]),
+                format => Err(format!("unknown format '{}', expected png, pbm or xbm", format)),
+            };
+        },
+        "save-state" => savestate::save(session, number(command, "slot", 1)? as usize)?,
+        "load-state" => savestate::load(session, number(command, "slot", 1)? as usize)?,
+        "state" => {},
+        "quit" => session.quit = true,
+        _ => return Err(format!("unknown command '{}'", name)),
+    }
+    Ok(Vec::new())
+}
+
+/// The answer to one line of a client.
+fn answer(line: &str, session: &mut Session) -> Json {
+    let command = match Json::parse(line) {
+        Ok(command) => command,
+        Err(e) => return Json::object(vec![("ok", false.into()), ("error", format!("invalid JSON: {}", e).into())]),
+    };
+    let mut members = Vec::new();
+    if let Some(id) = command.get("id") {
+        members.push(("id", id.clone()));
+    }
+    match execute(&command, session) {
+        Ok(extra) => {
+            members.push(("ok", true.into()));
+            members.extend(extra);
+            members.push(("state", session.state()));
+        },
+        Err(e) => {
+            members.push(("ok", false.into()));
+            members.push(("error", e.into()));
+        },
+    }
+    Json::object(members)
+}
+
+impl Remote for StdioServer {
+    fn poll(&mut self, session: &mut Session) {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        while let Ok(line) = self.lines.try_recv() {
+            let line = match line {
+                Some(line) => line,
+                None => {
+                    session.quit = true;
+                    break;
+                },
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let _ = writeln!(stdout, "{}", answer(&line, session));
+            if session.quit {
+                break;
+            }
+        }
+        let _ = stdout.flush();
+    }
+}
+
+/// The protocol served on a unix socket.
+#[cfg(unix)]
+pub struct SocketServer {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<Connection<UnixStream>>,
+}
+
+#[cfg(unix)]
+impl SocketServer {
+    /// Listens at `path`, replacing a socket left behind by an instance
+    /// that is gone but refusing to take over one that still answers.
+    pub fn bind(path: &Path) -> Result<Self, String> {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(format!("{} is in use by another instance", path.display()));
+            }
+            fs::remove_file(path).map_err(|e| format!("cannot replace {}: {}", path.display(), e))?;
+        }
+        let listener = UnixListener::bind(path)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|e| format!("cannot listen on {}: {}", path.display(), e))?;
+        info!("control socket at {}", path.display());
+        Ok(SocketServer { path: path.to_owned(), listener, clients: Vec::new() })
+    }
+}
+
+#[cfg(unix)]
+impl Remote for SocketServer {
+    fn poll(&mut self, session: &mut Session) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(Connection::new(stream));
+            }
+        }
+        self.clients.retain_mut(|client| {
+            let alive = client.read();
+            while let Some(line) = client.line() {
+                if !line.trim().is_empty() {
+                    client.output.extend_from_slice(answer(&line, session).to_string().as_bytes());
+                    client.output.push(b'\n');
+                }
+            }
+            // A client that sent its commands and shut its end still gets
+            // the answers.
+            client.flush() && (alive || !client.output.is_empty())
+        });
+    }
+
+    fn close(&mut self, _session: &Session) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("cannot remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruchip8::Chip8;
+
+    /// A session running a ROM that counts V0 up forever.
+    fn session() -> Session {
+        let mut session = Session::new(Chip8::new());
+        session.load(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        session
+    }
+
+    #[test]
+    fn answers_carry_the_id_and_the_state() {
+        let mut session = session();
+        let reply = answer(r#"{"id":7,"cmd":"pause"}"#, &mut session);
+        assert_eq!(reply.get("id"), Some(&Json::from(7u64)));
+        assert_eq!(reply.get("ok"), Some(&Json::Bool(true)));
+        assert_eq!(reply.get("state").and_then(|state| state.get("paused")), Some(&Json::Bool(true)));
+    }
+
+    #[test]
+    fn errors_say_what_went_wrong() {
+        let mut session = session();
+        for (line, error) in [
+            ("not json", None),
+            (r#"{"id":1}"#, Some("a command needs a 'cmd'")),
+            (r#"{"cmd":"dance"}"#, Some("unknown command 'dance'")),
+            (r#"{"cmd":"key","key":16,"pressed":true}"#, Some("'key' must be from 0 to 15")),
+            (r#"{"cmd":"step","n":-1}"#, Some("'n' must be a whole number")),
+            (r#"{"cmd":"write-mem","addr":768,"bytes":[1]}"#, Some("pause first")),
+        ] {
+            let reply = answer(line, &mut session);
+            assert_eq!(reply.get("ok"), Some(&Json::Bool(false)), "{}", line);
+            if let Some(error) = error {
+                assert_eq!(reply.get("error").and_then(Json::as_str), Some(error), "{}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn steps_and_pauses() {
+        let mut session = session();
+        execute(&Json::parse(r#"{"cmd":"step","n":3}"#).unwrap(), &mut session).unwrap();
+        assert!(session.paused);
+        assert_eq!(session.chip.v(0), 2);
+        // Counts past u32::MAX are clamped, not wrapped to none at all.
+        session.load(&[0x00, 0xFD]).unwrap();
+        execute(&Json::parse(r#"{"cmd":"step","n":4294967296}"#).unwrap(), &mut session).unwrap();
+        assert!(session.chip.is_halted());
+    }
+
+    #[test]
+    fn reads_and_writes_memory() {
+        let mut session = session();
+        answer(r#"{"cmd":"pause"}"#, &mut session);
+        let reply = answer(r#"{"cmd":"write-mem","addr":768,"bytes":[170,187]}"#, &mut session);
+        assert_eq!(reply.get("ok"), Some(&Json::Bool(true)));
+        let bytes = execute(&Json::parse(r#"{"cmd":"read-mem","addr":768,"len":2}"#).unwrap(), &mut session).unwrap();
+        assert_eq!(bytes, vec![("bytes", Json::Array(vec![170u64.into(), 187u64.into()]))]);
+
+        let len = session.chip.memory().len();
+        for line in [
+            format!(r#"{{"cmd":"read-mem","addr":{},"len":1}}"#, len),
+            r#"{"cmd":"read-mem","addr":1,"len":18446744073709500000}"#.to_owned(),
+            format!(r#"{{"cmd":"write-mem","addr":{},"bytes":[1,2]}}"#, len - 1),
+            r#"{"cmd":"write-mem","addr":768,"bytes":[256]}"#.to_owned(),
+        ] {
+            assert!(execute(&Json::parse(&line).unwrap(), &mut session).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn presses_keys_and_quits() {
+        let mut session = session();
+        answer(r#"{"cmd":"key","key":5,"pressed":true}"#, &mut session);
+        assert_eq!(session.local_keys(), 1 << 5);
+        answer(r#"{"cmd":"quit"}"#, &mut session);
+        assert!(session.quit);
+    }
+
+    #[test]
+    fn screenshots_in_each_format() {
+        let mut session = session();
+        let shot = |session: &mut Session, line: &str| execute(&Json::parse(line).unwrap(), session);
+        let png = shot(&mut session, r#"{"cmd":"screenshot","scale":1}"#).unwrap();
+        assert_eq!(png[0].0, "png");
+        let pbm = shot(&mut session, r#"{"cmd":"screenshot","format":"pbm"}"#).unwrap();
+        assert!(pbm[0].1.as_str().is_some_and(|text| text.starts_with("P1")));
+        assert!(shot(&mut session, r#"{"cmd":"screenshot","format":"gif"}"#).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Which bytes of a ROM ran and which were read as data.
+//!
+//! `--plugin coverage[=FILE]` marks every byte the machine executed as an
+//! instruction and every byte an instruction read as data, sprites for
+//! DXYN, registers for FX65 and XO-CHIP's 5XY3, the pattern for F002.
+//! When the session ends it writes a report to FILE, `coverage.txt` unless
+//! given: totals, then the ROM as runs of code, data and bytes never
+//! touched. A FILE ending in `.html` gets a hexdump colored by the same
+//! marks instead. Loading another ROM starts the count over.
+//!
+//! The ROM is taken to end at its last nonzero byte.
+
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use ruchip8::observer::EmuObserver;
+use ruchip8::{Chip8, Instruction, PROGRAM_START};
+
+use session::{Remote, Session};
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+
+/// A mark per byte of memory.
+type Marks = Arc<Mutex<Vec<u8>>>;
+
+struct Marker {
+    marks: Marks,
+}
+
+impl Marker {
+    fn mark(&self, addrs: Range<usize>, mark: u8) {
+        let mut marks = self.marks.lock().unwrap();
+        let end = addrs.end.min(marks.len());
+        for byte in &mut marks[addrs.start.min(end)..end] {
+            *byte |= mark;
+        }
+    }
+}
+
+impl EmuObserver for Marker {
+    fn on_instruction(&mut self, _chip: &Chip8, pc: usize, instruction: Instruction) {
+        self.mark(pc..pc + instruction.size(), EXECUTED);
+    }
+
+    fn on_read(&mut self, _chip: &Chip8, addrs: Range<usize>) {
+        self.mark(addrs, READ);
+    }
+}
+
+fn kind(mark: u8) -> &'static str {
+    match mark {
+        EXECUTED => "code",
+        READ => "data",
+        0 => "untouched",
+        _ => "code and data",
+    }
+}
+
+pub struct Coverage {
+    path: PathBuf,
+    rom: Option<u64>,
+    /// The ROM's addresses.
+    extent: Range<usize>,
+    marks: Marks,
+}
+
+impl Coverage {
+    fn summary(&self, marks: &[u8]) -> String {
+        let rom = &marks[self.extent.clone()];
+        let count = |mark: u8| rom.iter().filter(|&&byte| byte == mark).count();
+        let percent = |n: usize| n as f64 * 100.0 / rom.len().max(1) as f64;
+        let mut text = format!("ROM {:04X}-{:04X}, {} bytes\n", self.extent.start, self.extent.end.saturating_sub(1), rom.len());
+        for mark in [EXECUTED, READ, EXECUTED | READ, 0] {
+            let n = count(mark);
+            writeln!(text, "{:<14} {:>6} bytes {:>6.1}%", kind(mark), n, percent(n)).unwrap();
+        }
+        text
+    }
+
+    /// The ROM as runs of bytes with the same mark.
+    fn runs(&self, marks: &[u8]) -> Vec<(Range<usize>, u8)> {
+        let mut runs: Vec<(Range<usize>, u8)> = Vec::new();
+        for addr in self.extent.clone() {
+            match runs.last_mut() {
+                Some((range, mark)) if *mark == marks[addr] => range.end = addr + 1,
+                _ => runs.push((addr..addr + 1, marks[addr])),
+            }
+        }
+        runs
+    }
+
+    fn text(&self, marks: &[u8]) -> String {
+        let mut text = self.summary(marks);
+        text.push('\n');
+        for (range, mark) in self.runs(marks) {
+            writeln!(text, "{:04X}-{:04X} {:>5}  {}", range.start, range.end - 1, range.len(), kind(mark)).unwrap();
+        }
+        text
+    }
+
+    fn html(&self, marks: &[u8], memory: &[u8]) -> String {
+        let mut html = String::from("<!DOCTYPE html>\n<meta charset=\"utf-8\">\n<title>ruchip8 coverage</title>\n<style>\n\
+            body { font-family: monospace; background: #111; color: #ccc; }\n\
+            .code { background: #264d26; } .data { background: #1f3d66; } .both { background: #5c4d1a; }\n\
+            </style>\n<pre>");
+        html.push_str(&self.summary(marks));
+        html.push_str("\n<span class=\"code\">code</span> <span class=\"data\">data</span> <span class=\"both\">code and data</span> untouched\n\n");
+        let start = self.extent.start / 16 * 16;
+        for row in (start..self.extent.end).step_by(16) {
+            write!(html, "{:04X} ", row).unwrap();
+            for addr in row..row + 16 {
+                if !self.extent.contains(&addr) {
+                    html.push_str("   ");
+                    continue;
+                }
+                let class = match marks[addr] {
+                    0 => None,
+                    EXECUTED => Some("code"),
+                    READ => Some("data"),
+                    _ => Some("both"),
+                };
+                match class {
+                    Some(class) => write!(html, " <span class=\"{}\">{:02X}</span>", class, memory[addr]).unwrap(),
+                    None => write!(html, " {:02X}", memory[addr]).unwrap(),
+                }
+            }
+            html.push('\n');
+        }
+        html.push_str("</pre>\n");
+        html
+    }
+}
+
+/// The `--plugin coverage[=FILE]` remote.
+pub fn remote(arg: Option<&str>) -> Result<Box<dyn Remote>, String> {
+    Ok(Box::new(Coverage {
+        path: PathBuf::from(arg.unwrap_or("coverage.txt")),
+        rom: None,
+        extent: PROGRAM_START..PROGRAM_START,
+        marks: Arc::new(Mutex::new(Vec::new())),
+    }))
+}
+
+impl Remote for Coverage {
+    fn poll(&mut self, session: &mut Session) {
+        if session.rom_hash == self.rom {
+            return;
+        }
+        let memory = session.chip.memory();
+        let end = memory.iter().rposition(|&byte| byte != 0).map_or(PROGRAM_START, |last| last + 1);
+        self.extent = PROGRAM_START..end.max(PROGRAM_START);
+        *self.marks.lock().unwrap() = vec![0; memory.len()];
+        if self.rom.is_none() {
+            session.chip.add_observer(Box::new(Marker { marks: self.marks.clone() }));
+        }
+        self.rom = session.rom_hash;
+    }
+
+    fn close(&mut self, session: &Session) {
+        if self.rom.is_none() {
+            return;
+        }
+        let marks = self.marks.lock().unwrap();
+        let html = self.path.extension().is_some_and(|extension| extension == "html");
+        let report = if html { self.html(&marks, session.chip.memory()) } else { self.text(&marks) };
+        match fs::write(&self.path, report) {
+            Ok(()) => info!("wrote coverage to {}", self.path.display()),
+            Err(e) => warn!("cannot write {}: {}", self.path.display(), e),
+        }
+        for line in self.summary(&marks).lines() {
+            info!("{}", line);
+        }
+    }
+}
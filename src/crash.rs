@@ -0,0 +1,90 @@
+//! State dumps written when the machine stops on an error.
+//!
+//! Errors leave the machine as it was right before the faulting
+//! instruction, so the dump shows exactly what the program was about to do.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use debugger;
+use session::{Remote, Session};
+
+/// Bytes of memory shown on each side of PC.
+const MEMORY_WINDOW: usize = 0x40;
+/// Instructions disassembled on each side of PC.
+const LISTING_WINDOW: usize = 8;
+
+/// Registers, stack, memory and a disassembly around PC.
+pub fn dump(session: &Session) -> String {
+    let chip = &session.chip;
+    let pc = chip.pc();
+    let mut out = String::new();
+
+    writeln!(out, "ruchip8 {} crash dump", env!("CARGO_PKG_VERSION")).unwrap();
+    if let Some(e) = session.error {
+        writeln!(out, "error: {}", e).unwrap();
+    }
+    writeln!(out, "\n{}", debugger::registers(session)).unwrap();
+
+    writeln!(out, "stack, innermost first:").unwrap();
+    for addr in chip.stack().iter().rev() {
+        writeln!(out, "  {:04X}", addr).unwrap();
+    }
+
+    writeln!(out, "\nmemory:").unwrap();
+    if pc < chip.memory().len() {
+        let start = pc.saturating_sub(MEMORY_WINDOW) & !0xF;
+        let end = pc.saturating_add(MEMORY_WINDOW).min(chip.memory().len());
+        out.push_str(&debugger::memory(session, start, end - start).unwrap_or_default());
+    } else {
+        writeln!(out, "  PC {:04X} is past the end of memory, {} bytes", pc, chip.memory().len()).unwrap();
+    }
+
+    writeln!(out, "\ndisassembly:").unwrap();
+    out.push_str(&debugger::disassembly(session, LISTING_WINDOW));
+    out
+}
+
+/// Writes a dump whenever the session stops on a new error.
+pub struct CrashDump {
+    dir: PathBuf,
+    reported: bool,
+}
+
+impl CrashDump {
+    pub fn new(dir: PathBuf) -> Self {
+        CrashDump { dir, reported: false }
+    }
+
+    fn write(&self, session: &Session) -> Result<PathBuf, String> {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let path = self.dir.join(format!("ruchip8-crash-{}.txt", secs));
+        fs::write(&path, dump(session)).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+impl Remote for CrashDump {
+    fn poll(&mut self, _session: &mut Session) {}
+
+    fn frame(&mut self, session: &Session) {
+        let e = match session.error {
+            Some(e) => e,
+            None => {
+                self.reported = false;
+                return;
+            },
+        };
+        if self.reported {
+            return;
+        }
+        self.reported = true;
+        match self.write(session) {
+            Ok(path) => error!("the program crashed: {}. The machine state was saved to {}, \
+                                please attach it when reporting a bug", e, path.display()),
+            Err(why) => error!("the program crashed: {}, and no dump was written: {}", e, why),
+        }
+    }
+}
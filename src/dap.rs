@@ -0,0 +1,851 @@
+//! Debug Adapter Protocol server, for VS Code and other DAP clients.
+//!
+//! `--dap` speaks the protocol on stdin and stdout, for clients that start
+//! the adapter themselves, and `--dap-listen ADDR` serves one client at a
+//! time over TCP. The machine is thread 1, its call stack the stack trace:
+//! PC on top, then each CALL still waiting for its return. Breakpoints are
+//! set on source lines through the source map or on addresses as
+//! instruction breakpoints. The Registers scope holds V0 to VF, I, PC and
+//! the timers, which can be changed, and the Memory scope dumps memory 16
+//! bytes a row. Expressions typed in the debug console run as debugger
+//! commands.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use ruchip8::disasm;
+use ruchip8::{decode, decode_long, Instruction, LONG_PREFIX, MAX_MEMORY_SIZE};
+
+use cheats::Cheats;
+use connection::Connection;
+use debugger;
+use json::{base64, Json};
+use session::{Remote, Session, Temporary};
+
+/// The only thread, the machine.
+const THREAD: u64 = 1;
+/// Variable references of the two scopes.
+const REGISTERS: u64 = 1;
+const MEMORY: u64 = 2;
+/// Bytes per row of the Memory scope.
+const MEMORY_ROW: usize = 16;
+/// The largest message a client may send, in bytes.
+const MAX_MESSAGE: usize = 1 << 20;
+
+/// Where the client is: stdin with stdout, or a socket.
+enum Link {
+    /// Chunks of stdin from the thread reading it, and the rest of the
+    /// last one.
+    Stdio(Receiver<Vec<u8>>, Vec<u8>),
+    Tcp(TcpStream),
+}
+
+impl Read for Link {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Link::Stdio(ref chunks, ref mut rest) => {
+                if rest.is_empty() {
+                    match chunks.try_recv() {
+                        Ok(chunk) => *rest = chunk,
+                        Err(TryRecvError::Empty) => return Err(io::ErrorKind::WouldBlock.into()),
+                        Err(TryRecvError::Disconnected) => return Ok(0),
+                    }
+                }
+                let n = buf.len().min(rest.len());
+                buf[..n].copy_from_slice(&rest[..n]);
+                rest.drain(..n);
+                Ok(n)
+            },
+            Link::Tcp(ref mut stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Link {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Link::Stdio(..) => io::stdout().write(buf),
+            Link::Tcp(ref mut stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Link::Stdio(..) => io::stdout().flush(),
+            Link::Tcp(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+/// The client at the other end.
+struct Peer {
+    connection: Connection<Link>,
+    /// Set when the client sent something that loses the framing.
+    broken: bool,
+}
+
+impl Peer {
+    fn new(link: Link) -> Peer {
+        Peer { connection: Connection::new(link), broken: false }
+    }
+
+    /// The next whole message, framed by a `Content-Length` header.
+    fn message(&mut self) -> Option<Result<Json, String>> {
+        if self.broken {
+            return None;
+        }
+        let end = self.connection.input.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let header = String::from_utf8_lossy(&self.connection.input[..end]).into_owned();
+        let len = header.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, len)| len.trim().parse::<usize>().ok());
+        let len = match len {
+            Some(len) => len,
+            None => {
+                self.connection.input.drain(..end + 4);
+                return Some(Err("message without a Content-Length".to_owned()));
+            },
+        };
+        if len > MAX_MESSAGE {
+            self.connection.input.clear();
+            self.broken = true;
+            return Some(Err(format!("message of {} bytes, at most {} are taken", len, MAX_MESSAGE)));
+        }
+        if self.connection.input.len() < end + 4 + len {
+            return None;
+        }
+        let body: Vec<u8> = self.connection.input.drain(..end + 4 + len).skip(end + 4).collect();
+        Some(String::from_utf8(body).map_err(|e| e.to_string()).and_then(|body| Json::parse(&body)))
+    }
+
+    fn send(&mut self, message: &Json) {
+        let body = message.to_string();
+        self.connection.output.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+        self.connection.output.extend_from_slice(body.as_bytes());
+    }
+}
+
+/// What the machine was doing when last looked at, to tell when it stops.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Seen {
+    paused: bool,
+    breaks_hit: u64,
+    error: bool,
+    halted: bool,
+}
+
+impl Seen {
+    fn of(session: &Session) -> Seen {
+        Seen {
+            paused: session.paused,
+            breaks_hit: session.breaks_hit,
+            error: session.error.is_some(),
+            halted: session.chip.is_halted(),
+        }
+    }
+}
+
+pub struct DapServer {
+    listener: Option<TcpListener>,
+    peer: Option<Peer>,
+    seq: u64,
+    /// Whether the client started the emulator, which then ends with it.
+    owned: bool,
+    stop_on_entry: bool,
+    /// Breakpoint addresses by the mapped file they were set in.
+    source_breakpoints: BTreeMap<String, Vec<usize>>,
+    instruction_breakpoints: Vec<usize>,
+    /// The reason for the next stop, when the client asked for it.
+    stop_reason: Option<&'static str>,
+    /// A stop that came from handling a request, told after its response.
+    stop: Option<(&'static str, Option<String>)>,
+    /// `None` until the client is done configuring.
+    seen: Option<Seen>,
+}
+
+impl DapServer {
+    /// Serves the client on stdin and stdout.
+    pub fn stdio() -> Self {
+        let (tx, chunks) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0; 4096];
+            let stdin = io::stdin();
+            let mut stdin = stdin.lock();
+            loop {
+                match stdin.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => if tx.send(buf[..n].to_vec()).is_err() { break },
+                }
+            }
+        });
+        let peer = Peer::new(Link::Stdio(chunks, Vec::new()));
+        DapServer::new(None, Some(peer), true)
+    }
+
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        info!("debug adapter listening on {}", listener.local_addr()?);
+        Ok(DapServer::new(Some(listener), None, false))
+    }
+
+    fn new(listener: Option<TcpListener>, peer: Option<Peer>, owned: bool) -> Self {
+        DapServer {
+            listener,
+            peer,
+            seq: 0,
+            owned,
+            stop_on_entry: false,
+            source_breakpoints: BTreeMap::new(),
+            instruction_breakpoints: Vec::new(),
+            stop_reason: None,
+            stop: None,
+            seen: None,
+        }
+    }
+
+    fn send(&mut self, mut message: Vec<(&str, Json)>) {
+        self.seq += 1;
+        message.insert(0, ("seq", self.seq.into()));
+        if let Some(ref mut peer) = self.peer {
+            peer.send(&Json::object(message));
+        }
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.send(vec![("type", "event".into()), ("event", event.into()), ("body", body)]);
+    }
+
+    fn respond(&mut self, request: &Json, result: Result<Json, String>) {
+        let seq = request.get("seq").and_then(Json::as_u64).unwrap_or(0);
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("").to_owned();
+        let mut message = vec![
+            ("type", "response".into()),
+            ("request_seq", seq.into()),
+            ("command", command.into()),
+        ];
+        match result {
+            Ok(body) => {
+                message.push(("success", true.into()));
+                message.push(("body", body));
+            },
+            Err(e) => {
+                message.push(("success", false.into()));
+                message.push(("message", e.into()));
+            },
+        }
+        self.send(message);
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) {
+        let mut body = vec![
+            ("reason", reason.into()),
+            ("threadId", THREAD.into()),
+            ("allThreadsStopped", true.into()),
+        ];
+        if let Some(text) = text {
+            body.push(("text", text.into()));
+        }
+        self.event("stopped", Json::object(body));
+    }
+
+    /// Tells the client when the machine stopped by itself, once it is
+    /// done configuring.
+    fn check(&mut self, session: &Session) {
+        let now = Seen::of(session);
+        let before = match self.seen {
+            Some(before) => before,
+            None => return,
+        };
+        self.seen = Some(now);
+        if now == before {
+            return;
+        }
+        if now.error && !before.error {
+            let text = session.error.map(|e| e.to_string());
+            self.stopped("exception", text);
+        } else if now.halted && !before.halted {
+            self.stopped("pause", Some("program exited".to_owned()));
+        } else if now.paused && !before.paused {
+            let reason = match self.stop_reason.take() {
+                Some(reason) => reason,
+                None if now.breaks_hit != before.breaks_hit => "breakpoint",
+                None => "pause",
+            };
+            self.stopped(reason, None);
+        } else if !now.paused && before.paused {
+            self.event("continued", Json::object(vec![("threadId", THREAD.into()), ("allThreadsContinued", true.into())]));
+        }
+    }
+
+    fn handle(&mut self, request: &Json, session: &mut Session) -> Result<Json, String> {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+        let args = request.get("arguments").cloned().unwrap_or(Json::Null);
+        match command {
+            "initialize" => Ok(Json::object(vec![
+                ("supportsConfigurationDoneRequest", true.into()),
+                ("supportsInstructionBreakpoints", true.into()),
+                ("supportsReadMemoryRequest", true.into()),
+                ("supportsDisassembleRequest", true.into()),
+                ("supportsSetVariable", true.into()),
+                ("supportsTerminateRequest", true.into()),
+                ("supportsSteppingGranularity", true.into()),
+            ])),
+            "launch" => {
+                if let Some(program) = args.get("program").and_then(Json::as_str) {
+                    launch(session, Path::new(program))?;
+                }
+                self.stop_on_entry = args.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false);
+                self.seen = None;
+                session.paused = true;
+                Ok(Json::Null)
+            },
+            "attach" => {
+                self.stop_on_entry = true;
+                self.seen = None;
+                session.paused = true;
+                Ok(Json::Null)
+            },
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    self.seen = Some(Seen::of(session));
+                    self.stop = Some(("entry", None));
+                } else {
+                    session.paused = false;
+                    self.seen = Some(Seen::of(session));
+                }
+                Ok(Json::Null)
+            },
+            "setBreakpoints" => self.set_breakpoints(&args, session),
+            "setInstructionBreakpoints" => {
+                for addr in self.instruction_breakpoints.drain(..) {
+                    session.breakpoints.remove(&addr);
+                }
+                let mut results = Vec::new();
+                for breakpoint in args.get("breakpoints").and_then(Json::as_array).unwrap_or(&[]) {
+                    let offset = breakpoint.get("offset").and_then(Json::as_i64).unwrap_or(0);
+                    let addr = breakpoint.get("instructionReference").and_then(Json::as_str)
+                        .and_then(parse_reference)
+                        .and_then(|addr| addr.checked_add(offset))
+                        .filter(|&addr| addr >= 0 && (addr as usize) < session.chip.memory().len());
+                    match addr {
+                        Some(addr) => {
+                            session.breakpoints.insert(addr as usize);
+                            self.instruction_breakpoints.push(addr as usize);
+                            results.push(Json::object(vec![("verified", true.into()), ("instructionReference", reference(addr as usize).into())]));
+                        },
+                        None => results.push(Json::object(vec![("verified", false.into()), ("message", "not an address in memory".into())])),
+                    }
+                }
+                Ok(Json::object(vec![("breakpoints", results.into())]))
+            },
+            "setExceptionBreakpoints" => Ok(Json::object(vec![("breakpoints", Vec::new().into())])),
+            "threads" => Ok(Json::object(vec![("threads", vec![
+                Json::object(vec![("id", THREAD.into()), ("name", "CHIP-8".into())]),
+            ].into())])),
+            "stackTrace" => Ok(stack_trace(session)),
+            "scopes" => Ok(Json::object(vec![("scopes", vec![
+                Json::object(vec![("name", "Registers".into()), ("variablesReference", REGISTERS.into())]),
+                Json::object(vec![
+                    ("name", "Memory".into()),
+                    ("variablesReference", MEMORY.into()),
+                    ("indexedVariables", session.chip.memory().len().div_ceil(MEMORY_ROW).into()),
+                    ("expensive", true.into()),
+                ]),
+            ].into())])),
+            "variables" => variables(&args, session),
+            "setVariable" => set_variable(&args, session),
+            "continue" => {
+                session.paused = false;
+                self.seen = Some(Seen::of(session));
+                Ok(Json::object(vec![("allThreadsContinued", true.into())]))
+            },
+            "pause" => {
+                self.stop_reason = Some("pause");
+                session.paused = true;
+                Ok(Json::Null)
+            },
+            "next" => self.step_over(session),
+            "stepIn" => self.step(session),
+            "stepOut" => {
+                let depth = session.chip.stack().len();
+                if depth == 0 {
+                    return self.step(session);
+                }
+                self.stop_reason = Some("step");
+                session.run_to(Temporary { addr: None, depth: depth - 1 });
+                self.seen = Some(Seen::of(session));
+                Ok(Json::Null)
+            },
+            "readMemory" => read_memory(&args, session),
+            "disassemble" => disassemble(&args, session),
+            "evaluate" => {
+                let expression = args.get("expression").and_then(Json::as_str).unwrap_or("");
+                let out = debugger::execute(expression, session);
+                Ok(Json::object(vec![("result", out.trim_end().into()), ("variablesReference", 0u64.into())]))
+            },
+            "terminate" => {
+                session.quit = true;
+                Ok(Json::Null)
+            },
+            "disconnect" => {
+                let terminate = args.get("terminateDebuggee").and_then(Json::as_bool).unwrap_or(self.owned);
+                if terminate {
+                    session.quit = true;
+                } else {
+                    session.paused = false;
+                }
+                Ok(Json::Null)
+            },
+            _ => Err(format!("unsupported request '{}'", command)),
+        }
+    }
+
+    fn set_breakpoints(&mut self, args: &Json, session: &mut Session) -> Result<Json, String> {
+        let path = args.get("source").and_then(|source| source.get("path")).and_then(Json::as_str).unwrap_or("");
+        let file = session.source_map.file_for(path).map(str::to_owned);
+        let lines: Vec<u64> = args.get("breakpoints").and_then(Json::as_array).unwrap_or(&[]).iter()
+            .filter_map(|breakpoint| breakpoint.get("line").and_then(Json::as_u64))
+            .collect();
+        let file = match file {
+            Some(file) => file,
+            None => {
+                let results = lines.iter().map(|&line| Json::object(vec![
+                    ("verified", false.into()),
+                    ("line", line.into()),
+                    ("message", "no code from this file in the source map".into()),
+                ])).collect::<Vec<_>>();
+                return Ok(Json::object(vec![("breakpoints", results.into())]));
+            },
+        };
+        for addr in self.source_breakpoints.remove(&file).unwrap_or_default() {
+            session.breakpoints.remove(&addr);
+        }
+        let mut addrs = Vec::new();
+        let mut results = Vec::new();
+        for line in lines {
+            match session.source_map.addr(&file, line as usize) {
+                Some(addr) => {
+                    session.breakpoints.insert(addr);
+                    addrs.push(addr);
+                    results.push(Json::object(vec![
+                        ("verified", true.into()),
+                        ("line", line.into()),
+                        ("instructionReference", reference(addr).into()),
+                    ]));
+                },
+                None => results.push(Json::object(vec![
+                    ("verified", false.into()),
+                    ("line", line.into()),
+                    ("message", "no code from this line".into()),
+                ])),
+            }
+        }
+        self.source_breakpoints.insert(file, addrs);
+        Ok(Json::object(vec![("breakpoints", results.into())]))
+    }
+
+    fn step(&mut self, session: &mut Session) -> Result<Json, String> {
+        let result = session.step_through(1);
+        self.seen = Some(Seen::of(session));
+        self.stop = Some(match result {
+            Ok(_) => ("step", None),
+            Err(e) => ("exception", Some(e.to_string())),
+        });
+        Ok(Json::Null)
+    }
+
+    /// Steps, running a CALL through to its return.
+    fn step_over(&mut self, session: &mut Session) -> Result<Json, String> {
+        let pc = session.chip.pc();
+        if let Some((Instruction::Call { .. }, _)) = instruction_at(session, pc) {
+            let depth = session.chip.stack().len();
+            self.stop_reason = Some("step");
+            session.run_to(Temporary { addr: Some(pc + 2), depth });
+            self.seen = Some(Seen::of(session));
+            return Ok(Json::Null);
+        }
+        self.step(session)
+    }
+}
+
+/// Loads the program a `launch` request names, a ROM or Octo source, with
+/// its labels, source map and cheats.
+fn launch(session: &mut Session, path: &Path) -> Result<(), String> {
+    let program = ::read_program(path)?;
+    session.load(&program.rom).map_err(|e| e.to_string())?;
+    session.symbols = program.symbols;
+    session.source_map = program.source_map;
+    session.cheats = Cheats::for_rom(path)?;
+    Ok(())
+}
+
+/// An address as DAP memory and instruction references have it.
+fn reference(addr: usize) -> String {
+    format!("0x{:04X}", addr)
+}
+
+fn parse_reference(text: &str) -> Option<i64> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    i64::from_str_radix(digits, 16).ok()
+}
+
+/// The instruction at `addr` and its size.
+fn instruction_at(session: &Session, addr: usize) -> Option<(Instruction, usize)> {
+    let memory = session.chip.memory();
+    let word = |at: usize| memory.get(at..at.checked_add(2)?).map(|op| (op[0] as u16) << 8 | op[1] as u16);
+    let ops = word(addr)?;
+    if ops == LONG_PREFIX {
+        return word(addr.checked_add(2)?).and_then(|next| decode_long(ops, next)).map(|instruction| (instruction, 4));
+    }
+    decode(ops).map(|instruction| (instruction, 2))
+}
+
+fn instruction_text(session: &Session, addr: usize) -> String {
+    match instruction_at(session, addr) {
+        Some((instruction, _)) => disasm::symbolic(instruction, &session.symbols),
+        None => "???".to_owned(),
+    }
+}
+
+/// A stack frame for the code at `addr`.
+fn frame(session: &Session, id: usize, addr: usize) -> Json {
+    let name = match session.symbols.name(addr) {
+        Some(label) => format!("{:04X} {}: {}", addr, label, instruction_text(session, addr)),
+        None => format!("{:04X} {}", addr, instruction_text(session, addr)),
+    };
+    let mut frame = vec![
+        ("id", id.into()),
+        ("name", name.into()),
+        ("instructionPointerReference", reference(addr).into()),
+        ("line", 0u64.into()),
+        ("column", 0u64.into()),
+    ];
+    if let Some(location) = session.source_map.location(addr) {
+        let path = session.source_map.path(&location.file);
+        frame[3] = ("line", location.line.into());
+        frame.push(("source", Json::object(vec![
+            ("name", location.file.as_str().into()),
+            ("path", path.display().to_string().into()),
+        ])));
+    }
+    Json::object(frame)
+}
+
+/// PC, then the CALL of every pending return address, innermost first.
+fn stack_trace(session: &Session) -> Json {
+    let chip = &session.chip;
+    let mut frames = vec![frame(session, 0, chip.pc())];
+    for (n, &ret) in chip.stack().iter().rev().enumerate() {
+        frames.push(frame(session, n + 1, (ret as usize).saturating_sub(2)));
+    }
+    let total = frames.len();
+    Json::object(vec![("stackFrames", frames.into()), ("totalFrames", total.into())])
+}
+
+fn variable(name: &str, value: String) -> Json {
+    Json::object(vec![("name", name.into()), ("value", value.into()), ("variablesReference", 0u64.into())])
+}
+
+fn variables(args: &Json, session: &Session) -> Result<Json, String> {
+    let chip = &session.chip;
+    let list = match args.get("variablesReference").and_then(Json::as_u64) {
+        Some(REGISTERS) => {
+            let mut list: Vec<Json> = (0..16).map(|n| variable(&format!("V{:X}", n), format!("0x{:02X}", chip.v(n)))).collect();
+            for (name, value) in [("I", chip.i()), ("PC", chip.pc())] {
+                let mut register = variable(name, reference(value));
+                if let Json::Object(ref mut members) = register {
+                    members.push(("memoryReference".to_owned(), reference(value).into()));
+                }
+                list.push(register);
+            }
+            list.push(variable("DT", format!("0x{:02X}", chip.delay_timer())));
+            list.push(variable("ST", format!("0x{:02X}", chip.sound_timer())));
+            list
+        },
+        Some(MEMORY) => {
+            let rows = chip.memory().len().div_ceil(MEMORY_ROW);
+            let start = args.get("start").and_then(Json::as_u64).unwrap_or(0) as usize;
+            let count = args.get("count").and_then(Json::as_u64).map_or(rows, |count| count as usize);
+            (start.min(rows)..start.saturating_add(count).min(rows)).map(|row| {
+                let addr = row * MEMORY_ROW;
+                let bytes: Vec<String> = chip.memory()[addr..(addr + MEMORY_ROW).min(chip.memory().len())]
+                    .iter().map(|b| format!("{:02X}", b)).collect();
+                variable(&format!("{:04X}", addr), bytes.join(" "))
+            }).collect()
+        },
+        _ => return Err("unknown variables reference".to_owned()),
+    };
+    Ok(Json::object(vec![("variables", list.into())]))
+}
+
+fn set_variable(args: &Json, session: &mut Session) -> Result<Json, String> {
+    if args.get("variablesReference").and_then(Json::as_u64) != Some(REGISTERS) {
+        return Err("only registers can be set".to_owned());
+    }
+    let name = args.get("name").and_then(Json::as_str).unwrap_or("");
+    let text = args.get("value").and_then(Json::as_str).unwrap_or("").trim();
+    let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => usize::from_str_radix(digits, 16),
+        None => text.parse(),
+    }.map_err(|_| format!("invalid value '{}'", text))?;
+    let chip = &mut session.chip;
+    let byte = || u8::try_from(value).map_err(|_| format!("{} does not fit in a byte", value));
+    let shown = match name {
+        "I" => { chip.set_i(value); reference(value) },
+        "PC" => { chip.set_pc(value); reference(value) },
+        "DT" => { chip.set_delay_timer(byte()?); format!("0x{:02X}", value) },
+        "ST" => { chip.set_sound_timer(byte()?); format!("0x{:02X}", value) },
+        _ => {
+            let n = name.strip_prefix('V').and_then(|n| u8::from_str_radix(n, 16).ok()).filter(|&n| n < 16)
+                .ok_or_else(|| format!("no register {}", name))?;
+            chip.set_v(n, byte()?);
+            format!("0x{:02X}", value)
+        },
+    };
+    Ok(Json::object(vec![("value", shown.into())]))
+}
+
+fn read_memory(args: &Json, session: &Session) -> Result<Json, String> {
+    let memory = session.chip.memory();
+    let base = args.get("memoryReference").and_then(Json::as_str).and_then(parse_reference)
+        .ok_or("invalid memory reference")?;
+    let start = base.saturating_add(args.get("offset").and_then(Json::as_i64).unwrap_or(0));
+    let count = args.get("count").and_then(Json::as_u64).unwrap_or(0).min(MAX_MEMORY_SIZE as u64) as usize;
+    if start < 0 || start as usize >= memory.len() {
+        return Ok(Json::object(vec![("address", reference(start.max(0) as usize).into()), ("unreadableBytes", count.into())]));
+    }
+    let start = start as usize;
+    let end = (start + count).min(memory.len());
+    Ok(Json::object(vec![
+        ("address", reference(start).into()),
+        ("data", base64(&memory[start..end]).into()),
+        ("unreadableBytes", (count - (end - start)).into()),
+    ]))
+}
+
+fn disassemble(args: &Json, session: &Session) -> Result<Json, String> {
+    let base = args.get("memoryReference").and_then(Json::as_str).and_then(parse_reference)
+        .ok_or("invalid memory reference")?;
+    let offset = args.get("offset").and_then(Json::as_i64).unwrap_or(0);
+    // Instructions before the reference are taken as two bytes each,
+    // the size of all but F000 NNNN.
+    // No memory has more instructions than this, past it there is only ???.
+    let limit = MAX_MEMORY_SIZE as i64;
+    let first = args.get("instructionOffset").and_then(Json::as_i64).unwrap_or(0).clamp(-limit, limit);
+    let count = args.get("instructionCount").and_then(Json::as_u64).unwrap_or(0).min(limit as u64) as usize;
+    let mut addr = base.saturating_add(offset).saturating_add(first.min(0) * 2);
+    let mut list = Vec::with_capacity(count);
+    for n in 0..count as i64 + first.max(0) {
+        // Below 0 nothing is read, at 0 is as good as any there.
+        let at = addr.max(0) as usize;
+        let (text, size) = match Some(at).filter(|_| addr >= 0).and_then(|at| instruction_at(session, at)) {
+            Some((instruction, size)) => (disasm::symbolic(instruction, &session.symbols), size),
+            None => ("???".to_owned(), 2),
+        };
+        if n >= first.max(0) {
+            let bytes = session.chip.memory().get(at..at.saturating_add(size)).filter(|_| addr >= 0).map_or(String::new(), |bytes| {
+                bytes.iter().map(|b| format!("{:02X}", b)).collect()
+            });
+            let mut line = vec![
+                ("address", reference(addr.max(0) as usize).into()),
+                ("instructionBytes", bytes.into()),
+                ("instruction", text.into()),
+            ];
+            if let Some(label) = session.symbols.name(at).filter(|_| addr >= 0) {
+                line.push(("symbol", label.into()));
+            }
+            if let Some(location) = session.source_map.location(at).filter(|_| addr >= 0) {
+                line.push(("line", location.line.into()));
+                line.push(("location", Json::object(vec![
+                    ("name", location.file.as_str().into()),
+                    ("path", session.source_map.path(&location.file).display().to_string().into()),
+                ])));
+            }
+            list.push(Json::object(line));
+        }
+        addr = addr.saturating_add(size as i64);
+    }
+    Ok(Json::object(vec![("instructions", list.into())]))
+}
+
+impl Remote for DapServer {
+    fn poll(&mut self, session: &mut Session) {
+        if let Some(ref listener) = self.listener {
+            while let Ok((stream, peer)) = listener.accept() {
+                if self.peer.is_some() || stream.set_nonblocking(true).is_err() {
+                    info!("turned away debug client {}, one is attached", peer);
+                    continue;
+                }
+                info!("debug client {} attached", peer);
+                self.peer = Some(Peer::new(Link::Tcp(stream)));
+                self.seen = None;
+            }
+        }
+        let alive = match self.peer {
+            Some(ref mut peer) => peer.connection.read(),
+            None => return,
+        };
+        while let Some(message) = self.peer.as_mut().and_then(Peer::message) {
+            match message {
+                Ok(request) => {
+                    let result = self.handle(&request, session);
+                    self.respond(&request, result);
+                    if request.get("command").and_then(Json::as_str) == Some("initialize") {
+                        self.event("initialized", Json::Null);
+                    }
+                    if let Some((reason, text)) = self.stop.take() {
+                        self.stopped(reason, text);
+                    }
+                },
+                Err(e) => warn!("bad debug adapter message: {}", e),
+            }
+        }
+        self.check(session);
+        let flushed = self.peer.as_mut().is_some_and(|peer| peer.connection.flush() && !peer.broken);
+        if !(alive && flushed) {
+            info!("debug client detached");
+            self.peer = None;
+            for addr in self.instruction_breakpoints.drain(..)
+                .chain(self.source_breakpoints.values().flatten().cloned().collect::<Vec<_>>()) {
+                session.breakpoints.remove(&addr);
+            }
+            self.source_breakpoints.clear();
+            if self.owned {
+                session.quit = true;
+            }
+        }
+    }
+
+    fn frame(&mut self, session: &Session) {
+        if self.peer.is_none() {
+            return;
+        }
+        self.check(session);
+        if let Some(ref mut peer) = self.peer {
+            peer.connection.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruchip8::Chip8;
+
+    fn session() -> Session {
+        let mut session = Session::new(Chip8::new());
+        session.load(&[0x60, 0x01, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x00]).unwrap();
+        session
+    }
+
+    fn framed(body: &str) -> Vec<u8> {
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+    }
+
+    #[test]
+    fn frames_messages_split_anywhere() {
+        let (tx, chunks) = mpsc::channel();
+        let mut peer = Peer::new(Link::Stdio(chunks, Vec::new()));
+        let mut bytes = framed(r#"{"seq":1}"#);
+        // Larger than a read takes, so the chunk is handed over in parts.
+        bytes.extend(framed(&format!(r#"{{"seq":2,"pad":"{}"}}"#, "x".repeat(10_000))));
+        let (first, rest) = bytes.split_at(20);
+        tx.send(first.to_vec()).unwrap();
+        tx.send(rest.to_vec()).unwrap();
+
+        assert!(peer.connection.read());
+        let seq = |message: Option<Result<Json, String>>| message.unwrap().unwrap().get("seq").and_then(Json::as_u64);
+        assert_eq!(seq(peer.message()), Some(1));
+        assert_eq!(seq(peer.message()), Some(2));
+        assert!(peer.message().is_none());
+        drop(tx);
+        assert!(!peer.connection.read());
+    }
+
+    #[test]
+    fn drops_what_cannot_be_framed() {
+        let (tx, chunks) = mpsc::channel();
+        let mut peer = Peer::new(Link::Stdio(chunks, Vec::new()));
+        tx.send(b"Content-Type: json\r\n\r\n".to_vec()).unwrap();
+        tx.send(framed("{")).unwrap();
+        peer.connection.read();
+        assert!(peer.message().unwrap().is_err());
+        assert!(peer.message().unwrap().is_err());
+        assert!(!peer.broken);
+
+        tx.send(b"Content-Length: 18446744073709551615\r\n\r\n{}".to_vec()).unwrap();
+        peer.connection.read();
+        assert!(peer.message().unwrap().is_err());
+        assert!(peer.broken);
+        tx.send(framed("{}")).unwrap();
+        peer.connection.read();
+        assert!(peer.message().is_none());
+    }
+
+    #[test]
+    fn sends_framed_messages() {
+        let (_tx, chunks) = mpsc::channel();
+        let mut peer = Peer::new(Link::Stdio(chunks, Vec::new()));
+        peer.send(&Json::object(vec![("seq", 1u64.into())]));
+        assert_eq!(peer.connection.output, framed(r#"{"seq":1}"#));
+    }
+
+    #[test]
+    fn reads_memory_at_the_edges() {
+        let session = session();
+        let len = session.chip.memory().len();
+        let read = |args: &str| read_memory(&Json::parse(args).unwrap(), &session).unwrap();
+
+        let inside = read(r#"{"memoryReference":"0x0200","count":2}"#);
+        assert_eq!(inside.get("data").and_then(Json::as_str), Some(base64(&[0x60, 0x01]).as_str()));
+        assert_eq!(inside.get("unreadableBytes").and_then(Json::as_u64), Some(0));
+
+        let end = read(&format!(r#"{{"memoryReference":"{}","offset":-1,"count":4}}"#, reference(len)));
+        assert_eq!(end.get("unreadableBytes").and_then(Json::as_u64), Some(3));
+
+        let below = read(r#"{"memoryReference":"0x0000","offset":-1,"count":4}"#);
+        assert_eq!(below.get("unreadableBytes").and_then(Json::as_u64), Some(4));
+
+        let huge = read(r#"{"memoryReference":"0x7FFFFFFFFFFFFFFF","offset":9007199254740000,"count":1e18}"#);
+        assert_eq!(huge.get("unreadableBytes").and_then(Json::as_u64), Some(MAX_MEMORY_SIZE as u64));
+        assert!(read_memory(&Json::parse(r#"{"memoryReference":"nowhere"}"#).unwrap(), &session).is_err());
+    }
+
+    #[test]
+    fn disassembles_at_the_edges() {
+        let session = session();
+        let len = session.chip.memory().len();
+        let disassemble = |args: &str| {
+            let body = disassemble(&Json::parse(args).unwrap(), &session).unwrap();
+            body.get("instructions").and_then(Json::as_array).unwrap().to_vec()
+        };
+        let field = |line: &Json, name: &str| line.get(name).and_then(Json::as_str).unwrap_or("").to_owned();
+
+        let lines = disassemble(r#"{"memoryReference":"0x0200","instructionCount":3}"#);
+        let addresses: Vec<String> = lines.iter().map(|line| field(line, "address")).collect();
+        assert_eq!(addresses, ["0x0200", "0x0202", "0x0206"]);
+        assert_eq!(field(&lines[1], "instructionBytes"), "F0001234");
+
+        let before = disassemble(r#"{"memoryReference":"0x0000","instructionOffset":-1,"instructionCount":2}"#);
+        assert_eq!(before.len(), 2);
+        assert_eq!(field(&before[0], "instruction"), "???");
+        assert_eq!(field(&before[0], "instructionBytes"), "");
+        assert_eq!(field(&before[1], "address"), "0x0000");
+
+        let past = disassemble(&format!(r#"{{"memoryReference":"{}","instructionOffset":-1,"instructionCount":3}}"#,
+            reference(len)));
+        let text: Vec<String> = past.iter().map(|line| field(line, "instruction")).collect();
+        assert_eq!(text.len(), 3);
+        assert_eq!(&text[1..], ["???", "???"]);
+
+        let huge = disassemble(r#"{"memoryReference":"0x7FFFFFFFFFFFFFFF","instructionOffset":-9007199254740000,"instructionCount":1e18}"#);
+        assert_eq!(huge.len(), MAX_MEMORY_SIZE);
+    }
+}
@@ -0,0 +1,686 @@
+//! Interactive debugger, reachable from the terminal or over TCP.
+//!
+//! Both transports feed lines to the same command interpreter, so a session
+//! attached with `nc host 5555` behaves exactly like `--debug` on the local
+//! console. Addresses are hexadecimal, labels from the symbol file or
+//! `FILE:LINE` from the source map, counts are decimal.
+
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, BufRead, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Instant, SystemTime};
+
+use ruchip8::disasm;
+use ruchip8::{decode, decode_long, Instruction, LONG_PREFIX, TIMERS_CLOCK};
+use connection::Connection;
+use savestate;
+use search::{Filter, Search};
+use session::{Class, Remote, Session, Temporary};
+
+const PROMPT: &str = "(ruchip8) ";
+
+/// Instructions `list` shows on each side of PC by default.
+const LIST_WINDOW: usize = 8;
+/// Instructions on each side of PC shown whenever the machine stops.
+const FOLLOW_WINDOW: usize = 2;
+
+const HELP: &str = "\
+break ADDR     (b)  stop before the instruction at ADDR runs, which can
+                    also be a label from the symbol file or a FILE:LINE
+                    from the source map
+break CLASS    (b)  stop before any instruction of CLASS runs: draw, call,
+                    ret, jump, key, timer, store or load
+delete ADDR    (d)  remove the breakpoint at ADDR
+delete CLASS   (d)  remove the breakpoint on CLASS
+breaks              list breakpoints
+continue       (c)  resume execution
+pause          (p)  stop execution
+step [N]       (s)  execute N instructions, 1 by default
+next           (n)  like step, running a CALL through to its return
+finish         (f)  run until the current subroutine returns
+regs           (r)  show registers and timers
+mem ADDR [LEN] (x)  dump LEN bytes of memory from ADDR, 64 by default
+set mem ADDR BYTE...
+                    write the bytes, in hex, over memory from ADDR while
+                    paused
+set VX|I|PC N       set a register while paused, VX to a hex byte, I and
+                    PC to an address
+source [N]          show the source line PC came from with N lines on each
+                    side, 0 by default, when there is a source map
+list [N]       (l)  disassemble N instructions on each side of PC, 8 by
+                    default, > marking PC and * breakpoints
+stack               show return addresses
+sprite [N]          draw N bytes at I as a sprite, by default as many as
+                    the DXYN at PC draws; N = 0 in 128x64 is 16x16
+timers         (t)  show both timers, when they next count down and the
+                    latest FX15 and FX18
+keys           (k)  show the keypad, the key the program last tested and
+                    whether FX0A is waiting
+reset [hard]        restart the program, keeping memory as it is, or the
+                    ROM on cleared memory
+reload              read the ROM from disk again and restart it
+save N              save the machine to savestate slot N, from 1 to 10
+load N              load the machine from savestate slot N
+resume              load the machine from the autosave of the last run
+slots               list the filled savestate slots and when they were saved
+quit           (q)  close the emulator
+cheats              list cheats
+cheat N on|off      enable or disable cheat N
+search new          start a memory search with every address
+search FILTER       keep addresses that changed, unchanged, increased,
+                    decreased or eq VALUE since the last search
+search list         show the remaining addresses
+help           (h)  show this text
+";
+
+/// A label from the session's symbols, a `FILE:LINE` from its source map or
+/// a hexadecimal address.
+fn parse_addr(session: &Session, text: &str) -> Result<usize, String> {
+    if let Some(addr) = session.symbols.addr(text) {
+        return Ok(addr);
+    }
+    if let Some((file, line)) = text.rsplit_once(':') {
+        let line = line.parse().map_err(|_| format!("invalid line '{}'", line))?;
+        return session.source_map.addr(file, line).ok_or_else(|| format!("no code from {}", text));
+    }
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(digits, 16).map_err(|_| format!("invalid address '{}'", text))
+}
+
+/// `addr` in hexadecimal, with its label if it has one.
+fn describe(session: &Session, addr: usize) -> String {
+    match session.symbols.name(addr) {
+        Some(name) => format!("{:04X} ({})", addr, name),
+        None => format!("{:04X}", addr),
+    }
+}
+
+/// How long ago `time` was, roughly.
+fn ago(time: SystemTime) -> String {
+    let secs = time.elapsed().map_or(0, |d| d.as_secs());
+    match secs {
+        0..=59 => format!("{} s", secs),
+        60..=3599 => format!("{} min", secs / 60),
+        3600..=86_399 => format!("{} h", secs / 3600),
+        _ => format!("{} days", secs / 86_400),
+    }
+}
+
+/// A hexadecimal byte, with or without `0x`.
+fn parse_byte(text: &str) -> Result<u8, String> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    u8::from_str_radix(digits, 16).map_err(|_| format!("invalid byte '{}'", text))
+}
+
+fn parse_count(text: &str) -> Result<usize, String> {
+    text.parse().map_err(|_| format!("invalid count '{}'", text))
+}
+
+/// Registers, timers and the upcoming opcode.
+pub fn registers(session: &Session) -> String {
+    let chip = &session.chip;
+    let mut out = String::new();
+    let word = |at: usize| chip.memory().get(at..at + 2).map(|op| (op[0] as u16) << 8 | op[1] as u16);
+    let next = match word(chip.pc()) {
+        None => "----".to_owned(),
+        Some(ops) => {
+            let next = if ops == LONG_PREFIX { word(chip.pc() + 2) } else { Some(0) };
+            match next.and_then(|next| decode_long(ops, next)) {
+                Some(instruction @ Instruction::LongIndex { addr }) =>
+                    format!("{:04X} {:04X} {}", ops, addr, disasm::symbolic(instruction, &session.symbols)),
+                Some(instruction) => format!("{:04X} {}", ops, disasm::symbolic(instruction, &session.symbols)),
+                None => format!("{:04X}", ops),
+            }
+        },
+    };
+    writeln!(out, "PC={:04X} [{}]  I={:04X}  DT={:02X}  ST={:02X}  SP={}",
+             chip.pc(), next, chip.i(), chip.delay_timer(), chip.sound_timer(),
+             chip.stack().len()).unwrap();
+    for row in 0..2 {
+        let regs: Vec<String> = (row * 8..row * 8 + 8)
+            .map(|n| format!("V{:X}={:02X}", n, chip.v(n)))
+            .collect();
+        writeln!(out, "{}", regs.join(" ")).unwrap();
+    }
+    out
+}
+
+pub fn memory(session: &Session, addr: usize, len: usize) -> Result<String, String> {
+    let end = addr.checked_add(len).filter(|&end| end <= session.chip.memory().len())
+        .ok_or("range outside of memory")?;
+    let mut out = String::new();
+    for (row, bytes) in session.chip.memory()[addr..end].chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        writeln!(out, "{:04X}: {}", addr + row * 16, hex.join(" ")).unwrap();
+    }
+    Ok(out)
+}
+
+/// Rows `sprite` shows when PC is not at a DXYN.
+const SPRITE_ROWS: usize = 15;
+
+/// The sprite at I as the next DXYN would draw it, `rows` tall or 16x16
+/// for DXY0 in 128x64.
+pub fn sprite(session: &Session, rows: Option<usize>) -> Result<String, String> {
+    let chip = &session.chip;
+    let rows = rows.unwrap_or_else(|| {
+        let ops = chip.memory().get(chip.pc()..chip.pc() + 2).map(|op| (op[0] as u16) << 8 | op[1] as u16);
+        match ops.and_then(decode) {
+            Some(Instruction::Draw { n, .. }) => n as usize,
+            _ => SPRITE_ROWS,
+        }
+    });
+    let large = rows == 0 && chip.display().is_hires();
+    let (width, len) = if large { (2, 32) } else { (1, rows) };
+    let bytes = chip.memory().get(chip.i()..chip.i() + len).ok_or("sprite runs past the end of memory")?;
+    let mut out = String::new();
+    for (row, bytes) in bytes.chunks(width).enumerate() {
+        let pixels: String = bytes.iter()
+            .flat_map(|&byte| (0..8).map(move |bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' }))
+            .collect();
+        writeln!(out, "{:04X}: {}", chip.i() + row * width, pixels).unwrap();
+    }
+    Ok(out)
+}
+
+/// Timer values per character of the `timers` bars.
+const TIMER_BAR_STEP: u8 = 8;
+
+/// A timer as its value and a bar `TIMER_BAR_STEP` to a character.
+fn timer_bar(name: &str, value: u8) -> String {
+    let full = value.div_ceil(TIMER_BAR_STEP) as usize;
+    let empty = (u8::MAX / TIMER_BAR_STEP) as usize + 1 - full;
+    format!("{}={:02X} {:>3} |{}{}|\n", name, value, value, "#".repeat(full), ".".repeat(empty))
+}
+
+/// Both timers, the time left until they next count down and the latest
+/// timer writes.
+pub fn timers(session: &Session) -> String {
+    let chip = &session.chip;
+    let mut out = timer_bar("DT", chip.delay_timer());
+    out.push_str(&timer_bar("ST", chip.sound_timer()));
+    match session.next_tick {
+        _ if session.paused => writeln!(out, "paused, the timers are stopped").unwrap(),
+        Some(at) => {
+            let left = at.saturating_duration_since(Instant::now());
+            writeln!(out, "next count down in {:.1} ms, then every {:.1} ms",
+                     left.as_secs_f64() * 1000.0, 1000.0 / TIMERS_CLOCK as f64).unwrap()
+        },
+        None => writeln!(out, "the timers have not started").unwrap(),
+    }
+    for write in &session.timer_writes {
+        writeln!(out, "frame {:>6} {:04X}: LD {}, {:02X}", write.frame, write.pc,
+                 if write.sound { "ST" } else { "DT" }, write.value).unwrap();
+    }
+    out
+}
+
+/// The usual COSMAC VIP keypad layout.
+const KEYPAD: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// The keypad with held keys in brackets, the last key EX9E or EXA1 tested
+/// and the FX0A wait.
+pub fn keypad(session: &Session) -> String {
+    let chip = &session.chip;
+    let mut out = String::new();
+    for row in &KEYPAD {
+        let keys: Vec<String> = row.iter()
+            .map(|&key| if chip.is_key_down(key) { format!("[{:X}]", key) } else { format!(" {:X} ", key) })
+            .collect();
+        writeln!(out, "{}", keys.join("")).unwrap();
+    }
+    match chip.last_polled_key() {
+        Some(key) => writeln!(out, "last tested: {:X}", key).unwrap(),
+        None => writeln!(out, "last tested: none").unwrap(),
+    }
+    match chip.key_wait_register() {
+        Some(x) => writeln!(out, "FX0A waiting for a key into V{:X}", x).unwrap(),
+        None => writeln!(out, "FX0A not waiting").unwrap(),
+    }
+    out
+}
+
+/// Disassembly of `window` instructions on each side of PC, decoded from
+/// memory as it is now. PC gets a `>` and breakpoints a `*`.
+pub fn disassembly(session: &Session, window: usize) -> String {
+    let pc = session.chip.pc();
+    // Keep instructions aligned with PC, even when it is odd.
+    let before = window.min(pc / 2);
+    let mut out = String::new();
+    let memory = session.chip.memory();
+    for line in disasm::listing_with(memory, pc - before * 2, before + window + 1, &session.symbols) {
+        let at = |addr: &usize| line.starts_with(&format!("{:04X}:", addr));
+        let breakpoint = if session.breakpoints.iter().any(at) { '*' } else { ' ' };
+        let marker = if at(&pc) { '>' } else { ' ' };
+        writeln!(out, "{}{} {}", breakpoint, marker, line).unwrap();
+    }
+    out
+}
+
+/// The source around the line PC came from, `context` lines on each side,
+/// or nothing without a source map.
+pub fn source(session: &Session, context: usize) -> String {
+    let map = &session.source_map;
+    let location = match map.location(session.chip.pc()) {
+        Some(location) => location,
+        None => return String::new(),
+    };
+    let mut out = String::new();
+    for line in location.line.saturating_sub(context).max(1)..=location.line + context {
+        let marker = if line == location.line { '>' } else { ' ' };
+        match map.text(&location.file, line) {
+            Some(text) => writeln!(out, "{} {}:{}: {}", marker, location.file, line, text).unwrap(),
+            None if line == location.line => writeln!(out, "{} {}:{}", marker, location.file, line).unwrap(),
+            None => {},
+        }
+    }
+    out
+}
+
+/// How many search candidates a filter shows at most.
+const SEARCH_SHOWN: usize = 16;
+/// How many search candidates `search list` shows at most.
+const SEARCH_LIST: usize = 256;
+
+fn candidates(search: &Search, max: usize) -> String {
+    let mut out = format!("{} candidates\n", search.count());
+    if search.count() <= max {
+        for (addr, value) in search.candidates() {
+            writeln!(out, "{:04X}={:02X}", addr, value).unwrap();
+        }
+    }
+    out
+}
+
+/// Runs one command line against the session and returns what to print.
+pub fn execute(line: &str, session: &mut Session) -> String {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let result = match words.as_slice() {
+        [] => Ok(String::new()),
+        ["break", class] | ["b", class] if Class::parse(class).is_some() => {
+            session.break_classes.extend(Class::parse(class));
+            Ok(format!("breakpoint on {}\n", class))
+        },
+        ["delete", class] | ["d", class] if Class::parse(class).is_some() => {
+            if Class::parse(class).is_some_and(|class| session.break_classes.remove(&class)) {
+                Ok(format!("deleted breakpoint on {}\n", class))
+            } else {
+                Err(format!("no breakpoint on {}", class))
+            }
+        },
+        ["break", addr] | ["b", addr] => parse_addr(session, addr).map(|addr| {
+            session.breakpoints.insert(addr);
+            format!("breakpoint at {}\n", describe(session, addr))
+        }),
+        ["delete", addr] | ["d", addr] => parse_addr(session, addr).and_then(|addr| {
+            if session.breakpoints.remove(&addr) {
+                Ok(format!("deleted breakpoint at {}\n", describe(session, addr)))
+            } else {
+                Err(format!("no breakpoint at {}", describe(session, addr)))
+            }
+        }),
+        ["breaks"] => Ok(session.breakpoints.iter()
+            .map(|&addr| format!("{}\n", describe(session, addr)))
+            .chain(session.break_classes.iter().map(|class| format!("{}\n", class.name())))
+            .collect()),
+        ["continue"] | ["c"] => {
+            session.paused = false;
+            Ok(String::new())
+        },
+        ["pause"] | ["p"] => {
+            session.paused = true;
+            Ok(registers(session))
+        },
+        ["step"] | ["s"] => step(session, 1),
+        ["step", n] | ["s", n] => parse_count(n).and_then(|n| step(session, n)),
+        ["next"] | ["n"] => next(session),
+        ["finish"] | ["f"] => {
+            let depth = session.chip.stack().len();
+            if depth == 0 {
+                Err("not in a subroutine".to_owned())
+            } else {
+                session.run_to(Temporary { addr: None, depth: depth - 1 });
+                Ok(String::new())
+            }
+        },
+        ["regs"] | ["r"] => Ok(registers(session)),
+        ["mem", addr] | ["x", addr] => parse_addr(session, addr).and_then(|addr| memory(session, addr, 64)),
+        ["mem", addr, len] | ["x", addr, len] => parse_addr(session, addr)
+            .and_then(|addr| parse_count(len).and_then(|len| memory(session, addr, len))),
+        ["set", "mem", addr, bytes @ ..] if !bytes.is_empty() => parse_addr(session, addr).and_then(|addr| {
+            let bytes = bytes.iter().map(|byte| parse_byte(byte)).collect::<Result<Vec<u8>, String>>()?;
+            session.poke(addr, &bytes)?;
+            memory(session, addr, bytes.len())
+        }),
+        ["set", register, value] => set_register(session, register, value),
+        ["source"] => Ok(source(session, 0)),
+        ["source", n] => parse_count(n).map(|n| source(session, n)),
+        ["list"] | ["l"] => Ok(disassembly(session, LIST_WINDOW)),
+        ["list", n] | ["l", n] => parse_count(n).map(|n| disassembly(session, n)),
+        ["sprite"] => sprite(session, None),
+        ["sprite", n] => parse_count(n).and_then(|n| sprite(session, Some(n))),
+        ["timers"] | ["t"] => Ok(timers(session)),
+        ["keys"] | ["k"] => Ok(keypad(session)),
+        ["stack"] => Ok(session.chip.stack().iter().rev()
+            .map(|&addr| format!("{}\n", describe(session, addr as usize)))
+            .collect()),
+        ["reset"] => {
+            session.soft_reset();
+            Ok(registers(session))
+        },
+        ["reset", "hard"] => {
+            session.hard_reset();
+            Ok(registers(session))
+        },
+        ["reload"] => session.reload().map(|_| registers(session)),
+        ["save", slot] => parse_count(slot)
+            .and_then(|slot| savestate::save(session, slot).map(|_| format!("saved slot {}\n", slot))),
+        ["load", slot] => parse_count(slot)
+            .and_then(|slot| savestate::load(session, slot))
+            .map(|_| registers(session)),
+        ["slots"] => savestate::slots(session).map(|slots| slots.iter()
+            .map(|slot| format!("{:>2}  saved {} ago\n", slot.slot, ago(slot.saved)))
+            .collect()),
+        ["resume"] => savestate::resume(session).map(|_| registers(session)),
+        ["quit"] | ["q"] => {
+            session.quit = true;
+            Ok(String::new())
+        },
+        ["cheats"] => Ok(session.cheats.list.iter().enumerate()
+            .map(|(n, cheat)| format!("{:>2} {} {:04X}={:02X} {}\n", n,
+                                      if cheat.enabled { "on " } else { "off" },
+                                      cheat.addr, cheat.value, cheat.name))
+            .collect()),
+        ["cheat", n, state] => parse_count(n).and_then(|n| {
+            let enabled = match *state {
+                "on" => true,
+                "off" => false,
+                _ => return Err(format!("invalid cheat state '{}'", state)),
+            };
+            let cheat = session.cheats.list.get_mut(n).ok_or_else(|| format!("no cheat {}", n))?;
+            cheat.enabled = enabled;
+            Ok(String::new())
+        }),
+        ["search", "new"] => {
+            let search = Search::new(&session.chip);
+            let out = format!("{} candidates\n", search.count());
+            session.search = Some(search);
+            Ok(out)
+        },
+        ["search", "list"] => session.search.as_ref()
+            .ok_or_else(|| "no search, start one with 'search new'".to_owned())
+            .map(|search| candidates(search, SEARCH_LIST)),
+        ["search", filter @ ..] => Filter::parse(filter).and_then(|filter| {
+            let search = session.search.as_mut()
+                .ok_or("no search, start one with 'search new'")?;
+            search.filter(&session.chip, filter);
+            Ok(candidates(search, SEARCH_SHOWN))
+        }),
+        ["help"] | ["h"] => Ok(HELP.to_owned()),
+        _ => Err(format!("unknown command '{}', try 'help'", line.trim())),
+    };
+
+    match result {
+        Ok(out) => out,
+        Err(e) => format!("error: {}\n", e),
+    }
+}
+
+/// `set` for V0 to VF, I and PC, in any case.
+fn set_register(session: &mut Session, register: &str, value: &str) -> Result<String, String> {
+    if !session.paused {
+        return Err("pause first".to_owned());
+    }
+    match register.to_ascii_lowercase().as_str() {
+        "pc" => {
+            let pc = parse_addr(session, value)?;
+            if pc.checked_add(1).is_none_or(|end| end >= session.chip.memory().len()) {
+                return Err(format!("{:04X} is outside of memory", pc));
+            }
+            session.chip.set_pc(pc);
+        },
+        "i" => {
+            let i = parse_addr(session, value)?;
+            if i > 0xFFFF {
+                return Err(format!("I is 16 bits, {:X} does not fit", i));
+            }
+            session.chip.set_i(i);
+        },
+        name => {
+            let n = name.strip_prefix('v').filter(|n| n.len() == 1)
+                .and_then(|n| u8::from_str_radix(n, 16).ok())
+                .ok_or_else(|| format!("unknown register '{}'", register))?;
+            session.chip.set_v(n, parse_byte(value)?);
+        },
+    }
+    Ok(registers(session))
+}
+
+fn step(session: &mut Session, n: usize) -> Result<String, String> {
+    session.step_through(n.min(u32::MAX as usize) as u32).map_err(|e| e.to_string())?;
+    let mut out = registers(session);
+    out.push_str(&source(session, 0));
+    out.push_str(&disassembly(session, FOLLOW_WINDOW));
+    Ok(out)
+}
+
+/// Steps one instruction, or over it when it is a CALL.
+fn next(session: &mut Session) -> Result<String, String> {
+    let chip = &session.chip;
+    let pc = chip.pc();
+    let ops = chip.memory().get(pc..pc + 2).map(|op| (op[0] as u16) << 8 | op[1] as u16);
+    if let Some(Instruction::Call { .. }) = ops.and_then(decode) {
+        let depth = chip.stack().len();
+        session.run_to(Temporary { addr: Some(pc + 2), depth });
+        return Ok(String::new());
+    }
+    step(session, 1)
+}
+
+/// Announcements owed since the last poll: breakpoint hits, errors and
+/// the program exiting.
+struct Watcher {
+    breaks_hit: u64,
+    error: bool,
+    halted: bool,
+}
+
+impl Watcher {
+    fn new(session: &Session) -> Self {
+        Watcher { breaks_hit: session.breaks_hit, error: session.error.is_some(), halted: session.chip.is_halted() }
+    }
+
+    fn check(&mut self, session: &Session) -> Option<String> {
+        let mut out = String::new();
+        if session.breaks_hit != self.breaks_hit {
+            writeln!(out, "breakpoint hit at {}", describe(session, session.chip.pc())).unwrap();
+        }
+        if let (Some(e), false) = (session.error, self.error) {
+            writeln!(out, "stopped: {}", e).unwrap();
+        }
+        if session.chip.is_halted() && !self.halted {
+            writeln!(out, "program exited").unwrap();
+        }
+        self.breaks_hit = session.breaks_hit;
+        self.error = session.error.is_some();
+        self.halted = session.chip.is_halted();
+        if out.is_empty() {
+            None
+        } else {
+            out.push_str(&registers(session));
+            out.push_str(&source(session, 0));
+            out.push_str(&disassembly(session, FOLLOW_WINDOW));
+            Some(out)
+        }
+    }
+}
+
+/// Debugger on the terminal the emulator was started from.
+pub struct Console {
+    lines: Receiver<String>,
+    watcher: Option<Watcher>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) => if tx.send(line).is_err() { break },
+                    Err(_) => break,
+                }
+            }
+        });
+        print!("{}", PROMPT);
+        let _ = io::stdout().flush();
+        Console { lines, watcher: None }
+    }
+}
+
+impl Remote for Console {
+    fn poll(&mut self, session: &mut Session) {
+        let watcher = self.watcher.get_or_insert_with(|| Watcher::new(session));
+        while let Ok(line) = self.lines.try_recv() {
+            print!("{}{}", execute(&line, session), PROMPT);
+            let _ = io::stdout().flush();
+            watcher.check(session);
+        }
+    }
+
+    fn frame(&mut self, session: &Session) {
+        if let Some(out) = self.watcher.as_mut().and_then(|w| w.check(session)) {
+            print!("\n{}{}", out, PROMPT);
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+struct Client {
+    connection: Connection<TcpStream>,
+    watcher: Watcher,
+}
+
+/// Debugger consoles served to TCP clients, e.g. `nc host 5555`.
+pub struct DebugServer {
+    listener: TcpListener,
+    clients: HashMap<SocketAddr, Client>,
+}
+
+impl DebugServer {
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        info!("listening on {}", listener.local_addr()?);
+        Ok(DebugServer { listener, clients: HashMap::new() })
+    }
+}
+
+impl Remote for DebugServer {
+    fn poll(&mut self, session: &mut Session) {
+        while let Ok((stream, peer)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_err() {
+                continue;
+            }
+            info!("{} attached", peer);
+            let mut connection = Connection::new(stream);
+            connection.output.extend_from_slice(registers(session).as_bytes());
+            connection.output.extend_from_slice(PROMPT.as_bytes());
+            self.clients.insert(peer, Client { connection, watcher: Watcher::new(session) });
+        }
+
+        self.clients.retain(|peer, client| {
+            let connection = &mut client.connection;
+            let alive = connection.read();
+            while let Some(line) = connection.line() {
+                let out = execute(&line, session);
+                connection.output.extend_from_slice(out.as_bytes());
+                connection.output.extend_from_slice(PROMPT.as_bytes());
+                client.watcher.check(session);
+            }
+            let alive = alive && connection.flush();
+            if !alive {
+                info!("{} detached", peer);
+            }
+            alive
+        });
+    }
+
+    fn frame(&mut self, session: &Session) {
+        for client in self.clients.values_mut() {
+            if let Some(out) = client.watcher.check(session) {
+                let output = &mut client.connection.output;
+                output.extend_from_slice(b"\n");
+                output.extend_from_slice(out.as_bytes());
+                output.extend_from_slice(PROMPT.as_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruchip8::Chip8;
+
+    fn session() -> Session {
+        let mut session = Session::new(Chip8::new());
+        session.load(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        session.paused = true;
+        session
+    }
+
+    #[test]
+    fn sets_registers_in_any_case() {
+        let mut session = session();
+        set_register(&mut session, "VA", "0x7f").unwrap();
+        set_register(&mut session, "i", "ABC").unwrap();
+        set_register(&mut session, "Pc", "300").unwrap();
+        assert_eq!(session.chip.v(0xA), 0x7F);
+        assert_eq!(session.chip.i(), 0xABC);
+        assert_eq!(session.chip.pc(), 0x300);
+    }
+
+    #[test]
+    fn refuses_registers_that_cannot_be_set() {
+        let mut session = session();
+        let last = format!("{:X}", session.chip.memory().len() - 1);
+        for (register, value) in [
+            ("vg", "1"),
+            ("v10", "1"),
+            ("sp", "1"),
+            ("v0", "100"),
+            ("i", "10000"),
+            ("pc", last.as_str()),
+            ("pc", "FFFFFFFFFFFFFFFF"),
+            ("pc", "nowhere"),
+        ] {
+            assert!(set_register(&mut session, register, value).is_err(), "set {} {}", register, value);
+        }
+        assert_eq!(session.chip.pc(), 0x200);
+
+        session.paused = false;
+        assert_eq!(set_register(&mut session, "v0", "1"), Err("pause first".to_owned()));
+    }
+
+    #[test]
+    fn steps_as_many_as_asked() {
+        let mut session = session();
+        execute("step 3", &mut session);
+        assert_eq!(session.chip.v(0), 2);
+        // Past u32::MAX is clamped rather than wrapped to no steps.
+        session.load(&[0x00, 0xFD]).unwrap();
+        session.paused = true;
+        execute("step 4294967296", &mut session);
+        assert!(session.chip.is_halted());
+    }
+}
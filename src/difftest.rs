@@ -0,0 +1,163 @@
+//! Differential testing against a reference trace.
+//!
+//! `ruchip8 difftest [--seed N] [--timing N|vip|schip] ROM[:QUIRK,...] TRACE`
+//! runs the ROM instruction by instruction and checks each against the
+//! next record of TRACE, in the format `--trace` writes, stopping at the
+//! first one that differs in PC, opcode or the registers it changed. The
+//! trace may come from another emulator whose log was converted to it,
+//! or from a previous build: `--record FRAMES` writes TRACE instead, from
+//! that many frames run with the same seed and timing checking uses, so it
+//! can be checked against after a change. Cycle counts are shown but not
+//! compared, other emulators count differently.
+
+use std::collections::VecDeque;
+use std::path::Path;
+
+use ruchip8::{Error, Timing, REGISTER_SIZE};
+
+use compare;
+use session::Session;
+use trace::{Before, Reader, Record, Trace};
+
+const USAGE: &str = "usage: ruchip8 difftest [--seed N] [--timing N|vip|schip] [--record FRAMES] ROM[:QUIRK,...] TRACE";
+
+/// How many matching instructions the report shows before the divergence.
+const CONTEXT: usize = 8;
+
+/// Runs a session one instruction at a time, ticking the timers as often
+/// as `Session::run_frame` would.
+pub struct Stepper {
+    pub session: Session,
+    /// Cycles spent in the current frame.
+    spent: u32,
+}
+
+impl Stepper {
+    pub fn new(session: Session) -> Self {
+        Stepper { session, spent: 0 }
+    }
+
+    /// Runs one instruction and returns its record.
+    pub fn step(&mut self) -> Result<Record, Error> {
+        let session = &mut self.session;
+        let before = Before::of(&session.chip, session.stats.cycles);
+        session.step(1)?;
+        self.spent += (session.stats.cycles - before.cycle()) as u32;
+        let record = Record::of(&before, &session.chip);
+        if self.spent >= session.timing.budget() {
+            self.spent = 0;
+            session.chip.tick_timers();
+            session.stats.frames += 1;
+        }
+        Ok(record)
+    }
+}
+
+/// How two records of the same instruction differ, `None` if they do not.
+fn difference(expected: &Record, actual: &Record) -> Option<String> {
+    if expected.pc != actual.pc {
+        return Some(format!("PC is {:04X}, expected {:04X}", actual.pc, expected.pc));
+    }
+    if !expected.opcode.eq_ignore_ascii_case(&actual.opcode) {
+        return Some(format!("opcode is {}, expected {}", actual.opcode, expected.opcode));
+    }
+    let mut expected_changes = expected.changes.clone();
+    let mut actual_changes = actual.changes.clone();
+    expected_changes.sort();
+    actual_changes.sort();
+    if expected_changes != actual_changes {
+        return Some(format!("changed {}, expected {}", or_nothing(actual.changes_text()), or_nothing(expected.changes_text())));
+    }
+    None
+}
+
+fn or_nothing(changes: String) -> String {
+    if changes.is_empty() { "nothing".to_owned() } else { changes }
+}
+
+pub fn line(record: &Record) -> String {
+    format!("{:>10}  {:04X}  {:<8}  {:<16}  {}", record.cycle, record.pc, record.opcode, record.mnemonic, record.changes_text())
+}
+
+pub fn registers(session: &Session) -> String {
+    let chip = &session.chip;
+    let v: Vec<String> = (0..REGISTER_SIZE).map(|n| format!("V{:X}={:02X}", n, chip.v(n as u8))).collect();
+    let stack: Vec<String> = chip.stack().iter().map(|addr| format!("{:04X}", addr)).collect();
+    format!("{}\nI={:04X} DT={:02X} ST={:02X} stack [{}]",
+            v.join(" "), chip.i(), chip.delay_timer(), chip.sound_timer(), stack.join(" "))
+}
+
+pub fn command(args: &[String]) -> Result<(), String> {
+    let mut seed = 0;
+    let mut timing = Timing::default();
+    let mut record = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let n = args.next().ok_or("--seed needs a number")?;
+                seed = n.parse().map_err(|_| format!("invalid seed '{}'", n))?;
+            },
+            "--timing" => {
+                let text = args.next().ok_or("--timing needs instructions per frame, vip or schip")?;
+                timing = Timing::parse(text).ok_or_else(|| format!("invalid timing '{}'", text))?;
+            },
+            "--record" => {
+                let n = args.next().ok_or("--record needs a frame count")?;
+                record = Some(n.parse::<u32>().map_err(|_| format!("invalid frame count '{}'", n))?);
+            },
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'\n{}", flag, USAGE)),
+            path => paths.push(path),
+        }
+    }
+    let (spec, trace) = match paths[..] {
+        [spec, trace] => (spec, trace),
+        _ => return Err(USAGE.to_owned()),
+    };
+
+    let mut session = compare::session(spec, seed)?;
+    session.timing = timing;
+    if let Some(frames) = record {
+        session.trace = Some(Trace::create(Path::new(trace), Vec::new(), None)?);
+        for _ in 0..frames {
+            session.run_frame();
+        }
+        // Dropping the trace flushes it.
+        session.trace = None;
+        println!("recorded {} instructions over {} frames", session.stats.instructions, session.stats.frames);
+        return Ok(());
+    }
+    let mut reference = Reader::open(Path::new(trace))?;
+    let mut recent: VecDeque<Record> = VecDeque::with_capacity(CONTEXT);
+    let mut checked = 0u64;
+    let mut stepper = Stepper::new(session);
+
+    while let Some(expected) = reference.next().transpose()? {
+        if stepper.session.chip.is_halted() {
+            return Err(format!("the program exited after {} instructions, the trace goes on with\n{}",
+                               checked, line(&expected)));
+        }
+        let actual = match stepper.step() {
+            Ok(actual) => actual,
+            Err(e) => return Err(format!("instruction {} failed: {}, the trace has\n{}", checked + 1, e, line(&expected))),
+        };
+        if let Some(difference) = difference(&expected, &actual) {
+            println!("diverged at instruction {}: {}\n", checked + 1, difference);
+            for record in &recent {
+                println!("  {}", line(record));
+            }
+            println!("- {}", line(&expected));
+            println!("+ {}\n", line(&actual));
+            println!("{}", registers(&stepper.session));
+            return Err("the trace does not match".to_owned());
+        }
+        checked += 1;
+        if recent.len() == CONTEXT {
+            recent.pop_front();
+        }
+        recent.push_back(actual);
+    }
+    println!("{} instructions match the trace", checked);
+    Ok(())
+}
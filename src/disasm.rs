@@ -0,0 +1,316 @@
+//! Opcode to mnemonic translation, in the style of Cowgod's reference.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use font::{BIG_FONT_SET, BIG_FONT_START, FONT_SIZE};
+use instruction::{decode, decode_long, Instruction, LONG_PREFIX};
+use PROGRAM_START;
+
+/// Mnemonic for one opcode, or `None` when the machine does not know it.
+pub fn disassemble(ops: u16) -> Option<String> {
+    decode(ops).map(|instruction| instruction.to_string())
+}
+
+/// Names for addresses, read from a label file with one `ADDR=NAME` line
+/// per label, such as assemblers and Octo write. Addresses are hexadecimal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    names: BTreeMap<usize, String>,
+}
+
+impl Symbols {
+    /// Reads a label file. Blank lines and anything after a `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut names = BTreeMap::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (addr, name) = line.split_once('=')
+                .ok_or_else(|| format!("line {}: expected ADDR=NAME", n + 1))?;
+            let (addr, name) = (addr.trim(), name.trim());
+            let digits = addr.trim_start_matches("0x").trim_start_matches("0X");
+            let addr = usize::from_str_radix(digits, 16)
+                .map_err(|_| format!("line {}: invalid address '{}'", n + 1, addr))?;
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(format!("line {}: invalid name '{}'", n + 1, name));
+            }
+            names.insert(addr, name.to_owned());
+        }
+        Ok(Symbols { names })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Names `addr`, unless it has a name already.
+    pub fn insert(&mut self, addr: usize, name: &str) {
+        self.names.entry(addr).or_insert_with(|| name.to_owned());
+    }
+
+    /// The label at `addr`.
+    pub fn name(&self, addr: usize) -> Option<&str> {
+        self.names.get(&addr).map(String::as_str)
+    }
+
+    /// The address labelled `name`.
+    pub fn addr(&self, name: &str) -> Option<usize> {
+        self.names.iter().find(|&(_, label)| label == name).map(|(&addr, _)| addr)
+    }
+}
+
+/// The address an instruction jumps to, calls or points I at.
+fn target(instruction: Instruction) -> Option<usize> {
+    use instruction::Instruction::*;
+    match instruction {
+        Sys { addr } | Jump { addr } | Call { addr } | LoadIndex { addr } | JumpV0 { addr } => Some(addr as usize),
+        LongIndex { addr } => Some(addr as usize),
+        _ => None,
+    }
+}
+
+/// The mnemonic with the address it refers to replaced by its label.
+pub fn symbolic(instruction: Instruction, symbols: &Symbols) -> String {
+    let text = instruction.to_string();
+    match target(instruction).and_then(|addr| symbols.name(addr)) {
+        // The address is always the last operand.
+        Some(name) => match text.rsplit_once(' ') {
+            Some((mnemonic, _)) => format!("{} {}", mnemonic, name),
+            None => text,
+        },
+        None => text,
+    }
+}
+
+/// The instruction at `at`, reading the second word of F000 NNNN.
+fn instruction_at(memory: &[u8], at: usize) -> Option<Instruction> {
+    let word = |at: usize| memory.get(at..at + 2).map(|op| (op[0] as u16) << 8 | op[1] as u16);
+    let ops = word(at)?;
+    if ops == LONG_PREFIX {
+        return decode_long(ops, word(at + 2)?);
+    }
+    decode(ops)
+}
+
+/// Addresses of the instructions reachable from `start` by following
+/// jumps, calls and both ways out of skips, with the instruction there or
+/// `None` for an unknown opcode or an address past the end of `memory`.
+/// BNNN, 0NNN, 00FD and unknown opcodes end a path, so code only reached
+/// through a computed jump is missed.
+pub fn reachable(memory: &[u8], start: usize) -> BTreeMap<usize, Option<Instruction>> {
+    use instruction::Instruction::*;
+    let mut found = BTreeMap::new();
+    let mut pending = vec![start];
+    let mut seen = BTreeSet::new();
+    while let Some(at) = pending.pop() {
+        if !seen.insert(at) {
+            continue;
+        }
+        let instruction = instruction_at(memory, at);
+        found.insert(at, instruction);
+        let instruction = match instruction {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        let next = at + instruction.size();
+        match instruction {
+            Jump { addr } => pending.push(addr as usize),
+            Call { addr } => pending.extend_from_slice(&[addr as usize, next]),
+            SkipEqImm { .. } | SkipNeImm { .. } | SkipEqReg { .. } | SkipNeReg { .. } |
+            SkipKey { .. } | SkipNotKey { .. } => {
+                let skipped = instruction_at(memory, next).map_or(2, Instruction::size);
+                pending.extend_from_slice(&[next, next + skipped]);
+            },
+            Ret | JumpV0 { .. } | Sys { .. } | Exit => {},
+            _ => pending.push(next),
+        }
+    }
+    found
+}
+
+/// Disassembles `count` instructions of `memory` starting at `addr`, one
+/// `ADDR: OPCODE  MNEMONIC` line each. Unknown opcodes show as data.
+/// F000 NNNN takes one line with both words.
+pub fn listing(memory: &[u8], addr: usize, count: usize) -> Vec<String> {
+    listing_with(memory, addr, count, &Symbols::default())
+}
+
+/// Like `listing`, using the labels in `symbols` for addresses. Labelled
+/// instructions get a `NAME:` line of their own on top.
+pub fn listing_with(memory: &[u8], addr: usize, count: usize, symbols: &Symbols) -> Vec<String> {
+    let word = |at: usize| (memory[at] as u16) << 8 | memory[at + 1] as u16;
+    let mut lines = Vec::new();
+    let mut shown = 0;
+    let mut at = addr;
+    while shown < count && at + 1 < memory.len() {
+        if let Some(name) = symbols.name(at) {
+            lines.push(format!("{}:", name));
+        }
+        shown += 1;
+        let ops = word(at);
+        if ops == LONG_PREFIX && at + 3 < memory.len() {
+            let next = word(at + 2);
+            let text = decode_long(ops, next).map_or_else(String::new, |instruction| symbolic(instruction, symbols));
+            lines.push(format!("{:04X}: {:04X} {:04X}  {}", at, ops, next, text));
+            at += 4;
+            continue;
+        }
+        let text = decode(ops).map_or_else(|| format!("DW {:04X}", ops), |instruction| symbolic(instruction, symbols));
+        lines.push(format!("{:04X}: {:04X}  {}", at, ops, text));
+        at += 2;
+    }
+    lines
+}
+
+/// What `instruction` does in words, for readers new to CHIP-8, or `None`
+/// when the mnemonic says it all.
+pub fn comment(instruction: Instruction) -> Option<String> {
+    use instruction::Instruction::*;
+    Some(match instruction {
+        Cls => "clear the screen".to_owned(),
+        Ret => "return from the subroutine".to_owned(),
+        ScrollDown { n } => format!("scroll the screen down {} rows", n),
+        ScrollUp { n } => format!("scroll the screen up {} rows", n),
+        ScrollRight => "scroll the screen right 4 pixels".to_owned(),
+        ScrollLeft => "scroll the screen left 4 pixels".to_owned(),
+        Sys { .. } => "machine code routine, which interpreters skip".to_owned(),
+        Exit => "exit the interpreter".to_owned(),
+        LowRes => "switch to the 64x32 screen".to_owned(),
+        HighRes => "switch to the 128x64 screen".to_owned(),
+        SkipEqImm { x, nn } => format!("skip the next instruction if V{:X} == {:02X}", x, nn),
+        SkipNeImm { x, nn } => format!("skip the next instruction if V{:X} != {:02X}", x, nn),
+        SkipEqReg { x, y } => format!("skip the next instruction if V{:X} == V{:X}", x, y),
+        SkipNeReg { x, y } => format!("skip the next instruction if V{:X} != V{:X}", x, y),
+        StoreRange { x, y } => format!("save V{:X}..V{:X} to I", x, y),
+        LoadRange { x, y } => format!("load V{:X}..V{:X} from I", x, y),
+        AddImm { .. } => "VF is left alone, there is no carry".to_owned(),
+        Add { .. } => "VF = carry".to_owned(),
+        Sub { .. } => "VF = 1 unless it borrows".to_owned(),
+        SubN { x, y } => format!("V{:X} = V{:X} - V{:X}, VF = 1 unless it borrows", x, y, x),
+        Shr { .. } => "shift right by one, VF = the bit shifted out".to_owned(),
+        Shl { .. } => "shift left by one, VF = the bit shifted out".to_owned(),
+        JumpV0 { addr } => format!("jump to {:03X} + V0", addr),
+        Random { x, nn } => format!("V{:X} = random byte & {:02X}", x, nn),
+        Draw { x, y, n: 0 } => format!("draw 16x16 sprite at (V{:X},V{:X})", x, y),
+        Draw { x, y, n } => format!("draw {}-byte sprite at (V{:X},V{:X})", n, x, y),
+        SkipKey { x } => format!("skip the next instruction if key V{:X} is held", x),
+        SkipNotKey { x } => format!("skip the next instruction unless key V{:X} is held", x),
+        ReadDelay { x } => format!("V{:X} = delay timer", x),
+        WaitKey { x } => format!("wait for a key press and put it in V{:X}", x),
+        SetDelay { x } => format!("delay timer = V{:X}", x),
+        SetSound { x } => format!("sound timer = V{:X}, beeps until it runs out", x),
+        AddIndex { x } => format!("I += V{:X}", x),
+        Font { x } => format!("point I at the digit in V{:X}", x),
+        BigFont { x } => format!("point I at the large digit in V{:X}", x),
+        Bcd { x } => format!("BCD of V{:X} into I..I+2", x),
+        Store { x } => format!("save V0..V{:X} to I..I+{}", x, x),
+        Load { x } => format!("load V0..V{:X} from I..I+{}", x, x),
+        Audio => "load the 16 byte audio pattern at I".to_owned(),
+        Pitch { x } => format!("audio pitch = V{:X}", x),
+        SaveFlags { x } => format!("save V0..V{:X} to the persistent flags", x),
+        LoadFlags { x } => format!("load V0..V{:X} from the persistent flags", x),
+        _ => return None,
+    })
+}
+
+/// A byte as the 8 pixels it draws.
+fn pixels(byte: u8) -> String {
+    (0..8).map(|bit| if byte & 0x80 >> bit != 0 { '#' } else { '.' }).collect()
+}
+
+/// A listing line with its comment lined up after it.
+fn commented(line: String, comment: Option<String>) -> String {
+    match comment {
+        Some(comment) => format!("{:<32}; {}", line, comment),
+        None => line,
+    }
+}
+
+/// Disassembles `memory` from `start` up to `end` for someone studying the
+/// program loaded at `PROGRAM_START`. Code reachable from there gets the
+/// comments `comment` has, data that code points I at is shown a byte a
+/// line with the pixels it draws, and so is the font when `start` is below
+/// the program. Anything else is marked as unreached, since it may be code
+/// only a computed jump gets to or data only computed addresses read.
+pub fn annotated(memory: &[u8], start: usize, end: usize, symbols: &Symbols) -> Vec<String> {
+    use instruction::Instruction::*;
+    let end = end.min(memory.len());
+    let code = reachable(memory, PROGRAM_START);
+    // Where each table starts and the instructions pointing at it.
+    let mut tables: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (&at, instruction) in &code {
+        if let Some(LoadIndex { addr }) | Some(LongIndex { addr }) = *instruction {
+            let addr = addr as usize;
+            if addr >= PROGRAM_START && !code.contains_key(&addr) {
+                tables.entry(addr).or_default().push(at);
+            }
+        }
+    }
+    let in_table = |at: usize| {
+        tables.range(..=at).next_back().is_some_and(|(&table, _)| {
+            // A table runs until code or the next table.
+            code.range(table..=at).next().is_none()
+        })
+    };
+
+    let mut lines = Vec::new();
+    let mut at = start;
+    while at < end {
+        if let Some(name) = symbols.name(at) {
+            lines.push(format!("{}:", name));
+        }
+        let byte = memory[at];
+        if at < FONT_SIZE || (BIG_FONT_START..BIG_FONT_START + BIG_FONT_SET.len()).contains(&at) {
+            let glyph = if at < FONT_SIZE {
+                at.is_multiple_of(5).then(|| format!(" font {:X}", at / 5))
+            } else {
+                (at - BIG_FONT_START).is_multiple_of(10).then(|| format!(" large font {:X}", (at - BIG_FONT_START) / 10))
+            };
+            let line = format!("{:04X}: {:02X}    DB {:02X}", at, byte, byte);
+            lines.push(commented(line, Some(pixels(byte) + &glyph.unwrap_or_default())));
+            at += 1;
+            continue;
+        }
+        if let Some(references) = tables.get(&at) {
+            let from: Vec<String> = references.iter().map(|at| format!("{:04X}", at)).collect();
+            lines.push(format!("; data, I points here at {}", from.join(", ")));
+        }
+        if in_table(at) && !code.contains_key(&at) {
+            let line = format!("{:04X}: {:02X}    DB {:02X}", at, byte, byte);
+            lines.push(commented(line, Some(pixels(byte))));
+            at += 1;
+            continue;
+        }
+        if at + 1 >= end {
+            lines.push(format!("{:04X}: {:02X}    DB {:02X}", at, byte, byte));
+            break;
+        }
+        let ops = (byte as u16) << 8 | memory[at + 1] as u16;
+        let reached = code.contains_key(&at);
+        let (line, size, comment) = match instruction_at(memory, at) {
+            Some(instruction) => {
+                let words = if instruction.size() == 4 {
+                    format!("{:04X} {:04X}", ops, (memory[at + 2] as u16) << 8 | memory[at + 3] as u16)
+                } else {
+                    format!("{:04X}", ops)
+                };
+                let line = format!("{:04X}: {}  {}", at, words, symbolic(instruction, symbols));
+                let comment = match instruction {
+                    _ if !reached => Some("unreached".to_owned()),
+                    Jump { addr } if addr as usize == at => Some("wait here forever".to_owned()),
+                    _ => comment(instruction),
+                };
+                (line, instruction.size(), comment)
+            },
+            None => {
+                let comment = if reached { "unknown opcode" } else { "unreached" };
+                (format!("{:04X}: {:04X}  DW {:04X}", at, ops, ops), 2, Some(comment.to_owned()))
+            },
+        };
+        lines.push(commented(line, comment));
+        at += size;
+    }
+    lines
+}
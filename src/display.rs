@@ -0,0 +1,290 @@
+use core::mem;
+
+use palette::Palette;
+use {DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH};
+
+/// Each byte of a packed row as the eight pixels it holds.
+static UNPACKED: [[u8; 8]; 256] = unpacked();
+
+const fn unpacked() -> [[u8; 8]; 256] {
+    let mut table = [[0; 8]; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut x = 0;
+        while x < 8 {
+            table[byte][x] = (byte >> (7 - x)) as u8 & 1;
+            x += 1;
+        }
+        byte += 1;
+    }
+    table
+}
+
+/// Monochrome CHIP-8 framebuffer, one byte per pixel. Starts in the 64x32
+/// mode, SCHIP programs can switch it to 128x64.
+///
+/// Instructions draw into a packed buffer, one `u128` per row with the
+/// leftmost pixel in the top bit, so a sprite row is a shift, an AND for
+/// the collision and an XOR. Only the presented frame is unpacked to bytes.
+///
+/// The working buffer is what instructions draw into. `present` copies it to the
+/// presented buffer once a frame, at the 60Hz boundary, and frontends show
+/// `frame()` so they never catch a sprite half drawn. That boundary is the
+/// vertical blank the COSMAC VIP waits for before drawing.
+pub struct Display {
+    /// The working buffer, packed as above.
+    rows: [u128; HIRES_HEIGHT],
+    hires: bool,
+    presented: [u8; HIRES_HEIGHT * HIRES_WIDTH],
+    presented_hires: bool,
+    frame_ready: bool,
+    /// The presented frame as `drain_changes` last handed it out.
+    drained: [u8; HIRES_HEIGHT * HIRES_WIDTH],
+    drained_hires: bool,
+}
+
+/// A presented frame, see `Display::frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pixels: &'a [u8],
+    hires: bool,
+}
+
+impl<'a> Frame<'a> {
+    /// A frame of `pixels` in the mode `hires` picks, one byte per pixel
+    /// and row by row. Panics unless there are width by height of them.
+    pub fn new(pixels: &'a [u8], hires: bool) -> Self {
+        let frame = Frame { pixels, hires };
+        assert_eq!(pixels.len(), frame.width() * frame.height(), "wrong number of pixels");
+        frame
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { DISPLAY_WIDTH }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { DISPLAY_HEIGHT }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Returns whether the pixel at x,y is set.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width() + x] != 0
+    }
+
+    /// The pixels row by row, `width()` pixels per row.
+    pub fn pixels(&self) -> &'a [u8] {
+        self.pixels
+    }
+
+    /// The frame at 128x64 in both modes, lores pixels doubled, as RGBA in
+    /// `palette`'s colors, for frontends that draw one texture of a fixed
+    /// size. Panics unless `out` holds 128 by 64 by 4 bytes.
+    pub fn scaled_rgba(&self, palette: &Palette, out: &mut [u8]) {
+        assert_eq!(out.len(), HIRES_WIDTH * HIRES_HEIGHT * 4, "wrong size of RGBA buffer");
+        let (width, height) = (self.width(), self.height());
+        for (at, rgba) in out.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (at % HIRES_WIDTH, at / HIRES_WIDTH);
+            let [r, g, b] = palette.color(self.pixels[y * height / HIRES_HEIGHT * width + x * width / HIRES_WIDTH]);
+            rgba.copy_from_slice(&[r, g, b, 0xFF]);
+        }
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Display::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Self {
+        Display {
+            rows: [0; HIRES_HEIGHT],
+            hires: false,
+            presented: [0; HIRES_HEIGHT * HIRES_WIDTH],
+            presented_hires: false,
+            frame_ready: false,
+            drained: [0; HIRES_HEIGHT * HIRES_WIDTH],
+            drained_hires: false,
+        }
+    }
+
+    /// Width in pixels of the current mode.
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { DISPLAY_WIDTH }
+    }
+
+    /// Height in pixels of the current mode.
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { DISPLAY_HEIGHT }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switches between 64x32 and 128x64, clearing the screen.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear();
+    }
+
+    /// Switches to the mode `hires` picks and presents `screen`, which must
+    /// be `width()` by `height()` pixels for that mode.
+    pub fn restore(&mut self, hires: bool, screen: &[u8]) {
+        self.set_hires(hires);
+        let width = self.width();
+        for (packed, row) in self.rows.iter_mut().zip(screen.chunks(width)) {
+            *packed = row.iter().enumerate()
+                .filter(|&(_, &px)| px != 0)
+                .fold(0, |packed, (x, _)| packed | 1 << (127 - x));
+        }
+        self.present();
+    }
+
+    /// Returns whether the pixel at x,y is set.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & 1 << (127 - x) != 0
+    }
+
+    /// The working buffer, row by row, `width()` pixels per row, 1 for a
+    /// set pixel and 0 for an unset one.
+    pub fn screen(&self) -> impl Iterator<Item = u8> + '_ {
+        let width = self.width();
+        self.rows().iter().flat_map(move |&packed| (0..width).map(move |x| (packed >> (127 - x)) as u8 & 1))
+    }
+
+    /// The working buffer packed, one row of the current mode per entry
+    /// with pixel x in bit `127 - x`.
+    pub fn rows(&self) -> &[u128] {
+        &self.rows[..self.height()]
+    }
+
+    /// Makes what was drawn so far the presented frame and flags it ready.
+    pub fn present(&mut self) {
+        let (width, height) = (self.width(), self.height());
+        for (row, &packed) in self.presented.chunks_mut(width).zip(self.rows[..height].iter()) {
+            for (pixels, &byte) in row.chunks_mut(8).zip(packed.to_be_bytes().iter()) {
+                pixels.copy_from_slice(&UNPACKED[byte as usize]);
+            }
+        }
+        self.presented_hires = self.hires;
+        self.frame_ready = true;
+    }
+
+    /// The last presented frame.
+    pub fn frame(&self) -> Frame<'_> {
+        let len = if self.presented_hires { HIRES_WIDTH * HIRES_HEIGHT } else { DISPLAY_WIDTH * DISPLAY_HEIGHT };
+        Frame { pixels: &self.presented[..len], hires: self.presented_hires }
+    }
+
+    /// Whether a frame was presented since the last call, clearing the flag.
+    pub fn take_frame_ready(&mut self) -> bool {
+        mem::replace(&mut self.frame_ready, false)
+    }
+
+    /// The pixels of the presented frame that changed since the last call,
+    /// as x, y and whether the pixel is now set. The first call yields
+    /// every set pixel. After a mode switch the frontend is expected to
+    /// start over from a blank screen, so the set pixels all come again.
+    ///
+    /// Pixels the iterator does not get to are left for the next call.
+    pub fn drain_changes(&mut self) -> impl Iterator<Item = (usize, usize, bool)> + '_ {
+        if self.drained_hires != self.presented_hires {
+            self.drained = [0; HIRES_HEIGHT * HIRES_WIDTH];
+            self.drained_hires = self.presented_hires;
+        }
+        let frame = self.frame();
+        let (width, len) = (frame.width(), frame.pixels().len());
+        self.presented[..len].iter().zip(self.drained[..len].iter_mut()).enumerate()
+            .filter_map(move |(n, (&px, seen))| {
+                if *seen == px {
+                    return None;
+                }
+                *seen = px;
+                Some((n % width, n / width, px != 0))
+            })
+    }
+
+    /// The bits of a packed row that are on screen in the current mode.
+    fn width_mask(&self) -> u128 {
+        !0 << (128 - self.width())
+    }
+
+    /// Unsets every pixel.
+    pub fn clear(&mut self) {
+        self.rows = [0; HIRES_HEIGHT];
+    }
+
+    /// XORs a sprite onto the screen. The starting position wraps around
+    /// the screen whilst the sprite itself is clipped at the edges.
+    /// Returns true if any set pixel was unset.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.xor_rows(x, y, sprite.iter().map(|&byte| (byte as u16) << 8))
+    }
+
+    /// Like `draw`, with the SCHIP 16x16 sprite made of two bytes per row.
+    pub fn draw_large(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.xor_rows(x, y, sprite.chunks(2).map(|row| {
+            (row[0] as u16) << 8 | row.get(1).cloned().unwrap_or(0) as u16
+        }))
+    }
+
+    /// XORs rows of 16 pixels, most significant bit leftmost.
+    fn xor_rows<I: Iterator<Item = u16>>(&mut self, x: usize, y: usize, rows: I) -> bool {
+        let (width, height) = (self.width(), self.height());
+        let x = x % width;
+        let y = y % height;
+        let mask = self.width_mask();
+        let mut collision = false;
+
+        for (packed, sprite) in self.rows[y..height].iter_mut().zip(rows) {
+            let line = ((sprite as u128) << 112 >> x) & mask;
+            collision |= *packed & line != 0;
+            *packed ^= line;
+        }
+
+        collision
+    }
+
+    /// Moves everything down `n` rows, blanking the rows at the top.
+    pub fn scroll_down(&mut self, n: usize) {
+        let height = self.height();
+        let n = n.min(height);
+        let rows = &mut self.rows[..height];
+        rows.copy_within(..height - n, n);
+        rows[..n].fill(0);
+    }
+
+    /// Moves everything up `n` rows, blanking the rows at the bottom.
+    pub fn scroll_up(&mut self, n: usize) {
+        let height = self.height();
+        let n = n.min(height);
+        let rows = &mut self.rows[..height];
+        rows.copy_within(n.., 0);
+        rows[height - n..].fill(0);
+    }
+
+    /// Moves everything right `n` columns, blanking the columns on the left.
+    pub fn scroll_right(&mut self, n: usize) {
+        let height = self.height();
+        let mask = self.width_mask();
+        for packed in self.rows[..height].iter_mut() {
+            *packed = packed.checked_shr(n as u32).unwrap_or(0) & mask;
+        }
+    }
+
+    /// Moves everything left `n` columns, blanking the columns on the right.
+    pub fn scroll_left(&mut self, n: usize) {
+        let height = self.height();
+        let mask = self.width_mask();
+        for packed in self.rows[..height].iter_mut() {
+            *packed = packed.checked_shl(n as u32).unwrap_or(0) & mask;
+        }
+    }
+}
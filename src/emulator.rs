@@ -0,0 +1,263 @@
+//! Running the machine on a thread of its own, driven from another one.
+//!
+//! `Controller::spawn` moves a `Chip8` to an emulation thread that runs
+//! frames at `TIMERS_CLOCK` and publishes them through `handoff`. The UI
+//! thread keeps the `Controller` and sends it commands, which the machine
+//! takes between frames, so a slow frontend never holds the machine up and
+//! a fast-forwarding machine never holds the frontend up:
+//!
+//! ```
+//! # use ruchip8::emulator::Controller;
+//! # use ruchip8::Chip8;
+//! let mut controller = Controller::spawn(Chip8::new());
+//! controller.load(&[0x00, 0xE0, 0x12, 0x02]).unwrap();
+//! controller.set_key(0x5, true);
+//! if controller.frames().update() {
+//!     let _frame = controller.frames().frame();
+//! }
+//! let _state = controller.save_state().unwrap();
+//! ```
+//!
+//! The machine stops on errors, `Event::Error` says why. It starts over
+//! with `load`, a reset or `load_state`.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chip8::Chip8;
+use error::Error;
+use handoff::{FrameReader, FrameShare, FrameWriter};
+use snapshot::Savestate;
+use TIMERS_CLOCK;
+
+/// What the emulation thread tells the UI thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The machine stopped on `Error`.
+    Error(Error),
+    /// The buzzer started or stopped.
+    Sound(bool),
+    /// The machine ran 00FD.
+    Halted,
+}
+
+enum Command {
+    Load(Vec<u8>, Sender<Result<(), Error>>),
+    Pause(bool),
+    FastForward(bool),
+    SoftReset,
+    HardReset,
+    Key(u8, bool),
+    SaveState(Sender<Savestate>),
+    LoadState(Box<Savestate>, Sender<Result<(), Error>>),
+    Quit,
+}
+
+/// The handle the UI thread drives the emulation thread with. Dropping it
+/// stops the thread.
+pub struct Controller {
+    commands: Sender<Command>,
+    events: Receiver<Event>,
+    frames: FrameReader<'static>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Controller {
+    /// Moves `chip` to a new thread, paused until `load`, a reset,
+    /// `load_state` or `set_paused` starts it.
+    pub fn spawn(chip: Chip8) -> Controller {
+        let (commands, inbox) = mpsc::channel();
+        let (outbox, events) = mpsc::channel();
+        let (writer, frames) = FrameShare::new().into_split();
+        let mut emulation = Emulation { chip, rom: Vec::new(), writer, inbox, outbox, paused: true, fast: false, sound: false };
+        let thread = thread::Builder::new()
+            .name("emulation".to_owned())
+            .spawn(move || emulation.run())
+            .expect("cannot start the emulation thread");
+        Controller { commands, events, frames, thread: Some(thread) }
+    }
+
+    fn send(&self, command: Command) {
+        // The thread only goes away with the controller or by panicking,
+        // which `Drop` passes on.
+        let _ = self.commands.send(command);
+    }
+
+    /// Loads `rom` and starts it from a fresh reset.
+    pub fn load(&self, rom: &[u8]) -> Result<(), Error> {
+        let (reply, result) = mpsc::channel();
+        self.send(Command::Load(rom.to_vec(), reply));
+        result.recv().unwrap_or(Ok(()))
+    }
+
+    /// Stops or resumes the machine. Commands still go through while it is
+    /// paused.
+    pub fn set_paused(&self, paused: bool) {
+        self.send(Command::Pause(paused));
+    }
+
+    /// Runs frames back to back instead of at `TIMERS_CLOCK`.
+    pub fn set_fast_forward(&self, fast: bool) {
+        self.send(Command::FastForward(fast));
+    }
+
+    /// Starts the program over, see `Chip8::soft_reset`.
+    pub fn soft_reset(&self) {
+        self.send(Command::SoftReset);
+    }
+
+    /// Starts the ROM loaded last over on cleared memory, see
+    /// `Chip8::hard_reset`.
+    pub fn hard_reset(&self) {
+        self.send(Command::HardReset);
+    }
+
+    /// Presses or releases a key, see `Chip8::push_key`.
+    pub fn set_key(&self, key: u8, pressed: bool) {
+        self.send(Command::Key(key, pressed));
+    }
+
+    /// A savestate of the machine between two frames. It has no ROM hash,
+    /// the caller knows the ROM better.
+    pub fn save_state(&self) -> Option<Savestate> {
+        let (reply, state) = mpsc::channel();
+        self.send(Command::SaveState(reply));
+        state.recv().ok()
+    }
+
+    pub fn load_state(&self, state: Savestate) -> Result<(), Error> {
+        let (reply, result) = mpsc::channel();
+        self.send(Command::LoadState(Box::new(state), reply));
+        result.recv().unwrap_or(Ok(()))
+    }
+
+    /// The frames the machine presented, the newest after each `update`.
+    pub fn frames(&mut self) -> &mut FrameReader<'static> {
+        &mut self.frames
+    }
+
+    /// The next event the emulation thread sent, without waiting for one.
+    pub fn poll_event(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+}
+
+impl Drop for Controller {
+    fn drop(&mut self) {
+        self.send(Command::Quit);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() && !thread::panicking() {
+                panic!("the emulation thread panicked");
+            }
+        }
+    }
+}
+
+/// The emulation thread's side.
+struct Emulation {
+    chip: Chip8,
+    /// The ROM loaded last, for hard resets.
+    rom: Vec<u8>,
+    writer: FrameWriter<'static>,
+    inbox: Receiver<Command>,
+    outbox: Sender<Event>,
+    paused: bool,
+    fast: bool,
+    sound: bool,
+}
+
+impl Emulation {
+    fn run(&mut self) {
+        let frame_time = Duration::from_secs(1) / TIMERS_CLOCK;
+        let mut next_frame = Instant::now();
+        loop {
+            // Wait for the next frame, or for as long as it takes to be
+            // woken up while paused, taking commands in the meantime.
+            let command = if self.paused {
+                self.inbox.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else if self.fast {
+                self.inbox.try_recv().map_err(|e| match e {
+                    mpsc::TryRecvError::Empty => RecvTimeoutError::Timeout,
+                    mpsc::TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+                })
+            } else {
+                self.inbox.recv_timeout(next_frame.saturating_duration_since(Instant::now()))
+            };
+            match command {
+                Ok(Command::Quit) | Err(RecvTimeoutError::Disconnected) => return,
+                Ok(command) => {
+                    self.take(command);
+                    continue;
+                },
+                Err(RecvTimeoutError::Timeout) => {},
+            }
+            let now = Instant::now();
+            // After a stall, carry on from now rather than catching up.
+            next_frame = if now > next_frame + frame_time { now + frame_time } else { next_frame + frame_time };
+            self.frame();
+        }
+    }
+
+    fn take(&mut self, command: Command) {
+        match command {
+            Command::Load(rom, reply) => {
+                let result = self.chip.hard_reset(&rom);
+                self.paused = result.is_err();
+                if result.is_ok() {
+                    self.rom = rom;
+                }
+                let _ = reply.send(result);
+            },
+            Command::Pause(paused) => self.paused = paused,
+            Command::FastForward(fast) => self.fast = fast,
+            Command::SoftReset => {
+                self.chip.soft_reset();
+                self.paused = false;
+            },
+            Command::HardReset => {
+                // The ROM fitted when it was loaded.
+                let _ = self.chip.hard_reset(&self.rom);
+                self.paused = false;
+            },
+            Command::Key(key, pressed) => self.chip.push_key(key, pressed),
+            Command::SaveState(reply) => {
+                let _ = reply.send(Savestate::of(&self.chip, None));
+            },
+            Command::LoadState(state, reply) => {
+                let result = state.restore(&mut self.chip);
+                if result.is_ok() {
+                    self.paused = false;
+                    self.writer.publish(self.chip.display().frame());
+                }
+                let _ = reply.send(result);
+            },
+            Command::Quit => {},
+        }
+    }
+
+    fn frame(&mut self) {
+        let was_halted = self.chip.is_halted();
+        // Keys come through `push_key`, which `run_frame` must not undo.
+        let keys = self.chip.keys();
+        let output = match self.chip.run_frame(keys) {
+            Ok(output) => output,
+            Err(e) => {
+                self.paused = true;
+                let _ = self.outbox.send(Event::Error(e));
+                return;
+            },
+        };
+        if output.frame_ready {
+            self.writer.publish(output.display.frame());
+        }
+        let (sound, halted) = (output.sound, output.halted);
+        if sound != self.sound {
+            self.sound = sound;
+            let _ = self.outbox.send(Event::Sound(sound));
+        }
+        if halted && !was_halted {
+            let _ = self.outbox.send(Event::Halted);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error;
+
+use {MAX_MEMORY_SIZE, PROGRAM_START};
+
+/// Errors the machine can run into whilst executing a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The opcode at `pc` does not map to any instruction.
+    UnknownOpcode { opcode: u16, pc: usize },
+    /// A subroutine call was made with a full stack.
+    StackOverflow { pc: usize },
+    /// A return was made with an empty stack.
+    StackUnderflow { pc: usize },
+    /// An access to `addr` fell outside of the machine memory.
+    MemoryOutOfBounds { addr: usize, pc: usize },
+    /// The program wrote to `addr`, below `PROGRAM_START`, with write
+    /// protection on.
+    ProtectedWrite { addr: usize, pc: usize },
+    /// The program called the machine language routine at `addr` with
+    /// 0NNN, which the machine can't run.
+    MachineCall { addr: u16, pc: usize },
+    /// The program is larger than the memory available from `PROGRAM_START`.
+    RomTooLarge { size: usize },
+    /// A machine can't be built with `size` bytes of memory.
+    InvalidMemorySize { size: usize },
+    /// A snapshot is malformed or was taken on a machine with another
+    /// memory size.
+    InvalidSnapshot,
+    /// A savestate is in format `version`, newer than this build reads.
+    NewerSavestate { version: u16 },
+    /// A savestate was taken on a machine with `memory_size` bytes of
+    /// memory and the VY shift quirk set as `shift_vy`, unlike this one.
+    OtherMachine { memory_size: usize, shift_vy: bool },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnknownOpcode { opcode, pc } =>
+                write!(f, "{:0>4X} opcode not implemented at {:05X}", opcode, pc),
+            Error::StackOverflow { pc } =>
+                write!(f, "stack overflow at {:05X}", pc),
+            Error::StackUnderflow { pc } =>
+                write!(f, "return with an empty stack at {:05X}", pc),
+            Error::MemoryOutOfBounds { addr, pc } =>
+                write!(f, "memory access to {:05X} out of bounds at {:05X}", addr, pc),
+            Error::ProtectedWrite { addr, pc } =>
+                write!(f, "write to {:05X} below the program at {:05X}", addr, pc),
+            Error::MachineCall { addr, pc } =>
+                write!(f, "call to machine language routine {:03X} at {:05X}", addr, pc),
+            Error::RomTooLarge { size } =>
+                write!(f, "ROM of {} bytes does not fit in memory", size),
+            Error::InvalidMemorySize { size } =>
+                write!(f, "memory of {} bytes is not supported, it must be over {} and at most {}",
+                       size, PROGRAM_START, MAX_MEMORY_SIZE),
+            Error::InvalidSnapshot =>
+                write!(f, "snapshot does not fit this machine"),
+            Error::NewerSavestate { version } =>
+                write!(f, "savestate format {} is newer than this build reads", version),
+            Error::OtherMachine { memory_size, shift_vy } =>
+                write!(f, "savestate is for a machine with {} bytes of memory that shifts {}",
+                       memory_size, if shift_vy { "VY" } else { "VX" }),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for Error {}
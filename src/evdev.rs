@@ -0,0 +1,262 @@
+//! Keyboard and gamepad input read straight from Linux input devices.
+//!
+//! `--plugin evdev` reads every keyboard and gamepad under `/dev/input` and
+//! `--plugin evdev=DEVICE` just the one given, so the emulator takes keys
+//! with no window or browser in between: lower latency, and it works on a
+//! console without X, like a Raspberry Pi. `ruchip8 evdev` lists the
+//! devices. Reading them takes membership of the `input` group, or root.
+//!
+//! The keys map the usual way, the left hand block of a QWERTY keyboard
+//! onto the hex keypad:
+//!
+//! ```text
+//! 1 2 3 4      1 2 3 C
+//! Q W E R  ->  4 5 6 D
+//! A S D F      7 8 9 E
+//! Z X C V      A 0 B F
+//! ```
+//!
+//! Escape quits. Gamepads map as `ruchip8::gamepad` does by default.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use libc;
+
+use ruchip8::gamepad::{Button, Mapping};
+use ruchip8::KEY_COUNT;
+
+use session::{Remote, Session};
+
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const KEY_ESC: u16 = 1;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+/// Every gamepad has it, the bottom face button.
+const BTN_SOUTH: u16 = 0x130;
+
+/// Linux key codes in the order of the keypad keys they stand for.
+const KEYMAP: [u16; 16] = [
+    45, // X -> 0
+    2, 3, 4, // 1 2 3
+    16, 17, 18, // Q W E
+    30, 31, 32, // A S D
+    44, 46, // Z C -> A B
+    5, 19, 33, 47, // 4 R F V -> C D E F
+];
+
+/// Linux button codes and the buttons of the standard layout they are.
+const BUTTONS: [(u16, Button); 16] = [
+    (BTN_SOUTH, Button::A), (0x131, Button::B), (0x134, Button::X), (0x133, Button::Y),
+    (0x136, Button::LeftBumper), (0x137, Button::RightBumper),
+    (0x138, Button::LeftTrigger), (0x139, Button::RightTrigger),
+    (0x13A, Button::Back), (0x13B, Button::Start), (0x13D, Button::LeftStick), (0x13E, Button::RightStick),
+    (0x220, Button::Up), (0x221, Button::Down), (0x222, Button::Left), (0x223, Button::Right),
+];
+
+/// The size of a `struct input_event`: a timestamp, then the type and code
+/// as 16 bits and the value as 32.
+fn event_size() -> usize {
+    mem::size_of::<libc::timeval>() + 8
+}
+
+fn keypad_key(code: u16) -> Option<u8> {
+    KEYMAP.iter().position(|&mapped| mapped == code).map(|key| key as u8)
+}
+
+fn button(code: u16) -> Option<Button> {
+    BUTTONS.iter().find(|&&(mapped, _)| mapped == code).map(|&(_, button)| button)
+}
+
+/// `buttons` with `button` held or not.
+fn hold(buttons: u16, button: Button, held: bool) -> u16 {
+    let bit = 1 << button.index();
+    if held { buttons | bit } else { buttons & !bit }
+}
+
+/// An input device as listed under `/sys/class/input`.
+struct Device {
+    path: PathBuf,
+    name: String,
+    keyboard: bool,
+    gamepad: bool,
+}
+
+/// Every `/dev/input/eventN` there is.
+fn devices() -> Vec<Device> {
+    let mut devices: Vec<Device> = fs::read_dir("/sys/class/input").into_iter().flatten().flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+        .map(|entry| {
+            let sys = entry.path().join("device");
+            let name = fs::read_to_string(sys.join("name")).map(|name| name.trim().to_owned()).unwrap_or_default();
+            let bits = fs::read_to_string(sys.join("capabilities/key")).unwrap_or_default();
+            let keyboard = has_codes(&bits, &KEYMAP);
+            let gamepad = has_codes(&bits, &[BTN_SOUTH]);
+            Device { path: Path::new("/dev/input").join(entry.file_name()), name, keyboard, gamepad }
+        })
+        .collect();
+    devices.sort_by_key(|device| {
+        let number = device.path.to_string_lossy().trim_start_matches("/dev/input/event").parse::<u32>().ok();
+        number.unwrap_or(u32::MAX)
+    });
+    devices
+}
+
+/// Whether a key capability bitmap, hexadecimal words with the lowest
+/// last, has every one of `codes`. Keyboards have the keys of the keymap,
+/// mice and power buttons do not.
+fn has_codes(bits: &str, codes: &[u16]) -> bool {
+    let words: Vec<u64> = bits.split_whitespace().rev().filter_map(|word| u64::from_str_radix(word, 16).ok()).collect();
+    let word_bits = mem::size_of::<libc::c_long>() * 8;
+    codes.iter().all(|&code| {
+        let code = code as usize;
+        words.get(code / word_bits).is_some_and(|word| word & (1 << (code % word_bits)) != 0)
+    })
+}
+
+fn open(path: &Path) -> Result<File, String> {
+    OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => format!(
+            "cannot open {}: permission denied, add yourself to the 'input' group or run as root", path.display()),
+        _ => format!("cannot open {}: {}", path.display(), e),
+    })
+}
+
+/// Feeds the keys of one or more input devices to the session.
+pub struct Evdev {
+    devices: Vec<(PathBuf, File)>,
+    /// Bytes of an event split across reads, per device.
+    partial: Vec<Vec<u8>>,
+    /// Gamepad buttons held, per device.
+    buttons: Vec<u16>,
+    mapping: Mapping,
+}
+
+impl Evdev {
+    fn open(paths: Vec<PathBuf>) -> Result<Evdev, String> {
+        let devices = paths.into_iter()
+            .map(|path| open(&path).map(|file| (path, file)))
+            .collect::<Result<Vec<_>, String>>()?;
+        for (path, _) in &devices {
+            info!("reading keys from {}", path.display());
+        }
+        let partial = vec![Vec::new(); devices.len()];
+        let buttons = vec![0; devices.len()];
+        Ok(Evdev { devices, partial, buttons, mapping: Mapping::default() })
+    }
+}
+
+/// The `--plugin evdev[=DEVICE]` remote.
+pub fn remote(arg: Option<&str>) -> Result<Box<dyn Remote>, String> {
+    let paths = match arg {
+        Some(device) => vec![PathBuf::from(device)],
+        None => {
+            let usable: Vec<PathBuf> = devices().into_iter()
+                .filter(|device| device.keyboard || device.gamepad)
+                .map(|device| device.path)
+                .collect();
+            if usable.is_empty() {
+                return Err("no keyboard or gamepad found under /dev/input, name one with --plugin evdev=DEVICE".to_owned());
+            }
+            usable
+        },
+    };
+    Ok(Box::new(Evdev::open(paths)?))
+}
+
+/// `ruchip8 evdev` lists the input devices and which of them will do.
+pub fn command(args: &[String]) -> Result<(), String> {
+    if !args.is_empty() {
+        return Err("usage: ruchip8 evdev".to_owned());
+    }
+    let devices = devices();
+    if devices.is_empty() {
+        return Err("no input devices under /sys/class/input".to_owned());
+    }
+    for device in devices {
+        let kind = match (device.keyboard, device.gamepad) {
+            (true, _) => "keyboard",
+            (false, true) => "gamepad",
+            (false, false) => "",
+        };
+        let status = match (kind, open(&device.path)) {
+            ("", _) => "not a keyboard or gamepad".to_owned(),
+            (kind, Ok(_)) => kind.to_owned(),
+            (kind, Err(e)) => format!("{}, {}", kind, e.rsplit(": ").next().unwrap_or_default()),
+        };
+        println!("{:<20} {:<32} {}", device.path.display(), device.name, status);
+    }
+    Ok(())
+}
+
+impl Remote for Evdev {
+    fn poll(&mut self, session: &mut Session) {
+        let size = event_size();
+        let mut buffer = vec![0; size * 64];
+        let mut lost = Vec::new();
+        for (n, (path, file)) in self.devices.iter_mut().enumerate() {
+            loop {
+                let read = match file.read(&mut buffer) {
+                    // A regular file standing in for a device has run out.
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        warn!("stopped reading {}: {}", path.display(), e);
+                        lost.push(n);
+                        break;
+                    },
+                };
+                let partial = &mut self.partial[n];
+                partial.extend_from_slice(&buffer[..read]);
+                let whole = partial.len() / size * size;
+                for event in partial[..whole].chunks(size) {
+                    let mut buttons = self.buttons[n];
+                    let field = &event[size - 8..];
+                    let kind = u16::from_ne_bytes([field[0], field[1]]);
+                    let code = u16::from_ne_bytes([field[2], field[3]]);
+                    let value = i32::from_ne_bytes([field[4], field[5], field[6], field[7]]);
+                    match (kind, code) {
+                        // Most gamepads report the d-pad as a hat, -1 up or left.
+                        (EV_ABS, ABS_HAT0X) => {
+                            buttons = hold(buttons, Button::Left, value < 0);
+                            buttons = hold(buttons, Button::Right, value > 0);
+                        },
+                        (EV_ABS, ABS_HAT0Y) => {
+                            buttons = hold(buttons, Button::Up, value < 0);
+                            buttons = hold(buttons, Button::Down, value > 0);
+                        },
+                        // 2 is an autorepeat, the key is still down.
+                        (EV_KEY, _) if value == 2 => {},
+                        (EV_KEY, KEY_ESC) if value == 1 => session.quit = true,
+                        (EV_KEY, _) => if let Some(key) = keypad_key(code) {
+                            session.set_key(key, value == 1);
+                        } else if let Some(button) = button(code) {
+                            buttons = hold(buttons, button, value == 1);
+                        },
+                        _ => {},
+                    }
+                    // Event by event, so a tap is never lost.
+                    let (held, now) = (self.mapping.keys(self.buttons[n]), self.mapping.keys(buttons));
+                    for key in 0..KEY_COUNT as u8 {
+                        if (held ^ now) & (1 << key) != 0 {
+                            session.set_key(key, now & (1 << key) != 0);
+                        }
+                    }
+                    self.buttons[n] = buttons;
+                }
+                partial.drain(..whole);
+            }
+        }
+        for n in lost.into_iter().rev() {
+            self.devices.remove(n);
+            self.partial.remove(n);
+            self.buttons.remove(n);
+        }
+    }
+}
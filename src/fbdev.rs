@@ -0,0 +1,208 @@
+//! The screen drawn straight to a Linux framebuffer device.
+//!
+//! `--plugin fbdev` draws each frame on `/dev/fb0`, and `--plugin
+//! fbdev=DEVICE` on the one given, scaled by the largest whole number that
+//! fits and centered, so the emulator runs full screen on a console with no
+//! X or Wayland, a Raspberry Pi booted to the console among them. Keys come
+//! from `--plugin evdev` alongside it:
+//!
+//! ```text
+//! ruchip8 --plugin fbdev --plugin evdev game.ch8
+//! ```
+//!
+//! Writing to the device takes membership of the `video` group, or root.
+//! Run from a virtual console, the console stops drawing its text and
+//! cursor over the screen until the emulator quits.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+
+use libc;
+
+use ruchip8::{HIRES_HEIGHT, HIRES_WIDTH};
+
+use session::{Remote, Session};
+
+const FBIOGET_VSCREENINFO: libc::c_ulong = 0x4600;
+const FBIOGET_FSCREENINFO: libc::c_ulong = 0x4602;
+const KDSETMODE: libc::c_ulong = 0x4B3A;
+const KD_TEXT: libc::c_ulong = 0;
+const KD_GRAPHICS: libc::c_ulong = 1;
+
+/// Where one color channel sits in a pixel, `struct fb_bitfield`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct Bitfield {
+    offset: u32,
+    length: u32,
+    msb_right: u32,
+}
+
+/// `struct fb_var_screeninfo`, the mode the device is in.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct VarInfo {
+    xres: u32,
+    yres: u32,
+    xres_virtual: u32,
+    yres_virtual: u32,
+    xoffset: u32,
+    yoffset: u32,
+    bits_per_pixel: u32,
+    grayscale: u32,
+    red: Bitfield,
+    green: Bitfield,
+    blue: Bitfield,
+    transp: Bitfield,
+    nonstd: u32,
+    activate: u32,
+    height: u32,
+    width: u32,
+    accel_flags: u32,
+    /// pixclock to rotate, unused.
+    timings: [u32; 10],
+    colorspace: u32,
+    reserved: [u32; 4],
+}
+
+/// `struct fb_fix_screeninfo`, what does not change with the mode.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct FixInfo {
+    id: [u8; 16],
+    smem_start: libc::c_ulong,
+    smem_len: u32,
+    kind: u32,
+    type_aux: u32,
+    visual: u32,
+    xpanstep: u16,
+    ypanstep: u16,
+    ywrapstep: u16,
+    line_length: u32,
+    mmio_start: libc::c_ulong,
+    mmio_len: u32,
+    accel: u32,
+    capabilities: u16,
+    reserved: [u16; 2],
+}
+
+fn open(path: &str) -> Result<File, String> {
+    OpenOptions::new().read(true).write(true).open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => format!(
+            "cannot open {}: permission denied, add yourself to the 'video' group or run as root", path),
+        _ => format!("cannot open {}: {}", path, e),
+    })
+}
+
+/// Draws the session on a framebuffer device.
+pub struct Fbdev {
+    device: File,
+    var: VarInfo,
+    /// Bytes from the start of one row to the next.
+    stride: usize,
+    /// Device pixels to a CHIP-8 pixel in 128x64.
+    scale: usize,
+    /// Where the top left of the screen goes on the device.
+    origin: (usize, usize),
+    /// The pixels and colors last drawn.
+    last: (Vec<u8>, bool, [[u8; 3]; 4]),
+    /// The virtual console put in graphics mode, to put back in text mode.
+    console: bool,
+}
+
+/// The `--plugin fbdev[=DEVICE]` remote.
+pub fn remote(arg: Option<&str>) -> Result<Box<dyn Remote>, String> {
+    let path = arg.unwrap_or("/dev/fb0");
+    let device = open(path)?;
+    let mut var = VarInfo::default();
+    let mut fix = FixInfo::default();
+    unsafe {
+        if libc::ioctl(device.as_raw_fd(), FBIOGET_VSCREENINFO as _, &mut var) != 0
+            || libc::ioctl(device.as_raw_fd(), FBIOGET_FSCREENINFO as _, &mut fix) != 0 {
+            return Err(format!("{} is not a framebuffer: {}", path, io::Error::last_os_error()));
+        }
+    }
+    if ![16, 24, 32].contains(&var.bits_per_pixel) {
+        return Err(format!("{} is in {} bits a pixel, only 16, 24 and 32 will do", path, var.bits_per_pixel));
+    }
+    let (width, height) = (var.xres as usize, var.yres as usize);
+    let scale = (width / HIRES_WIDTH).min(height / HIRES_HEIGHT);
+    if scale == 0 {
+        return Err(format!("{} is {}x{}, too small for {}x{}", path, width, height, HIRES_WIDTH, HIRES_HEIGHT));
+    }
+    let origin = ((width - HIRES_WIDTH * scale) / 2 + var.xoffset as usize, (height - HIRES_HEIGHT * scale) / 2 + var.yoffset as usize);
+    info!("drawing the screen on {}, {}x{} in {} bits a pixel, scaled {}x", path, width, height, var.bits_per_pixel, scale);
+    // Fails, and need not work, away from a virtual console.
+    let console = unsafe { libc::ioctl(libc::STDIN_FILENO, KDSETMODE as _, KD_GRAPHICS) == 0 };
+    let fbdev = Fbdev { device, var, stride: fix.line_length as usize, scale, origin, last: (Vec::new(), false, [[0; 3]; 4]), console };
+    fbdev.clear();
+    Ok(Box::new(fbdev))
+}
+
+impl Fbdev {
+    fn bytes_per_pixel(&self) -> usize {
+        self.var.bits_per_pixel as usize / 8
+    }
+
+    /// A color as the device stores it.
+    fn pixel(&self, [r, g, b]: [u8; 3]) -> Vec<u8> {
+        let channel = |value: u8, field: Bitfield| {
+            let length = field.length.min(8);
+            ((value as u32) >> (8 - length)) << field.offset
+        };
+        let value = channel(r, self.var.red) | channel(g, self.var.green) | channel(b, self.var.blue);
+        value.to_le_bytes()[..self.bytes_per_pixel()].to_vec()
+    }
+
+    /// Blacks out the whole device.
+    fn clear(&self) {
+        let row = vec![0; self.stride];
+        for y in 0..self.var.yres as usize {
+            let _ = self.device.write_all_at(&row, ((y + self.var.yoffset as usize) * self.stride) as u64);
+        }
+    }
+}
+
+impl Remote for Fbdev {
+    fn poll(&mut self, _session: &mut Session) {}
+
+    fn frame(&mut self, session: &Session) {
+        if !session.drawing {
+            return;
+        }
+        let frame = session.chip.display().frame();
+        let colors = session.palette.colors;
+        let (pixels, hires) = (frame.pixels(), frame.is_hires());
+        if (pixels, hires, colors) == (&self.last.0[..], self.last.1, self.last.2) {
+            return;
+        }
+        let colors: Vec<Vec<u8>> = colors.iter().map(|&color| self.pixel(color)).collect();
+        // A lores pixel is twice the size of a hires one.
+        let size = self.scale * HIRES_WIDTH / frame.width();
+        let mut row = Vec::with_capacity(frame.width() * size * self.bytes_per_pixel());
+        for y in 0..frame.height() {
+            row.clear();
+            for &pixel in &pixels[y * frame.width()..(y + 1) * frame.width()] {
+                for _ in 0..size {
+                    row.extend_from_slice(&colors[(pixel & 3) as usize]);
+                }
+            }
+            for line in 0..size {
+                let offset = (self.origin.1 + y * size + line) * self.stride + self.origin.0 * self.bytes_per_pixel();
+                let _ = self.device.write_all_at(&row, offset as u64);
+            }
+        }
+        self.last = (pixels.to_vec(), hires, session.palette.colors);
+    }
+
+    fn close(&mut self, _session: &Session) {
+        self.clear();
+        if self.console {
+            unsafe {
+                libc::ioctl(libc::STDIN_FILENO, KDSETMODE as _, KD_TEXT);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Gamepad buttons onto the keypad.
+//!
+//! Buttons are named after the standard gamepad layout browsers report,
+//! which Linux input devices follow too. Face buttons go by position, so
+//! `a` is the bottom one whatever it is labelled. The default mapping puts
+//! the d-pad on 5 7 8 9, where Octo puts WASD, and `a` and `b` on 6 and 4,
+//! the keys next to them.
+//!
+//! A mapping reads as `BUTTON=KEY` pairs separated by commas, each KEY a
+//! hexadecimal digit or `none`, changing the default:
+//!
+//! ```
+//! use ruchip8::gamepad::{Button, Mapping};
+//!
+//! let mapping = Mapping::parse("up=2,down=8,start=f,a=none").unwrap();
+//! assert_eq!(mapping.key(Button::Up), Some(0x2));
+//! assert_eq!(mapping.key(Button::A), None);
+//! ```
+
+use KEY_COUNT;
+
+/// The buttons in the order the standard layout numbers them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    X,
+    Y,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Back,
+    Start,
+    LeftStick,
+    RightStick,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Number of buttons of the standard layout, the guide button aside.
+pub const BUTTON_COUNT: usize = 16;
+
+const BUTTONS: [Button; BUTTON_COUNT] = [
+    Button::A, Button::B, Button::X, Button::Y,
+    Button::LeftBumper, Button::RightBumper, Button::LeftTrigger, Button::RightTrigger,
+    Button::Back, Button::Start, Button::LeftStick, Button::RightStick,
+    Button::Up, Button::Down, Button::Left, Button::Right,
+];
+
+const NAMES: [&str; BUTTON_COUNT] = [
+    "a", "b", "x", "y", "lb", "rb", "lt", "rt", "back", "start", "ls", "rs", "up", "down", "left", "right",
+];
+
+impl Button {
+    /// The button numbered `index` in the standard layout.
+    pub fn from_index(index: usize) -> Option<Button> {
+        BUTTONS.get(index).copied()
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Parses one of `a b x y lb rb lt rt back start ls rs up down left
+    /// right`.
+    pub fn parse(name: &str) -> Option<Button> {
+        NAMES.iter().position(|&known| known == name).map(|index| BUTTONS[index])
+    }
+
+    pub fn name(self) -> &'static str {
+        NAMES[self.index()]
+    }
+}
+
+/// The keypad key each button presses, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    keys: [Option<u8>; BUTTON_COUNT],
+}
+
+impl Default for Mapping {
+    fn default() -> Self {
+        let mut mapping = Mapping::unmapped();
+        for &(button, key) in &[
+            (Button::Up, 0x5), (Button::Left, 0x7), (Button::Down, 0x8), (Button::Right, 0x9),
+            (Button::A, 0x6), (Button::B, 0x4),
+        ] {
+            mapping.set(button, Some(key));
+        }
+        mapping
+    }
+}
+
+impl Mapping {
+    /// A mapping where no button presses anything.
+    pub fn unmapped() -> Self {
+        Mapping { keys: [None; BUTTON_COUNT] }
+    }
+
+    /// Parses `BUTTON=KEY` pairs over the default mapping, see the module
+    /// documentation.
+    pub fn parse(text: &str) -> Option<Mapping> {
+        let mut mapping = Mapping::default();
+        for pair in text.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (button, key) = pair.split_once('=')?;
+            let key = match key {
+                "none" => None,
+                _ => Some(u8::from_str_radix(key, 16).ok().filter(|&key| (key as usize) < KEY_COUNT)?),
+            };
+            mapping.set(Button::parse(button)?, key);
+        }
+        Some(mapping)
+    }
+
+    pub fn key(&self, button: Button) -> Option<u8> {
+        self.keys[button.index()]
+    }
+
+    /// Maps `button` onto `key`. Panics unless the key is on the keypad.
+    pub fn set(&mut self, button: Button, key: Option<u8>) {
+        assert!(key.is_none_or(|key| (key as usize) < KEY_COUNT), "no such key");
+        self.keys[button.index()] = key;
+    }
+
+    /// The keys held down while `buttons` are, bit n of either standing for
+    /// button or key n. Several buttons may press the same key.
+    pub fn keys(&self, buttons: u16) -> u16 {
+        (0..BUTTON_COUNT)
+            .filter(|&button| buttons & (1 << button) != 0)
+            .filter_map(|button| self.keys[button])
+            .fold(0, |keys, key| keys | 1 << key)
+    }
+}
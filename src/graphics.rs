@@ -0,0 +1,47 @@
+//! Drawing the framebuffer onto any embedded-graphics `DrawTarget`, such as
+//! the SSD1306 or ST7789 drivers.
+
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{Point, Size};
+use embedded_graphics_core::pixelcolor::PixelColor;
+use embedded_graphics_core::primitives::Rectangle;
+
+use display::Display;
+use {DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Draws the presented frame with two colors, each CHIP-8 pixel blown up to a
+/// `scale` x `scale` square with the top left corner at `origin`. The 128x64
+/// mode covers the same area with pixels half the size, so it needs a scale
+/// of 2 or more to show every pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Renderer<C> {
+    pub on: C,
+    pub off: C,
+    pub scale: u32,
+    pub origin: Point,
+}
+
+impl<C: PixelColor> Renderer<C> {
+    pub fn new(on: C, off: C) -> Self {
+        Renderer { on, off, scale: 1, origin: Point::zero() }
+    }
+
+    /// The area the screen covers on the target.
+    pub fn area(&self) -> Rectangle {
+        let scale = self.scale.max(1);
+        Rectangle::new(self.origin, Size::new(DISPLAY_WIDTH as u32 * scale, DISPLAY_HEIGHT as u32 * scale))
+    }
+
+    pub fn draw<D: DrawTarget<Color = C>>(&self, display: &Display, target: &mut D) -> Result<(), D::Error> {
+        let scale = self.scale.max(1) as usize;
+        let (width, height) = (DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale);
+        // A CHIP-8 pixel is `step / 2` target pixels wide: `scale` in 64x32, half that in 128x64.
+        let frame = display.frame();
+        let step = if frame.is_hires() { scale } else { scale * 2 };
+        let colors = (0..width * height).map(|n| {
+            let (x, y) = (n % width, n / width);
+            if frame.pixel(x * 2 / step, y * 2 / step) { self.on } else { self.off }
+        });
+        target.fill_contiguous(&self.area(), colors)
+    }
+}
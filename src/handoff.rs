@@ -0,0 +1,158 @@
+//! Handing presented frames from the thread running the machine to the one
+//! drawing them, without either ever waiting on the other.
+//!
+//! `FrameShare` is a triple buffer: the writer fills one slot, the reader
+//! shows another and the third holds the newest finished frame. Publishing
+//! and picking up a frame swap a slot with the middle one atomically, so the
+//! reader always gets a whole frame, the newest one, and frames it had no
+//! time for are dropped rather than queued.
+//!
+//! Needs atomic swaps, which boards like the Cortex-M0 lack.
+//!
+//! ```
+//! # use ruchip8::handoff::FrameShare;
+//! # use ruchip8::Chip8;
+//! # use std::thread;
+//! let mut share = FrameShare::new();
+//! let (mut writer, mut reader) = share.split();
+//! thread::scope(|scope| {
+//!     scope.spawn(move || {
+//!         let mut chip = Chip8::new();
+//!         chip.load_rom(&[0x12, 0x00]).unwrap();
+//!         for _ in 0..60 {
+//!             chip.run_frame(0).unwrap();
+//!             writer.publish(chip.display().frame());
+//!         }
+//!     });
+//!     if reader.update() {
+//!         let _frame = reader.frame();
+//!     }
+//! });
+//! ```
+
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU8, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+use display::Frame;
+use {DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH};
+
+/// Set in `FrameShare::middle` while the reader has not taken the frame.
+const FRESH: u8 = 0b100;
+const SLOT: u8 = 0b011;
+
+struct Slot {
+    pixels: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    hires: bool,
+}
+
+impl Slot {
+    fn frame(&self) -> Frame<'_> {
+        let len = if self.hires { HIRES_WIDTH * HIRES_HEIGHT } else { DISPLAY_WIDTH * DISPLAY_HEIGHT };
+        Frame::new(&self.pixels[..len], self.hires)
+    }
+}
+
+/// Three frames shared by a `FrameWriter` and a `FrameReader`.
+pub struct FrameShare {
+    slots: [UnsafeCell<Slot>; 3],
+    /// The slot between the two sides, `FRESH` until the reader takes it.
+    middle: AtomicU8,
+}
+
+// The writer and the reader each only touch the slot they own, and trade it
+// for the middle one in a single atomic swap.
+unsafe impl Sync for FrameShare {}
+
+impl Default for FrameShare {
+    fn default() -> Self {
+        FrameShare::new()
+    }
+}
+
+impl FrameShare {
+    /// Three blank 64x32 frames.
+    pub fn new() -> Self {
+        let blank = || UnsafeCell::new(Slot { pixels: [0; HIRES_WIDTH * HIRES_HEIGHT], hires: false });
+        FrameShare { slots: [blank(), blank(), blank()], middle: AtomicU8::new(1) }
+    }
+
+    /// The two sides, which can go to different threads. The reader starts
+    /// out with whatever frame its slot held, blank the first time.
+    pub fn split(&mut self) -> (FrameWriter<'_>, FrameReader<'_>) {
+        *self.middle.get_mut() = 1;
+        let share = &*self;
+        (FrameWriter { share: Share::Borrowed(share), slot: 0 }, FrameReader { share: Share::Borrowed(share), slot: 2 })
+    }
+
+    /// Like `split`, for sides that outlive the scope they were made in,
+    /// such as one going to a thread of its own.
+    #[cfg(feature = "std")]
+    pub fn into_split(mut self) -> (FrameWriter<'static>, FrameReader<'static>) {
+        *self.middle.get_mut() = 1;
+        let share = Arc::new(self);
+        (FrameWriter { share: Share::Shared(share.clone()), slot: 0 }, FrameReader { share: Share::Shared(share), slot: 2 })
+    }
+}
+
+/// How a side holds on to the `FrameShare`.
+enum Share<'a> {
+    Borrowed(&'a FrameShare),
+    #[cfg(feature = "std")]
+    Shared(Arc<FrameShare>),
+}
+
+impl<'a> Deref for Share<'a> {
+    type Target = FrameShare;
+
+    fn deref(&self) -> &FrameShare {
+        match *self {
+            Share::Borrowed(share) => share,
+            #[cfg(feature = "std")]
+            Share::Shared(ref share) => share,
+        }
+    }
+}
+
+/// The side running the machine.
+pub struct FrameWriter<'a> {
+    share: Share<'a>,
+    slot: u8,
+}
+
+impl<'a> FrameWriter<'a> {
+    /// Copies `frame` in and makes it the newest one. Never blocks.
+    pub fn publish(&mut self, frame: Frame) {
+        // Only the writer touches its slot until the swap below gives it up.
+        let slot = unsafe { &mut *self.share.slots[self.slot as usize].get() };
+        slot.pixels[..frame.pixels().len()].copy_from_slice(frame.pixels());
+        slot.hires = frame.is_hires();
+        self.slot = self.share.middle.swap(self.slot | FRESH, Ordering::AcqRel) & SLOT;
+    }
+}
+
+/// The side drawing the frames.
+pub struct FrameReader<'a> {
+    share: Share<'a>,
+    slot: u8,
+}
+
+impl<'a> FrameReader<'a> {
+    /// Takes the newest frame if one came since the last call. Returns
+    /// whether one did. Never blocks.
+    pub fn update(&mut self) -> bool {
+        if self.share.middle.load(Ordering::Relaxed) & FRESH == 0 {
+            return false;
+        }
+        self.slot = self.share.middle.swap(self.slot, Ordering::AcqRel) & SLOT;
+        true
+    }
+
+    /// The frame `update` last took.
+    pub fn frame(&self) -> Frame<'_> {
+        // Only the reader touches its slot until `update` gives it up.
+        unsafe { &*self.share.slots[self.slot as usize].get() }.frame()
+    }
+}
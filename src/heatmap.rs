@@ -0,0 +1,180 @@
+//! A map of how often each address was read, written and executed.
+//!
+//! `--plugin heatmap[=FILE]` counts, over the whole session, the times
+//! each byte of memory ran as an instruction, was read as data and was
+//! written, and draws memory as a grid when the session ends, 64 bytes to a
+//! row, to FILE, `heatmap.png` unless given. Blue is for execution, green
+//! for reads and red for writes, brighter the more often, so code, sprite
+//! tables and a game's variables stand apart at a glance. A FILE ending in
+//! `.svg` gets a map that tells the counts of a byte when hovered, with the
+//! rows labelled by address.
+
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use png;
+
+use ruchip8::observer::EmuObserver;
+use ruchip8::{Chip8, Instruction};
+
+use session::{Remote, Session};
+
+/// Bytes to a row of the map.
+const ROW: usize = 64;
+
+/// Times a byte was executed, read and written.
+#[derive(Debug, Clone, Copy, Default)]
+struct Heat {
+    executed: u32,
+    read: u32,
+    written: u32,
+}
+
+type Heats = Arc<Mutex<Vec<Heat>>>;
+
+struct Counter {
+    heats: Heats,
+}
+
+impl Counter {
+    fn count(&self, addrs: Range<usize>, field: fn(&mut Heat) -> &mut u32) {
+        let mut heats = self.heats.lock().unwrap();
+        if heats.len() < addrs.end {
+            heats.resize(addrs.end, Heat::default());
+        }
+        for heat in &mut heats[addrs] {
+            let count = field(heat);
+            *count = count.saturating_add(1);
+        }
+    }
+}
+
+impl EmuObserver for Counter {
+    fn on_instruction(&mut self, _chip: &Chip8, pc: usize, instruction: Instruction) {
+        self.count(pc..pc + instruction.size(), |heat| &mut heat.executed);
+    }
+
+    fn on_read(&mut self, _chip: &Chip8, addrs: Range<usize>) {
+        self.count(addrs, |heat| &mut heat.read);
+    }
+
+    fn on_write(&mut self, _chip: &Chip8, addrs: Range<usize>) {
+        self.count(addrs, |heat| &mut heat.written);
+    }
+}
+
+/// The color of a byte: each count scaled against the largest of its
+/// kind on a log scale, so bytes touched a few times still show.
+struct Palette {
+    most: Heat,
+}
+
+impl Palette {
+    fn new(heats: &[Heat]) -> Self {
+        let most = heats.iter().fold(Heat::default(), |most, heat| Heat {
+            executed: most.executed.max(heat.executed),
+            read: most.read.max(heat.read),
+            written: most.written.max(heat.written),
+        });
+        Palette { most }
+    }
+
+    fn rgb(&self, heat: Heat) -> [u8; 3] {
+        let level = |count: u32, most: u32| match count {
+            0 => 0,
+            _ => (64.0 + 191.0 * (count as f64).ln_1p() / (most as f64).ln_1p()) as u8,
+        };
+        [level(heat.written, self.most.written), level(heat.read, self.most.read), level(heat.executed, self.most.executed)]
+    }
+}
+
+pub struct HeatMap {
+    path: PathBuf,
+    counting: bool,
+    heats: Heats,
+}
+
+impl HeatMap {
+    fn png(&self, heats: &[Heat]) -> Vec<u8> {
+        let rows = heats.len().div_ceil(ROW);
+        // 4K fits 512 pixels square, XO-CHIP's 64K gets smaller cells.
+        let cell = if rows > 64 { 2 } else { 8 };
+        let (width, height) = (ROW * cell, rows * cell);
+        let palette = Palette::new(heats);
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let heat = heats.get(y / cell * ROW + x / cell).copied().unwrap_or_default();
+                pixels.extend_from_slice(&palette.rgb(heat));
+            }
+        }
+        let mut out = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().expect("PNG header");
+            writer.write_image_data(&pixels).expect("PNG data");
+        }
+        out
+    }
+
+    fn svg(&self, heats: &[Heat]) -> String {
+        const CELL: usize = 10;
+        const MARGIN: usize = 40;
+        let rows = heats.len().div_ceil(ROW);
+        let palette = Palette::new(heats);
+        let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"9\">\n",
+                              MARGIN + ROW * CELL, rows * CELL);
+        writeln!(svg, "<rect x=\"{}\" width=\"{}\" height=\"{}\" fill=\"black\"/>", MARGIN, ROW * CELL, rows * CELL).unwrap();
+        for row in 0..rows {
+            if (row * ROW).is_multiple_of(0x100) {
+                writeln!(svg, "<text x=\"0\" y=\"{}\">{:04X}</text>", row * CELL + 9, row * ROW).unwrap();
+            }
+        }
+        for (addr, &heat) in heats.iter().enumerate() {
+            if heat.executed == 0 && heat.read == 0 && heat.written == 0 {
+                continue;
+            }
+            let [r, g, b] = palette.rgb(heat);
+            writeln!(svg, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:02x}{:02x}{:02x}\">\
+                           <title>{:04X}: executed {}, read {}, written {}</title></rect>",
+                     MARGIN + addr % ROW * CELL, addr / ROW * CELL, CELL, CELL, r, g, b,
+                     addr, heat.executed, heat.read, heat.written).unwrap();
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// The `--plugin heatmap[=FILE]` remote.
+pub fn remote(arg: Option<&str>) -> Result<Box<dyn Remote>, String> {
+    Ok(Box::new(HeatMap {
+        path: PathBuf::from(arg.unwrap_or("heatmap.png")),
+        counting: false,
+        heats: Heats::default(),
+    }))
+}
+
+impl Remote for HeatMap {
+    fn poll(&mut self, session: &mut Session) {
+        if !self.counting {
+            self.counting = true;
+            *self.heats.lock().unwrap() = vec![Heat::default(); session.chip.memory().len()];
+            session.chip.add_observer(Box::new(Counter { heats: self.heats.clone() }));
+        }
+    }
+
+    fn close(&mut self, _session: &Session) {
+        let heats = self.heats.lock().unwrap();
+        let svg = self.path.extension().is_some_and(|extension| extension == "svg");
+        let map = if svg { self.svg(&heats).into_bytes() } else { self.png(&heats) };
+        match fs::write(&self.path, map) {
+            Ok(()) => info!("wrote the memory heat map to {}", self.path.display()),
+            Err(e) => warn!("cannot write {}: {}", self.path.display(), e),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! How often each instruction ran.
+//!
+//! `ruchip8 histogram [--frames N] [--csv] ROM[:QUIRK,...]` runs a ROM
+//! without a window for N frames, a minute unless given, and prints a bar
+//! for each opcode by how many times it ran, most frequent first, after
+//! totals for groups of them: draws, skips, arithmetic and so on.
+//! `--plugin histogram[=FILE]` counts over a whole session instead, across
+//! resets and loads, and logs the table when it ends or writes it to FILE,
+//! as CSV if FILE ends in `.csv`. Opcodes go by pattern, `DXYN` or `8XY4`,
+//! whatever their operands.
+
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use ruchip8::observer::EmuObserver;
+use ruchip8::{Chip8, Instruction};
+
+use compare;
+use session::{Remote, Session};
+
+const USAGE: &str = "usage: ruchip8 histogram [--frames N] [--csv] ROM[:QUIRK,...]";
+
+/// Frames the command runs unless told otherwise, a minute.
+const FRAMES: u32 = 3600;
+
+const BAR_WIDTH: usize = 40;
+
+/// Times each opcode pattern ran.
+type Counts = Arc<Mutex<HashMap<&'static str, u64>>>;
+
+struct Counter {
+    counts: Counts,
+}
+
+impl EmuObserver for Counter {
+    fn on_instruction(&mut self, _chip: &Chip8, _pc: usize, instruction: Instruction) {
+        *self.counts.lock().unwrap().entry(instruction.pattern()).or_insert(0) += 1;
+    }
+}
+
+/// The group an opcode pattern belongs to.
+fn group(pattern: &str) -> &'static str {
+    match pattern {
+        "00E0" | "DXYN" | "00CN" | "00DN" | "00FB" | "00FC" | "00FE" | "00FF" => "drawing",
+        "3XNN" | "4XNN" | "5XY0" | "9XY0" | "EX9E" | "EXA1" => "skips",
+        "1NNN" | "2NNN" | "00EE" | "BNNN" | "0NNN" | "00FD" => "jumps and calls",
+        "ANNN" | "F000" | "FX1E" | "FX29" | "FX30" => "index",
+        "FX33" | "FX55" | "FX65" | "5XY2" | "5XY3" | "FX75" | "FX85" => "memory",
+        "FX07" | "FX0A" | "FX15" | "FX18" | "F002" | "FX3A" => "timers, keys and sound",
+        _ => "arithmetic",
+    }
+}
+
+/// Entries sorted by count, most first, then by name.
+fn sorted<'a>(counts: impl Iterator<Item = (&'a str, u64)>) -> Vec<(&'a str, u64)> {
+    let mut entries: Vec<(&str, u64)> = counts.collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    entries
+}
+
+fn table(counts: &HashMap<&'static str, u64>) -> String {
+    let total: u64 = counts.values().sum();
+    let percent = |n: u64| n as f64 * 100.0 / total.max(1) as f64;
+    let mut text = format!("{} instructions\n\n", total);
+
+    let mut groups: HashMap<&str, u64> = HashMap::new();
+    for (pattern, &count) in counts {
+        *groups.entry(group(pattern)).or_insert(0) += count;
+    }
+    for (group, count) in sorted(groups.into_iter()) {
+        writeln!(text, "{:<24} {:>12} {:>6.1}%", group, count, percent(count)).unwrap();
+    }
+    text.push('\n');
+
+    let entries = sorted(counts.iter().map(|(&pattern, &count)| (pattern, count)));
+    let most = entries.first().map_or(1, |entry| entry.1.max(1));
+    for (pattern, count) in entries {
+        let bar = "#".repeat(((count * BAR_WIDTH as u64).div_ceil(most)) as usize);
+        writeln!(text, "{}  {:>12} {:>6.1}%  {}", pattern, count, percent(count), bar).unwrap();
+    }
+    text
+}
+
+fn csv(counts: &HashMap<&'static str, u64>) -> String {
+    let mut text = String::from("opcode,group,count\n");
+    for (pattern, count) in sorted(counts.iter().map(|(&pattern, &count)| (pattern, count))) {
+        writeln!(text, "{},{},{}", pattern, group(pattern), count).unwrap();
+    }
+    text
+}
+
+pub fn command(args: &[String]) -> Result<(), String> {
+    let mut frames = FRAMES;
+    let mut as_csv = false;
+    let mut spec = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let n = args.next().ok_or("--frames needs a number")?;
+                frames = n.parse().map_err(|_| format!("invalid frame count '{}'", n))?;
+            },
+            "--csv" => as_csv = true,
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'\n{}", flag, USAGE)),
+            path if spec.is_none() => spec = Some(path),
+            _ => return Err(USAGE.to_owned()),
+        }
+    }
+    let spec = spec.ok_or(USAGE)?;
+
+    let mut session = compare::session(spec, 0)?;
+    let counts = Counts::default();
+    session.chip.add_observer(Box::new(Counter { counts: counts.clone() }));
+    for _ in 0..frames {
+        if session.chip.is_halted() || session.error.is_some() {
+            break;
+        }
+        session.run_frame();
+    }
+    if let Some(ref error) = session.error {
+        warn!("stopped after {} frames: {}", session.stats.frames, error);
+    }
+    let counts = counts.lock().unwrap();
+    print!("{}", if as_csv { csv(&counts) } else { table(&counts) });
+    Ok(())
+}
+
+pub struct Histogram {
+    path: Option<PathBuf>,
+    counting: bool,
+    counts: Counts,
+}
+
+/// The `--plugin histogram[=FILE]` remote.
+pub fn remote(arg: Option<&str>) -> Result<Box<dyn Remote>, String> {
+    Ok(Box::new(Histogram { path: arg.map(PathBuf::from), counting: false, counts: Counts::default() }))
+}
+
+impl Remote for Histogram {
+    fn poll(&mut self, session: &mut Session) {
+        if !self.counting {
+            self.counting = true;
+            session.chip.add_observer(Box::new(Counter { counts: self.counts.clone() }));
+        }
+    }
+
+    fn close(&mut self, _session: &Session) {
+        let counts = self.counts.lock().unwrap();
+        let path = match self.path {
+            Some(ref path) => path,
+            None => {
+                for line in table(&counts).lines() {
+                    info!("{}", line);
+                }
+                return;
+            },
+        };
+        let is_csv = path.extension().is_some_and(|extension| extension == "csv");
+        match fs::write(path, if is_csv { csv(&counts) } else { table(&counts) }) {
+            Ok(()) => info!("wrote the instruction histogram to {}", path.display()),
+            Err(e) => warn!("cannot write {}: {}", path.display(), e),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Glue for running the machine on a board without an operating system.
+//!
+//! A board provides a screen, a keypad and a clock; `Host` ties them to a
+//! `Chip8` and runs frames at `TIMERS_CLOCK` from a busy main loop:
+//!
+//! ```ignore
+//! let mut host = Host::new(chip, screen, keypad, clock);
+//! loop {
+//!     if let Err(e) = host.poll() {
+//!         // Show the error, then halt or reset.
+//!     }
+//! }
+//! ```
+//!
+//! A screen too slow to keep up at 60 frames a second, such as a small SPI
+//! panel, can be shown one frame in `frame_skip`. Every frame still runs,
+//! those late ones back to back, so games keep their speed.
+
+use chip8::Chip8;
+use display::Display;
+use error::Error;
+use TIMERS_CLOCK;
+
+/// Something the framebuffer can be shown on.
+pub trait Screen {
+    /// Shows `display.frame()`. Called after every frame that ran.
+    fn present(&mut self, display: &Display);
+}
+
+/// The hexadecimal keypad.
+pub trait Keypad {
+    /// The keys held right now, one bit per key with key 0 in bit 0.
+    fn keys(&mut self) -> u16;
+}
+
+/// A free running clock, usually a HAL timer.
+pub trait Clock {
+    /// Microseconds since some fixed point in time.
+    fn micros(&mut self) -> u64;
+}
+
+const FRAME_MICROS: u64 = 1_000_000 / TIMERS_CLOCK as u64;
+
+pub struct Host<S, K, C> {
+    pub chip: Chip8,
+    pub screen: S,
+    pub keypad: K,
+    pub clock: C,
+    /// Presents one frame in this many, at least 1.
+    pub frame_skip: u32,
+    /// When the next frame is due, in clock microseconds.
+    next_frame: u64,
+    /// Frames run since the last one presented.
+    skipped: u32,
+    /// Whether a skipped frame drew anything.
+    dirty: bool,
+}
+
+impl<S: Screen, K: Keypad, C: Clock> Host<S, K, C> {
+    pub fn new(chip: Chip8, screen: S, keypad: K, mut clock: C) -> Self {
+        let next_frame = clock.micros();
+        Host { chip, screen, keypad, clock, frame_skip: 1, next_frame, skipped: 0, dirty: false }
+    }
+
+    /// Runs a frame if one is due. Returns whether it did.
+    pub fn poll(&mut self) -> Result<bool, Error> {
+        let now = self.clock.micros();
+        if now < self.next_frame {
+            return Ok(false);
+        }
+        // After a stall, carry on from now rather than catching up. Frames
+        // skipped make up for the time presenting took.
+        self.next_frame = if now - self.next_frame > FRAME_MICROS * self.frame_skip.max(1) as u64 {
+            now + FRAME_MICROS
+        } else {
+            self.next_frame + FRAME_MICROS
+        };
+        self.frame()?;
+        Ok(true)
+    }
+
+    /// Reads the keypad, runs a frame with `Chip8::run_frame` and presents
+    /// the screen, if it changed and this is a frame to present. The chip's
+    /// timing sets how much a frame runs.
+    pub fn frame(&mut self) -> Result<(), Error> {
+        let output = self.chip.run_frame(self.keypad.keys())?;
+        self.dirty |= output.frame_ready;
+        self.skipped += 1;
+        if self.skipped >= self.frame_skip {
+            self.skipped = 0;
+            if self.dirty {
+                self.dirty = false;
+                self.screen.present(output.display);
+            }
+        }
+        Ok(())
+    }
+}
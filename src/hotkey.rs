@@ -0,0 +1,81 @@
+//! Emulator shortcuts frontends pass on by name.
+//!
+//! | Key                  | Action                                     |
+//! |----------------------|--------------------------------------------|
+//! | `F1` to `F10`        | load savestate slot 1 to 10                |
+//! | `shift+F1` to `F10`  | save savestate slot 1 to 10                |
+//! | `ctrl+r`             | soft reset, the program starts over        |
+//! | `ctrl+shift+r`       | hard reset, the ROM starts on fresh memory |
+//! | `ctrl+l`             | read the ROM from disk again and start it  |
+//! | `F11`                | show or hide the FPS overlay               |
+//! | `pause`              | pause or resume                            |
+//!
+//! Every hotkey shows a notice over the screen.
+
+use savestate::{self, SLOTS};
+use session::Session;
+
+/// What a hotkey does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    Save(usize),
+    Load(usize),
+    SoftReset,
+    HardReset,
+    Reload,
+    Overlay,
+    Pause,
+}
+
+impl Hotkey {
+    /// Parses a key of the table above, in any case.
+    pub fn parse(text: &str) -> Option<Hotkey> {
+        let text = text.to_ascii_lowercase();
+        match text.as_str() {
+            "ctrl+r" => return Some(Hotkey::SoftReset),
+            "ctrl+shift+r" | "shift+ctrl+r" => return Some(Hotkey::HardReset),
+            "ctrl+l" => return Some(Hotkey::Reload),
+            "f11" => return Some(Hotkey::Overlay),
+            "pause" => return Some(Hotkey::Pause),
+            _ => {},
+        }
+        let (save, key) = match text.strip_prefix("shift+") {
+            Some(key) => (true, key),
+            None => (false, text.as_str()),
+        };
+        let slot = key.strip_prefix('f')?.parse().ok().filter(|slot| (1..=SLOTS).contains(slot))?;
+        Some(if save { Hotkey::Save(slot) } else { Hotkey::Load(slot) })
+    }
+
+    pub fn press(self, session: &mut Session) -> Result<(), String> {
+        match self {
+            Hotkey::Save(slot) => savestate::save(session, slot),
+            Hotkey::Load(slot) => savestate::load(session, slot),
+            Hotkey::SoftReset => {
+                session.soft_reset();
+                session.show_notice("reset".to_owned());
+                Ok(())
+            },
+            Hotkey::HardReset => {
+                session.hard_reset();
+                session.show_notice("hard reset".to_owned());
+                Ok(())
+            },
+            Hotkey::Reload => {
+                session.reload()?;
+                session.show_notice("reloaded the ROM".to_owned());
+                Ok(())
+            },
+            Hotkey::Overlay => {
+                session.overlay = !session.overlay;
+                session.show_notice(format!("overlay {}", if session.overlay { "on" } else { "off" }));
+                Ok(())
+            },
+            Hotkey::Pause => {
+                session.paused = !session.paused;
+                session.show_notice(if session.paused { "paused" } else { "resumed" }.to_owned());
+                Ok(())
+            },
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! What a ROM is, without running it.
+//!
+//! `ruchip8 info [--db FILE] ROM` prints the size and SHA-1 of a ROM, the
+//! instruction set and screen mode its code uses and its entry in a ROM
+//! database, if one is given. Only the code reachable from `PROGRAM_START`
+//! is looked at, so sprite data is not taken for instructions.
+//!
+//! The database is a text file with one ROM per line as `SHA1 TITLE`, with
+//! `#` starting a comment. A `tickrate=N` word after the hash gives the
+//! instructions a frame the ROM was made for, such as
+//! `0159f9ea... Space Game tickrate=7`.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use ruchip8::disasm;
+use ruchip8::{Instruction, Level, PROGRAM_START, XO_MEMORY_SIZE};
+
+const USAGE: &str = "usage: ruchip8 info [--db FILE] ROM";
+
+/// SHA-1 of `data`, as 40 hex digits.
+pub fn sha1(data: &[u8]) -> String {
+    let mut state: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (n, word) in block.chunks(4).enumerate() {
+            w[n] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for n in 16..80 {
+            w[n] = (w[n - 3] ^ w[n - 8] ^ w[n - 14] ^ w[n - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (n, &word) in w.iter().enumerate() {
+            let (f, k) = match n / 20 {
+                0 => ((b & c) | (!b & d), 0x5A82_7999),
+                1 => (b ^ c ^ d, 0x6ED9_EBA1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (s, v) in state.iter_mut().zip(&[a, b, c, d, e]) {
+            *s = s.wrapping_add(*v);
+        }
+    }
+    state.iter().map(|s| format!("{:08x}", s)).collect()
+}
+
+/// Reads a ROM, checking it fits in memory.
+pub fn read_rom(path: &str) -> Result<Vec<u8>, String> {
+    let rom = fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    if rom.len() > XO_MEMORY_SIZE - PROGRAM_START {
+        return Err(format!("{} is {} bytes, more than any machine holds", path, rom.len()));
+    }
+    Ok(rom)
+}
+
+/// The code reachable in `rom` once loaded at `PROGRAM_START`.
+pub fn code(rom: &[u8]) -> BTreeMap<usize, Option<Instruction>> {
+    let mut memory = vec![0; PROGRAM_START + rom.len()];
+    memory[PROGRAM_START..].copy_from_slice(rom);
+    // The walk stays inside the ROM, the zeroes after it would read as 0NNN.
+    disasm::reachable(&memory, PROGRAM_START)
+}
+
+/// A ROM's line in the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub title: String,
+    pub tickrate: Option<u32>,
+}
+
+/// What `db` has for the ROM with hash `sha1`.
+pub fn lookup(db: &str, sha1: &str) -> Option<Record> {
+    let (_, rest) = db.lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(hash, _)| hash.eq_ignore_ascii_case(sha1))?;
+    let mut tickrate = None;
+    let mut title = Vec::new();
+    for word in rest.split_whitespace() {
+        match word.strip_prefix("tickrate=").and_then(|n| n.parse().ok()).filter(|&n| n > 0) {
+            Some(n) => tickrate = Some(n),
+            None => title.push(word),
+        }
+    }
+    Some(Record { title: title.join(" "), tickrate })
+}
+
+pub fn command(args: &[String]) -> Result<(), String> {
+    let (db, path) = match args {
+        [path] => (None, path),
+        [flag, db, path] if flag == "--db" => (Some(db), path),
+        _ => return Err(USAGE.to_owned()),
+    };
+    let rom = read_rom(path)?;
+    let code: Vec<(usize, Instruction)> = code(&rom).into_iter()
+        .filter_map(|(addr, instruction)| instruction.map(|instruction| (addr, instruction)))
+        .collect();
+
+    let hash = sha1(&rom);
+    println!("size        {} bytes", rom.len());
+    println!("sha1        {}", hash);
+
+    // The first instruction of the highest level, to show why.
+    let level = code.iter().map(|&(addr, instruction)| (instruction.level(), addr, instruction))
+        .filter(|&(level, _, _)| level > Level::Chip8)
+        .fold(None, |best: Option<(Level, usize, Instruction)>, found| match best {
+            Some(best) if best.0 >= found.0 => Some(best),
+            _ => Some(found),
+        });
+    match level {
+        Some((level, addr, instruction)) => println!("level       {}, {} at {:04X}", level, instruction, addr),
+        None => println!("level       {}", Level::Chip8),
+    }
+    match code.iter().find(|&&(_, instruction)| instruction == Instruction::HighRes) {
+        Some((addr, _)) => println!("resolution  128x64, switched to at {:04X}", addr),
+        None => println!("resolution  64x32"),
+    }
+    println!("code        {} reachable instructions", code.len());
+
+    if let Some(db) = db {
+        let text = fs::read_to_string(db).map_err(|e| format!("cannot read {}: {}", db, e))?;
+        match lookup(&text, &hash) {
+            Some(Record { title, tickrate: Some(n) }) => println!("database    {}, {} instructions a frame", title, n),
+            Some(Record { title, tickrate: None }) => println!("database    {}", title),
+            None => println!("database    no match"),
+        }
+    }
+    Ok(())
+}
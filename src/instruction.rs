@@ -0,0 +1,528 @@
+//! Decoded instructions, shared by the interpreter and every tool that
+//! needs to know what an opcode means.
+
+use core::fmt;
+
+/// One CHIP-8 instruction. `x` and `y` are register numbers, `nn` an 8 bit
+/// immediate, `addr` a 12 bit address and `n` a 4 bit nibble.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// 00E0: clear the screen.
+    Cls,
+    /// 00EE: return from a subroutine.
+    Ret,
+    /// 00CN: scroll the screen down N rows. SCHIP.
+    ScrollDown { n: u8 },
+    /// 00DN: scroll the screen up N rows. XO-CHIP.
+    ScrollUp { n: u8 },
+    /// 00FB: scroll the screen right 4 pixels. SCHIP.
+    ScrollRight,
+    /// 00FC: scroll the screen left 4 pixels. SCHIP.
+    ScrollLeft,
+    /// 0NNN: call the machine language routine at NNN. Only the COSMAC VIP
+    /// could run these.
+    Sys { addr: u16 },
+    /// 00FD: stop the machine. SCHIP.
+    Exit,
+    /// 00FE: switch to the 64x32 mode. SCHIP.
+    LowRes,
+    /// 00FF: switch to the 128x64 mode. SCHIP.
+    HighRes,
+    /// 1NNN: jump to NNN.
+    Jump { addr: u16 },
+    /// 2NNN: call the subroutine at NNN.
+    Call { addr: u16 },
+    /// 3XNN: skip the next instruction if VX == NN.
+    SkipEqImm { x: u8, nn: u8 },
+    /// 4XNN: skip the next instruction if VX != NN.
+    SkipNeImm { x: u8, nn: u8 },
+    /// 5XY0: skip the next instruction if VX == VY.
+    SkipEqReg { x: u8, y: u8 },
+    /// 5XY2: store VX to VY at I, backwards when X > Y. I is left alone.
+    /// XO-CHIP.
+    StoreRange { x: u8, y: u8 },
+    /// 5XY3: load VX to VY from I, backwards when X > Y. I is left alone.
+    /// XO-CHIP.
+    LoadRange { x: u8, y: u8 },
+    /// 6XNN: VX = NN.
+    LoadImm { x: u8, nn: u8 },
+    /// 7XNN: VX += NN, without touching VF.
+    AddImm { x: u8, nn: u8 },
+    /// 8XY0: VX = VY.
+    Move { x: u8, y: u8 },
+    /// 8XY1: VX |= VY.
+    Or { x: u8, y: u8 },
+    /// 8XY2: VX &= VY.
+    And { x: u8, y: u8 },
+    /// 8XY3: VX ^= VY.
+    Xor { x: u8, y: u8 },
+    /// 8XY4: VX += VY, VF = carry.
+    Add { x: u8, y: u8 },
+    /// 8XY5: VX -= VY, VF = no borrow.
+    Sub { x: u8, y: u8 },
+    /// 8XY6: VX = VX >> 1 (or VY >> 1), VF = the bit shifted out.
+    Shr { x: u8, y: u8 },
+    /// 8XY7: VX = VY - VX, VF = no borrow.
+    SubN { x: u8, y: u8 },
+    /// 8XYE: VX = VX << 1 (or VY << 1), VF = the bit shifted out.
+    Shl { x: u8, y: u8 },
+    /// 9XY0: skip the next instruction if VX != VY.
+    SkipNeReg { x: u8, y: u8 },
+    /// ANNN: I = NNN.
+    LoadIndex { addr: u16 },
+    /// F000 NNNN: I = NNNN, the word after the opcode. XO-CHIP.
+    LongIndex { addr: u16 },
+    /// BNNN: jump to NNN + V0.
+    JumpV0 { addr: u16 },
+    /// CXNN: VX = random & NN.
+    Random { x: u8, nn: u8 },
+    /// DXYN: draw the N byte sprite at I to VX, VY, VF = collision.
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E: skip the next instruction if key VX is down.
+    SkipKey { x: u8 },
+    /// EXA1: skip the next instruction if key VX is up.
+    SkipNotKey { x: u8 },
+    /// FX07: VX = delay timer.
+    ReadDelay { x: u8 },
+    /// FX0A: wait for a key press and store it in VX.
+    WaitKey { x: u8 },
+    /// FX15: delay timer = VX.
+    SetDelay { x: u8 },
+    /// FX18: sound timer = VX.
+    SetSound { x: u8 },
+    /// FX1E: I += VX.
+    AddIndex { x: u8 },
+    /// FX29: I = address of the font sprite for digit VX.
+    Font { x: u8 },
+    /// FX30: I = address of the 8x10 font sprite for digit VX. SCHIP.
+    BigFont { x: u8 },
+    /// FX33: store the decimal digits of VX at I, I+1 and I+2.
+    Bcd { x: u8 },
+    /// FX55: store V0 to VX at I, I += X + 1.
+    Store { x: u8 },
+    /// FX65: load V0 to VX from I, I += X + 1.
+    Load { x: u8 },
+    /// F002: load the 16 byte audio pattern at I. XO-CHIP.
+    Audio,
+    /// FX3A: set the audio pattern playback rate from VX. XO-CHIP.
+    Pitch { x: u8 },
+    /// FX75: store V0 to VX in the RPL flags. SCHIP.
+    SaveFlags { x: u8 },
+    /// FX85: load V0 to VX from the RPL flags. SCHIP.
+    LoadFlags { x: u8 },
+}
+
+/// The instruction sets, each extending the one before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Chip8,
+    Schip,
+    XoChip,
+}
+
+impl Level {
+    /// Parses `chip8`, `schip` or `xochip`.
+    pub fn parse(text: &str) -> Option<Level> {
+        match text {
+            "chip8" => Some(Level::Chip8),
+            "schip" => Some(Level::Schip),
+            "xochip" => Some(Level::XoChip),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Level::Chip8 => "CHIP-8",
+            Level::Schip => "SCHIP",
+            Level::XoChip => "XO-CHIP",
+        })
+    }
+}
+
+/// The first word of F000 NNNN, the only instruction taking two words.
+pub const LONG_PREFIX: u16 = 0xF000;
+
+/// Decodes one opcode group, picked by the top nibble.
+type Group = fn(u16) -> Option<Instruction>;
+
+/// Decoders indexed by the top nibble of the opcode.
+static GROUPS: [Group; 16] = [
+    group_0,
+    |ops| Some(Instruction::Jump { addr: ops & 0x0FFF }),
+    |ops| Some(Instruction::Call { addr: ops & 0x0FFF }),
+    |ops| Some(Instruction::SkipEqImm { x: x(ops), nn: ops as u8 }),
+    |ops| Some(Instruction::SkipNeImm { x: x(ops), nn: ops as u8 }),
+    |ops| match ops & 0xF {
+        0x0 => Some(Instruction::SkipEqReg { x: x(ops), y: y(ops) }),
+        0x2 => Some(Instruction::StoreRange { x: x(ops), y: y(ops) }),
+        0x3 => Some(Instruction::LoadRange { x: x(ops), y: y(ops) }),
+        _ => None,
+    },
+    |ops| Some(Instruction::LoadImm { x: x(ops), nn: ops as u8 }),
+    |ops| Some(Instruction::AddImm { x: x(ops), nn: ops as u8 }),
+    |ops| ALU[(ops & 0xF) as usize].map(|op| op(x(ops), y(ops))),
+    |ops| if ops & 0xF == 0 { Some(Instruction::SkipNeReg { x: x(ops), y: y(ops) }) } else { None },
+    |ops| Some(Instruction::LoadIndex { addr: ops & 0x0FFF }),
+    |ops| Some(Instruction::JumpV0 { addr: ops & 0x0FFF }),
+    |ops| Some(Instruction::Random { x: x(ops), nn: ops as u8 }),
+    |ops| Some(Instruction::Draw { x: x(ops), y: y(ops), n: (ops & 0xF) as u8 }),
+    |ops| match ops & 0xFF {
+        0x9E => Some(Instruction::SkipKey { x: x(ops) }),
+        0xA1 => Some(Instruction::SkipNotKey { x: x(ops) }),
+        _ => None,
+    },
+    |ops| if ops == 0xF002 { Some(Instruction::Audio) } else { MISC[(ops & 0xFF) as usize].map(|op| op(x(ops))) },
+];
+
+/// Builds an instruction from X and Y.
+type XY = Option<fn(u8, u8) -> Instruction>;
+/// Builds an instruction from X.
+type X = Option<fn(u8) -> Instruction>;
+
+/// 8XYN instructions, indexed by N.
+static ALU: [XY; 16] = [
+    Some(|x, y| Instruction::Move { x, y }),
+    Some(|x, y| Instruction::Or { x, y }),
+    Some(|x, y| Instruction::And { x, y }),
+    Some(|x, y| Instruction::Xor { x, y }),
+    Some(|x, y| Instruction::Add { x, y }),
+    Some(|x, y| Instruction::Sub { x, y }),
+    Some(|x, y| Instruction::Shr { x, y }),
+    Some(|x, y| Instruction::SubN { x, y }),
+    None, None, None, None, None, None,
+    Some(|x, y| Instruction::Shl { x, y }),
+    None,
+];
+
+/// FXNN instructions, indexed by NN.
+static MISC: [X; 256] = {
+    let mut table: [X; 256] = [None; 256];
+    table[0x07] = Some(|x| Instruction::ReadDelay { x });
+    table[0x0A] = Some(|x| Instruction::WaitKey { x });
+    table[0x15] = Some(|x| Instruction::SetDelay { x });
+    table[0x18] = Some(|x| Instruction::SetSound { x });
+    table[0x1E] = Some(|x| Instruction::AddIndex { x });
+    table[0x29] = Some(|x| Instruction::Font { x });
+    table[0x30] = Some(|x| Instruction::BigFont { x });
+    table[0x33] = Some(|x| Instruction::Bcd { x });
+    table[0x3A] = Some(|x| Instruction::Pitch { x });
+    table[0x55] = Some(|x| Instruction::Store { x });
+    table[0x65] = Some(|x| Instruction::Load { x });
+    table[0x75] = Some(|x| Instruction::SaveFlags { x });
+    table[0x85] = Some(|x| Instruction::LoadFlags { x });
+    table
+};
+
+fn x(ops: u16) -> u8 {
+    ((ops & 0x0F00) >> 8) as u8
+}
+
+fn y(ops: u16) -> u8 {
+    ((ops & 0x00F0) >> 4) as u8
+}
+
+fn group_0(ops: u16) -> Option<Instruction> {
+    match ops {
+        0x00E0 => Some(Instruction::Cls),
+        0x00EE => Some(Instruction::Ret),
+        0x00C0..=0x00CF => Some(Instruction::ScrollDown { n: (ops & 0xF) as u8 }),
+        0x00D0..=0x00DF => Some(Instruction::ScrollUp { n: (ops & 0xF) as u8 }),
+        0x00FB => Some(Instruction::ScrollRight),
+        0x00FC => Some(Instruction::ScrollLeft),
+        0x00FD => Some(Instruction::Exit),
+        0x00FE => Some(Instruction::LowRes),
+        0x00FF => Some(Instruction::HighRes),
+        _ => Some(Instruction::Sys { addr: ops & 0x0FFF }),
+    }
+}
+
+/// The instruction an opcode stands for, or `None` when the machine does
+/// not know it.
+#[inline]
+pub fn decode(ops: u16) -> Option<Instruction> {
+    GROUPS[(ops >> 12) as usize](ops)
+}
+
+/// Like `decode`, also knowing F000 NNNN, which takes its address from
+/// `next`, the word after the opcode.
+#[inline]
+pub fn decode_long(ops: u16, next: u16) -> Option<Instruction> {
+    if ops == LONG_PREFIX {
+        Some(Instruction::LongIndex { addr: next })
+    } else {
+        decode(ops)
+    }
+}
+
+/// `decode` written as one big match. It is the reference the table is
+/// tested and benchmarked against.
+#[doc(hidden)]
+pub fn decode_match(ops: u16) -> Option<Instruction> {
+    use self::Instruction::*;
+
+    let x = ((ops & 0x0F00) >> 8) as u8;
+    let y = ((ops & 0x00F0) >> 4) as u8;
+    let n = (ops & 0x000F) as u8;
+    let nn = (ops & 0x00FF) as u8;
+    let addr = ops & 0x0FFF;
+
+    let instruction = match ((ops & 0xF000) >> 12, x, y, n) {
+        (0x0, 0x0, 0xE, 0x0) => Cls,
+        (0x0, 0x0, 0xE, 0xE) => Ret,
+        (0x0, 0x0, 0xC, _) => ScrollDown { n },
+        (0x0, 0x0, 0xD, _) => ScrollUp { n },
+        (0x0, 0x0, 0xF, 0xB) => ScrollRight,
+        (0x0, 0x0, 0xF, 0xC) => ScrollLeft,
+        (0x0, 0x0, 0xF, 0xD) => Exit,
+        (0x0, 0x0, 0xF, 0xE) => LowRes,
+        (0x0, 0x0, 0xF, 0xF) => HighRes,
+        (0x0, _, _, _) => Sys { addr },
+        (0x1, _, _, _) => Jump { addr },
+        (0x2, _, _, _) => Call { addr },
+        (0x3, _, _, _) => SkipEqImm { x, nn },
+        (0x4, _, _, _) => SkipNeImm { x, nn },
+        (0x5, _, _, 0x0) => SkipEqReg { x, y },
+        (0x5, _, _, 0x2) => StoreRange { x, y },
+        (0x5, _, _, 0x3) => LoadRange { x, y },
+        (0x6, _, _, _) => LoadImm { x, nn },
+        (0x7, _, _, _) => AddImm { x, nn },
+        (0x8, _, _, 0x0) => Move { x, y },
+        (0x8, _, _, 0x1) => Or { x, y },
+        (0x8, _, _, 0x2) => And { x, y },
+        (0x8, _, _, 0x3) => Xor { x, y },
+        (0x8, _, _, 0x4) => Add { x, y },
+        (0x8, _, _, 0x5) => Sub { x, y },
+        (0x8, _, _, 0x6) => Shr { x, y },
+        (0x8, _, _, 0x7) => SubN { x, y },
+        (0x8, _, _, 0xE) => Shl { x, y },
+        (0x9, _, _, 0x0) => SkipNeReg { x, y },
+        (0xA, _, _, _) => LoadIndex { addr },
+        (0xB, _, _, _) => JumpV0 { addr },
+        (0xC, _, _, _) => Random { x, nn },
+        (0xD, _, _, _) => Draw { x, y, n },
+        (0xE, _, 0x9, 0xE) => SkipKey { x },
+        (0xE, _, 0xA, 0x1) => SkipNotKey { x },
+        (0xF, _, 0x0, 0x7) => ReadDelay { x },
+        (0xF, _, 0x0, 0xA) => WaitKey { x },
+        (0xF, _, 0x1, 0x5) => SetDelay { x },
+        (0xF, _, 0x1, 0x8) => SetSound { x },
+        (0xF, _, 0x1, 0xE) => AddIndex { x },
+        (0xF, _, 0x2, 0x9) => Font { x },
+        (0xF, 0x0, 0x0, 0x2) => Audio,
+        (0xF, _, 0x3, 0x0) => BigFont { x },
+        (0xF, _, 0x3, 0x3) => Bcd { x },
+        (0xF, _, 0x3, 0xA) => Pitch { x },
+        (0xF, _, 0x5, 0x5) => Store { x },
+        (0xF, _, 0x6, 0x5) => Load { x },
+        (0xF, _, 0x7, 0x5) => SaveFlags { x },
+        (0xF, _, 0x8, 0x5) => LoadFlags { x },
+        _ => return None,
+    };
+    Some(instruction)
+}
+
+impl Instruction {
+    /// How many bytes the instruction takes in memory.
+    pub fn size(self) -> usize {
+        match self {
+            Instruction::LongIndex { .. } => 4,
+            _ => 2,
+        }
+    }
+
+    /// The first instruction set that has the instruction. DXY0 counts as
+    /// CHIP-8, where it draws nothing.
+    pub fn level(self) -> Level {
+        use self::Instruction::*;
+        match self {
+            ScrollDown { .. } | ScrollRight | ScrollLeft | Exit | LowRes | HighRes |
+            BigFont { .. } | SaveFlags { .. } | LoadFlags { .. } => Level::Schip,
+            ScrollUp { .. } | StoreRange { .. } | LoadRange { .. } | LongIndex { .. } |
+            Audio | Pitch { .. } => Level::XoChip,
+            _ => Level::Chip8,
+        }
+    }
+
+    /// The opcode, the inverse of `decode`. For F000 NNNN that is the first
+    /// word, NNNN follows it.
+    pub fn encode(self) -> u16 {
+        use self::Instruction::*;
+
+        let xy = |op: u16, x: u8, y: u8, n: u16| op << 12 | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | n;
+        let xnn = |op: u16, x: u8, nn: u8| op << 12 | (x as u16 & 0xF) << 8 | nn as u16;
+        let fx = |x: u8, low: u16| 0xF000 | (x as u16 & 0xF) << 8 | low;
+        match self {
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            ScrollDown { n } => 0x00C0 | n as u16 & 0xF,
+            ScrollUp { n } => 0x00D0 | n as u16 & 0xF,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Sys { addr } => addr & 0x0FFF,
+            Exit => 0x00FD,
+            LowRes => 0x00FE,
+            HighRes => 0x00FF,
+            Jump { addr } => 0x1000 | addr & 0x0FFF,
+            Call { addr } => 0x2000 | addr & 0x0FFF,
+            SkipEqImm { x, nn } => xnn(0x3, x, nn),
+            SkipNeImm { x, nn } => xnn(0x4, x, nn),
+            SkipEqReg { x, y } => xy(0x5, x, y, 0x0),
+            StoreRange { x, y } => xy(0x5, x, y, 0x2),
+            LoadRange { x, y } => xy(0x5, x, y, 0x3),
+            LoadImm { x, nn } => xnn(0x6, x, nn),
+            AddImm { x, nn } => xnn(0x7, x, nn),
+            Move { x, y } => xy(0x8, x, y, 0x0),
+            Or { x, y } => xy(0x8, x, y, 0x1),
+            And { x, y } => xy(0x8, x, y, 0x2),
+            Xor { x, y } => xy(0x8, x, y, 0x3),
+            Add { x, y } => xy(0x8, x, y, 0x4),
+            Sub { x, y } => xy(0x8, x, y, 0x5),
+            Shr { x, y } => xy(0x8, x, y, 0x6),
+            SubN { x, y } => xy(0x8, x, y, 0x7),
+            Shl { x, y } => xy(0x8, x, y, 0xE),
+            SkipNeReg { x, y } => xy(0x9, x, y, 0x0),
+            LoadIndex { addr } => 0xA000 | addr & 0x0FFF,
+            LongIndex { .. } => LONG_PREFIX,
+            JumpV0 { addr } => 0xB000 | addr & 0x0FFF,
+            Random { x, nn } => xnn(0xC, x, nn),
+            Draw { x, y, n } => xy(0xD, x, y, n as u16 & 0xF),
+            SkipKey { x } => xnn(0xE, x, 0x9E),
+            SkipNotKey { x } => xnn(0xE, x, 0xA1),
+            ReadDelay { x } => fx(x, 0x07),
+            WaitKey { x } => fx(x, 0x0A),
+            SetDelay { x } => fx(x, 0x15),
+            SetSound { x } => fx(x, 0x18),
+            AddIndex { x } => fx(x, 0x1E),
+            Font { x } => fx(x, 0x29),
+            BigFont { x } => fx(x, 0x30),
+            Bcd { x } => fx(x, 0x33),
+            Store { x } => fx(x, 0x55),
+            Load { x } => fx(x, 0x65),
+            Audio => 0xF002,
+            Pitch { x } => fx(x, 0x3A),
+            SaveFlags { x } => fx(x, 0x75),
+            LoadFlags { x } => fx(x, 0x85),
+        }
+    }
+
+    /// The opcode with its operands as letters, such as `DXYN` or `8XY4`,
+    /// naming the instruction whatever the operands.
+    pub fn pattern(self) -> &'static str {
+        use self::Instruction::*;
+        match self {
+            Cls => "00E0",
+            Ret => "00EE",
+            ScrollDown { .. } => "00CN",
+            ScrollUp { .. } => "00DN",
+            ScrollRight => "00FB",
+            ScrollLeft => "00FC",
+            Sys { .. } => "0NNN",
+            Exit => "00FD",
+            LowRes => "00FE",
+            HighRes => "00FF",
+            Jump { .. } => "1NNN",
+            Call { .. } => "2NNN",
+            SkipEqImm { .. } => "3XNN",
+            SkipNeImm { .. } => "4XNN",
+            SkipEqReg { .. } => "5XY0",
+            StoreRange { .. } => "5XY2",
+            LoadRange { .. } => "5XY3",
+            LoadImm { .. } => "6XNN",
+            AddImm { .. } => "7XNN",
+            Move { .. } => "8XY0",
+            Or { .. } => "8XY1",
+            And { .. } => "8XY2",
+            Xor { .. } => "8XY3",
+            Add { .. } => "8XY4",
+            Sub { .. } => "8XY5",
+            Shr { .. } => "8XY6",
+            SubN { .. } => "8XY7",
+            Shl { .. } => "8XYE",
+            SkipNeReg { .. } => "9XY0",
+            LoadIndex { .. } => "ANNN",
+            LongIndex { .. } => "F000",
+            JumpV0 { .. } => "BNNN",
+            Random { .. } => "CXNN",
+            Draw { .. } => "DXYN",
+            SkipKey { .. } => "EX9E",
+            SkipNotKey { .. } => "EXA1",
+            ReadDelay { .. } => "FX07",
+            WaitKey { .. } => "FX0A",
+            SetDelay { .. } => "FX15",
+            SetSound { .. } => "FX18",
+            AddIndex { .. } => "FX1E",
+            Font { .. } => "FX29",
+            BigFont { .. } => "FX30",
+            Bcd { .. } => "FX33",
+            Store { .. } => "FX55",
+            Load { .. } => "FX65",
+            Audio => "F002",
+            Pitch { .. } => "FX3A",
+            SaveFlags { .. } => "FX75",
+            LoadFlags { .. } => "FX85",
+        }
+    }
+}
+
+/// Mnemonics in the style of Cowgod's reference.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Instruction::*;
+
+        match *self {
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            ScrollDown { n } => write!(f, "SCD {:X}", n),
+            ScrollUp { n } => write!(f, "SCU {:X}", n),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Sys { addr } => write!(f, "SYS {:03X}", addr),
+            Exit => write!(f, "EXIT"),
+            LowRes => write!(f, "LOW"),
+            HighRes => write!(f, "HIGH"),
+            Jump { addr } => write!(f, "JP {:03X}", addr),
+            Call { addr } => write!(f, "CALL {:03X}", addr),
+            SkipEqImm { x, nn } => write!(f, "SE V{:X}, {:02X}", x, nn),
+            SkipNeImm { x, nn } => write!(f, "SNE V{:X}, {:02X}", x, nn),
+            SkipEqReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            StoreRange { x, y } => write!(f, "LD [I], V{:X}-V{:X}", x, y),
+            LoadRange { x, y } => write!(f, "LD V{:X}-V{:X}, [I]", x, y),
+            LoadImm { x, nn } => write!(f, "LD V{:X}, {:02X}", x, nn),
+            AddImm { x, nn } => write!(f, "ADD V{:X}, {:02X}", x, nn),
+            Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Shr { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SubN { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Shl { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SkipNeReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LoadIndex { addr } => write!(f, "LD I, {:03X}", addr),
+            LongIndex { addr } => write!(f, "LD I, LONG {:04X}", addr),
+            JumpV0 { addr } => write!(f, "JP V0, {:03X}", addr),
+            Random { x, nn } => write!(f, "RND V{:X}, {:02X}", x, nn),
+            Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {:X}", x, y, n),
+            SkipKey { x } => write!(f, "SKP V{:X}", x),
+            SkipNotKey { x } => write!(f, "SKNP V{:X}", x),
+            ReadDelay { x } => write!(f, "LD V{:X}, DT", x),
+            WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            AddIndex { x } => write!(f, "ADD I, V{:X}", x),
+            Font { x } => write!(f, "LD F, V{:X}", x),
+            BigFont { x } => write!(f, "LD HF, V{:X}", x),
+            Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Store { x } => write!(f, "LD [I], V{:X}", x),
+            Load { x } => write!(f, "LD V{:X}, [I]", x),
+            Audio => write!(f, "LD AUDIO, [I]"),
+            Pitch { x } => write!(f, "LD PITCH, V{:X}", x),
+            SaveFlags { x } => write!(f, "LD R, V{:X}", x),
+            LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
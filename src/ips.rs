@@ -0,0 +1,73 @@
+//! IPS patches, the format ROM hacks, bugfixes and translations are
+//! usually passed around in.
+//!
+//! A patch is `PATCH` followed by records and `EOF`. A record is a 3 byte
+//! offset into the file and a 2 byte length, both big endian, then that
+//! many bytes to write there, or, for a length of 0, a 2 byte count and the
+//! byte to write that many times. A 3 byte length after `EOF` truncates the
+//! file to it. Writing past the end grows the file, with zeros for any gap.
+//!
+//! ```
+//! use ruchip8::ips;
+//!
+//! let patch = b"PATCH\x00\x00\x01\x00\x01\xAA\x00\x00\x04\x00\x00\x00\x02\xBBEOF";
+//! assert_eq!(ips::apply(&[1, 2, 3], patch).unwrap(), [1, 0xAA, 3, 0, 0xBB, 0xBB]);
+//! ```
+
+const MAGIC: &[u8] = b"PATCH";
+const END: &[u8] = b"EOF";
+
+/// Reads the patch as it goes, failing past its end.
+struct Reader<'a> {
+    patch: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self.patch.get(self.at..self.at + len)
+            .ok_or_else(|| format!("the patch ends in the middle of a record at byte {}", self.at))?;
+        self.at += len;
+        Ok(bytes)
+    }
+
+    fn number(&mut self, len: usize) -> Result<usize, String> {
+        Ok(self.bytes(len)?.iter().fold(0, |n, &byte| n << 8 | byte as usize))
+    }
+}
+
+/// Returns `rom` with `patch` applied.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if !patch.starts_with(MAGIC) {
+        return Err("not an IPS patch".to_owned());
+    }
+    let mut patched = rom.to_vec();
+    let mut reader = Reader { patch, at: MAGIC.len() };
+    loop {
+        if reader.at == patch.len() {
+            return Err("the patch ends without EOF".to_owned());
+        }
+        if patch[reader.at..].starts_with(END) {
+            reader.at += END.len();
+            break;
+        }
+        let offset = reader.number(3)?;
+        let len = reader.number(2)?;
+        let (len, fill) = match len {
+            0 => (reader.number(2)?, Some(reader.bytes(1)?[0])),
+            len => (len, None),
+        };
+        if patched.len() < offset + len {
+            patched.resize(offset + len, 0);
+        }
+        let target = &mut patched[offset..offset + len];
+        match fill {
+            Some(byte) => target.fill(byte),
+            None => target.copy_from_slice(reader.bytes(len)?),
+        }
+    }
+    if reader.at < patch.len() {
+        patched.truncate(reader.number(3)?);
+    }
+    Ok(patched)
+}
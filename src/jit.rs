@@ -0,0 +1,293 @@
+//! An experimental recompiler, behind the `jit` feature.
+//!
+//! Runs of straight-line register instructions, from the first one at some
+//! address up to the first jump, skip, draw or anything else touching more
+//! than V0-VF and I, are compiled to native code with Cranelift. Everything
+//! else goes through the interpreter, so a block never has to handle errors
+//! or leave early.
+//!
+//! Compiled code tells observers nothing and takes no queued key events
+//! between its instructions, so while observers are registered or
+//! `push_key` events wait, every instruction is interpreted and the
+//! machine behaves exactly as under `Chip8::execute_cycle`.
+//!
+//! Each block keeps a copy of the bytes it was compiled from and is thrown
+//! away when memory no longer matches, which keeps self-modifying programs
+//! working. Code for stale blocks is not freed until the `Jit` is dropped.
+
+use std::collections::HashMap;
+use std::mem;
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlagsData, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+
+use chip8::Chip8;
+use error::Error;
+use instruction::{decode, Instruction};
+use {FLAG, REGISTER_SIZE};
+
+/// The longest block compiled, in instructions.
+pub const MAX_BLOCK: usize = 64;
+
+/// Registers as compiled code sees them.
+#[repr(C)]
+struct State {
+    v: [u8; REGISTER_SIZE],
+    i: u64,
+}
+
+type Code = unsafe extern "C" fn(*mut State);
+
+struct Block {
+    /// The bytes the block was compiled from.
+    source: Vec<u8>,
+    /// Number of instructions in the block.
+    len: usize,
+    shift_vy: bool,
+    code: Code,
+}
+
+/// Runs a `Chip8` through compiled blocks where it can.
+pub struct Jit {
+    module: JITModule,
+    /// Compiled blocks by start address, `None` where the instruction at
+    /// that address can't start one.
+    blocks: HashMap<usize, Option<Block>>,
+}
+
+impl Jit {
+    pub fn new() -> Result<Self, String> {
+        let mut flags = settings::builder();
+        flags.set("opt_level", "speed").map_err(|e| e.to_string())?;
+        let isa = cranelift_native::builder()?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| e.to_string())?;
+        Ok(Jit {
+            module: JITModule::new(JITBuilder::with_isa(isa, default_libcall_names())),
+            blocks: HashMap::new(),
+        })
+    }
+
+    /// Number of blocks compiled and still valid.
+    pub fn compiled(&self) -> usize {
+        self.blocks.values().filter(|block| block.is_some()).count()
+    }
+
+    /// Runs exactly `cycles` instructions, as that many `execute_cycle`
+    /// calls would. Blocks that don't fit in what is left are interpreted.
+    pub fn run(&mut self, chip: &mut Chip8, cycles: usize) -> Result<(), Error> {
+        let mut left = cycles;
+        while left > 0 {
+            left -= self.step(chip, left)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the block at PC when it's at most `limit` instructions long,
+    /// one interpreted instruction otherwise. Returns the instructions run.
+    pub fn step(&mut self, chip: &mut Chip8, limit: usize) -> Result<usize, Error> {
+        let pc = chip.pc();
+        if chip.is_waiting_for_key() || chip.has_observers() || chip.has_queued_keys() {
+            chip.execute_cycle()?;
+            return Ok(1);
+        }
+        if !self.is_fresh(chip, pc) {
+            let block = self.compile(chip, pc);
+            self.blocks.insert(pc, block);
+        }
+        match self.blocks[&pc] {
+            Some(ref block) if block.len <= limit => {
+                let mut state = State { v: [0; REGISTER_SIZE], i: chip.i() as u64 };
+                for (n, v) in state.v.iter_mut().enumerate() {
+                    *v = chip.v(n as u8);
+                }
+                // The code only reads and writes through the pointer it is
+                // given, which lives until it returns.
+                unsafe { (block.code)(&mut state) };
+                for (n, &v) in state.v.iter().enumerate() {
+                    chip.set_v(n as u8, v);
+                }
+                chip.set_i(state.i as usize);
+                chip.set_pc(pc + block.len * 2);
+                Ok(block.len)
+            },
+            _ => {
+                chip.execute_cycle()?;
+                Ok(1)
+            },
+        }
+    }
+
+    /// Whether the block for `pc` still matches memory and the quirks.
+    fn is_fresh(&self, chip: &Chip8, pc: usize) -> bool {
+        match self.blocks.get(&pc) {
+            Some(Some(block)) => {
+                block.shift_vy == chip.shift_vy()
+                    && chip.memory().get(pc..pc + block.source.len()) == Some(&block.source[..])
+            },
+            // Only the first instruction was looked at.
+            Some(None) => decode(opcode(chip.memory(), pc)).is_none_or(|i| !compiles(i)),
+            None => false,
+        }
+    }
+
+    fn compile(&mut self, chip: &Chip8, pc: usize) -> Option<Block> {
+        let memory = chip.memory();
+        let instructions: Vec<Instruction> = (0..MAX_BLOCK)
+            .map(|n| pc + n * 2)
+            .take_while(|&at| at + 1 < memory.len())
+            .map(|at| decode(opcode(memory, at)))
+            .take_while(|i| i.is_some_and(compiles))
+            .map(Option::unwrap)
+            .collect();
+        if instructions.is_empty() {
+            return None;
+        }
+
+        let code = self.emit(&instructions, chip.shift_vy()).ok()?;
+        Some(Block {
+            source: memory[pc..pc + instructions.len() * 2].to_vec(),
+            len: instructions.len(),
+            shift_vy: chip.shift_vy(),
+            code,
+        })
+    }
+
+    fn emit(&mut self, instructions: &[Instruction], shift_vy: bool) -> Result<Code, String> {
+        let ptr = self.module.target_config().pointer_type();
+        let mut ctx = self.module.make_context();
+        ctx.func.signature.params.push(AbiParam::new(ptr));
+
+        let mut context = FunctionBuilderContext::new();
+        let mut b = FunctionBuilder::new(&mut ctx.func, &mut context);
+        let entry = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+        b.seal_block(entry);
+        let state = b.block_params(entry)[0];
+        let flags = MemFlagsData::trusted();
+        let i_offset = mem::size_of::<[u8; REGISTER_SIZE]>() as i32;
+
+        // Everything is loaded up front and stored back at the end.
+        let v: Vec<Variable> = (0..REGISTER_SIZE as i32).map(|n| {
+            let var = b.declare_var(types::I8);
+            let val = b.ins().load(types::I8, flags, state, n);
+            b.def_var(var, val);
+            var
+        }).collect();
+        let i = b.declare_var(types::I64);
+        let val = b.ins().load(types::I64, flags, state, i_offset);
+        b.def_var(i, val);
+
+        let vf = v[FLAG];
+        for &instruction in instructions {
+            use instruction::Instruction::*;
+            match instruction {
+                LoadImm { x, nn } => {
+                    let val = b.ins().iconst(types::I8, nn as i64);
+                    b.def_var(v[x as usize], val);
+                },
+                AddImm { x, nn } => {
+                    let vx = b.use_var(v[x as usize]);
+                    let val = b.ins().iadd_imm_u(vx, nn as i64);
+                    b.def_var(v[x as usize], val);
+                },
+                Move { x, y } => {
+                    let vy = b.use_var(v[y as usize]);
+                    b.def_var(v[x as usize], vy);
+                },
+                Or { x, y } | And { x, y } | Xor { x, y } => {
+                    let (vx, vy) = (b.use_var(v[x as usize]), b.use_var(v[y as usize]));
+                    let val = match instruction {
+                        Or { .. } => b.ins().bor(vx, vy),
+                        And { .. } => b.ins().band(vx, vy),
+                        _ => b.ins().bxor(vx, vy),
+                    };
+                    b.def_var(v[x as usize], val);
+                },
+                Add { x, y } => {
+                    let (vx, vy) = (b.use_var(v[x as usize]), b.use_var(v[y as usize]));
+                    let (sum, carry) = b.ins().uadd_overflow(vx, vy);
+                    b.def_var(v[x as usize], sum);
+                    b.def_var(vf, carry);
+                },
+                Sub { x, y } | SubN { x, y } => {
+                    let (vx, vy) = (b.use_var(v[x as usize]), b.use_var(v[y as usize]));
+                    let (from, by) = if let Sub { .. } = instruction { (vx, vy) } else { (vy, vx) };
+                    let diff = b.ins().isub(from, by);
+                    let no_borrow = b.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, from, by);
+                    b.def_var(v[x as usize], diff);
+                    b.def_var(vf, no_borrow);
+                },
+                Shr { x, y } | Shl { x, y } => {
+                    let val = b.use_var(v[if shift_vy { y } else { x } as usize]);
+                    let (shifted, bit) = if let Shr { .. } = instruction {
+                        (b.ins().ushr_imm_u(val, 1), b.ins().band_imm_u(val, 1))
+                    } else {
+                        (b.ins().ishl_imm_u(val, 1), b.ins().ushr_imm_u(val, 7))
+                    };
+                    b.def_var(v[x as usize], shifted);
+                    b.def_var(vf, bit);
+                },
+                LoadIndex { addr } => {
+                    let val = b.ins().iconst(types::I64, addr as i64);
+                    b.def_var(i, val);
+                },
+                AddIndex { x } => {
+                    let vx = wide(&mut b, v[x as usize]);
+                    let val = b.use_var(i);
+                    let sum = b.ins().iadd(val, vx);
+                    b.def_var(i, sum);
+                },
+                Font { x } => {
+                    let vx = wide(&mut b, v[x as usize]);
+                    let digit = b.ins().band_imm_u(vx, 0xF);
+                    let val = b.ins().imul_imm_u(digit, 5);
+                    b.def_var(i, val);
+                },
+                _ => unreachable!("{:?} does not compile", instruction),
+            }
+        }
+
+        for (n, &var) in v.iter().enumerate() {
+            let val = b.use_var(var);
+            b.ins().store(flags, val, state, n as i32);
+        }
+        let val = b.use_var(i);
+        b.ins().store(flags, val, state, i_offset);
+        b.ins().return_(&[]);
+        b.finalize(self.module.target_config());
+
+        let id = self.module.declare_anonymous_function(&ctx.func.signature).map_err(|e| e.to_string())?;
+        self.module.define_function(id, &mut ctx).map_err(|e| e.to_string())?;
+        self.module.clear_context(&mut ctx);
+        self.module.finalize_definitions().map_err(|e| e.to_string())?;
+        // The signature above is the one `Code` spells out.
+        Ok(unsafe { mem::transmute::<*const u8, Code>(self.module.get_finalized_function(id)) })
+    }
+}
+
+/// Whether blocks can hold `instruction`: it only touches V0-VF and I, can't
+/// fail and always moves on to the next instruction.
+fn compiles(instruction: Instruction) -> bool {
+    use instruction::Instruction::*;
+    matches!(instruction,
+        LoadImm { .. } | AddImm { .. } | Move { .. } | Or { .. } | And { .. } | Xor { .. }
+        | Add { .. } | Sub { .. } | SubN { .. } | Shr { .. } | Shl { .. }
+        | LoadIndex { .. } | AddIndex { .. } | Font { .. })
+}
+
+fn opcode(memory: &[u8], at: usize) -> u16 {
+    let byte = |at: usize| memory.get(at).cloned().unwrap_or(0) as u16;
+    byte(at) << 8 | byte(at + 1)
+}
+
+/// A register widened to the size of I.
+fn wide(b: &mut FunctionBuilder, var: Variable) -> Value {
+    let val = b.use_var(var);
+    b.ins().uextend(types::I64, val)
+}
@@ -0,0 +1,58 @@
+//! Key presses and releases waiting for the machine to take them.
+//!
+//! A keypad read once a frame misses a key tapped between two reads. Events
+//! queued with `Chip8::push_key` are applied in order as the machine runs
+//! instead, and a release waits until EX9E or EXA1 looked at the key or a
+//! frame went by, so the program sees even the shortest tap.
+
+/// Events the queue holds. Pushing onto a full queue applies the oldest
+/// event straight away.
+pub const KEY_QUEUE_SIZE: usize = 32;
+
+/// A key going down or up, stamped with `Chip8::frame_count` as of when it
+/// was pushed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: u8,
+    pub pressed: bool,
+    pub frame: u64,
+}
+
+/// A ring buffer of `KeyEvent`s, so the core needs no allocator.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyQueue {
+    events: [KeyEvent; KEY_QUEUE_SIZE],
+    /// Where the oldest event is.
+    head: usize,
+    len: usize,
+}
+
+impl KeyQueue {
+    pub fn new() -> Self {
+        KeyQueue { events: [KeyEvent { key: 0, pressed: false, frame: 0 }; KEY_QUEUE_SIZE], head: 0, len: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == KEY_QUEUE_SIZE
+    }
+
+    pub fn push(&mut self, event: KeyEvent) {
+        self.events[(self.head + self.len) % KEY_QUEUE_SIZE] = event;
+        self.len += 1;
+    }
+
+    pub fn peek(&self) -> Option<KeyEvent> {
+        if self.is_empty() { None } else { Some(self.events[self.head]) }
+    }
+
+    pub fn pop(&mut self) -> Option<KeyEvent> {
+        let event = self.peek()?;
+        self.head = (self.head + 1) % KEY_QUEUE_SIZE;
+        self.len -= 1;
+        Some(event)
+    }
+}
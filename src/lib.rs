@@ -0,0 +1,110 @@
+//! The CHIP-8 machine.
+//!
+//! Without the `std` feature, which the default features include, the core
+//! builds for `no_std` targets and needs no allocator. `host` has the glue
+//! for driving it from a board and `graphics`, behind the `embedded`
+//! feature, draws onto embedded-graphics displays. `audio` synthesizes the
+//! sound for audio backends, `gamepad` maps controllers onto the keypad,
+//! `palette` picks the colors to show the screen in and `observer` lets
+//! other code follow the machine as it runs. `handoff`
+//! passes frames to a render thread.
+//! Seeding from the OS, tracing, disassembly, screenshots, snapshots, IPS
+//! patches, the Octo compiler and `emulator`, which runs the machine on a
+//! thread of its own, need `std`. `jit` adds an experimental recompiler.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// `no_std` brings `core` in by itself.
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "jit")]
+extern crate cranelift_codegen;
+#[cfg(feature = "jit")]
+extern crate cranelift_frontend;
+#[cfg(feature = "jit")]
+extern crate cranelift_jit;
+#[cfg(feature = "jit")]
+extern crate cranelift_module;
+#[cfg(feature = "jit")]
+extern crate cranelift_native;
+#[cfg(feature = "embedded")]
+extern crate embedded_graphics_core;
+#[cfg(feature = "std")]
+extern crate png;
+#[cfg(feature = "std")]
+extern crate rand;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate tracing;
+
+pub mod audio;
+mod chip8;
+#[cfg(feature = "std")]
+pub mod disasm;
+mod display;
+mod error;
+mod font;
+pub mod gamepad;
+#[cfg(feature = "std")]
+pub mod emulator;
+#[cfg(feature = "embedded")]
+pub mod graphics;
+#[cfg(target_has_atomic = "8")]
+pub mod handoff;
+pub mod host;
+mod instruction;
+#[cfg(feature = "std")]
+pub mod ips;
+mod keys;
+#[cfg(feature = "jit")]
+pub mod jit;
+pub mod observer;
+#[cfg(feature = "std")]
+pub mod octo;
+pub mod palette;
+mod rng;
+#[cfg(feature = "std")]
+pub mod screenshot;
+#[cfg(feature = "std")]
+mod snapshot;
+mod timing;
+
+pub use chip8::{Chip8, Chip8Builder, FrameOutput, KeyWait, SysPolicy, WriteProtect, MAX_MEMORY_SIZE};
+pub use display::{Display, Frame};
+pub use error::Error;
+pub use font::{Font, FONT_SIZE};
+#[cfg(feature = "std")]
+pub use snapshot::{rom_hash, Profile, Savestate, Snapshot, SAVESTATE_MAGIC, SAVESTATE_VERSION};
+pub use keys::{KeyEvent, KEY_QUEUE_SIZE};
+pub use instruction::{decode, decode_long, decode_match, Instruction, Level, LONG_PREFIX};
+pub use timing::{cycles_for, Timing, SCHIP_FRAME_INSTRUCTIONS, VIP_FRAME_CYCLES};
+
+/// The default CPU clock, in Hz.
+pub const CPU_CLOCK: u32 = 600;
+/// The timers clock, in Hz.
+pub const TIMERS_CLOCK: u32 = 60;
+
+/// The index of the register used for the 'carry flag'.
+/// VF is used according to the CHIP 8 specifications.
+pub const FLAG: usize = 15;
+/// The size of the stack.
+pub const STACK_SIZE: usize = 16;
+/// The size of the register.
+pub const REGISTER_SIZE: usize = 16;
+/// Program always loads at this address (512).
+pub const PROGRAM_START: usize = 0x200;
+/// Machine memory size, unless the machine is built with another.
+pub const MEMORY_SIZE: usize = 4096;
+/// XO-CHIP machine memory size, reached with F000 NNNN.
+pub const XO_MEMORY_SIZE: usize = 0x10000;
+/// Number of keys on the hexadecimal keypad.
+pub const KEY_COUNT: usize = 16;
+
+/// Display width.
+pub const DISPLAY_WIDTH: usize = 64;
+/// Display height.
+pub const DISPLAY_HEIGHT: usize = 32;
+/// Display width in the SCHIP 128x64 mode.
+pub const HIRES_WIDTH: usize = 128;
+/// Display height in the SCHIP 128x64 mode.
+pub const HIRES_HEIGHT: usize = 64;
@@ -0,0 +1,250 @@
+//! The ROMs in the ROM directories, with what is known about them.
+//!
+//! `--rom-dir DIR`, which may be given more than once, names directories
+//! of ROMs, `roms/` under the data directory by default. They are scanned
+//! at startup and again whenever one changes: each ROM is hashed, its
+//! instruction set worked out as `info` does and its title looked up in
+//! the `--rom-db` database, in the format `info` reads. Hashes and
+//! instruction sets are kept in `library.tsv` in the data directory, so
+//! only new or changed files are read on the next scan.
+//!
+//! Whichever way a ROM file is loaded, its tick rate comes from the
+//! database or from Octo's options saved next to it, `pong.json` for
+//! `pong.8o` or `pong.ch8`, and sets the instructions a frame unless
+//! `--timing` was given.
+//!
+//! The boot menu lists the library, websocket clients ask for it with
+//! `library`, and `ruchip8 list [--db FILE] [DIR]...` prints it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ruchip8::Level;
+
+use info;
+use json::Json;
+use rpl;
+use session::{Remote, Session};
+
+/// Files the library takes for ROMs, and Octo sources.
+const EXTENSIONS: [&str; 6] = ["ch8", "c8", "sc8", "xo8", "8o", "ch10"];
+
+/// How often `Watch` looks for changed directories.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+const USAGE: &str = "usage: ruchip8 list [--db FILE] [DIR]...";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub size: u64,
+    /// When the file was last changed, in seconds since the Unix epoch.
+    pub modified: u64,
+    pub sha1: String,
+    /// The highest level the ROM has instructions of.
+    pub level: Level,
+    /// The title from the ROM database.
+    pub title: Option<String>,
+}
+
+impl Entry {
+    /// The title, or the file name without its extension.
+    pub fn name(&self) -> String {
+        match self.title {
+            Some(ref title) => title.clone(),
+            None => self.path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Chip8 => "chip8",
+        Level::Schip => "schip",
+        Level::XoChip => "xochip",
+    }
+}
+
+fn is_rom(path: &Path) -> bool {
+    path.extension().and_then(|extension| extension.to_str())
+        .is_some_and(|extension| EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[derive(Debug, Default)]
+pub struct Library {
+    pub dirs: Vec<PathBuf>,
+    /// The text of the ROM database, if any.
+    db: Option<String>,
+    /// Where hashes are kept between runs.
+    cache: Option<PathBuf>,
+    /// Every ROM, by path.
+    pub entries: Vec<Entry>,
+    /// Counts the scans that changed `entries`.
+    pub version: u64,
+    /// When each directory last changed as of the last scan.
+    stamps: Vec<Option<SystemTime>>,
+}
+
+impl Library {
+    pub fn new(dirs: Vec<PathBuf>, db: Option<String>, cache: Option<PathBuf>) -> Self {
+        Library { dirs, db, cache, ..Library::default() }
+    }
+
+    /// Reads the entries `cache` has, skipping lines it cannot make out.
+    fn cached(&self) -> Vec<Entry> {
+        let text = match self.cache.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(text) => text,
+            None => return Vec::new(),
+        };
+        text.lines().filter_map(|line| {
+            let mut fields = line.splitn(5, '\t');
+            let sha1 = fields.next()?.to_owned();
+            let size = fields.next()?.parse().ok()?;
+            let modified = fields.next()?.parse().ok()?;
+            let level = Level::parse(fields.next()?)?;
+            let path = PathBuf::from(fields.next()?);
+            Some(Entry { path, size, modified, sha1, level, title: None })
+        }).collect()
+    }
+
+    fn write_cache(&self) {
+        let path = match self.cache {
+            Some(ref path) => path,
+            None => return,
+        };
+        let text: String = self.entries.iter()
+            .map(|entry| format!("{}\t{}\t{}\t{}\t{}\n", entry.sha1, entry.size, entry.modified,
+                                 level_name(entry.level), entry.path.display()))
+            .collect();
+        if let Err(e) = fs::write(path, text) {
+            warn!("cannot write {}: {}", path.display(), e);
+        }
+    }
+
+    /// What is known about `path`, from `cached` when the file has not
+    /// changed since.
+    fn entry(&self, path: PathBuf, cached: &[Entry]) -> Option<Entry> {
+        let metadata = fs::metadata(&path).ok()?;
+        let modified = metadata.modified().ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_secs());
+        let mut entry = match cached.iter().find(|entry| entry.path == path && entry.size == metadata.len() && entry.modified == modified) {
+            Some(entry) => entry.clone(),
+            None => {
+                let rom = match fs::read(&path) {
+                    Ok(rom) => rom,
+                    Err(e) => {
+                        warn!("cannot read {}: {}", path.display(), e);
+                        return None;
+                    },
+                };
+                let level = info::code(&rom).values().flatten().map(|instruction| instruction.level()).max().unwrap_or(Level::Chip8);
+                Entry { path, size: metadata.len(), modified, sha1: info::sha1(&rom), level, title: None }
+            },
+        };
+        entry.title = self.db.as_ref().and_then(|db| info::lookup(db, &entry.sha1)).map(|record| record.title);
+        Some(entry)
+    }
+
+    /// Reads the directories again.
+    pub fn scan(&mut self) {
+        let cached = if self.entries.is_empty() { self.cached() } else { self.entries.clone() };
+        let mut paths = Vec::new();
+        for dir in &self.dirs {
+            match fs::read_dir(dir) {
+                Ok(entries) => paths.extend(entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.is_file() && is_rom(path))),
+                Err(e) => warn!("cannot read {}: {}", dir.display(), e),
+            }
+        }
+        paths.sort();
+        let entries: Vec<Entry> = paths.into_iter().filter_map(|path| self.entry(path, &cached)).collect();
+        self.stamps = self.dirs.iter().map(|dir| modified(dir)).collect();
+        if entries != self.entries {
+            info!("{} ROMs in the library", entries.len());
+            self.entries = entries;
+            self.version += 1;
+            self.write_cache();
+        }
+    }
+
+    /// The instructions a frame `rom`, read from `path`, was made for: the
+    /// `tickrate` of the Octo options saved next to it as `NAME.json`, or
+    /// else the one the ROM database has.
+    pub fn tickrate(&self, path: &Path, rom: &[u8]) -> Option<u32> {
+        let options = path.with_extension("json");
+        let from_options = match fs::read_to_string(&options) {
+            Ok(text) => match Json::parse(&text) {
+                Ok(json) => json.get("tickrate").and_then(Json::as_u64).filter(|&n| n > 0 && n <= u32::MAX as u64),
+                Err(e) => {
+                    warn!("cannot read {}: {}", options.display(), e);
+                    None
+                },
+            },
+            Err(_) => None,
+        };
+        from_options.map(|n| n as u32)
+            .or_else(|| self.db.as_ref().and_then(|db| info::lookup(db, &info::sha1(rom))).and_then(|record| record.tickrate))
+    }
+
+    /// Whether a directory changed since the last scan.
+    pub fn changed(&self) -> bool {
+        self.dirs.iter().zip(&self.stamps).any(|(dir, &stamp)| modified(dir) != stamp)
+    }
+}
+
+/// Scans the session's library again when a directory changes.
+pub struct Watch {
+    last: Instant,
+}
+
+impl Watch {
+    pub fn new() -> Self {
+        Watch { last: Instant::now() }
+    }
+}
+
+impl Remote for Watch {
+    fn poll(&mut self, session: &mut Session) {
+        if self.last.elapsed() < WATCH_INTERVAL {
+            return;
+        }
+        self.last = Instant::now();
+        if session.library.changed() {
+            session.library.scan();
+        }
+    }
+}
+
+/// `ruchip8 list`.
+pub fn command(args: &[String]) -> Result<(), String> {
+    let (db, dirs) = match args {
+        [flag, db, dirs @ ..] if flag == "--db" => (Some(db), dirs),
+        [flag] if flag == "--db" => return Err(USAGE.to_owned()),
+        dirs => (None, dirs),
+    };
+    let db = match db {
+        Some(path) => Some(fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?),
+        None => None,
+    };
+    let data_dir = rpl::data_dir();
+    let dirs: Vec<PathBuf> = if dirs.is_empty() {
+        data_dir.iter().map(|dir| dir.join("roms")).collect()
+    } else {
+        dirs.iter().map(PathBuf::from).collect()
+    };
+    if dirs.is_empty() {
+        return Err(format!("no data directory to find roms/ in\n{}", USAGE));
+    }
+    let mut library = Library::new(dirs, db, data_dir.map(|dir| dir.join("library.tsv")));
+    library.scan();
+    for entry in &library.entries {
+        println!("{:<32} {:<7} {:>6}  {}", entry.name(), entry.level.to_string(), entry.size, entry.path.display());
+    }
+    Ok(())
+}
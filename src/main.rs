@@ -1,431 +1,618 @@
+extern crate libc;
+extern crate png;
 extern crate rand;
-
-use rand::random;
-
-/// The default CPU clock, in Hz.
-const CPU_CLOCK: u32 = 600;
-/// The timers clock, in Hz.
-const TIMERS_CLOCK: u32 = 60;
-
-/// The index of the register used for the 'carry flag'.
-/// VF is used according to the CHIP 8 specifications.
-const FLAG: usize = 15;
-/// The size of the stack.
-const STACK_SIZE: usize = 16;
-/// The size of the register.
-const REGISTER_SIZE: usize = 16;
-/// Program always loads at this address (512).
-const PROGRAM_START: usize = 0x200;
-/// Machine memory size.
-const MEMORY_SIZE: usize = 4096;
-
-/// Display height.
-const DISPLAY_HEIGHT: usize = 64;
-/// Display width.
-const DISPLAY_WIDTH: usize = 32;
-
-
-/// Chip8 font set.
-static FONT_SET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
-];
-
-
-macro_rules! opcode_not_implemented {
-    ($op: expr, $pc: expr) => (
-        panic!("{:0>4X} opcode not implemented at {:05X}", $op as usize, $pc);
-    )
+extern crate rhai;
+extern crate ruchip8;
+extern crate tiny_http;
+#[macro_use]
+extern crate tracing;
+extern crate tracing_subscriber;
+extern crate tungstenite;
+
+mod api;
+mod cheats;
+mod compare;
+mod connection;
+mod control;
+mod coverage;
+mod crash;
+mod dap;
+mod debugger;
+mod difftest;
+#[cfg(target_os = "linux")]
+mod evdev;
+#[cfg(target_os = "linux")]
+mod fbdev;
+mod heatmap;
+mod histogram;
+mod hotkey;
+mod info;
+mod json;
+mod library;
+mod menu;
+mod metrics;
+mod netplay;
+mod persist;
+mod plugin;
+mod rpl;
+mod savestate;
+mod scripting;
+mod search;
+mod session;
+mod sourcemap;
+mod splash;
+#[cfg(unix)]
+mod terminal;
+mod trace;
+mod validate;
+mod websocket;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+use ruchip8::audio::{Latency, BEEP_FREQUENCY};
+use ruchip8::disasm::Symbols;
+use ruchip8::ips;
+use ruchip8::octo;
+use ruchip8::palette::{Palette, MIN_CONTRAST};
+use ruchip8::{Chip8, Font, KeyWait, SysPolicy, Timing, WriteProtect, FONT_SIZE, MEMORY_SIZE};
+use session::{Remote, Session};
+use sourcemap::SourceMap;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "usage: ruchip8 [--websocket ADDR] [--api-port PORT]\n               \
+                     [--stdio] [--control-socket PATH]\n               \
+                     [--debug] [--debug-listen ADDR] [--dap | --dap-listen ADDR]\n               \
+                     [--symbols FILE] [--source-map FILE] [--patch FILE]...\n               \
+                     [--script FILE] [--cheats FILE]\n               \
+                     [--netplay-host PORT | --netplay-connect ADDR]\n               \
+                     [--plugin NAME[=ARG]]... [--log FILTER] [--crash-dir DIR]\n               \
+                     [--autosave] [--resume] [--persist START-END]... [--overlay]\n               \
+                     [--rom-dir DIR]... [--rom-db FILE]\n               \
+                     [--trace FILE] [--trace-range START-END]... [--trace-max N]\n               \
+                     [--timing N|vip|schip] [--sys ignore|warn|halt]\n               \
+                     [--protect off|warn|trap] [--key-wait press|release]\n               \
+                     [--memory BYTES] [--font default|vip|dream6800|eti660|FILE]\n               \
+                     [--beep HZ] [--audio-latency low|balanced|safe|MS]\n               \
+                     [--palette NAME|COLORS] [--frame-skip N]\n               \
+                     [--metrics-port PORT] [--metrics-log SECONDS]\n               \
+                     [ROM | SOURCE.8o]\n       \
+                     ruchip8 run [OPTION]... ROM | SOURCE.8o\n       \
+                     ruchip8 plugins\n       \
+                     ruchip8 PLUGIN ARGS...";
+
+/// Command line options.
+struct Options {
+    rom: Option<String>,
+    websocket: Option<String>,
+    api_port: Option<u16>,
+    metrics_port: Option<u16>,
+    metrics_log: Option<Duration>,
+    netplay_host: Option<u16>,
+    netplay_connect: Option<String>,
+    debug: bool,
+    debug_listen: Option<String>,
+    dap: bool,
+    stdio: bool,
+    control_socket: Option<String>,
+    dap_listen: Option<String>,
+    script: Option<String>,
+    cheats: Option<String>,
+    symbols: Option<String>,
+    source_map: Option<String>,
+    patches: Vec<String>,
+    plugins: Vec<String>,
+    log: Option<String>,
+    crash_dir: Option<String>,
+    autosave: bool,
+    resume: bool,
+    persist: Vec<RangeInclusive<usize>>,
+    overlay: bool,
+    frame_skip: u32,
+    rom_dirs: Vec<String>,
+    rom_db: Option<String>,
+    trace: Option<String>,
+    trace_ranges: Vec<RangeInclusive<usize>>,
+    trace_max: Option<u64>,
+    /// Set by `--timing`, over the ROMs' own tick rates.
+    timing: Option<Timing>,
+    sys: SysPolicy,
+    protect: WriteProtect,
+    key_wait: KeyWait,
+    beep: f32,
+    audio_latency: Latency,
+    palette: Palette,
+    memory: usize,
+    font: [u8; FONT_SIZE],
 }
 
-
-struct Display {
-    screen: [u8; DISPLAY_HEIGHT * DISPLAY_WIDTH],
-}
-
-impl Display {
-    fn new() -> Self {
-        Display {
-            screen: [0; DISPLAY_HEIGHT * DISPLAY_WIDTH]
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        rom: None,
+        websocket: None,
+        api_port: None,
+        metrics_port: None,
+        metrics_log: None,
+        netplay_host: None,
+        netplay_connect: None,
+        debug: false,
+        debug_listen: None,
+        dap: false,
+        stdio: false,
+        control_socket: None,
+        dap_listen: None,
+        script: None,
+        cheats: None,
+        symbols: None,
+        source_map: None,
+        patches: Vec::new(),
+        plugins: Vec::new(),
+        log: None,
+        crash_dir: None,
+        autosave: false,
+        resume: false,
+        persist: Vec::new(),
+        overlay: false,
+        frame_skip: 1,
+        rom_dirs: Vec::new(),
+        rom_db: None,
+        trace: None,
+        trace_ranges: Vec::new(),
+        trace_max: None,
+        timing: None,
+        sys: SysPolicy::Halt,
+        protect: WriteProtect::Off,
+        key_wait: KeyWait::Press,
+        beep: BEEP_FREQUENCY,
+        audio_latency: Latency::default(),
+        palette: Palette::default(),
+        memory: MEMORY_SIZE,
+        font: *Font::Default.glyphs(),
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--websocket" => {
+                let addr = args.next().ok_or("--websocket needs an address")?;
+                options.websocket = Some(addr.clone());
+            },
+            "--api-port" => {
+                let port = args.next().ok_or("--api-port needs a port")?;
+                options.api_port = Some(port.parse().map_err(|_| format!("invalid port '{}'", port))?);
+            },
+            "--metrics-port" => {
+                let port = args.next().ok_or("--metrics-port needs a port")?;
+                options.metrics_port = Some(port.parse().map_err(|_| format!("invalid port '{}'", port))?);
+            },
+            "--metrics-log" => {
+                let seconds = args.next().ok_or("--metrics-log needs a number of seconds")?;
+                let seconds = seconds.parse::<u64>().ok().filter(|&seconds| seconds > 0)
+                    .ok_or_else(|| format!("invalid number of seconds '{}'", seconds))?;
+                options.metrics_log = Some(Duration::from_secs(seconds));
+            },
+            "--debug" => options.debug = true,
+            "--stdio" => options.stdio = true,
+            "--control-socket" => {
+                let path = args.next().ok_or("--control-socket needs a path")?;
+                options.control_socket = Some(path.clone());
+            },
+            "--debug-listen" => {
+                let addr = args.next().ok_or("--debug-listen needs an address")?;
+                options.debug_listen = Some(addr.clone());
+            },
+            "--dap" => options.dap = true,
+            "--dap-listen" => {
+                let addr = args.next().ok_or("--dap-listen needs an address")?;
+                options.dap_listen = Some(addr.clone());
+            },
+            "--script" => {
+                let path = args.next().ok_or("--script needs a file")?;
+                options.script = Some(path.clone());
+            },
+            "--cheats" => {
+                let path = args.next().ok_or("--cheats needs a file")?;
+                options.cheats = Some(path.clone());
+            },
+            "--symbols" => {
+                let path = args.next().ok_or("--symbols needs a file")?;
+                options.symbols = Some(path.clone());
+            },
+            "--source-map" => {
+                let path = args.next().ok_or("--source-map needs a file")?;
+                options.source_map = Some(path.clone());
+            },
+            "--patch" => {
+                let path = args.next().ok_or("--patch needs a file")?;
+                options.patches.push(path.clone());
+            },
+            "--log" => {
+                let filter = args.next().ok_or("--log needs a filter, such as debug or ruchip8::cpu=trace")?;
+                options.log = Some(filter.clone());
+            },
+            "--crash-dir" => {
+                let dir = args.next().ok_or("--crash-dir needs a directory")?;
+                options.crash_dir = Some(dir.clone());
+            },
+            "--autosave" => options.autosave = true,
+            "--overlay" => options.overlay = true,
+            "--frame-skip" => {
+                let n = args.next().ok_or("--frame-skip needs a number of frames")?;
+                options.frame_skip = n.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("invalid number of frames '{}'", n))?;
+            },
+            "--rom-dir" => {
+                let dir = args.next().ok_or("--rom-dir needs a directory")?;
+                options.rom_dirs.push(dir.clone());
+            },
+            "--rom-db" => {
+                let path = args.next().ok_or("--rom-db needs a file")?;
+                options.rom_db = Some(path.clone());
+            },
+            // Resuming on every launch needs the autosave to resume from.
+            "--resume" => {
+                options.autosave = true;
+                options.resume = true;
+            },
+            "--trace" => {
+                let path = args.next().ok_or("--trace needs a file")?;
+                options.trace = Some(path.clone());
+            },
+            "--persist" => {
+                let range = args.next().ok_or("--persist needs START-END")?;
+                options.persist.push(trace::parse_range(range)?);
+            },
+            "--trace-range" => {
+                let range = args.next().ok_or("--trace-range needs START-END")?;
+                options.trace_ranges.push(trace::parse_range(range)?);
+            },
+            "--trace-max" => {
+                let max = args.next().ok_or("--trace-max needs a number of records")?;
+                options.trace_max = Some(max.parse().map_err(|_| format!("invalid record count '{}'", max))?);
+            },
+            "--timing" => {
+                let timing = args.next().ok_or("--timing needs instructions per frame, vip or schip")?;
+                options.timing = Some(Timing::parse(timing).ok_or_else(|| format!("invalid timing '{}'", timing))?);
+            },
+            "--sys" => {
+                let policy = args.next().ok_or("--sys needs ignore, warn or halt")?;
+                options.sys = SysPolicy::parse(policy).ok_or_else(|| format!("invalid 0NNN policy '{}'", policy))?;
+            },
+            "--protect" => {
+                let protect = args.next().ok_or("--protect needs off, warn or trap")?;
+                options.protect = WriteProtect::parse(protect).ok_or_else(|| format!("invalid write protection '{}'", protect))?;
+            },
+            "--beep" => {
+                let hz = args.next().ok_or("--beep needs a frequency in Hz")?;
+                options.beep = hz.parse().ok()
+                    .filter(|hz| (20.0..=20_000.0).contains(hz))
+                    .ok_or_else(|| format!("invalid beep frequency '{}', expected 20 to 20000 Hz", hz))?;
+            },
+            "--audio-latency" => {
+                let text = args.next().ok_or("--audio-latency needs low, balanced, safe or milliseconds")?;
+                options.audio_latency = Latency::parse(text).ok_or_else(|| {
+                    format!("invalid audio latency '{}', expected low, balanced, safe or milliseconds", text)
+                })?;
+            },
+            "--palette" => {
+                let text = args.next().ok_or("--palette needs a name or colors")?;
+                options.palette = Palette::parse(text).ok_or_else(|| {
+                    format!("invalid palette '{}', expected default, high-contrast, deuteranopia, protanopia or #RRGGBB,#RRGGBB[,#RRGGBB,#RRGGBB]", text)
+                })?;
+            },
+            "--key-wait" => {
+                let key_wait = args.next().ok_or("--key-wait needs press or release")?;
+                options.key_wait = KeyWait::parse(key_wait).ok_or_else(|| format!("invalid key wait '{}'", key_wait))?;
+            },
+            "--memory" => {
+                let size = args.next().ok_or("--memory needs a size in bytes")?;
+                options.memory = size.parse().map_err(|_| format!("invalid memory size '{}'", size))?;
+            },
+            "--font" => {
+                let font = args.next().ok_or("--font needs a name or a file")?;
+                options.font = match Font::parse(font) {
+                    Some(font) => *font.glyphs(),
+                    None => read_font(Path::new(font))?,
+                };
+            },
+            "--plugin" => {
+                let plugin = args.next().ok_or("--plugin needs a name")?;
+                options.plugins.push(plugin.clone());
+            },
+            "--netplay-host" => {
+                let port = args.next().ok_or("--netplay-host needs a port")?;
+                options.netplay_host = Some(port.parse().map_err(|_| format!("invalid port '{}'", port))?);
+            },
+            "--netplay-connect" => {
+                let addr = args.next().ok_or("--netplay-connect needs an address")?;
+                options.netplay_connect = Some(addr.clone());
+            },
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'", flag)),
+            path if options.rom.is_none() => options.rom = Some(path.to_owned()),
+            extra => return Err(format!("unexpected argument '{}'", extra)),
         }
     }
 
-    /// Get coordinate x,y in one dimensional linear space.
-    fn get_coord(x: usize, y: usize) -> usize {
-        y * DISPLAY_WIDTH + x
+    if options.netplay_host.is_some() && options.netplay_connect.is_some() {
+        return Err("--netplay-host and --netplay-connect are exclusive".to_owned());
     }
+    // They would all read stdin.
+    if [options.dap, options.debug, options.stdio].iter().filter(|&&reads| reads).count() > 1 {
+        return Err("--dap, --debug and --stdio are exclusive".to_owned());
+    }
+    Ok(options)
 }
 
-/// CHIP-8 machine struct.
-struct Chip8 {
-    /// Index register
-    i: usize,
-    /// Program counter
-    pc: usize,
-    /// Registers refer to as V0 to VF where VF is used primarily for carry
-    v: [u8; REGISTER_SIZE],
-    /// Stack
-    stack: Vec<u16>,
-    /// Stack pointer
-    sp: usize,
-    /// Machine memory
-    memory: [u8; MEMORY_SIZE],
-    /// Delay timer
-    delay_timer: u8,
-    /// Sound timer
-    sound_timer: u8,
-    /// Wait for key press
-    wait_for_key: (bool, u8),
-    /// Original implementation requires VY to be shifted instead of VX.
-    /// Though many many ROM assume otherwise.
-    shift_vy: bool,
+/// Logs to stderr. `--log` takes precedence over `RUST_LOG`, the default
+/// only shows the `info` level, so per instruction tracing stays quiet.
+fn init_logging(filter: Option<&str>) -> Result<(), String> {
+    let filter = match filter {
+        Some(filter) => EnvFilter::try_new(filter).map_err(|e| format!("invalid log filter: {}", e))?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
+    Ok(())
 }
 
-impl Chip8 {
-    fn new() -> Self {
-        Chip8 {
-            i: 0, 
-            pc: PROGRAM_START,
-            sp: 0,
-            v: [0; REGISTER_SIZE],
-            stack: Vec::<u16>::new(),
-            memory: [0; MEMORY_SIZE],
-            delay_timer: 0,
-            sound_timer: 0,
-            wait_for_key: (false, 0),
-            shift_vy: false,
-        }
+fn run(options: Options) -> Result<(), String> {
+    init_logging(options.log.as_deref())?;
+    let mut remotes: Vec<Box<dyn Remote>> = Vec::new();
+    if let Some(ref path) = options.script {
+        remotes.push(Box::new(scripting::Script::load(Path::new(path))?));
     }
-
-    /// Reinitialize the machine whilst keeping the program inside the memory.
-    fn reset(&mut self) {
-        self.v = [0; REGISTER_SIZE];
-        self.stack.clear();
-        self.pc = PROGRAM_START;
-        self.i = 0;
-        self.sp = 0;
+    for arg in &options.plugins {
+        remotes.push(plugin::remote(arg)?);
     }
-
-    fn execute_cycle(&mut self) {
-        let ops = self.get_opcode();
-        self.check_opcode(ops);
+    if let Some(ref addr) = options.websocket {
+        remotes.push(Box::new(websocket::WebSocketServer::bind(addr).map_err(|e| e.to_string())?));
     }
-
-    /// Fetches 2 bytes 
-    fn get_opcode(&self) -> u16 {
-        (self.memory[self.pc] as u16) << 8 | (self.memory[self.pc+1] as u16)
+    if let Some(port) = options.api_port {
+        remotes.push(Box::new(api::ApiServer::bind(port).map_err(|e| e.to_string())?));
     }
-
-    /// Checks the given opcode and execute an operation.
-    fn check_opcode(&mut self, ops: u16) {
-        // Set the opcode tuples in the following pattern:
-        // 0xABCD
-        let op_tuple = (
-            ((ops & 0xF000) >> 12) as u8,
-            ((ops & 0x0F00) >> 8) as u8,
-            ((ops & 0x00F0) >> 4) as u8,
-            (ops & 0x000F) as u8,
-        );
-
-        // Match the opcode
-        // TODO: refactor it to make it easier to see
-        match op_tuple {
-            // 0x0NNN Execute machine language subroutine at address NNN. Ignore.
-            // Inclement program counter by two since every instruction is two bytes long.
-            // With an exception for jump and subroutine call.
-            (0x0, 0x0, 0xE, 0x0) => self.cls(),
-            (0x0, 0x0, 0xE, 0xE) => self.reset(),
-            (0x1, _, _, _) => self.jump_addr(ops & 0x0FFF),
-            (0x2, _, _, _) => self.call_sub(ops & 0x0FFF),
-            (0x3, x, _, _) => self.se_vx(x, (ops & 0x00FF) as u8),
-            (0x4, x, _, _) => self.sne_vx(x, (ops & 0x00FF) as u8),
-            (0x5, x, y, 0x0) => self.se_vx_vy(x, y),
-            (0x6, x, _, _) => self.set_reg_vn(x, (ops & 0x00FF) as u8),
-            (0x7, x, _, _) => {
-                // Adds the value NN to register VX.
-                let vx = self.read_reg_vn(x);
-                self.set_reg_vn(x, vx.wrapping_add((ops & 0x00FF) as u8));
-            },
-            (0x8, x, y, 0x0) => {
-                // Stores the value of register VY in register VX.
-                let vy = self.read_reg_vn(y);
-                self.set_reg_vn(x, vy);
-            },
-            (0x8, x, y, 0x1) => self.or_vx_vy(x, y),
-            (0x8, x, y, 0x2) => self.and_vx_vy(x, y),
-            (0x8, x, y, 0x3) => self.xor_vx_vy(x, y),
-            (0x8, x, y, 0x4) => self.add_vx_vy(x, y),
-            (0x8, x, y, 0x5) => self.sub_vx_vy(x, y),
-            (0x8, x, y, 0x6) => self.rshft_vx_vy(x, y),
-            (0x8, x, y, 0x7) => self.subn_vx_vy(x, y),
-            (0x8, x, y, 0xE) => self.lshft_vx_vy(x, y),
-            (0x9, x, y, 0x0) => self.skip_ne_vx_vy(x, y),
-            (0xA, _, _, _) => self.set_i_addr(ops & 0x0FFF),
-            (0xB, _, _, _) => {
-                // Jumps to address NNN + V0.
-                let v0 = self.read_reg_vn(0) as u16;
-                self.jump_addr(ops & 0x0FFF + v0);
-            },
-            (0xC, x, _, _) => self.rnd_vx_nn(x, (ops & 0x00FF) as u8),
-            (0xD, x, y, n) => self.draw_vx_vy(x, y, n),
-            (0xE, x, 0x9, 0xE) => self.skip_vx(x),
-            (0xE, x, 0xA, 0x1) => self.skipn_vx(x),
-            (0xF, x, 0x0, 0x7) => self.set_delay(x),
-            (0xF, x, 0x0, 0xA) => self.wait_vx(x),
-            (0xF, x, 0x1, 0x5) => self.set_vx_delay(x),
-            (0xF, x, 0x1, 0x8) => self.set_vx_sound(x),
-            (0xF, x, 0x1, 0xE) => self.add_vx_to_i(x),
-            (0xF, x, 0x2, 0x9) => self.set_i_sprite(x),
-            (0xF, x, 0x3, 0x3) => self.set_bcd_vx(x),
-            (0xF, x, 0x5, 0x5) => self.set_mem_regs(x),
-            (0xF, x, 0x6, 0x5) => self.fill_regs_mem(x),
-            _ => opcode_not_implemented!(ops, self.pc),
-        }
-        
+    if let Some(ref addr) = options.debug_listen {
+        remotes.push(Box::new(debugger::DebugServer::bind(addr).map_err(|e| e.to_string())?));
     }
-
-    /// Set V at index N to a specific value.
-    fn set_reg_vn(&mut self, n: u8, val: u8) {
-        self.v[n as usize] = val;
-        self.pc += 2;
+    if options.debug {
+        remotes.push(Box::new(debugger::Console::new()));
     }
-
-    /// Read a value of V at index N.
-    fn read_reg_vn(&mut self, n: u8) -> u8 {
-        self.v[n as usize]
+    if options.dap {
+        remotes.push(Box::new(dap::DapServer::stdio()));
     }
-
-    /// Clears the display
-    fn cls(&mut self) {
-        // TODO
-        self.pc += 2;
+    if options.stdio {
+        remotes.push(Box::new(control::StdioServer::new()));
     }
-
-    /// Returns from the subroutine, by setting the program counter
-    /// to the address from the top of stack.
-    fn ret(&mut self) {
-        let addr = self.stack.pop().expect("Stored address");
-        self.jump_addr(addr);
-        //self.pc += 2;
+    if let Some(ref path) = options.control_socket {
+        #[cfg(unix)]
+        remotes.push(Box::new(control::SocketServer::bind(Path::new(path))?));
+        #[cfg(not(unix))]
+        return Err(format!("cannot use {}, --control-socket needs unix sockets", path));
     }
-
-    /// Jumps program counter to a specified address
-    fn jump_addr(&mut self, addr: u16) {
-        self.pc = addr as usize;
-    }
-
-    /// Calls a subroutine by pushing the current PC to the stack,
-    /// then jumps to the given address.
-    fn call_sub(&mut self, addr: u16) {
-        self.stack.push(self.pc as u16);
-        self.jump_addr(addr);
-    }
-
-    /// Skips the following instruction if the value of register VX equals NN.
-    fn se_vx(&mut self, x: u8, nn: u8) {
-        self.pc += if self.v[x as usize] == nn {4} else {2};
-    }
-
-    /// Skips the following instruction if the value of register VX is not equal to NN.
-    fn sne_vx(&mut self, x: u8, nn: u8) {
-        self.pc += if self.v[x as usize] != nn {4} else {2};
-    }
-
-    /// Skips the following instruction if the value of 
-    /// register VX is equal to the value of register VY.
-    fn se_vx_vy(&mut self, x: u8, y: u8) {
-        self.pc += if self.v[x as usize] == self.v[y as usize] {4} else {2};
-    }
-
-    fn or_vx_vy(&mut self, x: u8, y: u8) {
-        self.v[x as usize] |= self.v[y as usize];
-        self.pc += 2;
-    }
-
-    fn and_vx_vy(&mut self, x: u8, y: u8) {
-        self.v[x as usize] &= self.v[y as usize];
-        self.pc += 2;
-    }
-
-    fn xor_vx_vy(&mut self, x: u8, y: u8) {
-        self.v[x as usize] ^= self.v[y as usize];
-        self.pc += 2;
-    }
-
-    fn add_vx_vy(&mut self, x: u8, y: u8) {
-        let sum: u16 = self.v[x as usize] as u16 + self.v[y as usize] as u16;
-        self.v[x as usize] = sum as u8;
-        self.v[FLAG] = if sum > 0xFF {0x1} else {0x0};
-        self.pc += 2;
-    }
-
-    /// Subtract the value of register VY from register VX
-    /// Set VF to 00 if a borrow occurs
-    /// Set VF to 01 if a borrow does not occur.
-    fn sub_vx_vy(&mut self, x: u8, y: u8) {
-        let diff: i8 = self.v[x as usize] as i8 - self.v[y as usize] as i8;
-        self.v[x as usize] = diff as u8;
-        self.v[FLAG] = if diff < 0 {0x1} else {0x0};
-        self.pc += 2;
-    }
-
-    /// Set register VX to the value of VY minus VX
-    /// Set VF to 00 if a borrow occurs
-    /// Set VF to 01 if a borrow does not occur.
-    fn subn_vx_vy(&mut self, x: u8, y: u8) {
-        let diff: i8 = self.v[y as usize] as i8 - self.v[x as usize] as i8;
-        self.v[x as usize] = diff as u8;
-        self.v[FLAG] = if diff < 0 {0x1} else {0x0};        
-        self.pc += 2;
-    }
-
-    /// Store the value of register VY shifted right one bit in register VX
-    /// Set register VF to the least significant bit prior to the shift.
-    fn rshft_vx_vy(&mut self, x: u8, y: u8) {
-        let n = if self.shift_vy {y} else {x};
-        self.v[FLAG] = self.v[n as usize] & 0x01; // 0000 0001
-        self.v[x as usize] = self.v[n as usize] >> 1;
-        self.pc += 2;
-    }
-
-    /// Store the value of register VY shifted left one bit in register VX
-    /// Set register VF to the most significant bit prior to the shift.
-    fn lshft_vx_vy(&mut self, x: u8, y: u8) {
-        let n = if self.shift_vy {y} else {x};
-        self.v[FLAG] = self.v[n as usize] & 0x80; // 1000 0000
-        self.v[x as usize] = self.v[n as usize] << 1;
-        self.pc += 2;
-    }
-
-    /// Skips the following instruction if the value of register VX is not equal 
-    /// to the value of register VY.
-    fn skip_ne_vx_vy(&mut self, x: u8, y: u8) {
-        self.pc += if self.v[x as usize] != self.v[y as usize] {4} else {2};
-    }
-
-    /// Stores memory address NNN in register I.
-    fn set_i_addr(&mut self, addr: u16) {
-        self.i = addr as usize;
-        self.pc += 2;
-    }
-
-    /// Sets VX to a random number with a mask of NN.
-    fn rnd_vx_nn(&mut self, x: u8, nn: u8) {
-        self.v[x as usize] = rand::random::<u8>() & nn;
-        self.pc += 2;
-    }
-
-    /// Draws a sprite at position VX, VY with N bytes of sprite data starting at the address stored in I
-    /// Set VF to 01 if any set pixels are changed to unset, and 00 otherwise.
-    fn draw_vx_vy(&mut self, x: u8, y: u8, n: u8) {
-        let pos_x = self.v[x as usize] as usize;
-        let pos_y = self.v[y as usize] as usize;
-        let start = self.i;
-        let end = self.i + n as usize;
-        // TODO: draw stuff
-        self.pc += 2;
-    }
-
-    /// Skips the following instruction if the key corresponding to the hex value 
-    /// currently stored in register VX is pressed.
-    fn skip_vx(&mut self, x: u8) {
-        // TODO: implement keypad
+    if let Some(ref addr) = options.dap_listen {
+        remotes.push(Box::new(dap::DapServer::bind(addr).map_err(|e| e.to_string())?));
     }
-
-    /// Skips the following instruction if the key corresponding to the hex value 
-    /// currently stored in register VX is not pressed.
-    fn skipn_vx(&mut self, x: u8) {
-        // TODO: implement keypad
-    }
-
-    /// Stores the current value of the delay timer in register VX.
-    fn set_delay(&mut self, x: u8) {
-        self.v[x as usize] = self.delay_timer;
-        self.pc += 2;
-    }
-
-    /// Waits for a keypress and store the result in register VX.
-    fn wait_vx(&mut self, x: u8) {
-        self.wait_for_key = (true, x);
-    }
-
-    /// Sets the delay timer to the value of register VX.
-    fn set_vx_delay(&mut self, x: u8) {
-        self.delay_timer = self.v[x as usize];
-        self.pc += 2;
+    if remotes.is_empty() {
+        return Err("no frontend selected, try --websocket or --api-port".to_owned());
     }
+    if options.metrics_port.is_some() || options.metrics_log.is_some() {
+        let mut metrics = metrics::Metrics::new();
+        if let Some(port) = options.metrics_port {
+            metrics = metrics.serve(port).map_err(|e| e.to_string())?;
+        }
+        if let Some(every) = options.metrics_log {
+            metrics = metrics.log_every(every);
+        }
+        remotes.push(Box::new(metrics));
+    }
+    let crash_dir = options.crash_dir.as_ref().map_or_else(|| PathBuf::from("."), PathBuf::from);
+    remotes.push(Box::new(crash::CrashDump::new(crash_dir)));
+    let data_dir = rpl::data_dir();
+    match data_dir {
+        Some(ref dir) => remotes.push(Box::new(rpl::RplFlags::new(dir.clone()))),
+        None => warn!("no data directory, RPL flags and savestates will not be saved"),
+    }
+    if !options.persist.is_empty() {
+        let dir = data_dir.clone().ok_or("--persist needs a data directory to keep memory in")?;
+        // Early, so the other remotes see the memory it restores.
+        remotes.insert(0, Box::new(persist::Persist::new(dir, options.persist.clone())));
+    }
+    if options.autosave {
+        // First, so the other remotes start from the resumed machine.
+        remotes.insert(0, Box::new(savestate::AutoSave::new(options.resume)));
+    }
+
+    let (rom, symbols, source_map) = match options.rom {
+        Some(ref path) => {
+            let program = read_program(Path::new(path))?;
+            (Some(program.rom), program.symbols, program.source_map)
+        },
+        None => (None, Symbols::default(), SourceMap::default()),
+    };
+    // As read, for looking up its tick rate.
+    let original = rom.clone();
+    let rom = match rom {
+        Some(rom) => Some(apply_patches(rom, &options.patches)?),
+        None if !options.patches.is_empty() => return Err("--patch needs a ROM".to_owned()),
+        None => None,
+    };
+
+    let chip = Chip8::builder()
+        .memory_size(options.memory)
+        .font(&options.font)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut session = Session::new(chip);
+    session.default_timing = options.timing.unwrap_or_default();
+    session.timing = session.default_timing;
+    session.timing_given = options.timing.is_some();
+    session.data_dir = data_dir;
+    session.chip.set_sys_policy(options.sys);
+    session.chip.set_write_protect(options.protect);
+    session.chip.set_key_wait(options.key_wait);
+    session.beep = options.beep;
+    session.audio_latency = options.audio_latency;
+    session.palette = options.palette;
+    session.overlay = options.overlay;
+    session.frame_skip = options.frame_skip;
+    let rom_dirs: Vec<PathBuf> = if options.rom_dirs.is_empty() {
+        session.data_dir.iter().map(|dir| dir.join("roms")).filter(|dir| dir.is_dir()).collect()
+    } else {
+        options.rom_dirs.iter().map(PathBuf::from).collect()
+    };
+    let rom_db = match options.rom_db {
+        Some(ref path) => Some(fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?),
+        None => None,
+    };
+    let cache = session.data_dir.as_ref().map(|dir| dir.join("library.tsv"));
+    session.library = library::Library::new(rom_dirs, rom_db, cache);
+    if !session.library.dirs.is_empty() {
+        session.library.scan();
+        remotes.push(Box::new(library::Watch::new()));
+    }
+    let contrast = options.palette.min_contrast();
+    if contrast < MIN_CONTRAST {
+        warn!("the palette has a contrast ratio of only {:.1}, {} or more is easier to see", contrast, MIN_CONTRAST);
+    }
+    session.persistent = options.persist.clone();
+    session.cheats = match (options.cheats.as_ref(), options.rom.as_ref()) {
+        (Some(path), _) => cheats::Cheats::load(Path::new(path))?,
+        (None, Some(rom)) => cheats::Cheats::for_rom(Path::new(rom))?,
+        (None, None) => cheats::Cheats::default(),
+    };
+    session.symbols = match options.symbols {
+        Some(ref path) => read_symbols(Path::new(path))?,
+        None => symbols,
+    };
+    session.source_map = match options.source_map {
+        Some(ref path) => SourceMap::load(Path::new(path))?,
+        None => source_map,
+    };
+    if let Some(ref path) = options.trace {
+        let ranges = options.trace_ranges.clone();
+        session.trace = Some(trace::Trace::create(Path::new(path), ranges, options.trace_max)?);
+    }
+    let netplay = options.netplay_host.is_some() || options.netplay_connect.is_some();
+    if netplay {
+        let rom = rom.as_ref().ok_or("netplay needs a ROM")?;
+        let hash = ruchip8::rom_hash(rom);
+        let peer = match options.netplay_host {
+            Some(port) => netplay::Netplay::host(port, hash, rand::random()),
+            None => netplay::Netplay::connect(options.netplay_connect.as_ref().unwrap(), hash),
+        }.map_err(|e| format!("netplay: {}", e))?;
+        session.seed = Some(peer.seed());
+        // Last, so it sends the keys every other remote set this frame.
+        remotes.push(Box::new(peer));
+    }
+
+    match rom.zip(original) {
+        Some((rom, original)) => {
+            session.load(&rom).map_err(|e| e.to_string())?;
+            session.rom_path = options.rom.as_ref().map(PathBuf::from);
+            session.patches = options.patches.clone();
+            session.choose_timing(&original);
+        },
+        // Until a remote loads something.
+        None if session.library.dirs.is_empty() => splash::show(&mut session)?,
+        None => {
+            // The menu, to choose from the library.
+            let mut menu = menu::Menu::new();
+            menu.show(&mut session)?;
+            remotes.push(Box::new(menu));
+        },
+    }
+    // Give the local debugger the first instruction, or the debug
+    // adapter the chance to set breakpoints.
+    if options.debug || options.dap {
+        session.paused = true;
+    }
+
+    session::run(&mut session, &mut remotes);
+    Ok(())
+}
 
-    /// Sets the sound timer to the value of register VX.
-    fn set_vx_sound(&mut self, x: u8) {
-        self.sound_timer = self.v[x as usize];
-        self.pc += 2;
+/// Reads a set of 4x5 digits, 5 bytes for each digit from 0 to F.
+fn read_font(path: &Path) -> Result<[u8; FONT_SIZE], String> {
+    let bytes = fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    if bytes.len() != FONT_SIZE {
+        return Err(format!("{} is {} bytes, a font is {}", path.display(), bytes.len(), FONT_SIZE));
     }
+    let mut font = [0; FONT_SIZE];
+    font.copy_from_slice(&bytes);
+    Ok(font)
+}
 
-    /// Adds the value stored in register VX to register I.
-    fn add_vx_to_i(&mut self, x: u8) {
-        self.i += self.v[x as usize] as usize;
-        self.pc += 2;
-    }
+/// Reads a label file of `ADDR=NAME` lines.
+fn read_symbols(path: &Path) -> Result<Symbols, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    Symbols::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
 
-    /// Sets I to the memory address of the sprite data corresponding to the hexadecimal digit 
-    /// stored in register VX.
-    fn set_i_sprite(&mut self, x: u8) {
-        // Multiply by 5 because a sprite has 5 lines, a line equates to one byte.
-        self.i = (self.v[x as usize] * 5) as usize;
-        self.pc += 2;
+/// The labels stored next to `rom`, with its extension replaced by `.sym`,
+/// or none when there is no such file.
+fn symbols_for_rom(rom: &Path) -> Result<Symbols, String> {
+    let path = rom.with_extension("sym");
+    match fs::metadata(&path) {
+        Ok(_) => read_symbols(&path),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Symbols::default()),
+        Err(e) => Err(format!("cannot read {}: {}", path.display(), e)),
     }
+}
 
-    /// Stores the binary-coded decimal equivalent of the value stored in register VX at addresses I, I+1, and I+2
-    fn set_bcd_vx(&mut self, x: u8) {
-        let vx = self.read_reg_vn(x);
-        self.memory[self.i] = vx / 100;
-        self.memory[self.i+1] = (vx / 10) % 10;
-        self.memory[self.i+2] = (vx % 100) % 10;
-        self.pc += 2;
-    }
+/// A program to run: a ROM with the labels and source map stored next to
+/// it, or Octo source, compiled on the spot.
+struct Program {
+    rom: Vec<u8>,
+    symbols: Symbols,
+    source_map: SourceMap,
+}
 
-    /// Stores the values of registers V0 to VX inclusive in memory starting at address I
-    /// I is set to I + X + 1 after operation.
-    fn set_mem_regs(&mut self, x: u8) {
-        let x_usize = x as usize;
-        for i in 0..(x_usize + 1) {
-            self.memory[self.i + i] = self.v[i];
-        }
-        self.i += x_usize + 1;
-        self.pc += 2;
+/// Applies IPS patches to `rom` in order, leaving the files alone.
+fn apply_patches(mut rom: Vec<u8>, paths: &[String]) -> Result<Vec<u8>, String> {
+    for path in paths {
+        let patch = fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        rom = ips::apply(&rom, &patch).map_err(|e| format!("{}: {}", path, e))?;
+        info!("applied {}", path);
     }
+    Ok(rom)
+}
 
-    /// Fills registers V0 to VX inclusive with the values stored in memory starting at address I
-    /// I is set to I + X + 1 after operation.
-    fn fill_regs_mem(&mut self, x: u8) {
-        let x_usize = x as usize;
-        for i in 0..(x_usize + 1) {
-            self.v[i] = self.memory[self.i + i];
+fn read_program(path: &Path) -> Result<Program, String> {
+    if path.extension().is_some_and(|extension| extension == "8o") {
+        let source = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let program = octo::compile(&source).map_err(|e| format!("{}:{}: {}", path.display(), e.line, e.message))?;
+        let mut symbols = Symbols::default();
+        for (name, &addr) in &program.labels {
+            symbols.insert(addr, name);
         }
-        self.i += x_usize + 1;
-        self.pc += 2;
-    }
+        return Ok(Program {
+            source_map: SourceMap::for_source(path, &source, &program.lines),
+            symbols,
+            rom: program.rom,
+        });
+    }
+    let rom = fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    Ok(Program { rom, symbols: symbols_for_rom(path)?, source_map: SourceMap::for_rom(path)? })
 }
 
 fn main() {
-    // Setup graphic
-    // Setup input
-    // Initialize the system
-    // Game loop
-
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = args.first().and_then(|name| plugin::find(name)).and_then(|p| p.command);
+    let result = match (args.first().map(String::as_str), command) {
+        (Some("plugins"), _) => plugin::list(&args[1..]),
+        (Some("run"), _) => parse_args(&args[1..]).and_then(run),
+        (_, Some(command)) => init_logging(None).and_then(|_| command(&args[1..])),
+        _ => parse_args(&args).and_then(run),
+    };
+
+    if let Err(e) = result {
+        eprintln!("ruchip8: {}", e);
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,236 @@
+//! A boot menu of the ROMs in the library, for cabinets and handhelds.
+//!
+//! Started without a ROM, the emulator shows the ROMs in the ROM
+//! directories, see `library`, by title where the ROM database has one,
+//! instead of idling. The menu follows the library as it changes. The menu is a CHIP-8 program, written in Octo here and compiled
+//! for each page, so every frontend shows it and it is played like a game,
+//! with the keys gamepads map to: 5 (W) and 8 (S) move, 7 (A) and 9 (D)
+//! turn the page and 6 (E) or 4 (Q) loads the ROM. When a game exits with
+//! 00FD the menu comes back.
+
+use std::fmt::Write;
+
+use ruchip8::disasm::Symbols;
+use ruchip8::octo;
+use ruchip8::Timing;
+
+use session::{Remote, Session};
+use sourcemap::SourceMap;
+
+/// ROMs on a page, under the title line.
+const ROWS: usize = 9;
+/// Characters of a name that fit right of the cursor in 128x64.
+const NAME_LENGTH: usize = 30;
+
+/// The pace the menu runs at, fast enough to draw a page at once.
+const TIMING: Timing = Timing::Fixed(5000);
+
+/// What the menu program leaves at `result` besides a row.
+const NEXT_PAGE: u8 = 0xFE;
+const PREVIOUS_PAGE: u8 = 0xFF;
+
+/// Glyphs of 3x5 pixels, in the high bits. Other characters show as `?`.
+pub const GLYPHS: [(char, [u8; 5]); 48] = [
+    ('A', [0x40, 0xA0, 0xE0, 0xA0, 0xA0]), ('B', [0xC0, 0xA0, 0xC0, 0xA0, 0xC0]),
+    ('C', [0x60, 0x80, 0x80, 0x80, 0x60]), ('D', [0xC0, 0xA0, 0xA0, 0xA0, 0xC0]),
+    ('E', [0xE0, 0x80, 0xC0, 0x80, 0xE0]), ('F', [0xE0, 0x80, 0xC0, 0x80, 0x80]),
+    ('G', [0x60, 0x80, 0xA0, 0xA0, 0x60]), ('H', [0xA0, 0xA0, 0xE0, 0xA0, 0xA0]),
+    ('I', [0xE0, 0x40, 0x40, 0x40, 0xE0]), ('J', [0x20, 0x20, 0x20, 0xA0, 0x40]),
+    ('K', [0xA0, 0xA0, 0xC0, 0xA0, 0xA0]), ('L', [0x80, 0x80, 0x80, 0x80, 0xE0]),
+    ('M', [0xA0, 0xE0, 0xE0, 0xA0, 0xA0]), ('N', [0xC0, 0xA0, 0xA0, 0xA0, 0xA0]),
+    ('O', [0x40, 0xA0, 0xA0, 0xA0, 0x40]), ('P', [0xC0, 0xA0, 0xC0, 0x80, 0x80]),
+    ('Q', [0x40, 0xA0, 0xA0, 0xC0, 0x60]), ('R', [0xC0, 0xA0, 0xC0, 0xA0, 0xA0]),
+    ('S', [0x60, 0x80, 0x40, 0x20, 0xC0]), ('T', [0xE0, 0x40, 0x40, 0x40, 0x40]),
+    ('U', [0xA0, 0xA0, 0xA0, 0xA0, 0xE0]), ('V', [0xA0, 0xA0, 0xA0, 0xA0, 0x40]),
+    ('W', [0xA0, 0xA0, 0xE0, 0xE0, 0xA0]), ('X', [0xA0, 0xA0, 0x40, 0xA0, 0xA0]),
+    ('Y', [0xA0, 0xA0, 0x40, 0x40, 0x40]), ('Z', [0xE0, 0x20, 0x40, 0x80, 0xE0]),
+    ('0', [0xE0, 0xA0, 0xA0, 0xA0, 0xE0]), ('1', [0x40, 0xC0, 0x40, 0x40, 0xE0]),
+    ('2', [0xC0, 0x20, 0x40, 0x80, 0xE0]), ('3', [0xC0, 0x20, 0x40, 0x20, 0xC0]),
+    ('4', [0xA0, 0xA0, 0xE0, 0x20, 0x20]), ('5', [0xE0, 0x80, 0xC0, 0x20, 0xC0]),
+    ('6', [0x60, 0x80, 0xC0, 0xA0, 0x40]), ('7', [0xE0, 0x20, 0x40, 0x40, 0x40]),
+    ('8', [0x40, 0xA0, 0x40, 0xA0, 0x40]), ('9', [0x40, 0xA0, 0x60, 0x20, 0xC0]),
+    ('-', [0x00, 0x00, 0xE0, 0x00, 0x00]), ('_', [0x00, 0x00, 0x00, 0x00, 0xE0]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x40]), ('(', [0x20, 0x40, 0x40, 0x40, 0x20]),
+    (')', [0x80, 0x40, 0x40, 0x40, 0x80]), ('!', [0x40, 0x40, 0x40, 0x00, 0x40]),
+    ('\'', [0x40, 0x40, 0x00, 0x00, 0x00]), ('+', [0x00, 0x40, 0xE0, 0x40, 0x00]),
+    ('&', [0x40, 0xA0, 0x40, 0xA0, 0x60]), ('/', [0x20, 0x20, 0x40, 0x80, 0x80]),
+    ('?', [0xC0, 0x20, 0x40, 0x00, 0x40]), ('>', [0x80, 0x40, 0x20, 0x40, 0x80]),
+];
+
+pub struct Menu {
+    page: usize,
+    /// The version of the library the page showing lists.
+    version: u64,
+    /// The hash of the page showing and where it leaves the choice, `None`
+    /// while a game runs.
+    showing: Option<(u64, usize)>,
+    /// The session's own timing, for the games.
+    timing: Timing,
+}
+
+/// The glyph `c` is drawn with.
+pub fn glyph(c: char) -> usize {
+    let c = c.to_ascii_uppercase();
+    GLYPHS.iter().position(|&(glyph, _)| glyph == c).unwrap_or_else(|| glyph('?'))
+}
+
+/// Octo defining `glyph-N` for each glyph `print_at` draws, 240 bytes.
+pub fn glyphs(out: &mut String) {
+    for (n, &(_, rows)) in GLYPHS.iter().enumerate() {
+        let bytes: Vec<String> = rows.iter().map(|row| format!("0x{:02X}", row)).collect();
+        writeln!(out, ": glyph-{} {}", n, bytes.join(" ")).unwrap();
+    }
+}
+
+/// Octo drawing `text` with its top left at pixel `x`, `y`, taking v1 and
+/// v2.
+pub fn print_at(out: &mut String, text: &str, x: usize, y: usize) {
+    writeln!(out, "  v1 := {}  v2 := {}", x, y).unwrap();
+    for c in text.chars() {
+        if c != ' ' {
+            writeln!(out, "  i := glyph-{}  sprite v1 v2 5", glyph(c)).unwrap();
+        }
+        out.push_str("  v1 += 4\n");
+    }
+}
+
+/// Octo drawing `text` from column `x` of text row `row`.
+fn print(out: &mut String, text: &str, x: usize, row: usize) {
+    print_at(out, text, x * 4, row * 6 + 1);
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        Menu { page: 0, version: 0, showing: None, timing: Timing::default() }
+    }
+
+    fn pages(session: &Session) -> usize {
+        session.library.entries.len().div_ceil(ROWS).max(1)
+    }
+
+    /// The page showing as an Octo program.
+    fn source(&self, session: &Session) -> String {
+        let names: Vec<String> = session.library.entries.iter().skip(self.page * ROWS).take(ROWS)
+            .map(|entry| entry.name().chars().take(NAME_LENGTH).collect())
+            .collect();
+        // A byte of padding keeps the code after the glyphs aligned.
+        let mut out = String::from(": result 0 0\n");
+        glyphs(&mut out);
+        // v4 is the row of the cursor, v5 the last row there is.
+        writeln!(out, "
+: draw-cursor
+  v1 := 0  v2 := v4  v2 += v2  v3 := v2  v2 += v2  v2 += v3  v2 += 7
+  i := glyph-{}  sprite v1 v2 5
+  return
+: up
+  if v4 == 0 then return
+  draw-cursor  v4 += -1  draw-cursor
+  return
+: down
+  if v4 == v5 then return
+  draw-cursor  v4 += 1  draw-cursor
+  return
+: leave
+  i := result  save v0
+  exit
+: main
+  hires
+  clear", glyph('>')).unwrap();
+        let title = format!("ROMS {}/{}", self.page + 1, Menu::pages(session));
+        print(&mut out, &title, 0, 0);
+        if names.is_empty() {
+            print(&mut out, "NO ROMS FOUND", 2, 1);
+            out.push_str("  loop again\n");
+            return out;
+        }
+        for (row, name) in names.iter().enumerate() {
+            print(&mut out, name, 2, row + 1);
+        }
+        writeln!(out, "  v4 := 0  v5 := {}
+  draw-cursor
+  loop
+    v0 := key
+    if v0 == 5 then up
+    if v0 == 8 then down
+    if v0 == 6 then jump choose
+    if v0 == 4 then jump choose
+    v1 := {}
+    if v0 == 9 then jump turn
+    v1 := {}
+    if v0 == 7 then jump turn
+  again
+: choose
+  v0 := v4
+  jump leave
+: turn
+  v0 := v1
+  jump leave", names.len() - 1, NEXT_PAGE, PREVIOUS_PAGE).unwrap();
+        out
+    }
+
+    /// Loads the page the menu is on.
+    pub fn show(&mut self, session: &mut Session) -> Result<(), String> {
+        // The library may have shrunk.
+        self.page = self.page.min(Menu::pages(session) - 1);
+        self.version = session.library.version;
+        let program = octo::compile(&self.source(session)).map_err(|e| format!("menu line {}: {}", e.line, e.message))?;
+        session.load(&program.rom).map_err(|e| e.to_string())?;
+        session.rom_path = None;
+        session.patches.clear();
+        session.symbols = Symbols::default();
+        session.source_map = SourceMap::default();
+        session.paused = false;
+        if self.showing.is_none() {
+            self.timing = session.timing;
+        }
+        session.timing = TIMING;
+        self.showing = session.rom_hash.zip(program.labels.get("result").copied());
+        Ok(())
+    }
+
+    fn choose(&mut self, session: &mut Session, choice: u8) -> Result<(), String> {
+        let pages = Menu::pages(session);
+        match choice {
+            NEXT_PAGE => self.page = (self.page + 1) % pages,
+            PREVIOUS_PAGE => self.page = (self.page + pages - 1) % pages,
+            row => {
+                session.timing = self.timing;
+                // Unpauses the menu, which paused on leaving with 00FD.
+                session.open(self.page * ROWS + row as usize)?;
+                self.showing = None;
+                return Ok(());
+            },
+        }
+        self.show(session)
+    }
+}
+
+impl Remote for Menu {
+    fn poll(&mut self, session: &mut Session) {
+        if self.showing.is_some_and(|(hash, _)| session.rom_hash != Some(hash)) {
+            // Something else loaded a ROM over the menu.
+            session.timing = self.timing;
+            self.showing = None;
+        }
+        if self.showing.is_some() && session.library.version != self.version {
+            if let Err(e) = self.show(session) {
+                warn!("cannot show the menu: {}", e);
+            }
+            return;
+        }
+        if !session.chip.is_halted() || session.error.is_some() {
+            return;
+        }
+        let result = match self.showing {
+            Some((_, addr)) => self.choose(session, session.chip.memory()[addr]),
+            // A game exited.
+            None => self.show(session),
+        };
+        if let Err(e) = result {
+            session.show_notice(e);
+            if let Err(e) = self.show(session) {
+                warn!("cannot show the menu: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Runtime metrics, for benchmarking frontends and keeping an eye on
+//! kiosk cabinets.
+//!
+//! `--metrics-port PORT` serves them at `http://127.0.0.1:PORT/metrics` in
+//! the Prometheus text format, `--metrics-log SECONDS` logs a line of them
+//! that often:
+//!
+//! ```text
+//! fps 60.0, ips 660, frame time 0.21ms (max 0.90ms)
+//! ```
+//!
+//! Rates and frame times are over the last whole second. The frame time is
+//! what a frame took before the loop slept, so it tells how much headroom
+//! there is.
+
+use std::fmt::Write;
+use std::io;
+use std::time::{Duration, Instant};
+
+use tiny_http::{Header, Response, Server};
+
+use session::{Remote, Session};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// What the last whole second measured.
+#[derive(Debug, Clone, Copy, Default)]
+struct Rates {
+    fps: f64,
+    ips: f64,
+    frame_time: Duration,
+    frame_time_max: Duration,
+}
+
+/// Counts for the second in progress.
+struct Window {
+    start: Instant,
+    frames: u64,
+    instructions: u64,
+    frame_time: Duration,
+    frame_time_max: Duration,
+}
+
+impl Window {
+    fn new(start: Instant) -> Self {
+        Window { start, frames: 0, instructions: 0, frame_time: Duration::ZERO, frame_time_max: Duration::ZERO }
+    }
+
+    fn rates(&self, elapsed: Duration) -> Rates {
+        let seconds = elapsed.as_secs_f64();
+        Rates {
+            fps: self.frames as f64 / seconds,
+            ips: self.instructions as f64 / seconds,
+            frame_time: self.frame_time.checked_div(self.frames as u32).unwrap_or_default(),
+            frame_time_max: self.frame_time_max,
+        }
+    }
+}
+
+pub struct Metrics {
+    server: Option<Server>,
+    log_every: Option<Duration>,
+    last_log: Instant,
+    window: Window,
+    rates: Rates,
+    /// Frames the loop ran, paused or not.
+    frames: u64,
+    instructions: u64,
+    /// `Stats::instructions` at the last frame, to count them across resets.
+    seen_instructions: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Metrics {
+            server: None,
+            log_every: None,
+            last_log: now,
+            window: Window::new(now),
+            rates: Rates::default(),
+            frames: 0,
+            instructions: 0,
+            seen_instructions: 0,
+        }
+    }
+
+    /// Serves `/metrics` on localhost only, like the control API.
+    pub fn serve(mut self, port: u16) -> io::Result<Self> {
+        let server = Server::http(("127.0.0.1", port)).map_err(|e| io::Error::other(e.to_string()))?;
+        info!("serving metrics on http://127.0.0.1:{}/metrics", port);
+        self.server = Some(server);
+        Ok(self)
+    }
+
+    pub fn log_every(mut self, every: Duration) -> Self {
+        self.log_every = Some(every);
+        self
+    }
+
+    fn log_line(&self) -> String {
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+        format!("fps {:.1}, ips {:.0}, frame time {:.2}ms (max {:.2}ms)",
+                self.rates.fps, self.rates.ips, ms(self.rates.frame_time), ms(self.rates.frame_time_max))
+    }
+
+    fn prometheus(&self, session: &Session) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            writeln!(text, "# HELP ruchip8_{} {}", name, help).unwrap();
+            writeln!(text, "# TYPE ruchip8_{} {}", name, kind).unwrap();
+            writeln!(text, "ruchip8_{} {}", name, value).unwrap();
+        };
+        metric("frames_total", "counter", "Frames the loop ran.", self.frames as f64);
+        metric("instructions_total", "counter", "Instructions the machine ran.", self.instructions as f64);
+        metric("fps", "gauge", "Frames per second over the last second.", self.rates.fps);
+        metric("ips", "gauge", "Instructions per second over the last second.", self.rates.ips);
+        metric("frame_time_seconds", "gauge", "Average time a frame took over the last second.",
+               self.rates.frame_time.as_secs_f64());
+        metric("frame_time_max_seconds", "gauge", "Longest a frame took over the last second.",
+               self.rates.frame_time_max.as_secs_f64());
+        metric("paused", "gauge", "1 while paused.", session.paused as u8 as f64);
+        text
+    }
+}
+
+impl Remote for Metrics {
+    fn poll(&mut self, session: &mut Session) {
+        let server = match self.server {
+            Some(ref server) => server,
+            None => return,
+        };
+        while let Ok(Some(request)) = server.try_recv() {
+            let response = match request.url() {
+                "/metrics" => Response::from_string(self.prometheus(session))
+                    .with_header(Header::from_bytes(&b"Content-Type"[..], &b"text/plain; version=0.0.4"[..]).expect("valid header")),
+                _ => Response::from_string("not found\n").with_status_code(404),
+            };
+            if let Err(e) = request.respond(response) {
+                warn!("failed to respond: {}", e);
+            }
+        }
+    }
+
+    fn frame(&mut self, session: &Session) {
+        let instructions = session.stats.instructions.checked_sub(self.seen_instructions)
+            // A load or reset started the count over.
+            .unwrap_or(session.stats.instructions);
+        self.seen_instructions = session.stats.instructions;
+        self.frames += 1;
+        self.instructions += instructions;
+
+        let window = &mut self.window;
+        window.frames += 1;
+        window.instructions += instructions;
+        // The time `run` measured for the frame before this one.
+        window.frame_time += session.frame_time;
+        window.frame_time_max = window.frame_time_max.max(session.frame_time);
+
+        let now = Instant::now();
+        let elapsed = now - window.start;
+        if elapsed >= WINDOW {
+            self.rates = window.rates(elapsed);
+            self.window = Window::new(now);
+        }
+        if self.log_every.is_some_and(|every| now - self.last_log >= every) {
+            self.last_log = now;
+            info!("{}", self.log_line());
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Experimental two player lockstep over UDP.
+//!
+//! Both peers run the same ROM with the same seed and exchange their held
+//! keys once per frame. A frame only runs once the peer's keys for it have
+//! arrived, and both sides apply the union of the two key sets, so the two
+//! machines execute identically. Each input packet repeats the previous
+//! frame's keys, which covers a single lost packet without a round trip.
+//!
+//! The guest keeps sending `HELLO` with a hash of its ROM until the host
+//! answers with `WELCOME` and the seed to use. Pausing or resetting only one
+//! side is not synchronised and will stall or desync the session.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use session::{Remote, Session};
+
+const MAGIC: &[u8; 4] = b"R8NP";
+const HELLO: u8 = 0;
+const WELCOME: u8 = 1;
+const REJECT: u8 = 2;
+const INPUT: u8 = 3;
+
+/// How long to wait for a packet before sending ours again.
+const RESEND_INTERVAL: Duration = Duration::from_millis(50);
+/// How long the peer may stay silent before netplay is abandoned.
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum Packet {
+    Hello { rom_hash: u64 },
+    Welcome { seed: u64 },
+    Reject,
+    Input { frame: u32, keys: u16, prev_keys: u16 },
+}
+
+impl Packet {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        match *self {
+            Packet::Hello { rom_hash } => {
+                out.push(HELLO);
+                out.extend_from_slice(&rom_hash.to_be_bytes());
+            },
+            Packet::Welcome { seed } => {
+                out.push(WELCOME);
+                out.extend_from_slice(&seed.to_be_bytes());
+            },
+            Packet::Reject => out.push(REJECT),
+            Packet::Input { frame, keys, prev_keys } => {
+                out.push(INPUT);
+                out.extend_from_slice(&frame.to_be_bytes());
+                out.extend_from_slice(&keys.to_be_bytes());
+                out.extend_from_slice(&prev_keys.to_be_bytes());
+            },
+        }
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Packet> {
+        if bytes.len() < 5 || &bytes[..4] != MAGIC {
+            return None;
+        }
+        let body = &bytes[5..];
+        let u64_at = |at: usize| body.get(at..at + 8).map(|b| {
+            let mut buf = [0; 8];
+            buf.copy_from_slice(b);
+            u64::from_be_bytes(buf)
+        });
+        match bytes[4] {
+            HELLO => u64_at(0).map(|rom_hash| Packet::Hello { rom_hash }),
+            WELCOME => u64_at(0).map(|seed| Packet::Welcome { seed }),
+            REJECT => Some(Packet::Reject),
+            INPUT if body.len() >= 8 => Some(Packet::Input {
+                frame: u32::from_be_bytes([body[0], body[1], body[2], body[3]]),
+                keys: u16::from_be_bytes([body[4], body[5]]),
+                prev_keys: u16::from_be_bytes([body[6], body[7]]),
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "netplay peer did not answer")
+}
+
+pub struct Netplay {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    /// The seed handed out during the handshake, resent if the guest missed it.
+    seed: u64,
+    /// The next frame to run.
+    frame: u32,
+    /// Keys sent for the previous frame.
+    prev_keys: u16,
+    /// Peer keys that arrived ahead of the frame they belong to.
+    pending: HashMap<u32, u16>,
+    connected: bool,
+}
+
+impl Netplay {
+    fn new(socket: UdpSocket, peer: SocketAddr, seed: u64) -> io::Result<Self> {
+        socket.set_read_timeout(Some(RESEND_INTERVAL))?;
+        Ok(Netplay {
+            socket, peer, seed,
+            frame: 0,
+            prev_keys: 0,
+            pending: HashMap::new(),
+            connected: true,
+        })
+    }
+
+    /// The seed both machines must use.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Waits for a guest on `port` running the ROM with hash `rom_hash`.
+    pub fn host(port: u16, rom_hash: u64, seed: u64) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        info!("waiting for a guest on port {}", port);
+
+        let mut buf = [0; 64];
+        loop {
+            let (len, peer) = socket.recv_from(&mut buf)?;
+            if let Some(Packet::Hello { rom_hash: theirs }) = Packet::decode(&buf[..len]) {
+                if theirs != rom_hash {
+                    warn!("{} runs a different ROM, rejecting", peer);
+                    socket.send_to(&Packet::Reject.encode(), peer)?;
+                    continue;
+                }
+                socket.send_to(&Packet::Welcome { seed }.encode(), peer)?;
+                info!("{} joined", peer);
+                return Netplay::new(socket, peer, seed);
+            }
+        }
+    }
+
+    /// Joins a host at `addr`, returning once it accepted the ROM.
+    pub fn connect(addr: &str, rom_hash: u64) -> io::Result<Self> {
+        let peer = addr.to_socket_addrs()?.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_read_timeout(Some(RESEND_INTERVAL))?;
+
+        let started = Instant::now();
+        let mut buf = [0; 64];
+        while started.elapsed() < PEER_TIMEOUT {
+            socket.send_to(&Packet::Hello { rom_hash }.encode(), peer)?;
+            match socket.recv_from(&mut buf) {
+                Ok((len, from)) if from == peer => match Packet::decode(&buf[..len]) {
+                    Some(Packet::Welcome { seed }) => {
+                        info!("joined {}", peer);
+                        return Netplay::new(socket, peer, seed);
+                    },
+                    Some(Packet::Reject) => return Err(io::Error::new(
+                        io::ErrorKind::InvalidData, "host runs a different ROM")),
+                    _ => {},
+                },
+                Ok(_) => {},
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock
+                    || e.kind() == io::ErrorKind::TimedOut => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Err(timed_out())
+    }
+
+    /// Sends our keys for the current frame and blocks until the peer's
+    /// keys for it arrive.
+    fn exchange(&mut self, keys: u16) -> io::Result<u16> {
+        let packet = Packet::Input { frame: self.frame, keys, prev_keys: self.prev_keys }.encode();
+        let mut buf = [0; 64];
+        let mut last_heard = Instant::now();
+
+        loop {
+            // Send before checking, the peer may still be waiting on us.
+            self.socket.send_to(&packet, self.peer)?;
+            if let Some(theirs) = self.pending.remove(&self.frame) {
+                return Ok(theirs);
+            }
+
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) if from == self.peer => {
+                    last_heard = Instant::now();
+                    match Packet::decode(&buf[..len]) {
+                        Some(Packet::Input { frame, keys, prev_keys }) => {
+                            if frame >= self.frame {
+                                self.pending.insert(frame, keys);
+                            }
+                            if frame > self.frame {
+                                self.pending.entry(frame - 1).or_insert(prev_keys);
+                            }
+                        },
+                        // The guest missed our welcome.
+                        Some(Packet::Hello { .. }) => {
+                            self.socket.send_to(&Packet::Welcome { seed: self.seed }.encode(), from)?;
+                        },
+                        _ => {},
+                    }
+                },
+                Ok(_) => {},
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock
+                    || e.kind() == io::ErrorKind::TimedOut => {
+                    if last_heard.elapsed() > PEER_TIMEOUT {
+                        return Err(timed_out());
+                    }
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Remote for Netplay {
+    fn poll(&mut self, session: &mut Session) {
+        session.lockstep = self.connected;
+        // Only frames that actually run are numbered, see the module docs.
+        if !self.connected || session.paused {
+            return;
+        }
+        let keys = session.local_keys();
+        match self.exchange(keys) {
+            Ok(theirs) => {
+                session.remote_keys = theirs;
+                self.prev_keys = keys;
+                self.frame += 1;
+            },
+            Err(e) => {
+                warn!("{}, continuing alone", e);
+                session.remote_keys = 0;
+                session.lockstep = false;
+                self.connected = false;
+            },
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Callbacks for code that follows the machine as it runs.
+//!
+//! Recorders, scripts and debug UIs implement `EmuObserver` and register it
+//! with `Chip8::add_observer` instead of wrapping the execution loop. Every
+//! callback gets the machine as it is right after the event, and does
+//! nothing unless overridden. Registering needs `std`.
+
+use core::ops::Range;
+
+use chip8::Chip8;
+use instruction::Instruction;
+use keys::KeyEvent;
+
+/// Something told about what the machine does.
+pub trait EmuObserver {
+    /// The instruction fetched from `pc` ran.
+    fn on_instruction(&mut self, _chip: &Chip8, _pc: usize, _instruction: Instruction) {}
+
+    /// An instruction read `addrs` as data: a sprite, registers or an
+    /// audio pattern.
+    fn on_read(&mut self, _chip: &Chip8, _addrs: Range<usize>) {}
+
+    /// An instruction wrote `addrs`: BCD digits or registers.
+    fn on_write(&mut self, _chip: &Chip8, _addrs: Range<usize>) {}
+
+    /// `tick_timers` presented a frame.
+    fn on_frame(&mut self, _chip: &Chip8) {}
+
+    /// A DXYN ran, with VX, VY, N and whether it unset any pixel.
+    fn on_draw(&mut self, _chip: &Chip8, _x: u8, _y: u8, _rows: u8, _collision: bool) {}
+
+    /// The sound timer started running.
+    fn on_sound_start(&mut self, _chip: &Chip8) {}
+
+    /// The sound timer ran out or was stopped.
+    fn on_sound_stop(&mut self, _chip: &Chip8) {}
+
+    /// FX0A started waiting for a key to go into VX.
+    fn on_key_wait(&mut self, _chip: &Chip8, _x: u8) {}
+
+    /// A key event queued with `Chip8::push_key` took effect. Recording
+    /// these and pushing them again at the same frames replays the input.
+    fn on_key(&mut self, _chip: &Chip8, _event: KeyEvent) {}
+}
@@ -0,0 +1,921 @@
+//! Compiler for Octo, the high-level assembly language most CHIP-8 programs
+//! are written in nowadays.
+//!
+//! `compile` takes the source of one `.8o` file and gives back the ROM, its
+//! labels and the source line each statement starts at, which is what a
+//! symbol file and a source map need. It knows the language Octo documents:
+//! registers and `:alias`, labels, `:const`, `:macro`, `:calc` and `{ }`
+//! expressions, `:byte`, `:pointer`, `:call`, `:org`, `:next`, `:unpack`,
+//! `if ... then`, `if ... begin ... else ... end`, `loop ... while ...
+//! again` and the SCHIP and XO-CHIP statements. `:breakpoint` and
+//! `:monitor` belong to Octo's debugger and are skipped, `:stringmode` and
+//! `:assert` are not supported.
+//!
+//! As in Octo, execution starts with a jump to `main`, left out when `main`
+//! is the first thing in the program, and comparisons with `<`, `>`, `<=`
+//! and `>=` go through VF. Expressions are evaluated right to left without
+//! operator precedence, so `{ 2 * 3 + 1 }` is 8. They only see constants
+//! and labels defined above them.
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+
+use instruction::{Instruction, LONG_PREFIX};
+use {FLAG, PROGRAM_START, XO_MEMORY_SIZE};
+
+/// Macro expansions a program may do, to stop a macro that expands to
+/// itself.
+const MAX_EXPANSIONS: usize = 100_000;
+
+/// Operators of `{ }` expressions taking two operands.
+const BINARY: [&str; 19] = [
+    "+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>", "pow", "min", "max", "<", ">", "<=", ">=", "==", "!=",
+];
+
+/// A compiled program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// The bytes to load at `PROGRAM_START`.
+    pub rom: Vec<u8>,
+    pub labels: BTreeMap<String, usize>,
+    /// The source line, counted from 1, of the statement the code at each
+    /// address starts. Code from a macro belongs to the line using it.
+    pub lines: BTreeMap<usize, usize>,
+}
+
+/// Why a program does not compile, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    /// Counted from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for CompileError {}
+
+/// Compiles Octo source, see the module documentation.
+pub fn compile(source: &str) -> Result<Program, CompileError> {
+    let mut tokens = Vec::new();
+    for (n, line) in source.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or("");
+        tokens.extend(code.split_whitespace().map(|text| Token { text: text.to_owned(), line: n + 1 }));
+    }
+    let mut compiler = Compiler {
+        tokens,
+        at: 0,
+        line: 1,
+        rom: Vec::new(),
+        here: PROGRAM_START,
+        labels: BTreeMap::new(),
+        constants: BTreeMap::new(),
+        aliases: BTreeMap::new(),
+        macros: BTreeMap::new(),
+        fixups: Vec::new(),
+        flow: Vec::new(),
+        lines: BTreeMap::new(),
+        statement: None,
+        expansions: 0,
+        preamble: true,
+    };
+    // Room for the jump to main.
+    compiler.emit_op(0x1000)?;
+    while compiler.at < compiler.tokens.len() {
+        compiler.statement()?;
+    }
+    compiler.finish()
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// A value that may name a label further down.
+enum Value {
+    Known(f64),
+    Forward(String),
+}
+
+/// How a label's address goes into code compiled before the label.
+#[derive(Debug, Clone, Copy)]
+enum Patch {
+    /// The low 12 bits of the word at the address.
+    Twelve,
+    /// The whole word.
+    Sixteen,
+    /// The byte, as `:unpack` loads v0: the nibble and the top 4 bits of a
+    /// 12 bit address, or the top byte of a 16 bit one.
+    High(Option<u8>),
+    /// The byte, as `:unpack` loads v1.
+    Low,
+}
+
+struct Fixup {
+    at: usize,
+    name: String,
+    line: usize,
+    patch: Patch,
+}
+
+/// A control structure waiting for its end.
+enum Flow {
+    /// The jump to patch to `else` or `end`.
+    Begin { jump: usize, line: usize },
+    Else { jump: usize, line: usize },
+    /// Where `again` jumps back to and the jumps out of each `while`.
+    Loop { start: usize, breaks: Vec<usize>, line: usize },
+}
+
+/// A condition as the instructions testing it: some that set VF up, then
+/// one skipping the next instruction unless it holds and one skipping it
+/// when it does.
+struct Condition {
+    setup: Vec<u16>,
+    skip_unless: u16,
+    skip_when: u16,
+}
+
+struct Compiler {
+    tokens: Vec<Token>,
+    at: usize,
+    /// The line of the last token read, for errors.
+    line: usize,
+    rom: Vec<u8>,
+    here: usize,
+    labels: BTreeMap<String, usize>,
+    constants: BTreeMap<String, f64>,
+    aliases: BTreeMap<String, u8>,
+    macros: BTreeMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    flow: Vec<Flow>,
+    lines: BTreeMap<usize, usize>,
+    /// The line of the statement being compiled until its first byte is.
+    statement: Option<usize>,
+    expansions: usize,
+    /// Whether the program still starts with the jump to main.
+    preamble: bool,
+}
+
+fn number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    } as f64;
+    Some(if negative { -value } else { value })
+}
+
+impl Compiler {
+    fn error(&self, message: String) -> CompileError {
+        CompileError { line: self.line, message }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.at).map(|token| token.text.as_str())
+    }
+
+    fn next(&mut self) -> Result<String, CompileError> {
+        let token = self.tokens.get(self.at).cloned().ok_or_else(|| self.error("unexpected end of the program".to_owned()))?;
+        self.at += 1;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), CompileError> {
+        let text = self.next()?;
+        if text != expected {
+            return Err(self.error(format!("expected '{}', found '{}'", expected, text)));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<(), CompileError> {
+        if let Some(line) = self.statement.take() {
+            self.lines.insert(self.here, line);
+        }
+        for &byte in bytes {
+            if !(PROGRAM_START..XO_MEMORY_SIZE).contains(&self.here) {
+                return Err(self.error(format!("code at {:04X} is outside the program", self.here)));
+            }
+            let at = self.here - PROGRAM_START;
+            if self.rom.len() <= at {
+                self.rom.resize(at + 1, 0);
+            }
+            self.rom[at] = byte;
+            self.here += 1;
+        }
+        Ok(())
+    }
+
+    fn emit_op(&mut self, op: u16) -> Result<(), CompileError> {
+        self.emit(&op.to_be_bytes())
+    }
+
+    fn emit_instruction(&mut self, instruction: Instruction) -> Result<(), CompileError> {
+        self.emit_op(instruction.encode())
+    }
+
+    fn register_of(&self, text: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(text) {
+            return Some(register);
+        }
+        let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn register(&mut self) -> Result<u8, CompileError> {
+        let text = self.next()?;
+        self.register_of(&text).ok_or_else(|| self.error(format!("expected a register, found '{}'", text)))
+    }
+
+    /// The value of a number, constant or label defined so far.
+    fn known(&self, text: &str) -> Option<f64> {
+        number(text)
+            .or_else(|| self.constants.get(text).cloned())
+            .or_else(|| self.labels.get(text).map(|&addr| addr as f64))
+    }
+
+    fn value(&mut self) -> Result<Value, CompileError> {
+        let text = self.next()?;
+        if text == "{" {
+            return self.expression().map(Value::Known);
+        }
+        if let Some(value) = self.known(&text) {
+            return Ok(Value::Known(value));
+        }
+        if self.register_of(&text).is_some() || text.starts_with(':') {
+            return Err(self.error(format!("expected a value, found '{}'", text)));
+        }
+        Ok(Value::Forward(text))
+    }
+
+    /// A value that has to be known here, not a label below.
+    fn known_value(&mut self) -> Result<f64, CompileError> {
+        match self.value()? {
+            Value::Known(value) => Ok(value),
+            Value::Forward(name) => Err(self.error(format!("'{}' is not defined", name))),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, CompileError> {
+        let value = self.known_value()?.floor();
+        if !(-128.0..=255.0).contains(&value) {
+            return Err(self.error(format!("{} does not fit in a byte", value)));
+        }
+        Ok(value as i64 as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u8, CompileError> {
+        let value = self.known_value()?.floor();
+        if !(0.0..=15.0).contains(&value) {
+            return Err(self.error(format!("{} does not fit in 4 bits", value)));
+        }
+        Ok(value as u8)
+    }
+
+    fn address(&self, value: f64, bits: u32) -> Result<usize, CompileError> {
+        let value = value.floor();
+        if value < 0.0 || value >= (1u32 << bits) as f64 {
+            return Err(self.error(format!("address {} does not fit in {} bits", value, bits)));
+        }
+        Ok(value as usize)
+    }
+
+    fn fixup(&mut self, at: usize, name: String, patch: Patch) {
+        self.fixups.push(Fixup { at, name, line: self.line, patch });
+    }
+
+    /// Emits `op` with a 12 bit address in its low bits.
+    fn emit_address(&mut self, op: u16) -> Result<(), CompileError> {
+        match self.value()? {
+            Value::Known(value) => {
+                let addr = self.address(value, 12)?;
+                self.emit_op(op | addr as u16)
+            },
+            Value::Forward(name) => {
+                let at = self.here;
+                self.fixup(at, name, Patch::Twelve);
+                self.emit_op(op)
+            },
+        }
+    }
+
+    /// Emits a 16 bit address.
+    fn emit_long(&mut self) -> Result<(), CompileError> {
+        match self.value()? {
+            Value::Known(value) => {
+                let addr = self.address(value, 16)?;
+                self.emit_op(addr as u16)
+            },
+            Value::Forward(name) => {
+                let at = self.here;
+                self.fixup(at, name, Patch::Sixteen);
+                self.emit_op(0)
+            },
+        }
+    }
+
+    /// Points the jump at `at` to `target`.
+    fn patch_jump(&mut self, at: usize, target: usize) -> Result<(), CompileError> {
+        if target > 0xFFF {
+            return Err(self.error(format!("cannot jump to {:04X}, past 12 bits", target)));
+        }
+        let op = Instruction::Jump { addr: target as u16 }.encode().to_be_bytes();
+        self.rom[at - PROGRAM_START..at - PROGRAM_START + 2].copy_from_slice(&op);
+        Ok(())
+    }
+
+    fn define(&mut self, name: String, addr: usize) -> Result<(), CompileError> {
+        if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+            return Err(self.error(format!("'{}' is already defined", name)));
+        }
+        if self.register_of(&name).is_some() || number(&name).is_some() {
+            return Err(self.error(format!("'{}' cannot be a name", name)));
+        }
+        // Nothing before main, so the program can start with it.
+        if name == "main" && self.preamble && self.here == PROGRAM_START + 2 && self.rom.len() == 2 {
+            self.preamble = false;
+            self.rom.clear();
+            self.here = PROGRAM_START;
+            for label in self.labels.values_mut().filter(|label| **label == PROGRAM_START + 2) {
+                *label = PROGRAM_START;
+            }
+            self.labels.insert(name, PROGRAM_START);
+            return Ok(());
+        }
+        self.labels.insert(name, addr);
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<String, CompileError> {
+        let name = self.next()?;
+        if name.starts_with(':') || name == "{" || name == "}" {
+            return Err(self.error(format!("expected a name, found '{}'", name)));
+        }
+        Ok(name)
+    }
+
+    fn statement(&mut self) -> Result<(), CompileError> {
+        use instruction::Instruction::*;
+
+        let text = self.next()?;
+        self.statement = Some(self.line);
+        match text.as_str() {
+            ":" => {
+                let name = self.name()?;
+                let here = self.here;
+                self.define(name, here)?;
+            },
+            ":next" => {
+                let name = self.name()?;
+                let here = self.here;
+                self.define(name, here + 1)?;
+            },
+            ":const" => {
+                let name = self.name()?;
+                let value = self.known_value()?;
+                if self.labels.contains_key(&name) || self.constants.insert(name.clone(), value).is_some() {
+                    return Err(self.error(format!("'{}' is already defined", name)));
+                }
+            },
+            ":calc" => {
+                let name = self.name()?;
+                self.expect("{")?;
+                let value = self.expression()?;
+                if self.labels.contains_key(&name) {
+                    return Err(self.error(format!("'{}' is already defined", name)));
+                }
+                self.constants.insert(name, value);
+            },
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                if self.register_of(&name).is_some() && !self.aliases.contains_key(&name) {
+                    return Err(self.error(format!("'{}' is a register already", name)));
+                }
+                self.aliases.insert(name, register);
+            },
+            ":macro" => self.define_macro()?,
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit(&[byte])?;
+            },
+            ":pointer" => self.emit_long()?,
+            ":call" => self.emit_address(0x2000)?,
+            ":org" => {
+                let value = self.known_value()?;
+                self.here = self.address(value, 16)?;
+            },
+            ":unpack" => self.unpack()?,
+            ":breakpoint" => { self.name()?; },
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            },
+            ":stringmode" | ":assert" => return Err(self.error(format!("{} is not supported", text))),
+            "return" | ";" => self.emit_instruction(Ret)?,
+            "clear" => self.emit_instruction(Cls)?,
+            "hires" => self.emit_instruction(HighRes)?,
+            "lores" => self.emit_instruction(LowRes)?,
+            "exit" => self.emit_instruction(Exit)?,
+            "scroll-left" => self.emit_instruction(ScrollLeft)?,
+            "scroll-right" => self.emit_instruction(ScrollRight)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit_instruction(ScrollDown { n })?;
+            },
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit_instruction(ScrollUp { n })?;
+            },
+            "audio" => self.emit_instruction(Audio)?,
+            "plane" => {
+                let n = self.nibble()?;
+                if n > 3 {
+                    return Err(self.error(format!("there is no plane {}", n)));
+                }
+                self.emit_op(0xF001 | (n as u16) << 8)?;
+            },
+            "jump" => self.emit_address(0x1000)?,
+            "jump0" => self.emit_address(0xB000)?,
+            "native" => self.emit_address(0x0000)?,
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit_instruction(Draw { x, y, n })?;
+            },
+            "save" | "load" => {
+                let x = self.register()?;
+                let instruction = if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    if text == "save" { StoreRange { x, y } } else { LoadRange { x, y } }
+                } else if text == "save" {
+                    Store { x }
+                } else {
+                    Load { x }
+                };
+                self.emit_instruction(instruction)?;
+            },
+            "bcd" => {
+                let x = self.register()?;
+                self.emit_instruction(Bcd { x })?;
+            },
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit_instruction(SaveFlags { x })?;
+            },
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit_instruction(LoadFlags { x })?;
+            },
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let instruction = match text.as_str() {
+                    "delay" => SetDelay { x },
+                    "buzzer" => SetSound { x },
+                    _ => Pitch { x },
+                };
+                self.emit_instruction(instruction)?;
+            },
+            "i" => self.index()?,
+            "if" => self.branch()?,
+            "else" => match self.flow.pop() {
+                Some(Flow::Begin { jump, line }) => {
+                    let at = self.here;
+                    self.emit_op(0x1000)?;
+                    let here = self.here;
+                    self.patch_jump(jump, here)?;
+                    self.flow.push(Flow::Else { jump: at, line });
+                },
+                _ => return Err(self.error("'else' without 'if ... begin'".to_owned())),
+            },
+            "end" => match self.flow.pop() {
+                Some(Flow::Begin { jump, .. }) | Some(Flow::Else { jump, .. }) => {
+                    let here = self.here;
+                    self.patch_jump(jump, here)?;
+                },
+                _ => return Err(self.error("'end' without 'begin'".to_owned())),
+            },
+            "loop" => {
+                let start = self.here;
+                let line = self.line;
+                self.flow.push(Flow::Loop { start, breaks: Vec::new(), line });
+            },
+            "while" => {
+                let condition = self.condition()?;
+                for op in condition.setup {
+                    self.emit_op(op)?;
+                }
+                self.emit_op(condition.skip_when)?;
+                let at = self.here;
+                self.emit_op(0x1000)?;
+                match self.flow.iter_mut().rev().find_map(|flow| match *flow {
+                    Flow::Loop { ref mut breaks, .. } => Some(breaks),
+                    _ => None,
+                }) {
+                    Some(breaks) => breaks.push(at),
+                    None => return Err(self.error("'while' outside a loop".to_owned())),
+                }
+            },
+            "again" => match self.flow.pop() {
+                Some(Flow::Loop { start, breaks, .. }) => {
+                    let at = self.here;
+                    self.emit_op(0x1000)?;
+                    self.patch_jump(at, start)?;
+                    let here = self.here;
+                    for jump in breaks {
+                        self.patch_jump(jump, here)?;
+                    }
+                },
+                _ => return Err(self.error("'again' without 'loop'".to_owned())),
+            },
+            "then" | "begin" | "{" | "}" => return Err(self.error(format!("unexpected '{}'", text))),
+            _ => {
+                if let Some(x) = self.register_of(&text) {
+                    return self.assignment(x);
+                }
+                if let Some(definition) = self.macros.get(&text).cloned() {
+                    return self.expand(&text, definition);
+                }
+                if let Some(&addr) = self.labels.get(&text) {
+                    let addr = self.address(addr as f64, 12)?;
+                    return self.emit_instruction(Call { addr: addr as u16 });
+                }
+                if self.known(&text).is_some() {
+                    self.at -= 1;
+                    let byte = self.byte()?;
+                    return self.emit(&[byte]);
+                }
+                if text.starts_with(':') {
+                    return Err(self.error(format!("unknown directive '{}'", text)));
+                }
+                // A subroutine further down.
+                let at = self.here;
+                self.fixup(at, text, Patch::Twelve);
+                self.emit_op(0x2000)?;
+            },
+        }
+        Ok(())
+    }
+
+    /// `vX OP ...`, the register already read.
+    fn assignment(&mut self, x: u8) -> Result<(), CompileError> {
+        use instruction::Instruction::*;
+
+        let op = self.next()?;
+        let y = self.peek().and_then(|text| self.register_of(text));
+        if y.is_some() {
+            self.next()?;
+        }
+        let instruction = match (op.as_str(), y) {
+            (":=", Some(y)) => Move { x, y },
+            (":=", None) => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    Random { x, nn: self.byte()? }
+                },
+                Some("key") => {
+                    self.next()?;
+                    WaitKey { x }
+                },
+                Some("delay") => {
+                    self.next()?;
+                    ReadDelay { x }
+                },
+                _ => LoadImm { x, nn: self.byte()? },
+            },
+            ("+=", Some(y)) => Add { x, y },
+            ("+=", None) => AddImm { x, nn: self.byte()? },
+            ("-=", Some(y)) => Sub { x, y },
+            ("-=", None) => AddImm { x, nn: self.byte()?.wrapping_neg() },
+            ("=-", Some(y)) => SubN { x, y },
+            ("|=", Some(y)) => Or { x, y },
+            ("&=", Some(y)) => And { x, y },
+            ("^=", Some(y)) => Xor { x, y },
+            (">>=", Some(y)) => Shr { x, y },
+            ("<<=", Some(y)) => Shl { x, y },
+            ("=-", None) | ("|=", None) | ("&=", None) | ("^=", None) | (">>=", None) | ("<<=", None) => {
+                let text = self.next()?;
+                return Err(self.error(format!("{} needs a register, found '{}'", op, text)));
+            },
+            _ => return Err(self.error(format!("unknown operator '{}'", op))),
+        };
+        self.emit_instruction(instruction)
+    }
+
+    /// `i := ...` or `i += vX`.
+    fn index(&mut self) -> Result<(), CompileError> {
+        use instruction::Instruction::*;
+
+        let op = self.next()?;
+        match (op.as_str(), self.peek()) {
+            (":=", Some("hex")) => {
+                self.next()?;
+                let x = self.register()?;
+                self.emit_instruction(Font { x })
+            },
+            (":=", Some("bighex")) => {
+                self.next()?;
+                let x = self.register()?;
+                self.emit_instruction(BigFont { x })
+            },
+            (":=", Some("long")) => {
+                self.next()?;
+                self.emit_op(LONG_PREFIX)?;
+                self.emit_long()
+            },
+            (":=", _) => self.emit_address(0xA000),
+            ("+=", _) => {
+                let x = self.register()?;
+                self.emit_instruction(AddIndex { x })
+            },
+            _ => Err(self.error(format!("unknown operator '{}' for i", op))),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, CompileError> {
+        let x = self.register()? as u16;
+        let op = self.next()?;
+        let skip = |op: u16, operand: u16| op << 12 | x << 8 | operand;
+        let condition = match op.as_str() {
+            "key" | "-key" => {
+                let (unless, when) = (skip(0xE, 0xA1), skip(0xE, 0x9E));
+                if op == "key" { (Vec::new(), unless, when) } else { (Vec::new(), when, unless) }
+            },
+            "==" | "!=" => {
+                let (unless, when) = match self.peek().and_then(|text| self.register_of(text)) {
+                    Some(y) => {
+                        self.next()?;
+                        (skip(0x9, (y as u16) << 4), skip(0x5, (y as u16) << 4))
+                    },
+                    None => {
+                        let nn = self.byte()? as u16;
+                        (skip(0x4, nn), skip(0x3, nn))
+                    },
+                };
+                if op == "==" { (Vec::new(), unless, when) } else { (Vec::new(), when, unless) }
+            },
+            "<" | ">" | "<=" | ">=" => {
+                if x == FLAG as u16 {
+                    return Err(self.error("comparisons go through vf, so they cannot compare vf".to_owned()));
+                }
+                let load = match self.peek().and_then(|text| self.register_of(text)) {
+                    Some(y) => {
+                        self.next()?;
+                        0x8F00 | (y as u16) << 4
+                    },
+                    None => 0x6F00 | self.byte()? as u16,
+                };
+                // VF := VX - operand for < and >=, operand - VX for > and
+                // <=, leaving VF 1 unless it borrowed.
+                let subtract = if op == "<" || op == ">=" { 0x8F07 } else { 0x8F05 } | x << 4;
+                let flag = if op == "<" || op == ">" { 0 } else { 1 };
+                (vec![load, subtract], 0x4F00 | flag, 0x3F00 | flag)
+            },
+            _ => return Err(self.error(format!("unknown comparison '{}'", op))),
+        };
+        Ok(Condition { setup: condition.0, skip_unless: condition.1, skip_when: condition.2 })
+    }
+
+    /// `if CONDITION then STATEMENT` or `if CONDITION begin`.
+    fn branch(&mut self) -> Result<(), CompileError> {
+        let line = self.line;
+        let condition = self.condition()?;
+        for &op in &condition.setup {
+            self.emit_op(op)?;
+        }
+        match self.next()?.as_str() {
+            "then" => self.emit_op(condition.skip_unless),
+            "begin" => {
+                self.emit_op(condition.skip_when)?;
+                let jump = self.here;
+                self.flow.push(Flow::Begin { jump, line });
+                self.emit_op(0x1000)
+            },
+            text => Err(self.error(format!("expected 'then' or 'begin', found '{}'", text))),
+        }
+    }
+
+    /// `:unpack NIBBLE NAME` or `:unpack long NAME`: v0 and v1 get the
+    /// address, for `i := long` or self-modifying code to use.
+    fn unpack(&mut self) -> Result<(), CompileError> {
+        let nibble = if self.peek() == Some("long") {
+            self.next()?;
+            None
+        } else {
+            Some(self.nibble()?)
+        };
+        let (high, low) = match self.value()? {
+            Value::Known(value) => {
+                let addr = self.address(value, if nibble.is_some() { 12 } else { 16 })?;
+                (high_byte(nibble, addr), addr as u8)
+            },
+            Value::Forward(name) => {
+                let at = self.here;
+                self.fixup(at + 1, name.clone(), Patch::High(nibble));
+                self.fixup(at + 3, name, Patch::Low);
+                (0, 0)
+            },
+        };
+        self.emit_op(0x6000 | high as u16)?;
+        self.emit_op(0x6100 | low as u16)
+    }
+
+    /// `:macro NAME PARAMS... { BODY }`.
+    fn define_macro(&mut self) -> Result<(), CompileError> {
+        let name = self.name()?;
+        let mut params = Vec::new();
+        loop {
+            let text = self.next()?;
+            if text == "{" {
+                break;
+            }
+            params.push(text);
+        }
+        let mut body = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self.tokens.get(self.at).cloned().ok_or_else(|| self.error(format!("macro '{}' has no closing '}}'", name)))?;
+            self.at += 1;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => break,
+                "}" => depth -= 1,
+                _ => {},
+            }
+            body.push(token);
+        }
+        self.macros.insert(name, Macro { params, body });
+        Ok(())
+    }
+
+    /// Replaces a use of a macro with its body.
+    fn expand(&mut self, name: &str, definition: Macro) -> Result<(), CompileError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(self.error(format!("macro '{}' expands forever", name)));
+        }
+        let mut args = Vec::with_capacity(definition.params.len());
+        for _ in &definition.params {
+            args.push(self.next()?);
+        }
+        let line = self.line;
+        let body: Vec<Token> = definition.body.iter().map(|token| {
+            let text = match definition.params.iter().position(|param| *param == token.text) {
+                Some(n) => args[n].clone(),
+                None => token.text.clone(),
+            };
+            Token { text, line }
+        }).collect();
+        let at = self.at;
+        self.tokens.splice(at..at, body);
+        Ok(())
+    }
+
+    /// The rest of a `{ }` expression, the `{` already read.
+    fn expression(&mut self) -> Result<f64, CompileError> {
+        let value = self.calc()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    fn calc(&mut self) -> Result<f64, CompileError> {
+        let left = self.term()?;
+        let op = match self.peek() {
+            Some(op) if BINARY.contains(&op) => op.to_owned(),
+            _ => return Ok(left),
+        };
+        self.next()?;
+        let right = self.calc()?;
+        let int = |value: f64| value as i64;
+        let truth = |holds: bool| if holds { 1.0 } else { 0.0 };
+        Ok(match op.as_str() {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" | "%" if right == 0.0 => return Err(self.error("division by zero".to_owned())),
+            "/" => left / right,
+            "%" => left % right,
+            "&" => (int(left) & int(right)) as f64,
+            "|" => (int(left) | int(right)) as f64,
+            "^" => (int(left) ^ int(right)) as f64,
+            "<<" => (int(left) << (int(right) & 63)) as f64,
+            ">>" => (int(left) >> (int(right) & 63)) as f64,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => truth(left < right),
+            ">" => truth(left > right),
+            "<=" => truth(left <= right),
+            ">=" => truth(left >= right),
+            "==" => truth(left == right),
+            _ => truth(left != right),
+        })
+    }
+
+    fn term(&mut self) -> Result<f64, CompileError> {
+        let text = self.next()?;
+        if let Some(value) = self.known(&text) {
+            return Ok(value);
+        }
+        Ok(match text.as_str() {
+            "(" => {
+                let value = self.calc()?;
+                self.expect(")")?;
+                value
+            },
+            "HERE" => self.here as f64,
+            "PI" => ::std::f64::consts::PI,
+            "E" => ::std::f64::consts::E,
+            "-" => -self.term()?,
+            "~" => !(self.term()? as i64) as f64,
+            "!" => if self.term()? == 0.0 { 1.0 } else { 0.0 },
+            "abs" => self.term()?.abs(),
+            "sqrt" => self.term()?.sqrt(),
+            "sin" => self.term()?.sin(),
+            "cos" => self.term()?.cos(),
+            "floor" => self.term()?.floor(),
+            "ceil" => self.term()?.ceil(),
+            "@" => {
+                let addr = self.term()?;
+                let addr = self.address(addr, 16)?;
+                addr.checked_sub(PROGRAM_START).and_then(|at| self.rom.get(at)).cloned().unwrap_or(0) as f64
+            },
+            _ => return Err(self.error(format!("'{}' is not defined", text))),
+        })
+    }
+
+    fn finish(mut self) -> Result<Program, CompileError> {
+        if let Some(flow) = self.flow.last() {
+            let (line, message) = match *flow {
+                Flow::Begin { line, .. } | Flow::Else { line, .. } => (line, "'begin' without 'end'"),
+                Flow::Loop { line, .. } => (line, "'loop' without 'again'"),
+            };
+            return Err(CompileError { line, message: message.to_owned() });
+        }
+        if self.preamble {
+            let main = *self.labels.get("main").ok_or_else(|| CompileError {
+                line: self.line,
+                message: "the program has no 'main' label".to_owned(),
+            })?;
+            self.patch_jump(PROGRAM_START, main)?;
+        }
+        for fixup in &self.fixups {
+            self.line = fixup.line;
+            let addr = self.labels.get(&fixup.name).cloned()
+                .or_else(|| self.constants.get(&fixup.name).map(|&value| value as usize))
+                .ok_or_else(|| self.error(format!("'{}' is not defined", fixup.name)))?;
+            let at = fixup.at - PROGRAM_START;
+            match fixup.patch {
+                Patch::Twelve => {
+                    let addr = self.address(addr as f64, 12)
+                        .map_err(|_| self.error(format!("'{}' is at {:04X}, out of reach of 12 bits", fixup.name, addr)))?;
+                    self.rom[at] |= (addr >> 8) as u8;
+                    self.rom[at + 1] = addr as u8;
+                },
+                Patch::Sixteen => {
+                    self.rom[at..at + 2].copy_from_slice(&(addr as u16).to_be_bytes());
+                },
+                Patch::High(nibble) => self.rom[at] = high_byte(nibble, addr),
+                Patch::Low => self.rom[at] = addr as u8,
+            }
+        }
+        Ok(Program { rom: self.rom, labels: self.labels, lines: self.lines })
+    }
+}
+
+/// What `:unpack` loads v0 with.
+fn high_byte(nibble: Option<u8>, addr: usize) -> u8 {
+    match nibble {
+        Some(nibble) => nibble << 4 | (addr >> 8) as u8 & 0xF,
+        None => (addr >> 8) as u8,
+    }
+}
@@ -0,0 +1,124 @@
+//! Colors to show the screen in.
+//!
+//! A palette has a color for each value a pixel can take: 0 for the
+//! background, 1 for a lit pixel and, for XO-CHIP's two planes, 1 for the
+//! first plane alone, 2 for the second alone and 3 for both. Besides the
+//! default white on black there are palettes for accessibility:
+//!
+//! | Name            |                                                    |
+//! |-----------------|----------------------------------------------------|
+//! | `default`       | white on black, greys for the second plane         |
+//! | `high-contrast` | white, yellow and cyan on black                    |
+//! | `deuteranopia`  | orange, sky blue and yellow, apart for red-green   |
+//! | `protanopia`    | sky blue, yellow and white, with no reds at all    |
+//!
+//! Each keeps a contrast ratio of at least 4.5 between the background and
+//! every other color, the WCAG level for text. A palette may also be given
+//! as its colors, `#RRGGBB` separated by commas, two or four of them:
+//!
+//! ```
+//! use ruchip8::palette::Palette;
+//!
+//! let palette = Palette::parse("#101010,#f0f0f0").unwrap();
+//! assert_eq!(palette.color(1), [0xF0, 0xF0, 0xF0]);
+//! assert_eq!(Palette::parse("high-contrast"), Some(Palette::HIGH_CONTRAST));
+//! ```
+
+/// An sRGB color.
+pub type Rgb = [u8; 3];
+
+/// The contrast ratio below which a palette deserves a warning, WCAG's
+/// for graphics.
+pub const MIN_CONTRAST: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Background, first plane, second plane, both planes.
+    pub colors: [Rgb; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::DEFAULT
+    }
+}
+
+/// The built-in palettes, by name.
+pub const PALETTES: [(&str, Palette); 4] = [
+    ("default", Palette::DEFAULT),
+    ("high-contrast", Palette::HIGH_CONTRAST),
+    ("deuteranopia", Palette::DEUTERANOPIA),
+    ("protanopia", Palette::PROTANOPIA),
+];
+
+impl Palette {
+    pub const DEFAULT: Palette = Palette { colors: [[0x00, 0x00, 0x00], [0xFF, 0xFF, 0xFF], [0xAA, 0xAA, 0xAA], [0x77, 0x77, 0x77]] };
+    pub const HIGH_CONTRAST: Palette = Palette { colors: [[0x00, 0x00, 0x00], [0xFF, 0xFF, 0xFF], [0xFF, 0xFF, 0x00], [0x00, 0xC8, 0xFF]] };
+    /// The Okabe-Ito colors, told apart by eyes short of green cones.
+    pub const DEUTERANOPIA: Palette = Palette { colors: [[0x00, 0x00, 0x00], [0xE6, 0x9F, 0x00], [0x56, 0xB4, 0xE9], [0xF0, 0xE4, 0x42]] };
+    /// Red looks dark without red cones, so there is none.
+    pub const PROTANOPIA: Palette = Palette { colors: [[0x00, 0x00, 0x00], [0x56, 0xB4, 0xE9], [0xF0, 0xE4, 0x42], [0xFF, 0xFF, 0xFF]] };
+
+    /// A built-in palette by name, or colors as in the module
+    /// documentation. With two colors the planes both take the second.
+    pub fn parse(text: &str) -> Option<Palette> {
+        if let Some(&(_, palette)) = PALETTES.iter().find(|&&(name, _)| name == text) {
+            return Some(palette);
+        }
+        let mut colors = [[0; 3]; 4];
+        let mut count = 0;
+        for part in text.split(',') {
+            *colors.get_mut(count)? = parse_color(part.trim())?;
+            count += 1;
+        }
+        match count {
+            2 => Some(Palette { colors: [colors[0], colors[1], colors[1], colors[1]] }),
+            4 => Some(Palette { colors }),
+            _ => None,
+        }
+    }
+
+    /// The color of a pixel of value `pixel`, its low two bits.
+    pub fn color(&self, pixel: u8) -> Rgb {
+        self.colors[(pixel & 3) as usize]
+    }
+
+    /// The lowest contrast ratio between the background and another color.
+    #[cfg(feature = "std")]
+    pub fn min_contrast(&self) -> f32 {
+        self.colors[1..].iter().map(|&color| contrast_ratio(self.colors[0], color)).fold(f32::INFINITY, f32::min)
+    }
+}
+
+fn parse_color(text: &str) -> Option<Rgb> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// `#rrggbb`.
+#[cfg(feature = "std")]
+pub fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// The WCAG relative luminance, 0 for black to 1 for white.
+#[cfg(feature = "std")]
+pub fn luminance(color: Rgb) -> f32 {
+    let linear = |channel: u8| {
+        let c = channel as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * linear(color[0]) + 0.7152 * linear(color[1]) + 0.0722 * linear(color[2])
+}
+
+/// The WCAG contrast ratio of two colors, from 1 for the same color to 21
+/// for black on white.
+#[cfg(feature = "std")]
+pub fn contrast_ratio(a: Rgb, b: Rgb) -> f32 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
@@ -0,0 +1,133 @@
+//! Keeps part of memory across runs, for high scores.
+//!
+//! Only SCHIP games have RPL flags to keep a high score in, see `rpl`.
+//! Plenty of others keep theirs in memory and lose it when the emulator
+//! quits. `--persist START-END` names such a range, in hex and inclusive,
+//! and may be given more than once: the ranges are saved under `persist/`
+//! in the data directory, named after the ROM's hash, when the ROM is
+//! unloaded or the emulator quits, and written back over memory right
+//! after the same ROM is loaded again. Hard resets leave them be. The
+//! ranges apply to every ROM the session loads.
+
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use session::{Remote, Session};
+
+pub struct Persist {
+    dir: PathBuf,
+    ranges: Vec<RangeInclusive<usize>>,
+    /// The ROM the bytes below belong to.
+    rom: Option<u64>,
+    /// The ranges as of the last frame, one after the other.
+    bytes: Vec<u8>,
+}
+
+impl Persist {
+    pub fn new(data_dir: PathBuf, ranges: Vec<RangeInclusive<usize>>) -> Self {
+        Persist { dir: data_dir.join("persist"), ranges, rom: None, bytes: Vec::new() }
+    }
+
+    fn path(&self, rom: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", rom))
+    }
+
+    /// The ranges that fit in `memory`.
+    fn ranges<'a>(&'a self, memory: &'a [u8]) -> impl Iterator<Item = RangeInclusive<usize>> + 'a {
+        self.ranges.iter().filter(move |range| *range.end() < memory.len()).cloned()
+    }
+
+    fn read(&self, memory: &[u8]) -> Vec<u8> {
+        self.ranges(memory).flat_map(|range| memory[range].iter().copied()).collect()
+    }
+
+    fn save(&self) {
+        let rom = match self.rom {
+            Some(rom) => rom,
+            None => return,
+        };
+        let path = self.path(rom);
+        match fs::create_dir_all(&self.dir).and_then(|_| fs::write(&path, &self.bytes)) {
+            Ok(()) => debug!("saved persistent memory to {}", path.display()),
+            Err(e) => warn!("cannot write {}: {}", path.display(), e),
+        }
+    }
+
+    /// Writes `saved` over the ranges.
+    fn write(&self, session: &mut Session, saved: &[u8]) {
+        let ranges: Vec<RangeInclusive<usize>> = self.ranges(session.chip.memory()).collect();
+        let mut saved = saved;
+        for range in ranges {
+            let (bytes, rest) = saved.split_at(range.clone().count());
+            // The ranges lie within memory.
+            let _ = session.chip.write_memory(*range.start(), bytes);
+            saved = rest;
+        }
+    }
+
+    fn restore(&self, session: &mut Session, rom: u64) {
+        let path = self.path(rom);
+        let saved = match fs::read(&path) {
+            Ok(saved) => saved,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!("cannot read {}: {}", path.display(), e);
+                return;
+            },
+        };
+        if saved.len() != self.read(session.chip.memory()).len() {
+            warn!("{} was saved for other --persist ranges, leaving memory be", path.display());
+            return;
+        }
+        self.write(session, &saved);
+        debug!("restored persistent memory from {}", path.display());
+    }
+}
+
+impl Remote for Persist {
+    fn poll(&mut self, session: &mut Session) {
+        if session.rom_hash == self.rom {
+            // Remotes may have run frames of their own.
+            self.bytes = self.read(session.chip.memory());
+            return;
+        }
+        // The ROM before, as it was on its last frame.
+        self.save();
+        self.rom = session.rom_hash;
+        if let Some(rom) = self.rom {
+            self.restore(session, rom);
+        }
+        self.bytes = self.read(session.chip.memory());
+    }
+
+    fn frame(&mut self, session: &Session) {
+        if session.rom_hash == self.rom {
+            self.bytes = self.read(session.chip.memory());
+        }
+    }
+
+    fn close(&mut self, session: &Session) {
+        if session.rom_hash == self.rom {
+            self.bytes = self.read(session.chip.memory());
+        }
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruchip8::Chip8;
+
+    #[test]
+    fn writes_back_the_ranges_that_fit() {
+        let persist = Persist::new(PathBuf::new(), vec![0x300..=0x301, 0x310..=0x310, 0xFFFF..=0xFFFF]);
+        let mut session = Session::new(Chip8::new());
+        persist.write(&mut session, &[1, 2, 3]);
+        assert_eq!(&session.chip.memory()[0x300..0x302], &[1, 2]);
+        assert_eq!(session.chip.memory()[0x310], 3);
+        assert_eq!(persist.read(session.chip.memory()), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,263 @@
+//! Compiled-in plugins.
+//!
+//! A plugin is an entry in `PLUGINS`. It can add a subcommand, run as
+//! `ruchip8 NAME ARGS...` instead of the emulator, and a remote, enabled with
+//! `--plugin NAME[=ARG]`, which sees the session every frame like any other
+//! frontend. Tools such as exporters and visualizers live here instead of in
+//! the core crate.
+
+use std::fs;
+use std::path::Path;
+
+use ruchip8::palette::Palette;
+use ruchip8::{disasm, screenshot, Chip8, Display, PROGRAM_START, XO_MEMORY_SIZE};
+
+use compare;
+use coverage;
+use difftest;
+#[cfg(target_os = "linux")]
+use evdev;
+#[cfg(target_os = "linux")]
+use fbdev;
+use heatmap;
+use histogram;
+use info;
+use library;
+use session::{Remote, Session};
+#[cfg(unix)]
+use terminal;
+use validate;
+
+/// Runs a subcommand with the arguments after its name.
+pub type Command = fn(&[String]) -> Result<(), String>;
+/// Builds a remote from the text after `=`, if any.
+pub type RemoteFactory = fn(Option<&str>) -> Result<Box<dyn Remote>, String>;
+
+pub struct Plugin {
+    pub name: &'static str,
+    /// One line shown by `ruchip8 plugins`.
+    pub about: &'static str,
+    /// Runs `ruchip8 NAME ARGS...`.
+    pub command: Option<Command>,
+    /// Builds the remote for `--plugin NAME[=ARG]`.
+    pub remote: Option<RemoteFactory>,
+}
+
+pub static PLUGINS: &[Plugin] = &[
+    Plugin {
+        name: "compare",
+        about: "compare ROM[:QUIRK,...] ROM[:QUIRK,...]... runs machines side by side \
+                and reports where their screens diverge",
+        command: Some(compare::command),
+        remote: None,
+    },
+    Plugin {
+        name: "coverage",
+        about: "--plugin coverage[=FILE] writes which bytes of the ROM ran and which were \
+                read as data when the session ends, as a hexdump if FILE ends in .html",
+        command: None,
+        remote: Some(coverage::remote),
+    },
+    Plugin {
+        name: "difftest",
+        about: "difftest [--record FRAMES] ROM[:QUIRK,...] TRACE checks every instruction \
+                against a trace and reports the first that differs, or records one",
+        command: Some(difftest::command),
+        remote: None,
+    },
+    Plugin {
+        name: "disasm",
+        about: "disasm [--font] ROM prints the code of a ROM with comments on what each \
+                instruction does, its data tables as pixels and the font with --font",
+        command: Some(disasm_command),
+        remote: None,
+    },
+    #[cfg(target_os = "linux")]
+    Plugin {
+        name: "evdev",
+        about: "evdev lists the input devices, --plugin evdev[=DEVICE] takes keys from \
+                every keyboard or DEVICE without a window, on Linux",
+        command: Some(evdev::command),
+        remote: Some(evdev::remote),
+    },
+    #[cfg(target_os = "linux")]
+    Plugin {
+        name: "fbdev",
+        about: "--plugin fbdev[=DEVICE] draws the screen full screen on /dev/fb0 or DEVICE, \
+                for a console without X or Wayland, on Linux",
+        command: None,
+        remote: Some(fbdev::remote),
+    },
+    Plugin {
+        name: "heatmap",
+        about: "--plugin heatmap[=FILE] draws how often each address was executed, read \
+                and written when the session ends, as PNG or as SVG if FILE ends in .svg",
+        command: None,
+        remote: Some(heatmap::remote),
+    },
+    Plugin {
+        name: "histogram",
+        about: "histogram [--frames N] [--csv] ROM counts how often each opcode runs, \
+                --plugin histogram[=FILE] over a whole session",
+        command: Some(histogram::command),
+        remote: Some(histogram::remote),
+    },
+    Plugin {
+        name: "info",
+        about: "info [--db FILE] ROM shows the size, hash, instruction set and screen mode \
+                of a ROM and its entry in a ROM database",
+        command: Some(info::command),
+        remote: None,
+    },
+    Plugin {
+        name: "list",
+        about: "list [--db FILE] [DIR]... lists the ROMs in DIR, roms/ in the data directory \
+                by default, with their titles from a ROM database",
+        command: Some(library::command),
+        remote: None,
+    },
+    Plugin {
+        name: "quirkdiff",
+        about: "quirkdiff ROM QUIRK,... QUIRK,... runs a ROM under two sets of quirks in \
+                lockstep and reports the first instruction their states diverge at",
+        command: Some(compare::lockstep),
+        remote: None,
+    },
+    Plugin {
+        name: "screenshot",
+        about: "screenshot ROM OUT [FRAMES] saves the screen after FRAMES frames, \
+                --plugin screenshot=OUT keeps OUT up to date, as PBM or XBM text \
+                when OUT ends in .pbm or .xbm, PNG otherwise",
+        command: Some(screenshot_command),
+        remote: Some(screenshot_remote),
+    },
+    #[cfg(unix)]
+    Plugin {
+        name: "terminal",
+        about: "--plugin terminal[=kitty|sixel|blocks|braille] draws the screen in the terminal and \
+                takes keys from it, as images where the terminal shows them",
+        command: None,
+        remote: Some(terminal::remote),
+    },
+    Plugin {
+        name: "validate",
+        about: "validate [--level chip8|schip|xochip] ROM warns about unknown opcodes, \
+                stray jumps and sprites past memory without running the ROM",
+        command: Some(validate::command),
+        remote: None,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Plugin> {
+    PLUGINS.iter().find(|plugin| plugin.name == name)
+}
+
+/// Builds the remote for a `--plugin` argument.
+pub fn remote(arg: &str) -> Result<Box<dyn Remote>, String> {
+    let (name, value) = match arg.find('=') {
+        Some(at) => (&arg[..at], Some(&arg[at + 1..])),
+        None => (arg, None),
+    };
+    let plugin = find(name).ok_or_else(|| format!("unknown plugin '{}'", name))?;
+    let remote = plugin.remote.ok_or_else(|| format!("plugin '{}' has no remote", name))?;
+    remote(value)
+}
+
+/// Lists the plugins, for `ruchip8 plugins`.
+pub fn list(_args: &[String]) -> Result<(), String> {
+    for plugin in PLUGINS {
+        println!("{:<12} {}", plugin.name, plugin.about);
+    }
+    Ok(())
+}
+
+fn disasm_command(args: &[String]) -> Result<(), String> {
+    let (font, path) = match args {
+        [path] => (false, path),
+        [flag, path] if flag == "--font" => (true, path),
+        _ => return Err("usage: ruchip8 disasm [--font] ROM".to_owned()),
+    };
+    let rom = info::read_rom(path)?;
+    let mut chip = Chip8::builder().memory_size(XO_MEMORY_SIZE).build().map_err(|e| e.to_string())?;
+    chip.load_rom(&rom).map_err(|e| e.to_string())?;
+    let symbols = ::symbols_for_rom(Path::new(path))?;
+    let start = if font { 0 } else { PROGRAM_START };
+    for line in disasm::annotated(chip.memory(), start, PROGRAM_START + rom.len(), &symbols) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+const SCREENSHOT_SCALE: usize = 10;
+/// The smallest scale that keeps every pixel of the 128x64 mode, for
+/// screens meant to be pasted into code.
+const TEXT_SCALE: usize = 2;
+
+/// The screen in the format `path`'s extension names, PNG unless it is
+/// `.pbm` or `.xbm`, in the colors of `palette` if given.
+fn screenshot_for(path: &str, display: &Display, palette: Option<&Palette>) -> Vec<u8> {
+    let path = Path::new(path);
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("pbm") => screenshot::to_pbm(display, TEXT_SCALE).into_bytes(),
+        Some("xbm") => {
+            // The file name makes the C identifier, as other XBM writers have it.
+            let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+            let mut name: String = stem.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+            if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                name.insert_str(0, "screen_");
+            }
+            screenshot::to_xbm(display, TEXT_SCALE, &name).into_bytes()
+        },
+        _ => match palette {
+            Some(palette) => screenshot::to_png_in(display, SCREENSHOT_SCALE, palette),
+            None => screenshot::to_png(display, SCREENSHOT_SCALE),
+        },
+    }
+}
+
+fn screenshot_command(args: &[String]) -> Result<(), String> {
+    let (rom, out, frames) = match args {
+        [rom, out] => (rom, out, 60),
+        [rom, out, frames] => (rom, out, frames.parse().map_err(|_| format!("invalid frame count '{}'", frames))?),
+        _ => return Err("usage: ruchip8 screenshot ROM OUT [FRAMES]".to_owned()),
+    };
+    let rom = fs::read(rom).map_err(|e| format!("cannot read {}: {}", rom, e))?;
+
+    let mut session = Session::new(Chip8::new());
+    session.load(&rom).map_err(|e| e.to_string())?;
+    for _ in 0..frames {
+        session.run_frame();
+    }
+    if let Some(e) = session.error {
+        warn!("stopped early: {}", e);
+    }
+    let image = screenshot_for(out, session.chip.display(), None);
+    fs::write(out, image).map_err(|e| format!("cannot write {}: {}", out, e))
+}
+
+/// Rewrites an image whenever the screen changes.
+struct ScreenshotRemote {
+    path: String,
+    last: Vec<u8>,
+}
+
+fn screenshot_remote(arg: Option<&str>) -> Result<Box<dyn Remote>, String> {
+    let path = arg.ok_or("--plugin screenshot needs a file, as in screenshot=OUT")?;
+    Ok(Box::new(ScreenshotRemote { path: path.to_owned(), last: Vec::new() }))
+}
+
+impl Remote for ScreenshotRemote {
+    fn poll(&mut self, _session: &mut Session) {}
+
+    fn frame(&mut self, session: &Session) {
+        let screen = session.chip.display().frame().pixels();
+        if !session.drawing || screen == &self.last[..] {
+            return;
+        }
+        self.last = screen.to_vec();
+        let image = screenshot_for(&self.path, session.chip.display(), Some(&session.palette));
+        if let Err(e) = fs::write(&self.path, image) {
+            warn!("cannot write {}: {}", self.path, e);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+/// Deterministic random number generator backing CXNN.
+///
+/// An xorshift64* generator: tiny, fast and fully described by one `u64`,
+/// so two machines seeded alike draw the same numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Run the seed through splitmix64 so close seeds diverge quickly
+        // and the state can never be the xorshift fixed point of zero.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Rng { state: if z == 0 {1} else {z} }
+    }
+
+    /// A generator carrying on from `state`, as `state` returned it.
+    #[cfg(feature = "std")]
+    pub fn from_state(state: u64) -> Self {
+        Rng { state: if state == 0 {1} else {state} }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
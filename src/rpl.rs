@@ -0,0 +1,91 @@
+//! Keeps SCHIP RPL flags across runs.
+//!
+//! FX75 stores registers in flags that, on the HP48, outlived the program.
+//! Games use them for high scores and progress, so each ROM gets a small
+//! file of its flags under `rpl/` in the data directory, named after the
+//! ROM's hash.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use session::{Remote, Session};
+
+/// Where ruchip8 keeps files between runs: `$XDG_DATA_HOME/ruchip8`,
+/// falling back to `~/.local/share/ruchip8`, `~/Library/Application
+/// Support/ruchip8` on macOS and `%APPDATA%\ruchip8` on Windows.
+pub fn data_dir() -> Option<PathBuf> {
+    let var = |name: &str| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let base = if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local/share")))
+    };
+    base.map(|base| base.join("ruchip8"))
+}
+
+/// Restores the flags when a ROM is loaded and saves them when they change.
+pub struct RplFlags {
+    dir: PathBuf,
+    /// The ROM the flags below belong to.
+    rom: Option<u64>,
+    saved: Vec<u8>,
+}
+
+impl RplFlags {
+    pub fn new(data_dir: PathBuf) -> Self {
+        RplFlags { dir: data_dir.join("rpl"), rom: None, saved: Vec::new() }
+    }
+
+    fn path(&self, rom: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", rom))
+    }
+
+    fn save(&self, rom: u64, flags: &[u8]) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(rom);
+        fs::write(&path, flags)?;
+        Ok(path)
+    }
+}
+
+impl Remote for RplFlags {
+    fn poll(&mut self, session: &mut Session) {
+        if session.rom_hash == self.rom {
+            return;
+        }
+        self.rom = session.rom_hash;
+        if let Some(rom) = self.rom {
+            let path = self.path(rom);
+            match fs::read(&path) {
+                Ok(flags) => {
+                    session.chip.set_rpl_flags(&flags);
+                    debug!("restored RPL flags from {}", path.display());
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {},
+                Err(e) => warn!("cannot read {}: {}", path.display(), e),
+            }
+        }
+        self.saved = session.chip.rpl_flags().to_vec();
+    }
+
+    fn frame(&mut self, session: &Session) {
+        let rom = match self.rom {
+            // A ROM loaded during this frame gets its flags on the next poll.
+            Some(rom) if session.rom_hash == Some(rom) => rom,
+            _ => return,
+        };
+        let flags = session.chip.rpl_flags();
+        if flags == &self.saved[..] {
+            return;
+        }
+        self.saved = flags.to_vec();
+        match self.save(rom, flags) {
+            Ok(path) => debug!("saved RPL flags to {}", path.display()),
+            Err(e) => warn!("cannot save RPL flags: {}", e),
+        }
+    }
+}
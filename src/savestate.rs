@@ -0,0 +1,171 @@
+//! Savestates in numbered slots, kept per ROM.
+//!
+//! Slot N of a ROM is `states/HASH/N.state` in the data directory, HASH
+//! being the ROM's hash as for the RPL flags. Frontends bind Shift+F1 to
+//! Shift+F10 to saving slots 1 to 10 and F1 to F10 to loading them, see
+//! `hotkey`, and every save or load shows a notice over the screen. Each state carries a
+//! thumbnail for the load menu to tell the slots apart by, see `slots`.
+//!
+//! With `--autosave`, `AutoSave` also keeps `auto.state` there, written on
+//! the way out and every `AUTOSAVE_INTERVAL` in case the power goes. The
+//! next run of the same ROM offers to resume from it, or does so right
+//! away with `--resume`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use ruchip8::Savestate;
+use session::{Remote, Session};
+
+/// Slots per ROM, numbered from 1.
+pub const SLOTS: usize = 10;
+
+/// How often `AutoSave` writes while the machine runs.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The savestate file `name` of the loaded ROM.
+fn path(session: &Session, name: &str) -> Result<PathBuf, String> {
+    let dir = session.data_dir.as_ref().ok_or("no data directory to keep savestates in")?;
+    let rom = session.rom_hash.ok_or("no ROM loaded")?;
+    Ok(dir.join("states").join(format!("{:016x}", rom)).join(format!("{}.state", name)))
+}
+
+fn slot_path(session: &Session, slot: usize) -> Result<PathBuf, String> {
+    if !(1..=SLOTS).contains(&slot) {
+        return Err(format!("no slot {}, slots go from 1 to {}", slot, SLOTS));
+    }
+    path(session, &slot.to_string())
+}
+
+/// Writes `state` through a temporary file, so losing power halfway
+/// leaves the previous state in place.
+fn write(path: &Path, state: &[u8]) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    path.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&tmp, state))
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    debug!("saved {}", path.display());
+    Ok(())
+}
+
+/// Reads a state, `None` when there is none.
+fn read(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    match fs::read(path) {
+        Ok(state) => Ok(Some(state)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("cannot read {}: {}", path.display(), e)),
+    }
+}
+
+/// A slot holding a state, as a load menu shows it.
+#[derive(Debug, Clone)]
+pub struct Slot {
+    pub slot: usize,
+    pub saved: SystemTime,
+    /// The screen as a PNG, when the state has one.
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+/// The slots of the loaded ROM that hold a state. Ones that cannot be read
+/// are left out with a warning.
+pub fn slots(session: &Session) -> Result<Vec<Slot>, String> {
+    let mut slots = Vec::new();
+    for slot in 1..=SLOTS {
+        let path = slot_path(session, slot)?;
+        let state = match read(&path) {
+            Ok(Some(state)) => state,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("{}", e);
+                continue;
+            },
+        };
+        let saved = fs::metadata(&path).and_then(|meta| meta.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+        match Savestate::from_bytes(&state) {
+            Ok(state) => slots.push(Slot { slot, saved, thumbnail: state.thumbnail }),
+            Err(e) => warn!("{}: {}", path.display(), e),
+        }
+    }
+    Ok(slots)
+}
+
+/// Saves the machine to `slot`, replacing what was there.
+pub fn save(session: &mut Session, slot: usize) -> Result<(), String> {
+    write(&slot_path(session, slot)?, &session.save_state())?;
+    session.show_notice(format!("saved slot {}", slot));
+    Ok(())
+}
+
+/// Puts the machine back in the state saved to `slot`.
+pub fn load(session: &mut Session, slot: usize) -> Result<(), String> {
+    let state = read(&slot_path(session, slot)?)?.ok_or_else(|| format!("slot {} is empty", slot))?;
+    session.load_state(&state).map_err(|e| format!("cannot load slot {}: {}", slot, e))?;
+    session.show_notice(format!("loaded slot {}", slot));
+    Ok(())
+}
+
+/// Picks up where the last run of the ROM left off.
+pub fn resume(session: &mut Session) -> Result<(), String> {
+    let state = read(&path(session, "auto")?)?.ok_or("no autosave for this ROM")?;
+    session.load_state(&state).map_err(|e| format!("cannot resume: {}", e))?;
+    session.show_notice("resumed the last run".to_owned());
+    Ok(())
+}
+
+/// Keeps the autosave of the running ROM, see the module documentation.
+pub struct AutoSave {
+    /// Whether to resume without asking.
+    resume: bool,
+    /// The ROM the autosave is for.
+    rom: Option<u64>,
+    saved: Instant,
+}
+
+impl AutoSave {
+    pub fn new(resume: bool) -> Self {
+        AutoSave { resume, rom: None, saved: Instant::now() }
+    }
+
+    fn save(&mut self, session: &Session) {
+        self.saved = Instant::now();
+        // Resuming into a crash or an exited program helps nobody.
+        if session.rom_hash != self.rom || session.error.is_some() || session.chip.is_halted() {
+            return;
+        }
+        if let Err(e) = path(session, "auto").and_then(|path| write(&path, &session.save_state())) {
+            warn!("autosave failed: {}", e);
+        }
+    }
+}
+
+impl Remote for AutoSave {
+    fn poll(&mut self, session: &mut Session) {
+        if session.rom_hash == self.rom {
+            return;
+        }
+        self.rom = session.rom_hash;
+        self.saved = Instant::now();
+        match path(session, "auto") {
+            Ok(ref path) if path.exists() => {},
+            _ => return,
+        }
+        if !self.resume {
+            session.show_notice("this ROM was left running last time and can be resumed".to_owned());
+        } else if let Err(e) = resume(session) {
+            warn!("{}", e);
+        }
+    }
+
+    fn frame(&mut self, session: &Session) {
+        if !session.paused && self.saved.elapsed() >= AUTOSAVE_INTERVAL {
+            self.save(session);
+        }
+    }
+
+    fn close(&mut self, session: &Session) {
+        self.save(session);
+    }
+}
@@ -0,0 +1,167 @@
+//! Image export of the framebuffer.
+//!
+//! Besides PNG the screen can be had as plain PBM or as XBM, text formats
+//! that need no image library to read and paste into source code as they
+//! are. Both mark lit pixels with 1, which viewers draw black, as ink.
+//! Sixel is for drawing the screen inline in terminals that show images,
+//! Braille for drawing it small in any terminal.
+
+use png;
+
+use display::Display;
+use palette::Palette;
+use {DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Encodes the presented frame as a greyscale PNG, each pixel blown up to a
+/// `scale` x `scale` square. The 128x64 mode gives an image the same size,
+/// with pixels half as large.
+pub fn to_png(display: &Display, scale: usize) -> Vec<u8> {
+    let pixels = scaled(display, scale).map(|on| if on {0xFF} else {0x00}).collect();
+    encode(pixels, scale, png::ColorType::Grayscale)
+}
+
+/// Like `to_png`, in the colors of `palette`.
+pub fn to_png_in(display: &Display, scale: usize, palette: &Palette) -> Vec<u8> {
+    let pixels = scaled(display, scale).flat_map(|on| palette.color(on as u8)).collect();
+    encode(pixels, scale, png::ColorType::Rgb)
+}
+
+/// Plain PBM, `P1`, at the size `to_png` gives.
+pub fn to_pbm(display: &Display, scale: usize) -> String {
+    let (width, height) = size(scale);
+    let pixels: Vec<bool> = scaled(display, scale).collect();
+    let mut out = format!("P1\n{} {}\n", width, height);
+    for row in pixels.chunks(width) {
+        // The format keeps lines to 70 characters.
+        for line in row.chunks(64) {
+            out.extend(line.iter().map(|&on| if on {'1'} else {'0'}));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// XBM, a C array named `name_bits` with `name_width` and `name_height`
+/// beside it, at the size `to_png` gives.
+pub fn to_xbm(display: &Display, scale: usize, name: &str) -> String {
+    let (width, height) = size(scale);
+    let pixels: Vec<bool> = scaled(display, scale).collect();
+    // Each row starts on a new byte, its leftmost pixel in the lowest bit.
+    let bytes: Vec<String> = pixels.chunks(width)
+        .flat_map(|row| row.chunks(8).map(|pixels| {
+            let byte = pixels.iter().rev().fold(0u8, |byte, &on| byte << 1 | on as u8);
+            format!("0x{:02x}", byte)
+        }))
+        .collect();
+    let mut out = format!("#define {0}_width {1}\n#define {0}_height {2}\nstatic unsigned char {0}_bits[] = {{\n",
+                          name, width, height);
+    for line in bytes.chunks(12) {
+        out.push_str("   ");
+        for byte in line {
+            out.push(' ');
+            out.push_str(byte);
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str("};\n");
+    out
+}
+
+/// Sixel, at the size `to_png` gives and in the colors of `palette`, as
+/// the escape sequence a terminal draws at the cursor.
+pub fn to_sixel(display: &Display, scale: usize, palette: &Palette) -> String {
+    let (width, height) = size(scale);
+    let pixels: Vec<bool> = scaled(display, scale).collect();
+    // Pixels square, the background drawn, then the size.
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for pixel in 0..2 {
+        let percent = |channel: u8| (channel as u32 * 100 + 127) / 255;
+        let [r, g, b] = palette.color(pixel);
+        out.push_str(&format!("#{};2;{};{};{}", pixel, percent(r), percent(g), percent(b)));
+    }
+    // Each character is a column of six pixels, the lowest bit on top.
+    for top in (0..height).step_by(6) {
+        for (pixel, on) in [(0, false), (1, true)] {
+            out.push_str(&format!("#{}", pixel));
+            let columns = (0..width).map(|x| {
+                let bits = (0..6).filter(|&row| top + row < height && pixels[(top + row) * width + x] == on)
+                    .fold(0, |bits, row| bits | 1 << row);
+                (63 + bits) as u8 as char
+            });
+            let mut run: Option<(char, usize)> = None;
+            for c in columns.map(Some).chain(Some(None)) {
+                match (run, c) {
+                    (Some((last, n)), Some(c)) if last == c => run = Some((last, n + 1)),
+                    (last, c) => {
+                        if let Some((last, n)) = last {
+                            if n > 3 {
+                                out.push_str(&format!("!{}{}", n, last));
+                            } else {
+                                out.extend((0..n).map(|_| last));
+                            }
+                        }
+                        run = c.map(|c| (c, 1));
+                    },
+                }
+            }
+            // Back to the start of the band for the other color.
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+/// The screen as lines of Braille characters, each one a block of 2x4
+/// pixels with a dot for every lit one: 32x8 characters for 64x32, 64x16
+/// in 128x64.
+pub fn to_braille(display: &Display) -> String {
+    // The bits of the dots, by row and column.
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    let frame = display.frame();
+    let mut out = String::new();
+    for top in (0..frame.height()).step_by(4) {
+        for left in (0..frame.width()).step_by(2) {
+            let dots = (0..4).flat_map(|row| (0..2).map(move |column| (row, column)))
+                .filter(|&(row, column)| frame.pixel(left + column, top + row))
+                .fold(0, |dots, (row, column)| dots | DOTS[row][column]);
+            // The dots follow U+2800, the blank pattern, in the order of their bits.
+            out.push(char::from_u32(0x2800 + dots).unwrap_or(' '));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The width and height of an image at `scale`.
+fn size(scale: usize) -> (usize, usize) {
+    let scale = scale.max(1);
+    (DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale)
+}
+
+/// Whether each pixel of the image is set, row by row.
+fn scaled(display: &Display, scale: usize) -> impl Iterator<Item = bool> + '_ {
+    let scale = scale.max(1);
+    let (width, height) = size(scale);
+
+    // A CHIP-8 pixel is `step / 2` image pixels wide: `scale` in 64x32, half that in 128x64.
+    let frame = display.frame();
+    let step = if frame.is_hires() { scale } else { scale * 2 };
+    (0..height).flat_map(move |y| (0..width).map(move |x| frame.pixel(x * 2 / step, y * 2 / step)))
+}
+
+fn encode(pixels: Vec<u8>, scale: usize, color: png::ColorType) -> Vec<u8> {
+    let (width, height) = size(scale);
+    let mut out = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        // Writing into a Vec only fails on invalid parameters, which are fixed here.
+        let mut writer = encoder.write_header().expect("PNG header");
+        writer.write_image_data(&pixels).expect("PNG data");
+    }
+    out
+}
@@ -0,0 +1,238 @@
+//! Rhai scripting hooks.
+//!
+//! A script is run once at startup and may then define any of these
+//! callbacks, which are called between frames in the order the events
+//! happened:
+//!
+//! ```text
+//! fn on_frame() { ... }             // after every frame
+//! fn on_draw(x, y, rows) { ... }    // for every DXYN, with VX, VY and N
+//! fn on_write(addr, old, value) { ... } // when a watched byte changes
+//! fn on_exit() { ... }              // when the program runs 00FD
+//! ```
+//!
+//! The machine is reachable through `reg(n)`, `set_reg(n, v)`, `peek(addr)`,
+//! `poke(addr, v)`, `pc()`, `set_pc(addr)`, `index()`, `set_index(addr)`,
+//! `dt()`, `set_dt(v)`, `st()`, `set_st(v)`, `key(k, pressed)` and `pause()`.
+//! `watch(addr)` subscribes `on_write` to an address.
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::{Engine, EvalAltResult, Scope, AST};
+
+use ruchip8::Chip8;
+use session::{Event, Remote, Session};
+
+/// The copy of the machine a callback sees. It is taken right before the
+/// callback and written back right after, so scripts never hold on to the
+/// session itself.
+#[derive(Default)]
+struct Machine {
+    v: [u8; 16],
+    i: usize,
+    pc: usize,
+    dt: u8,
+    st: u8,
+    memory: Vec<u8>,
+    keys: Vec<(u8, bool)>,
+    watches: Vec<usize>,
+    pause: bool,
+    dirty: bool,
+}
+
+impl Machine {
+    fn load(&mut self, chip: &Chip8) {
+        for n in 0..16 {
+            self.v[n] = chip.v(n as u8);
+        }
+        self.i = chip.i();
+        self.pc = chip.pc();
+        self.dt = chip.delay_timer();
+        self.st = chip.sound_timer();
+        self.memory.clear();
+        self.memory.extend_from_slice(chip.memory());
+        self.dirty = false;
+    }
+
+    fn store(&mut self, session: &mut Session) {
+        if self.dirty {
+            let chip = &mut session.chip;
+            for n in 0..16 {
+                chip.set_v(n as u8, self.v[n]);
+            }
+            chip.set_i(self.i);
+            chip.set_pc(self.pc);
+            chip.set_delay_timer(self.dt);
+            chip.set_sound_timer(self.st);
+            // Only the runs of bytes the script changed, so the machine
+            // keeps the instructions it decoded elsewhere.
+            let len = self.memory.len().min(chip.memory().len());
+            let mut at = 0;
+            while at < len {
+                if chip.memory()[at] == self.memory[at] {
+                    at += 1;
+                    continue;
+                }
+                let end = (at..len).find(|&n| chip.memory()[n] == self.memory[n]).unwrap_or(len);
+                let _ = chip.write_memory(at, &self.memory[at..end]);
+                at = end;
+            }
+        }
+        for (key, pressed) in self.keys.drain(..) {
+            session.set_key(key, pressed);
+        }
+        for addr in self.watches.drain(..) {
+            session.watch(addr);
+        }
+        if self.pause {
+            session.paused = true;
+            self.pause = false;
+        }
+    }
+}
+
+type Shared = Rc<RefCell<Machine>>;
+
+fn register_api(engine: &mut Engine, machine: &Shared) {
+    let m = machine.clone();
+    engine.register_fn("reg", move |n: i64| m.borrow().v[(n & 0xF) as usize] as i64);
+    let m = machine.clone();
+    engine.register_fn("set_reg", move |n: i64, val: i64| {
+        let mut m = m.borrow_mut();
+        m.v[(n & 0xF) as usize] = val as u8;
+        m.dirty = true;
+    });
+    let m = machine.clone();
+    engine.register_fn("peek", move |addr: i64| {
+        let m = m.borrow();
+        m.memory[addr as usize % m.memory.len()] as i64
+    });
+    let m = machine.clone();
+    engine.register_fn("poke", move |addr: i64, val: i64| {
+        let mut m = m.borrow_mut();
+        let len = m.memory.len();
+        m.memory[addr as usize % len] = val as u8;
+        m.dirty = true;
+    });
+    let m = machine.clone();
+    engine.register_fn("pc", move || m.borrow().pc as i64);
+    let m = machine.clone();
+    engine.register_fn("set_pc", move |addr: i64| {
+        let mut m = m.borrow_mut();
+        m.pc = addr as usize % m.memory.len();
+        m.dirty = true;
+    });
+    let m = machine.clone();
+    engine.register_fn("index", move || m.borrow().i as i64);
+    let m = machine.clone();
+    engine.register_fn("set_index", move |addr: i64| {
+        let mut m = m.borrow_mut();
+        m.i = addr as usize % m.memory.len();
+        m.dirty = true;
+    });
+    let m = machine.clone();
+    engine.register_fn("dt", move || m.borrow().dt as i64);
+    let m = machine.clone();
+    engine.register_fn("set_dt", move |val: i64| {
+        let mut m = m.borrow_mut();
+        m.dt = val as u8;
+        m.dirty = true;
+    });
+    let m = machine.clone();
+    engine.register_fn("st", move || m.borrow().st as i64);
+    let m = machine.clone();
+    engine.register_fn("set_st", move |val: i64| {
+        let mut m = m.borrow_mut();
+        m.st = val as u8;
+        m.dirty = true;
+    });
+    let m = machine.clone();
+    engine.register_fn("key", move |key: i64, pressed: bool| {
+        m.borrow_mut().keys.push(((key & 0xF) as u8, pressed));
+    });
+    let m = machine.clone();
+    engine.register_fn("watch", move |addr: i64| {
+        let mut m = m.borrow_mut();
+        let len = m.memory.len();
+        m.watches.push(addr as usize % len);
+    });
+    let m = machine.clone();
+    engine.register_fn("pause", move || m.borrow_mut().pause = true);
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    machine: Shared,
+    on_frame: bool,
+    on_draw: bool,
+    on_write: bool,
+    on_exit: bool,
+    /// The top level code still has to run against the loaded machine.
+    started: bool,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let machine = Shared::default();
+        let mut engine = Engine::new();
+        register_api(&mut engine, &machine);
+
+        let ast = engine.compile_file(path.to_path_buf())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let defines = |name: &str| ast.iter_functions().any(|f| f.name == name);
+        let (on_frame, on_draw, on_write) = (defines("on_frame"), defines("on_draw"), defines("on_write"));
+        let on_exit = defines("on_exit");
+
+        Ok(Script {
+            engine, ast, machine,
+            scope: Scope::new(),
+            on_frame, on_draw, on_write, on_exit,
+            started: false,
+        })
+    }
+
+    fn report(result: Result<(), Box<EvalAltResult>>) {
+        if let Err(e) = result {
+            warn!("{}", e);
+        }
+    }
+
+    fn call(&mut self, session: &mut Session, name: &str, args: Vec<rhai::Dynamic>) {
+        self.machine.borrow_mut().load(&session.chip);
+        let result = self.engine.call_fn::<rhai::Dynamic>(&mut self.scope, &self.ast, name, args);
+        Script::report(result.map(|_| ()));
+        self.machine.borrow_mut().store(session);
+    }
+}
+
+impl Remote for Script {
+    fn poll(&mut self, session: &mut Session) {
+        if !self.started {
+            self.started = true;
+            session.record_draws = self.on_draw;
+            self.machine.borrow_mut().load(&session.chip);
+            let result = self.engine.run_ast_with_scope(&mut self.scope, &self.ast);
+            Script::report(result);
+            self.machine.borrow_mut().store(session);
+            return;
+        }
+
+        for event in ::std::mem::take(&mut session.events) {
+            match event {
+                Event::Draw { x, y, rows } if self.on_draw => self.call(session, "on_draw",
+                    vec![(x as i64).into(), (y as i64).into(), (rows as i64).into()]),
+                Event::Write { addr, old, new } if self.on_write => self.call(session, "on_write",
+                    vec![(addr as i64).into(), (old as i64).into(), (new as i64).into()]),
+                Event::Exit if self.on_exit => self.call(session, "on_exit", Vec::new()),
+                _ => {},
+            }
+        }
+        if self.on_frame && !session.paused {
+            self.call(session, "on_frame", Vec::new());
+        }
+    }
+}
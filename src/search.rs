@@ -0,0 +1,73 @@
+//! Narrowing down where a game keeps a variable.
+//!
+//! A search starts with every address as a candidate and a snapshot of
+//! memory. Each filter compares memory against the previous snapshot, drops
+//! the addresses that do not match and takes a new snapshot, so playing a
+//! little between filters ("lost a life, so it decreased") quickly leaves a
+//! handful of addresses.
+
+use ruchip8::Chip8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(u8),
+}
+
+impl Filter {
+    pub fn parse(words: &[&str]) -> Result<Filter, String> {
+        match words {
+            ["changed"] => Ok(Filter::Changed),
+            ["unchanged"] => Ok(Filter::Unchanged),
+            ["increased"] => Ok(Filter::Increased),
+            ["decreased"] => Ok(Filter::Decreased),
+            ["eq", value] => u8::from_str_radix(value, 16)
+                .map(Filter::Equals)
+                .map_err(|_| format!("invalid value '{}'", value)),
+            _ => Err("expected changed, unchanged, increased, decreased or eq VALUE".to_owned()),
+        }
+    }
+
+    fn matches(self, old: u8, new: u8) -> bool {
+        match self {
+            Filter::Changed => new != old,
+            Filter::Unchanged => new == old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+            Filter::Equals(value) => new == value,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Search {
+    snapshot: Vec<u8>,
+    candidates: Vec<usize>,
+}
+
+impl Search {
+    pub fn new(chip: &Chip8) -> Self {
+        let snapshot = chip.memory().to_vec();
+        Search { candidates: (0..snapshot.len()).collect(), snapshot }
+    }
+
+    /// Keeps the candidates that match `filter` and takes a new snapshot.
+    pub fn filter(&mut self, chip: &Chip8, filter: Filter) {
+        let memory = chip.memory();
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|&addr| filter.matches(snapshot[addr], memory[addr]));
+        self.snapshot.copy_from_slice(memory);
+    }
+
+    /// The remaining addresses with their value in the last snapshot.
+    pub fn candidates(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        self.candidates.iter().map(move |&addr| (addr, self.snapshot[addr]))
+    }
+
+    pub fn count(&self) -> usize {
+        self.candidates.len()
+    }
+}
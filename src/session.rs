@@ -0,0 +1,887 @@
+//! Headless run loop shared by the remote control frontends.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+use std::mem;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ruchip8::audio::{Latency, BEEP_FREQUENCY};
+use ruchip8::disasm::Symbols;
+use ruchip8::palette::Palette;
+use ruchip8::{decode, rom_hash, Chip8, Error, Instruction, Level, Savestate, Timing, TIMERS_CLOCK};
+
+use libc;
+
+use cheats::Cheats;
+use info;
+use json::Json;
+use library::Library;
+use search::Search;
+use sourcemap::SourceMap;
+use trace::{self, Trace};
+
+/// The machine plus the bits of run state remotes can change.
+pub struct Session {
+    pub chip: Chip8,
+    pub paused: bool,
+    /// The error that stopped the machine, cleared by reset or load.
+    pub error: Option<Error>,
+    /// Seed given to every machine this session loads, random when unset.
+    pub seed: Option<u64>,
+    /// Keys held on this host, one bit per key.
+    local_keys: u16,
+    /// Key changes on this host since the last frame or step, in order.
+    local_events: Vec<(u8, bool)>,
+    /// Keys held by a netplay peer, merged with the local ones.
+    pub remote_keys: u16,
+    /// Set while a netplay peer is connected. Keys then reach the machine
+    /// as they stand at the start of each frame, alike on both peers, and
+    /// taps shorter than that are lost.
+    pub lockstep: bool,
+    /// The merged keys as the machine was last given them.
+    applied_keys: u16,
+    /// Addresses that pause the machine before the instruction there runs.
+    pub breakpoints: BTreeSet<usize>,
+    /// Kinds of instruction that pause the machine before they run.
+    pub break_classes: BTreeSet<Class>,
+    /// A one-off breakpoint for stepping over or out of subroutines.
+    pub temporary: Option<Temporary>,
+    /// Number of times a breakpoint stopped the machine.
+    pub breaks_hit: u64,
+    /// The breakpoint the machine last stopped at, which lets execution
+    /// carry on past it when resumed.
+    stopped_at: Option<usize>,
+    /// Whether to record an `Event::Draw` for every DXYN.
+    pub record_draws: bool,
+    /// Watched addresses with their last seen value.
+    watches: BTreeMap<usize, u8>,
+    /// Events recorded since they were last drained.
+    pub events: Vec<Event>,
+    /// Values written back to memory after every frame.
+    pub cheats: Cheats,
+    /// Labels for addresses, shown by the debugger.
+    pub symbols: Symbols,
+    /// Where the code came from in its Octo source, if known.
+    pub source_map: SourceMap,
+    /// The memory search in progress, if any.
+    pub search: Option<Search>,
+    /// Where executed instructions are written, if anywhere.
+    pub trace: Option<Trace>,
+    /// How many cycles a frame has and what instructions cost.
+    pub timing: Timing,
+    /// The timing for ROM files with no tick rate, see `choose_timing`.
+    pub default_timing: Timing,
+    /// Set when `--timing` chose the timing over the ROMs' own.
+    pub timing_given: bool,
+    /// Frequency frontends beep at while the sound timer runs, in Hz.
+    pub beep: f32,
+    /// How far ahead of the speaker frontends schedule the sound.
+    pub audio_latency: Latency,
+    /// Colors screenshots and clients show the screen in.
+    pub palette: Palette,
+    pub stats: Stats,
+    /// How long `run` spent on the last frame, sleep aside.
+    pub frame_time: Duration,
+    /// `rom_hash` of the ROM loaded last.
+    pub rom_hash: Option<u64>,
+    /// The ROM loaded last, as loaded, for hard resets.
+    rom: Vec<u8>,
+    /// The file the ROM loaded last was read from, for `reload`, and the
+    /// patches applied to it. Whatever loads a ROM from elsewhere clears
+    /// it.
+    pub rom_path: Option<PathBuf>,
+    pub patches: Vec<String>,
+    /// The highest level the ROM loaded last has instructions of.
+    pub level: Level,
+    /// Memory ranges hard resets leave as they are, see `persist`.
+    pub persistent: Vec<RangeInclusive<usize>>,
+    /// The latest FX15 and FX18, oldest first, at most `TIMER_LOG`.
+    pub timer_writes: VecDeque<TimerWrite>,
+    /// When `run` next ticks the timers, unless paused.
+    pub next_tick: Option<Instant>,
+    /// Where files kept between runs go, `None` without a data directory.
+    pub data_dir: Option<PathBuf>,
+    /// Confirmations for the user and when they were made, oldest first,
+    /// at most `NOTICE_LINES`.
+    notices: VecDeque<(String, Instant)>,
+    /// Whether frontends draw `rates` over the screen.
+    pub overlay: bool,
+    /// Frontends draw one frame in this many, at least 1, for hosts or
+    /// clients too slow to draw them all.
+    pub frame_skip: u32,
+    /// Whether frontends draw the frame that just ran, see `frame_skip`.
+    pub drawing: bool,
+    /// How fast `run` ran the machine over the last whole second.
+    pub rates: Rates,
+    /// The ROMs in the ROM directories.
+    pub library: Library,
+    /// Set to end `run` after the current frame.
+    pub quit: bool,
+}
+
+/// How long frontends show a notice over the screen.
+pub const NOTICE_TIME: Duration = Duration::from_secs(2);
+
+/// How many notices frontends show at once, the oldest go first.
+pub const NOTICE_LINES: usize = 4;
+
+/// How many timer writes `Session::timer_writes` keeps.
+pub const TIMER_LOG: usize = 16;
+
+/// An FX15 or FX18 that ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerWrite {
+    /// `Stats::frames` when it ran.
+    pub frame: u64,
+    pub pc: usize,
+    /// The sound timer if true, the delay timer otherwise.
+    pub sound: bool,
+    pub value: u8,
+}
+
+/// Counts since the machine was last loaded or reset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub frames: u64,
+    pub instructions: u64,
+    /// Cycles under the session's timing.
+    pub cycles: u64,
+    /// Instructions and cycles the last frame ran.
+    pub frame_instructions: u32,
+    pub frame_cycles: u32,
+}
+
+/// Frames and instructions the machine ran a second.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rates {
+    pub fps: f64,
+    pub ips: f64,
+}
+
+impl Rates {
+    /// How fast the machine runs next to a real one, 1 at full speed.
+    pub fn speed(&self) -> f64 {
+        self.fps / TIMERS_CLOCK as f64
+    }
+}
+
+/// Counts for the second `run` is measuring `Rates` over.
+struct Meter {
+    start: Instant,
+    frames: u64,
+    instructions: u64,
+    /// `stats` as of the last frame, to count across resets.
+    seen: Stats,
+}
+
+impl Meter {
+    fn new(start: Instant) -> Self {
+        Meter { start, frames: 0, instructions: 0, seen: Stats::default() }
+    }
+
+    fn frame(&mut self, session: &mut Session) {
+        let stats = session.stats;
+        // A load or reset started the counts over.
+        self.frames += stats.frames.checked_sub(self.seen.frames).unwrap_or(stats.frames);
+        self.instructions += stats.instructions.checked_sub(self.seen.instructions).unwrap_or(stats.instructions);
+        self.seen = stats;
+
+        let now = Instant::now();
+        let seconds = (now - self.start).as_secs_f64();
+        if seconds >= 1.0 {
+            session.rates = Rates { fps: self.frames as f64 / seconds, ips: self.instructions as f64 / seconds };
+            *self = Meter { seen: stats, ..Meter::new(now) };
+        }
+    }
+}
+
+/// Something noteworthy the machine did, recorded for scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A DXYN ran with VX, VY and N.
+    Draw { x: u8, y: u8, rows: u8 },
+    /// A watched address changed value.
+    Write { addr: usize, old: u8, new: u8 },
+    /// The program ran 00FD and the machine halted.
+    Exit,
+}
+
+/// A breakpoint that goes away once hit, set at a stack depth so recursion
+/// does not stop it early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Temporary {
+    /// Where to stop, anywhere when `None`.
+    pub addr: Option<usize>,
+    /// The deepest the stack may be when stopping.
+    pub depth: usize,
+}
+
+impl Temporary {
+    fn reached(&self, pc: usize, depth: usize) -> bool {
+        depth <= self.depth && self.addr.is_none_or(|addr| addr == pc)
+    }
+}
+
+/// Kinds of instruction to break on, whatever their address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Class {
+    /// DXYN.
+    Draw,
+    /// 2NNN.
+    Call,
+    /// 00EE.
+    Ret,
+    /// 1NNN and BNNN.
+    Jump,
+    /// EX9E, EXA1 and FX0A.
+    Key,
+    /// FX07, FX15 and FX18.
+    Timer,
+    /// FX33, FX55, 5XY2 and FX75.
+    Store,
+    /// FX65, 5XY3 and FX85.
+    Load,
+}
+
+impl Class {
+    pub const ALL: [Class; 8] = [
+        Class::Draw, Class::Call, Class::Ret, Class::Jump, Class::Key, Class::Timer, Class::Store, Class::Load,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Draw => "draw",
+            Class::Call => "call",
+            Class::Ret => "ret",
+            Class::Jump => "jump",
+            Class::Key => "key",
+            Class::Timer => "timer",
+            Class::Store => "store",
+            Class::Load => "load",
+        }
+    }
+
+    /// Parses one of the names `name` gives.
+    pub fn parse(text: &str) -> Option<Class> {
+        Class::ALL.iter().cloned().find(|class| class.name() == text)
+    }
+
+    /// The class `instruction` belongs to, if any.
+    pub fn of(instruction: Instruction) -> Option<Class> {
+        use ruchip8::Instruction::*;
+        match instruction {
+            Draw { .. } => Some(Class::Draw),
+            Call { .. } => Some(Class::Call),
+            Ret => Some(Class::Ret),
+            Jump { .. } | JumpV0 { .. } => Some(Class::Jump),
+            SkipKey { .. } | SkipNotKey { .. } | WaitKey { .. } => Some(Class::Key),
+            ReadDelay { .. } | SetDelay { .. } | SetSound { .. } => Some(Class::Timer),
+            Bcd { .. } | Store { .. } | StoreRange { .. } | SaveFlags { .. } => Some(Class::Store),
+            Load { .. } | LoadRange { .. } | LoadFlags { .. } => Some(Class::Load),
+            _ => None,
+        }
+    }
+}
+
+/// A control surface that gets a chance to act once per frame.
+pub trait Remote {
+    /// Handles pending requests before the frame runs.
+    fn poll(&mut self, session: &mut Session);
+
+    /// Publishes the state after the frame ran.
+    fn frame(&mut self, _session: &Session) {}
+
+    /// The session is ending, after its last frame.
+    fn close(&mut self, _session: &Session) {}
+}
+
+impl Session {
+    pub fn new(chip: Chip8) -> Self {
+        Session {
+            chip,
+            paused: false,
+            error: None,
+            seed: None,
+            local_keys: 0,
+            local_events: Vec::new(),
+            remote_keys: 0,
+            lockstep: false,
+            applied_keys: 0,
+            breakpoints: BTreeSet::new(),
+            break_classes: BTreeSet::new(),
+            temporary: None,
+            breaks_hit: 0,
+            stopped_at: None,
+            record_draws: false,
+            watches: BTreeMap::new(),
+            events: Vec::new(),
+            cheats: Cheats::default(),
+            symbols: Symbols::default(),
+            source_map: SourceMap::default(),
+            search: None,
+            trace: None,
+            timing: Timing::default(),
+            default_timing: Timing::default(),
+            timing_given: false,
+            beep: BEEP_FREQUENCY,
+            audio_latency: Latency::default(),
+            palette: Palette::default(),
+            stats: Stats::default(),
+            frame_time: Duration::ZERO,
+            rom_hash: None,
+            rom: Vec::new(),
+            rom_path: None,
+            patches: Vec::new(),
+            level: Level::Chip8,
+            persistent: Vec::new(),
+            timer_writes: VecDeque::new(),
+            next_tick: None,
+            data_dir: None,
+            notices: VecDeque::new(),
+            overlay: false,
+            frame_skip: 1,
+            drawing: true,
+            rates: Rates::default(),
+            library: Library::default(),
+            quit: false,
+        }
+    }
+
+    /// Replaces the machine with a fresh one running `rom`, keeping quirks,
+    /// memory size, font and observers.
+    /// Loading the same ROM again also keeps its RPL flags.
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), Error> {
+        let mut chip = Chip8::builder()
+            .memory_size(self.chip.memory().len())
+            .font(self.chip.font())
+            .build()?;
+        chip.set_shift_vy(self.chip.shift_vy());
+        chip.set_sys_policy(self.chip.sys_policy());
+        chip.set_write_protect(self.chip.write_protect());
+        chip.set_key_wait(self.chip.key_wait());
+        if let Some(seed) = self.seed {
+            chip.set_seed(seed);
+        }
+        chip.load_rom(rom)?;
+        let hash = rom_hash(rom);
+        if self.rom_hash == Some(hash) {
+            chip.set_rpl_flags(self.chip.rpl_flags());
+        }
+        self.rom_hash = Some(hash);
+        for (&addr, value) in self.watches.iter_mut() {
+            *value = chip.memory()[addr];
+        }
+        for observer in self.chip.take_observers() {
+            chip.add_observer(observer);
+        }
+        self.chip = chip;
+        self.rom = rom.to_vec();
+        self.level = info::code(rom).values().flatten().map(|instruction| instruction.level()).max().unwrap_or(Level::Chip8);
+        self.applied_keys = 0;
+        self.error = None;
+        self.temporary = None;
+        self.stats = Stats::default();
+        Ok(())
+    }
+
+    /// Starts the program over with memory as it left it, see
+    /// `Chip8::soft_reset`.
+    pub fn soft_reset(&mut self) {
+        self.chip.soft_reset();
+        self.restarted();
+    }
+
+    /// Starts the ROM loaded last over on cleared memory, as if loaded
+    /// again but keeping the machine, its observers and the `persistent`
+    /// ranges.
+    pub fn hard_reset(&mut self) {
+        let memory = self.chip.memory();
+        let kept: Vec<(RangeInclusive<usize>, Vec<u8>)> = self.persistent.iter()
+            .filter(|range| *range.end() < memory.len())
+            .map(|range| (range.clone(), memory[range.clone()].to_vec()))
+            .collect();
+        // The ROM fitted when it was loaded.
+        let _ = self.chip.hard_reset(&self.rom);
+        for (range, bytes) in kept {
+            // Filtered to lie within memory above.
+            let _ = self.chip.write_memory(*range.start(), &bytes);
+        }
+        for (&addr, value) in self.watches.iter_mut() {
+            *value = self.chip.memory()[addr];
+        }
+        self.restarted();
+    }
+
+    /// Reads the ROM again from `rom_path`, with its labels and source map,
+    /// and starts it on a fresh machine, to pick up a rebuilt ROM or restart
+    /// a crashed one without starting the emulator again.
+    pub fn reload(&mut self) -> Result<(), String> {
+        let path = self.rom_path.clone().ok_or("the ROM was not loaded from a file")?;
+        let program = ::read_program(&path)?;
+        let rom = ::apply_patches(program.rom.clone(), &self.patches)?;
+        self.load(&rom).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.symbols = program.symbols;
+        self.source_map = program.source_map;
+        self.choose_timing(&program.rom);
+        Ok(())
+    }
+
+    /// Sets `timing` for `rom`, just read from `rom_path`: the tick rate
+    /// the library finds for it, or `default_timing`. Leaves it be when
+    /// `timing_given`.
+    pub fn choose_timing(&mut self, rom: &[u8]) {
+        if self.timing_given {
+            return;
+        }
+        let tickrate = self.rom_path.as_ref().and_then(|path| self.library.tickrate(path, rom));
+        self.timing = match tickrate {
+            Some(n) => {
+                self.show_notice(format!("tickrate {}", n));
+                Timing::Fixed(n)
+            },
+            None => self.default_timing,
+        };
+    }
+
+    /// Starts ROM `index` of the library, without patches.
+    pub fn open(&mut self, index: usize) -> Result<(), String> {
+        let entry = self.library.entries.get(index).ok_or("no such ROM")?.clone();
+        self.rom_path = Some(entry.path.clone());
+        self.patches.clear();
+        self.reload()?;
+        self.paused = false;
+        self.show_notice(format!("loaded {}", entry.name()));
+        Ok(())
+    }
+
+    fn restarted(&mut self) {
+        self.error = None;
+        self.temporary = None;
+        self.stats = Stats::default();
+    }
+
+    /// The machine state, as `Savestate::to_bytes` encodes it.
+    pub fn save_state(&self) -> Vec<u8> {
+        Savestate::of(&self.chip, self.rom_hash).to_bytes()
+    }
+
+    /// Puts back a state `save_state` returned, clearing any error. Fails
+    /// when the state is for another ROM or another machine.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let state = Savestate::from_bytes(state).map_err(|e| e.to_string())?;
+        if state.rom_hash.is_some() && state.rom_hash != self.rom_hash {
+            return Err("savestate is for another ROM".to_owned());
+        }
+        state.restore(&mut self.chip).map_err(|e| e.to_string())?;
+        for (&addr, value) in self.watches.iter_mut() {
+            *value = self.chip.memory()[addr];
+        }
+        self.error = None;
+        self.temporary = None;
+        self.stopped_at = None;
+        Ok(())
+    }
+
+    /// Edits memory for the user, only while paused so the program does
+    /// not race the edit. Watches take the new values without an event.
+    pub fn poke(&mut self, addr: usize, bytes: &[u8]) -> Result<(), String> {
+        if !self.paused {
+            return Err("pause first".to_owned());
+        }
+        self.chip.write_memory(addr, bytes).map_err(|_| "range outside of memory".to_owned())?;
+        for (&addr, value) in self.watches.range_mut(addr..addr + bytes.len()) {
+            *value = self.chip.memory()[addr];
+        }
+        Ok(())
+    }
+
+    /// Logs `text` and has frontends that draw notices show it over the
+    /// screen for `NOTICE_TIME`, below the notices still showing.
+    pub fn show_notice(&mut self, text: String) {
+        info!("{}", text);
+        self.notices.retain(|(_, shown)| shown.elapsed() < NOTICE_TIME);
+        if self.notices.len() == NOTICE_LINES {
+            self.notices.pop_front();
+        }
+        self.notices.push_back((text, Instant::now()));
+    }
+
+    /// The notices to show right now, oldest first.
+    pub fn notices(&self) -> impl Iterator<Item = &str> {
+        self.notices.iter()
+            .filter(|(_, shown)| shown.elapsed() < NOTICE_TIME)
+            .map(|(text, _)| text.as_str())
+    }
+
+    /// A window title: the ROM's file name and level, then how fast it runs
+    /// or whether it is paused or stopped, as in
+    /// `pong.ch8 (CHIP-8) 100% - ruchip8`.
+    pub fn title(&self) -> String {
+        let name = match self.rom_path.as_ref().and_then(|path| path.file_name()) {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None if self.rom.is_empty() => return "ruchip8".to_owned(),
+            None => "ROM".to_owned(),
+        };
+        let state = if self.error.is_some() || self.chip.is_halted() {
+            " stopped".to_owned()
+        } else if self.paused {
+            " paused".to_owned()
+        } else if self.rates.fps > 0.0 {
+            format!(" {:.0}%", self.rates.speed() * 100.0)
+        } else {
+            String::new()
+        };
+        format!("{} ({}){} - ruchip8", name, self.level, state)
+    }
+
+    /// The latest notice to show right now, if any.
+    pub fn notice(&self) -> Option<&str> {
+        self.notices().last()
+    }
+
+    /// Records a key change on this host. It reaches the machine at the
+    /// start of the next frame or step, so lockstep peers see it together.
+    /// Outside of lockstep it goes through `Chip8::push_key`, so the
+    /// program sees even a tap that was over by then.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        let keys = if pressed { self.local_keys | 1 << key } else { self.local_keys & !(1 << key) };
+        if keys != self.local_keys {
+            self.local_keys = keys;
+            self.local_events.push((key, pressed));
+        }
+    }
+
+    /// Resumes until `temporary` is reached, walking over any breakpoint at
+    /// the current address.
+    pub fn run_to(&mut self, temporary: Temporary) {
+        self.temporary = Some(temporary);
+        self.stopped_at = Some(self.chip.pc());
+        self.paused = false;
+    }
+
+    /// Records an `Event::Write` whenever the byte at `addr` changes.
+    pub fn watch(&mut self, addr: usize) {
+        if let Some(&value) = self.chip.memory().get(addr) {
+            self.watches.insert(addr, value);
+        }
+    }
+
+    pub fn local_keys(&self) -> u16 {
+        self.local_keys
+    }
+
+    fn apply_keys(&mut self) {
+        let keys = self.local_keys | self.remote_keys;
+        if self.lockstep {
+            self.local_events.clear();
+            for key in 0..16 {
+                let pressed = keys & (1 << key) != 0;
+                if self.chip.is_key_down(key) != pressed {
+                    self.chip.set_key(key, pressed);
+                }
+            }
+            self.applied_keys = keys;
+            return;
+        }
+        for (key, pressed) in mem::take(&mut self.local_events) {
+            self.push_key(key, pressed || self.remote_keys & (1 << key) != 0);
+        }
+        for key in 0..16 {
+            self.push_key(key, keys & (1 << key) != 0);
+        }
+    }
+
+    /// Hands the machine a change of the merged keys, if it is one.
+    fn push_key(&mut self, key: u8, pressed: bool) {
+        let bit = 1 << key;
+        if (self.applied_keys & bit != 0) != pressed {
+            self.applied_keys ^= bit;
+            self.chip.push_key(key, pressed);
+        }
+    }
+
+    /// Executes up to `n` instructions. Stops early and pauses on an error or
+    /// when reaching a breakpoint. Returns whether all `n` instructions ran.
+    pub fn step(&mut self, n: u32) -> Result<bool, Error> {
+        self.apply_keys();
+        for _ in 0..n {
+            if self.cycle()?.is_none() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Pauses and executes up to `n` instructions like `step`, walking over
+    /// breakpoints since that is what the user asked for.
+    pub fn step_through(&mut self, n: u32) -> Result<bool, Error> {
+        self.paused = true;
+        let breakpoints = mem::take(&mut self.breakpoints);
+        let classes = mem::take(&mut self.break_classes);
+        let result = self.step(n);
+        self.breakpoints = breakpoints;
+        self.break_classes = classes;
+        result
+    }
+
+    /// Like `step`, running instructions until they cost `budget` cycles.
+    /// A machine waiting on FX0A idles for the rest of the budget, as with
+    /// `Chip8::run_frame`, untraced and uncounted.
+    pub fn step_cycles(&mut self, budget: u32) -> Result<bool, Error> {
+        self.apply_keys();
+        let mut spent = 0;
+        while spent < budget {
+            if self.chip.is_waiting_for_key() {
+                break;
+            }
+            match self.cycle()? {
+                Some(cycles) => spent += cycles,
+                None => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs one instruction, returning the cycles it took, or `None` when
+    /// a breakpoint or 00FD stopped it.
+    fn cycle(&mut self) -> Result<Option<u32>, Error> {
+        if self.chip.is_halted() {
+            return Ok(None);
+        }
+        let pc = self.chip.pc();
+        let temporary = self.temporary.is_some_and(|t| t.reached(pc, self.chip.stack().len()));
+        if (temporary || self.breakpoints.contains(&pc) || self.breaks_on_class(pc)) && self.stopped_at != Some(pc) {
+            self.stopped_at = Some(pc);
+            // Stopping for any reason ends the step it was for.
+            self.temporary = None;
+            self.breaks_hit += 1;
+            self.paused = true;
+            debug!("breakpoint at {:04X}", pc);
+            return Ok(None);
+        }
+        self.stopped_at = None;
+
+        if self.record_draws {
+            self.record_draw(pc);
+        }
+        // A stalled FX0A runs nothing, it is neither traced nor counted.
+        let waiting = self.chip.is_waiting_for_key();
+        let before = match self.trace {
+            Some(ref trace) if !waiting && trace.wants(pc) => Some(trace::Before::of(&self.chip, self.stats.cycles)),
+            _ => None,
+        };
+        let cycles = match self.chip.execute_timed(self.timing) {
+            Ok(cycles) => cycles,
+            Err(e) => {
+                warn!("stopped: {}", e);
+                self.error = Some(e);
+                self.paused = true;
+                return Err(e);
+            },
+        };
+        if !self.watches.is_empty() {
+            self.record_writes();
+        }
+        if let Some(ref before) = before {
+            let chip = &self.chip;
+            if !self.trace.as_mut().is_some_and(|trace| trace.record(before, chip)) {
+                self.trace = None;
+            }
+        }
+        self.record_timer_write(pc);
+        if self.chip.is_halted() {
+            info!("program exited at {:04X}", pc);
+            self.events.push(Event::Exit);
+            self.paused = true;
+        }
+        if !waiting {
+            self.stats.instructions += 1;
+            self.stats.frame_instructions += 1;
+        }
+        self.stats.cycles += cycles as u64;
+        self.stats.frame_cycles += cycles;
+        Ok(Some(cycles))
+    }
+
+    fn breaks_on_class(&self, pc: usize) -> bool {
+        if self.break_classes.is_empty() {
+            return false;
+        }
+        let ops = match self.chip.memory().get(pc..pc + 2) {
+            Some(op) => (op[0] as u16) << 8 | op[1] as u16,
+            None => return false,
+        };
+        decode(ops).and_then(Class::of).is_some_and(|class| self.break_classes.contains(&class))
+    }
+
+    fn record_draw(&mut self, pc: usize) {
+        let ops = match self.chip.memory().get(pc..pc + 2) {
+            Some(op) => (op[0] as u16) << 8 | op[1] as u16,
+            None => return,
+        };
+        if let Some(Instruction::Draw { x, y, n }) = decode(ops) {
+            self.events.push(Event::Draw { x: self.chip.v(x), y: self.chip.v(y), rows: n });
+        }
+    }
+
+    /// Logs the instruction that just ran at `pc` if it set a timer.
+    fn record_timer_write(&mut self, pc: usize) {
+        let sound = match self.chip.memory().get(pc..pc + 2) {
+            Some(&[hi, 0x15]) if hi >> 4 == 0xF => false,
+            Some(&[hi, 0x18]) if hi >> 4 == 0xF => true,
+            _ => return,
+        };
+        let value = if sound { self.chip.sound_timer() } else { self.chip.delay_timer() };
+        if self.timer_writes.len() == TIMER_LOG {
+            self.timer_writes.pop_front();
+        }
+        self.timer_writes.push_back(TimerWrite { frame: self.stats.frames, pc, sound, value });
+    }
+
+    fn record_writes(&mut self) {
+        let memory = self.chip.memory();
+        for (&addr, old) in self.watches.iter_mut() {
+            let new = memory[addr];
+            if new != *old {
+                self.events.push(Event::Write { addr, old: *old, new });
+                *old = new;
+            }
+        }
+    }
+
+    /// Runs one 60Hz frame worth of cycles unless paused, then applies the
+    /// cheats. A frame cut short by 00FD still ends, presenting what it
+    /// drew, and a program that exited keeps its timers counting down, as
+    /// with `Chip8::run_frame`, so a sound left running stops.
+    pub fn run_frame(&mut self) {
+        if self.paused && !self.chip.is_halted() {
+            return;
+        }
+        self.stats.frame_instructions = 0;
+        self.stats.frame_cycles = 0;
+        let ran = self.step_cycles(self.timing.budget());
+        if matches!(ran, Ok(true)) || self.chip.is_halted() {
+            self.chip.tick_timers();
+            self.stats.frames += 1;
+        }
+        self.cheats.apply(&mut self.chip);
+    }
+
+    /// Machine state as a JSON object, without the framebuffer.
+    pub fn state(&self) -> Json {
+        let chip = &self.chip;
+        let byte = |value: u8| Json::from(value as u64);
+        let rounded = |value: f64, places: i32| Json::Number((value * 10f64.powi(places)).round() / 10f64.powi(places));
+        let overlay = if self.overlay {
+            Json::object(vec![
+                ("fps", rounded(self.rates.fps, 1)),
+                ("ips", rounded(self.rates.ips, 0)),
+                ("speed", rounded(self.rates.speed(), 2)),
+            ])
+        } else {
+            Json::Null
+        };
+        Json::object(vec![
+            ("paused", self.paused.into()),
+            ("halted", chip.is_halted().into()),
+            ("error", self.error.map(|e| e.to_string()).into()),
+            ("pc", chip.pc().into()),
+            ("i", chip.i().into()),
+            ("v", (0..16).map(|n| byte(chip.v(n))).collect::<Vec<_>>().into()),
+            ("stack", chip.stack().iter().map(|&addr| Json::from(addr as u64)).collect::<Vec<_>>().into()),
+            ("dt", byte(chip.delay_timer())),
+            ("st", byte(chip.sound_timer())),
+            ("notice", self.notice().into()),
+            ("notices", self.notices().map(Json::from).collect::<Vec<_>>().into()),
+            ("overlay", overlay),
+        ])
+    }
+
+    pub fn state_json(&self) -> String {
+        self.state().to_string()
+    }
+
+    /// `stats` as a JSON object.
+    pub fn stats_json(&self) -> String {
+        let stats = &self.stats;
+        Json::object(vec![
+            ("frames", stats.frames.into()),
+            ("instructions", stats.instructions.into()),
+            ("cycles", stats.cycles.into()),
+            ("frame_instructions", (stats.frame_instructions as u64).into()),
+            ("frame_cycles", (stats.frame_cycles as u64).into()),
+        ]).to_string()
+    }
+
+    /// The presented frame packed row by row, eight pixels per byte with the
+    /// leftmost pixel in the most significant bit, as hex.
+    pub fn screen_hex(&self) -> String {
+        let mut screen = String::new();
+        for byte in self.chip.display().frame().pixels().chunks(8) {
+            let packed = byte.iter().fold(0u8, |acc, &px| acc << 1 | (px & 1));
+            write!(screen, "{:02x}", packed).unwrap();
+        }
+        screen
+    }
+}
+
+/// Set by SIGINT and SIGTERM once `run` started.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Drives the session at 60 frames per second until `Session::quit` is
+/// set or the process gets SIGINT or SIGTERM, then closes the remotes.
+pub fn run(session: &mut Session, remotes: &mut [Box<dyn Remote>]) {
+    let frame_time = Duration::from_secs(1) / TIMERS_CLOCK;
+    let handler = interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // Storing to an atomic is all the handler does, which is signal safe.
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+
+    let mut frame = 0u64;
+    let mut meter = Meter::new(Instant::now());
+    // When the frame running is due to end.
+    let mut deadline = Instant::now();
+
+    while !session.quit && !INTERRUPTED.load(Ordering::SeqCst) {
+        let frame_start = Instant::now();
+        let _span = trace_span!("frame", frame).entered();
+        frame += 1;
+
+        for remote in remotes.iter_mut() {
+            remote.poll(session);
+        }
+        session.run_frame();
+        session.next_tick = Some(frame_start + frame_time);
+        meter.frame(session);
+        session.drawing = frame.is_multiple_of(session.frame_skip.max(1) as u64);
+        for remote in remotes.iter_mut() {
+            remote.frame(session);
+        }
+        session.frame_time = frame_start.elapsed();
+
+        deadline += frame_time;
+        let now = Instant::now();
+        if let Some(rest) = deadline.checked_duration_since(now) {
+            thread::sleep(rest);
+        } else if now - deadline > frame_time * session.frame_skip.max(1) {
+            // After a stall, carry on from now rather than catching up.
+            // Frames not drawn make up for the time drawing took.
+            deadline = now;
+        }
+    }
+
+    info!("shutting down");
+    for remote in remotes.iter_mut() {
+        remote.close(session);
+    }
+}
@@ -0,0 +1,300 @@
+//! Machine state captured for savestates.
+//!
+//! `Chip8::snapshot` takes one and `Chip8::restore` puts it back. A
+//! snapshot holds what the program can see: registers, memory, timers, the
+//! screen and the random number generator. The held keys belong to the
+//! host and the quirks to the machine configuration, so neither is kept.
+//!
+//! Savestate files wrap a snapshot in a `Savestate`: `SAVESTATE_MAGIC`,
+//! the format version as a little endian `u16`, the `Profile` of the
+//! machine, the ROM hash if known, a thumbnail of the screen and then the
+//! snapshot as `Snapshot::to_bytes` encodes it. Reading a file of an older version
+//! migrates it step by step, one of a newer version fails with
+//! `Error::NewerSavestate` rather than being misread.
+//!
+//! | Version | Changes                                                  |
+//! |---------|----------------------------------------------------------|
+//! | 0       | the bare snapshot, without a header                      |
+//! | 1       | magic, version, profile and ROM hash ahead of it         |
+//! | 2       | a PNG thumbnail before the snapshot, its length first    |
+
+use audio::AUDIO_PATTERN_SIZE;
+use chip8::Chip8;
+use error::Error;
+use screenshot;
+use {REGISTER_SIZE, STACK_SIZE};
+
+/// What a savestate file starts with, from version 1 on.
+pub const SAVESTATE_MAGIC: [u8; 8] = *b"RUCHIP8S";
+/// The format version `Savestate::to_bytes` writes.
+pub const SAVESTATE_VERSION: u16 = 2;
+
+/// A machine's state at one point, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub i: usize,
+    pub pc: usize,
+    pub v: [u8; REGISTER_SIZE],
+    /// Return addresses, oldest first.
+    pub stack: Vec<u16>,
+    /// As long as the memory of the machine it was taken on.
+    pub memory: Vec<u8>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// The register FX0A is waiting to store a key in, if any.
+    pub key_wait: Option<u8>,
+    pub last_polled: Option<u8>,
+    pub halted: bool,
+    pub rpl: [u8; REGISTER_SIZE],
+    pub pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pub pitch: u8,
+    pub hires: bool,
+    /// The working screen, one byte per pixel, in the mode `hires` picks.
+    pub screen: Vec<u8>,
+    pub rng: u64,
+}
+
+/// Reads the fields back in the order `Snapshot::to_bytes` wrote them.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < n {
+            return Err(Error::InvalidSnapshot);
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidSnapshot),
+        }
+    }
+
+    /// A byte that is there when the flag before it is set.
+    fn option_u8(&mut self) -> Result<Option<u8>, Error> {
+        Ok(if self.bool()? { Some(self.u8()?) } else { None })
+    }
+}
+
+impl Snapshot {
+    /// Encodes the snapshot, little endian, the fields in declaration order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.memory.len() + self.screen.len() + 128);
+        let option_u8 = |bytes: &mut Vec<u8>, value: Option<u8>| match value {
+            Some(value) => bytes.extend_from_slice(&[1, value]),
+            None => bytes.push(0),
+        };
+        bytes.extend_from_slice(&(self.i as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.pc as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.v);
+        bytes.push(self.stack.len() as u8);
+        for addr in &self.stack {
+            bytes.extend_from_slice(&addr.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.memory);
+        bytes.push(self.delay_timer);
+        bytes.push(self.sound_timer);
+        option_u8(&mut bytes, self.key_wait);
+        option_u8(&mut bytes, self.last_polled);
+        bytes.push(self.halted as u8);
+        bytes.extend_from_slice(&self.rpl);
+        match self.pattern {
+            Some(ref pattern) => {
+                bytes.push(1);
+                bytes.extend_from_slice(pattern);
+            },
+            None => bytes.push(0),
+        }
+        bytes.push(self.pitch);
+        bytes.push(self.hires as u8);
+        bytes.extend_from_slice(&(self.screen.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.screen);
+        bytes.extend_from_slice(&self.rng.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes what `to_bytes` wrote, failing with `Error::InvalidSnapshot`
+    /// on anything else.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, Error> {
+        let mut r = Reader { bytes };
+        let i = r.u32()? as usize;
+        let pc = r.u32()? as usize;
+        let v = r.array()?;
+        let depth = r.u8()? as usize;
+        if depth > STACK_SIZE {
+            return Err(Error::InvalidSnapshot);
+        }
+        let stack = r.take(depth * 2)?.chunks(2).map(|addr| u16::from_le_bytes([addr[0], addr[1]])).collect();
+        let len = r.u32()? as usize;
+        let memory = r.take(len)?.to_vec();
+        let delay_timer = r.u8()?;
+        let sound_timer = r.u8()?;
+        let key_wait = r.option_u8()?;
+        let last_polled = r.option_u8()?;
+        let halted = r.bool()?;
+        let rpl = r.array()?;
+        let pattern = if r.bool()? { Some(r.array()?) } else { None };
+        let pitch = r.u8()?;
+        let hires = r.bool()?;
+        let len = r.u32()? as usize;
+        let screen = r.take(len)?.to_vec();
+        let rng = r.u64()?;
+        if !r.bytes.is_empty() {
+            return Err(Error::InvalidSnapshot);
+        }
+        Ok(Snapshot {
+            i, pc, v, stack, memory, delay_timer, sound_timer, key_wait, last_polled, halted, rpl, pattern, pitch,
+            hires, screen, rng,
+        })
+    }
+}
+
+/// The configuration of the machine a snapshot was taken on, which the
+/// machine restoring it must share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    pub memory_size: usize,
+    pub shift_vy: bool,
+}
+
+impl Profile {
+    pub fn of(chip: &Chip8) -> Profile {
+        Profile { memory_size: chip.memory().len(), shift_vy: chip.shift_vy() }
+    }
+}
+
+/// FNV-1a, enough to tell two different ROMs apart. Hosts key savestates
+/// and RPL flags with it.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// A snapshot as kept in a file, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Savestate {
+    pub profile: Profile,
+    /// The hash the host gave the ROM, `None` in states migrated from
+    /// version 0.
+    pub rom_hash: Option<u64>,
+    /// The presented frame as a 64x32 PNG, 128x64 frames scaled down.
+    /// `None` in states migrated from before version 2.
+    pub thumbnail: Option<Vec<u8>>,
+    pub snapshot: Snapshot,
+}
+
+impl Savestate {
+    /// A savestate of `chip` running the ROM with hash `rom_hash`.
+    pub fn of(chip: &Chip8, rom_hash: Option<u64>) -> Savestate {
+        Savestate {
+            profile: Profile::of(chip),
+            rom_hash,
+            thumbnail: Some(screenshot::to_png(chip.display(), 1)),
+            snapshot: chip.snapshot(),
+        }
+    }
+
+    /// Encodes the savestate in format `SAVESTATE_VERSION`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SAVESTATE_MAGIC.to_vec();
+        bytes.extend_from_slice(&SAVESTATE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.profile.memory_size as u32).to_le_bytes());
+        bytes.push(self.profile.shift_vy as u8);
+        match self.rom_hash {
+            Some(hash) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&hash.to_le_bytes());
+            },
+            None => bytes.push(0),
+        }
+        let thumbnail = self.thumbnail.as_deref().unwrap_or_default();
+        bytes.extend_from_slice(&(thumbnail.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(thumbnail);
+        bytes.extend_from_slice(&self.snapshot.to_bytes());
+        bytes
+    }
+
+    /// Decodes a savestate of any version up to `SAVESTATE_VERSION`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Savestate, Error> {
+        if !bytes.starts_with(&SAVESTATE_MAGIC) {
+            return Savestate::from_version_0(bytes);
+        }
+        let mut r = Reader { bytes: &bytes[SAVESTATE_MAGIC.len()..] };
+        let version = r.u16()?;
+        if version > SAVESTATE_VERSION {
+            return Err(Error::NewerSavestate { version });
+        }
+        if version == 0 {
+            return Err(Error::InvalidSnapshot);
+        }
+        let memory_size = r.u32()? as usize;
+        let shift_vy = r.bool()?;
+        let rom_hash = if r.bool()? { Some(r.u64()?) } else { None };
+        // Version 1 went straight on to the snapshot.
+        let thumbnail = match version {
+            1 => None,
+            _ => match r.u32()? as usize {
+                0 => None,
+                len => Some(r.take(len)?.to_vec()),
+            },
+        };
+        let snapshot = Snapshot::from_bytes(r.bytes)?;
+        if snapshot.memory.len() != memory_size {
+            return Err(Error::InvalidSnapshot);
+        }
+        Ok(Savestate { profile: Profile { memory_size, shift_vy }, rom_hash, thumbnail, snapshot })
+    }
+
+    /// Version 0 was the bare snapshot. The only way to change the quirk
+    /// back then was `Chip8::set_shift_vy`, which the emulator never
+    /// called, and the ROM was only known from where the file was.
+    fn from_version_0(bytes: &[u8]) -> Result<Savestate, Error> {
+        let snapshot = Snapshot::from_bytes(bytes)?;
+        debug!("migrating a version 0 savestate");
+        let profile = Profile { memory_size: snapshot.memory.len(), shift_vy: false };
+        Ok(Savestate { profile, rom_hash: None, thumbnail: None, snapshot })
+    }
+
+    /// Restores the snapshot on `chip`, failing with
+    /// `Error::OtherMachine` when the profiles differ.
+    pub fn restore(&self, chip: &mut Chip8) -> Result<(), Error> {
+        if Profile::of(chip) != self.profile {
+            return Err(Error::OtherMachine {
+                memory_size: self.profile.memory_size,
+                shift_vy: self.profile.shift_vy,
+            });
+        }
+        chip.restore(&self.snapshot)
+    }
+}
@@ -0,0 +1,131 @@
+//! Source maps tying a ROM back to the Octo source it was built from.
+//!
+//! A source map lists one instruction per line as `ADDR=FILE:LINE`, the
+//! address in hexadecimal and the line counted from 1, with `#` starting a
+//! comment:
+//!
+//! ```text
+//! 200=game.8o:12
+//! 202=game.8o:13
+//! 2A0=sprites.8o:4
+//! ```
+//!
+//! Source files are looked up next to the map. A missing one only means
+//! its lines cannot be shown.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A place in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    locations: BTreeMap<usize, Location>,
+    /// Lines of the source files that could be read, by file name.
+    sources: BTreeMap<String, Vec<String>>,
+    /// Where the map is, which the file names are relative to.
+    dir: PathBuf,
+}
+
+impl SourceMap {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut locations = BTreeMap::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let location = line.split_once('=').and_then(|(addr, place)| {
+                let (file, number) = place.trim().rsplit_once(':')?;
+                Some((addr.trim(), file, number))
+            });
+            let (addr, file, number) = location.ok_or_else(|| format!("line {}: expected ADDR=FILE:LINE", n + 1))?;
+            let addr = usize::from_str_radix(addr, 16)
+                .map_err(|_| format!("line {}: invalid address '{}'", n + 1, addr))?;
+            let number = number.parse().ok().filter(|&number| number > 0)
+                .ok_or_else(|| format!("line {}: invalid line number '{}'", n + 1, number))?;
+            locations.insert(addr, Location { file: file.to_owned(), line: number });
+        }
+        Ok(SourceMap { locations, sources: BTreeMap::new(), dir: PathBuf::new() })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let mut map = SourceMap::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        map.dir = dir.to_owned();
+        let files: Vec<String> = map.locations.values().map(|location| location.file.clone()).collect();
+        for file in files {
+            if map.sources.contains_key(&file) {
+                continue;
+            }
+            match fs::read_to_string(dir.join(&file)) {
+                Ok(source) => { map.sources.insert(file, source.lines().map(str::to_owned).collect()); },
+                Err(e) => warn!("cannot read {}: {}", file, e),
+            }
+        }
+        Ok(map)
+    }
+
+    /// The map stored next to `rom`, with its extension replaced by
+    /// `.map`, or none when there is no such file.
+    pub fn for_rom(rom: &Path) -> Result<Self, String> {
+        let path = rom.with_extension("map");
+        match fs::metadata(&path) {
+            Ok(_) => SourceMap::load(&path),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(SourceMap::default()),
+            Err(e) => Err(format!("cannot read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// The map of a program compiled from `source`, read from `path`, given
+    /// the line each address came from.
+    pub fn for_source(path: &Path, source: &str, lines: &BTreeMap<usize, usize>) -> Self {
+        let file = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let locations = lines.iter()
+            .map(|(&addr, &line)| (addr, Location { file: file.clone(), line }))
+            .collect();
+        let mut sources = BTreeMap::new();
+        sources.insert(file, source.lines().map(str::to_owned).collect());
+        let dir = path.parent().unwrap_or_else(|| Path::new("")).to_owned();
+        SourceMap { locations, sources, dir }
+    }
+
+    /// Where the code at `addr` came from: the closest mapped address at or
+    /// below it, since a macro can expand to several instructions.
+    pub fn location(&self, addr: usize) -> Option<&Location> {
+        self.locations.range(..=addr).next_back().map(|(_, location)| location)
+    }
+
+    /// The first address built from `line` of `file`.
+    pub fn addr(&self, file: &str, line: usize) -> Option<usize> {
+        self.locations.iter()
+            .find(|&(_, location)| location.file == file && location.line == line)
+            .map(|(&addr, _)| addr)
+    }
+
+    /// The mapped file `path` refers to, comparing the trailing components
+    /// so that an absolute path finds the name the map uses.
+    pub fn file_for(&self, path: &str) -> Option<&str> {
+        self.locations.values()
+            .map(|location| location.file.as_str())
+            .find(|file| Path::new(path).ends_with(file))
+    }
+
+    /// Where `file` is on disk.
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    /// The text of `line` of `file`, if the file could be read.
+    pub fn text(&self, file: &str, line: usize) -> Option<&str> {
+        self.sources.get(file).and_then(|lines| lines.get(line - 1)).map(String::as_str)
+    }
+}
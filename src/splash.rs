@@ -0,0 +1,64 @@
+//! What runs when the emulator starts with no ROM and no ROMs to choose from.
+//!
+//! Instead of a black screen, frontends show a program of the emulator's
+//! own: the name in large letters with "DROP A ROM HERE" blinking under it,
+//! until a remote loads a ROM. It is written in Octo and compiled at
+//! startup, like the boot menu, so it goes through the machine, timers and
+//! display the way a game does.
+
+use std::fmt::Write;
+
+use ruchip8::octo;
+
+use menu::{self, GLYPHS};
+use session::Session;
+
+const LOGO: &str = "RUCHIP8";
+const TEXT: &str = "DROP A ROM HERE";
+
+/// Frames the text stays on and off for.
+const BLINK: u8 = 30;
+
+/// A 3x5 glyph at twice the size, 6x10.
+fn doubled(rows: [u8; 5]) -> [u8; 10] {
+    let mut out = [0; 10];
+    for (n, &row) in rows.iter().enumerate() {
+        let wide = (0..3).filter(|bit| row & (0x80 >> bit) != 0).fold(0, |wide, bit| wide | 0xC0 >> (bit * 2));
+        out[n * 2] = wide;
+        out[n * 2 + 1] = wide;
+    }
+    out
+}
+
+/// The splash as an Octo program.
+fn source() -> String {
+    let mut out = String::new();
+    for (n, c) in LOGO.chars().enumerate() {
+        let bytes: Vec<String> = doubled(GLYPHS[menu::glyph(c)].1).iter().map(|row| format!("0x{:02X}", row)).collect();
+        writeln!(out, ": logo-{} {}", n, bytes.join(" ")).unwrap();
+    }
+    menu::glyphs(&mut out);
+    out.push_str(": text\n");
+    menu::print_at(&mut out, TEXT, (64 - TEXT.len() * 4) / 2, 20);
+    out.push_str("  return\n: main\n  clear\n");
+    let x = (64 - LOGO.len() * 8) / 2 + 1;
+    writeln!(out, "  v1 := {}  v2 := 4", x).unwrap();
+    for n in 0..LOGO.len() {
+        writeln!(out, "  i := logo-{}  sprite v1 v2 10  v1 += 8", n).unwrap();
+    }
+    // Drawing the text again takes it off.
+    writeln!(out, "  loop
+    text
+    v0 := {}  delay := v0
+    loop
+      v0 := delay
+    while v0 != 0 again
+  again", BLINK).unwrap();
+    out
+}
+
+/// Loads the splash.
+pub fn show(session: &mut Session) -> Result<(), String> {
+    let program = octo::compile(&source()).map_err(|e| format!("splash line {}: {}", e.line, e.message))?;
+    session.load(&program.rom).map_err(|e| e.to_string())
+}
@@ -0,0 +1,250 @@
+//! The screen drawn in the terminal the emulator runs in.
+//!
+//! `--plugin terminal` draws each frame at the top of the terminal, under a
+//! line with the title and the latest notice, and takes keys typed into it,
+//! so a game plays over SSH with nothing else installed. Terminals that
+//! show images get the pixels as they are: the Kitty graphics protocol in
+//! Kitty, WezTerm and Ghostty, Sixel where the terminal says it has it when
+//! asked, foot, xterm -ti vt340 and mlterm among them. Anywhere else the
+//! screen is drawn with half blocks in 24 bit color, two pixels to a
+//! character. `--plugin terminal=braille` packs 2x4 pixels into each
+//! Braille character instead, 32x8 characters for the whole screen, small
+//! enough for a tmux pane or a status bar. `=kitty`, `=sixel` or `=blocks`
+//! picks one of the others.
+//!
+//! Keys map like `evdev` maps them, 1234 QWER ASDF ZXCV onto the keypad.
+//! Terminals only tell when a key goes down, so each press holds the key
+//! for a few frames, and autorepeat keeps it held. Ctrl+C quits. The
+//! terminal takes the keys, so this goes with neither `--stdio` nor
+//! `--debug`.
+
+use std::env;
+use std::io::{self, Write};
+use std::mem;
+
+use libc;
+
+use ruchip8::screenshot;
+
+use json::base64;
+use session::{Remote, Session};
+
+/// Characters in the order of the keypad keys they stand for.
+const KEYMAP: &[u8; 16] = b"x123qweasdzc4rfv";
+
+/// Frames a key stays down after the terminal sends it.
+const HOLD: u8 = 8;
+
+/// Image pixels to a CHIP-8 pixel in 64x32.
+const SCALE: usize = 8;
+
+/// How long the terminal has to answer whether it has Sixel, in ms.
+const QUERY_TIMEOUT: libc::c_int = 200;
+
+/// Kitty takes images base64 encoded in pieces of at most this size.
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Kitty,
+    Sixel,
+    Blocks,
+    Braille,
+}
+
+impl Mode {
+    pub fn parse(text: &str) -> Option<Mode> {
+        match text {
+            "kitty" => Some(Mode::Kitty),
+            "sixel" => Some(Mode::Sixel),
+            "blocks" => Some(Mode::Blocks),
+            "braille" => Some(Mode::Braille),
+            _ => None,
+        }
+    }
+}
+
+/// The bytes waiting on stdin, without waiting more than `timeout` ms for
+/// the first.
+fn read_stdin(timeout: libc::c_int) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut wait = timeout;
+    loop {
+        let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+        if unsafe { libc::poll(&mut fd, 1, wait) } <= 0 {
+            return out;
+        }
+        let mut buf = [0u8; 256];
+        let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        if n <= 0 {
+            return out;
+        }
+        out.extend_from_slice(&buf[..n as usize]);
+        wait = 0;
+    }
+}
+
+/// What the terminal can draw, from its name or, for Sixel, by asking it.
+fn detect() -> Mode {
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    if env::var_os("KITTY_WINDOW_ID").is_some() || var("TERM").contains("kitty")
+        || ["WezTerm", "ghostty"].contains(&var("TERM_PROGRAM").as_str()) {
+        return Mode::Kitty;
+    }
+    // Primary device attributes, answered as ESC [ ? 62 ; 4 ; ... c with
+    // 4 for Sixel.
+    print!("\x1b[c");
+    let _ = io::stdout().flush();
+    let reply = read_stdin(QUERY_TIMEOUT);
+    let reply = String::from_utf8_lossy(&reply);
+    let sixel = reply.split("\x1b[?").nth(1)
+        .and_then(|reply| reply.split('c').next())
+        .is_some_and(|attributes| attributes.split(';').any(|attribute| attribute == "4"));
+    if sixel { Mode::Sixel } else { Mode::Blocks }
+}
+
+pub struct Terminal {
+    mode: Mode,
+    /// The terminal settings to put back.
+    saved: libc::termios,
+    /// Frames each key stays down for.
+    held: [u8; 16],
+    /// The pixels and status line last drawn.
+    last: (Vec<u8>, String),
+}
+
+pub fn remote(arg: Option<&str>) -> Result<Box<dyn Remote>, String> {
+    let mode = match arg {
+        Some(text) => Some(Mode::parse(text).ok_or_else(|| format!("no terminal mode '{}', try kitty, sixel, blocks or braille", text))?),
+        None => None,
+    };
+    if unsafe { libc::isatty(libc::STDIN_FILENO) == 0 || libc::isatty(libc::STDOUT_FILENO) == 0 } {
+        return Err("--plugin terminal needs a terminal".to_owned());
+    }
+    let saved = unsafe {
+        let mut saved: libc::termios = mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut saved) != 0 {
+            return Err(format!("cannot read the terminal settings: {}", io::Error::last_os_error()));
+        }
+        // Keys as they are typed, unechoed. Ctrl+C still interrupts.
+        let mut raw = saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw);
+        saved
+    };
+    let mode = mode.unwrap_or_else(detect);
+    info!("drawing the screen in the terminal as {:?}", mode);
+    // Clear the screen and hide the cursor.
+    print!("\x1b[2J\x1b[?25l");
+    Ok(Box::new(Terminal { mode, saved, held: [0; 16], last: (Vec::new(), String::new()) }))
+}
+
+impl Terminal {
+    /// The screen as half blocks, the upper pixel in the foreground color.
+    fn blocks(session: &Session, out: &mut String) {
+        let frame = session.chip.display().frame();
+        let color = |x: usize, y: usize| session.palette.color(frame.pixel(x, y) as u8);
+        let mut last = None;
+        for y in (0..frame.height()).step_by(2) {
+            for x in 0..frame.width() {
+                let colors = (color(x, y), color(x, y + 1));
+                if last != Some(colors) {
+                    let ([r, g, b], [br, bg, bb]) = colors;
+                    out.push_str(&format!("\x1b[38;2;{};{};{};48;2;{};{};{}m", r, g, b, br, bg, bb));
+                    last = Some(colors);
+                }
+                out.push('▀');
+            }
+            out.push_str("\x1b[0m\n");
+            last = None;
+        }
+    }
+
+    /// The screen as Braille, lit pixels in the first plane's color.
+    fn braille(session: &Session, out: &mut String) {
+        let ([r, g, b], [br, bg, bb]) = (session.palette.color(1), session.palette.color(0));
+        for line in screenshot::to_braille(session.chip.display()).lines() {
+            out.push_str(&format!("\x1b[38;2;{};{};{};48;2;{};{};{}m{}\x1b[0m\n", r, g, b, br, bg, bb, line));
+        }
+    }
+
+    /// The screen as a PNG sent with the Kitty graphics protocol, in place
+    /// of the one drawn before.
+    fn kitty(session: &Session, out: &mut String) {
+        let png = screenshot::to_png_in(session.chip.display(), SCALE, &session.palette);
+        let data = base64(&png);
+        let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+        for (n, chunk) in chunks.iter().enumerate() {
+            let more = (n + 1 < chunks.len()) as u8;
+            if n == 0 {
+                out.push_str(&format!("\x1b_Ga=T,f=100,i=1,p=1,q=2,C=1,m={};", more));
+            } else {
+                out.push_str(&format!("\x1b_Gm={};", more));
+            }
+            out.push_str(&String::from_utf8_lossy(chunk));
+            out.push_str("\x1b\\");
+        }
+    }
+}
+
+impl Remote for Terminal {
+    fn poll(&mut self, session: &mut Session) {
+        for (key, held) in self.held.iter_mut().enumerate() {
+            if *held > 0 {
+                *held -= 1;
+                if *held == 0 {
+                    session.set_key(key as u8, false);
+                }
+            }
+        }
+        for byte in read_stdin(0) {
+            // What follows an escape is a sequence for another key.
+            if byte == 0x1b {
+                break;
+            }
+            if let Some(key) = KEYMAP.iter().position(|&c| c == byte.to_ascii_lowercase()) {
+                if self.held[key] == 0 {
+                    session.set_key(key as u8, true);
+                }
+                self.held[key] = HOLD;
+            }
+        }
+    }
+
+    fn frame(&mut self, session: &Session) {
+        if !session.drawing {
+            return;
+        }
+        let pixels = session.chip.display().frame().pixels().to_vec();
+        let status = match session.notice() {
+            Some(notice) => format!("{} | {}", session.title(), notice),
+            None => session.title(),
+        };
+        if (&pixels, &status) == (&self.last.0, &self.last.1) {
+            return;
+        }
+        let mut out = format!("\x1b[H{}\x1b[K\n", status);
+        match self.mode {
+            Mode::Kitty => Terminal::kitty(session, &mut out),
+            Mode::Sixel => out.push_str(&screenshot::to_sixel(session.chip.display(), SCALE, &session.palette)),
+            Mode::Blocks => Terminal::blocks(session, &mut out),
+            Mode::Braille => Terminal::braille(session, &mut out),
+        }
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(out.as_bytes()).and_then(|_| stdout.flush());
+        self.last = (pixels, status);
+    }
+
+    fn close(&mut self, _session: &Session) {
+        if self.mode == Mode::Kitty {
+            print!("\x1b_Ga=d,d=i,i=1,q=2\x1b\\");
+        }
+        print!("\x1b[0m\x1b[?25h\x1b[2J\x1b[H");
+        let _ = io::stdout().flush();
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! How long instructions take, for pacing the machine.
+//!
+//! A frame gets a budget of cycles and runs instructions until their costs
+//! use it up, so "N instructions per frame" is just a budget of N with every
+//! instruction costing one.
+
+use instruction::Instruction;
+use {CPU_CLOCK, TIMERS_CLOCK};
+
+/// Machine cycles the COSMAC VIP has in a 60Hz frame: a 1.76MHz clock and
+/// 8 clocks per machine cycle. Time spent in the display interrupt is not
+/// taken out.
+pub const VIP_FRAME_CYCLES: u32 = 1_760_900 / 8 / TIMERS_CLOCK;
+
+/// Instructions per frame SCHIP games are usually tuned for.
+pub const SCHIP_FRAME_INSTRUCTIONS: u32 = 30;
+
+/// Machine cycles an instruction takes on the COSMAC VIP interpreter.
+///
+/// Close to measurements of the original rather than exact: skips cost the
+/// same taken or not, BCD does not depend on the digits and draws leave out
+/// waiting for the display interrupt.
+pub fn cycles_for(instruction: Instruction) -> u32 {
+    use instruction::Instruction::*;
+    match instruction {
+        Cls => 24,
+        // Not on the VIP, priced like CLS.
+        ScrollDown { .. } | ScrollUp { .. } | ScrollRight | ScrollLeft | Exit | LowRes | HighRes => 24,
+        Ret => 10,
+        Jump { .. } => 12,
+        Call { .. } => 26,
+        // What the routine costs is up to the routine.
+        Sys { .. } => 26,
+        SkipEqImm { .. } | SkipNeImm { .. } => 10,
+        SkipEqReg { .. } | SkipNeReg { .. } => 14,
+        LoadImm { .. } => 6,
+        AddImm { .. } => 10,
+        Move { .. } | Or { .. } | And { .. } | Xor { .. } | Add { .. } |
+        Sub { .. } | Shr { .. } | SubN { .. } | Shl { .. } => 44,
+        LoadIndex { .. } => 12,
+        JumpV0 { .. } => 22,
+        Random { .. } => 36,
+        Draw { n, .. } => 68 + 46 * n as u32,
+        SkipKey { .. } | SkipNotKey { .. } => 14,
+        ReadDelay { .. } | WaitKey { .. } | SetDelay { .. } | SetSound { .. } => 10,
+        AddIndex { .. } | Font { .. } | BigFont { .. } => 16,
+        Bcd { .. } => 80,
+        Store { x } | Load { x } => 14 + 14 * (x as u32 + 1),
+        // Not on the VIP, priced like FX55.
+        SaveFlags { x } | LoadFlags { x } => 14 + 14 * (x as u32 + 1),
+        // XO-CHIP, priced like the VIP instructions doing the same work.
+        Audio => 14 + 14 * 16,
+        LongIndex { .. } => 2 * 12,
+        StoreRange { x, y } | LoadRange { x, y } => 14 + 14 * (x.max(y) - x.min(y) + 1) as u32,
+        Pitch { .. } => 10,
+    }
+}
+
+/// How many cycles a frame has and what each instruction costs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// A fixed number of instructions per frame.
+    Fixed(u32),
+    /// COSMAC VIP machine cycles, see `cycles_for`.
+    Vip,
+    /// The faster pace of SCHIP on the HP48, a fixed
+    /// `SCHIP_FRAME_INSTRUCTIONS` per frame.
+    Schip,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing::Fixed(CPU_CLOCK / TIMERS_CLOCK)
+    }
+}
+
+impl Timing {
+    /// Cycles available in one frame.
+    pub fn budget(&self) -> u32 {
+        match *self {
+            Timing::Fixed(n) => n,
+            Timing::Vip => VIP_FRAME_CYCLES,
+            Timing::Schip => SCHIP_FRAME_INSTRUCTIONS,
+        }
+    }
+
+    /// Cycles `instruction` takes out of the budget.
+    pub fn cost(&self, instruction: Instruction) -> u32 {
+        match *self {
+            Timing::Fixed(_) | Timing::Schip => 1,
+            Timing::Vip => cycles_for(instruction),
+        }
+    }
+
+    /// Parses `vip`, `schip` or a number of instructions per frame.
+    pub fn parse(text: &str) -> Option<Timing> {
+        match text {
+            "vip" => Some(Timing::Vip),
+            "schip" => Some(Timing::Schip),
+            n => n.parse().ok().filter(|&n| n > 0).map(Timing::Fixed),
+        }
+    }
+}
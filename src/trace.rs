@@ -0,0 +1,294 @@
+//! Instruction traces written to a file for offline analysis.
+//!
+//! `--trace FILE` writes a record for every instruction the machine runs:
+//! the cycle count before it, PC, the opcode, its mnemonic and the registers
+//! it changed with their new values. A FILE ending in `.csv` gets CSV with
+//! a header row, any other JSON Lines:
+//!
+//! ```text
+//! {"cycle":120,"pc":"0204","opcode":"7001","mnemonic":"ADD V0, 01","changes":{"V0":6}}
+//! ```
+//!
+//! In CSV the changes go in one column, as `V0=06 I=0300`. Traces grow
+//! fast, so `--trace-range START-END` keeps only instructions at those
+//! addresses, as often as given, and `--trace-max N` stops after N records.
+//!
+//! `ruchip8 difftest` reads traces back, see `difftest`.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use ruchip8::{decode, decode_long, Chip8, Instruction, LONG_PREFIX, REGISTER_SIZE};
+
+use json::Json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    JsonLines,
+    Csv,
+}
+
+impl Format {
+    fn of(path: &Path) -> Format {
+        if path.extension().is_some_and(|extension| extension == "csv") { Format::Csv } else { Format::JsonLines }
+    }
+}
+
+/// What an instruction can change, taken before and after it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Registers {
+    v: [u8; REGISTER_SIZE],
+    i: usize,
+    delay_timer: u8,
+    sound_timer: u8,
+}
+
+impl Registers {
+    fn of(chip: &Chip8) -> Registers {
+        let mut v = [0; REGISTER_SIZE];
+        for (n, register) in v.iter_mut().enumerate() {
+            *register = chip.v(n as u8);
+        }
+        Registers { v, i: chip.i(), delay_timer: chip.delay_timer(), sound_timer: chip.sound_timer() }
+    }
+
+    /// The registers that differ in `after`, with their new values.
+    fn changes(&self, after: &Registers) -> Vec<(String, usize)> {
+        let mut changes: Vec<(String, usize)> = (0..REGISTER_SIZE)
+            .filter(|&n| self.v[n] != after.v[n])
+            .map(|n| (format!("V{:X}", n), after.v[n] as usize))
+            .collect();
+        if self.i != after.i {
+            changes.push(("I".to_owned(), after.i));
+        }
+        if self.delay_timer != after.delay_timer {
+            changes.push(("DT".to_owned(), after.delay_timer as usize));
+        }
+        if self.sound_timer != after.sound_timer {
+            changes.push(("ST".to_owned(), after.sound_timer as usize));
+        }
+        changes
+    }
+}
+
+/// One instruction of a trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub cycle: u64,
+    pub pc: usize,
+    pub opcode: String,
+    pub mnemonic: String,
+    /// Registers the instruction changed, by name, with their new values.
+    pub changes: Vec<(String, usize)>,
+}
+
+impl Record {
+    /// The record of the instruction `before` was taken for, which left
+    /// the machine as `chip` is.
+    pub fn of(before: &Before, chip: &Chip8) -> Record {
+        Record {
+            cycle: before.cycle,
+            pc: before.pc,
+            opcode: before.opcode.clone(),
+            mnemonic: before.instruction.map_or_else(String::new, |instruction| instruction.to_string()),
+            changes: before.registers.changes(&Registers::of(chip)),
+        }
+    }
+
+    /// `changes` formatted as in CSV.
+    pub fn changes_text(&self) -> String {
+        let changes: Vec<String> = self.changes.iter().map(|(name, value)| match name.as_str() {
+            "I" => format!("I={:04X}", value),
+            _ => format!("{}={:02X}", name, value),
+        }).collect();
+        changes.join(" ")
+    }
+
+    fn to_json(&self) -> Json {
+        let changes = Json::object(self.changes.iter().map(|(name, value)| (name.as_str(), (*value).into())));
+        Json::object(vec![
+            ("cycle", self.cycle.into()),
+            ("pc", format!("{:04X}", self.pc).into()),
+            ("opcode", self.opcode.as_str().into()),
+            ("mnemonic", self.mnemonic.as_str().into()),
+            ("changes", changes),
+        ])
+    }
+
+    fn from_json(line: &str) -> Result<Record, String> {
+        let record = Json::parse(line)?;
+        let text = |name: &str| record.get(name).and_then(Json::as_str).unwrap_or_default().to_owned();
+        let changes = match record.get("changes") {
+            Some(Json::Object(members)) => members.iter()
+                .map(|(name, value)| value.as_u64().map(|value| (name.clone(), value as usize)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or("changes must be whole numbers")?,
+            _ => Vec::new(),
+        };
+        Ok(Record {
+            cycle: record.get("cycle").and_then(Json::as_u64).unwrap_or(0),
+            pc: usize::from_str_radix(&text("pc"), 16).map_err(|_| "no hexadecimal pc")?,
+            opcode: text("opcode"),
+            mnemonic: text("mnemonic"),
+            changes,
+        })
+    }
+
+    fn from_csv(line: &str) -> Result<Record, String> {
+        let mut fields = line.splitn(4, ',');
+        let mut field = || fields.next().ok_or("missing fields");
+        let cycle = field()?.parse().map_err(|_| "invalid cycle")?;
+        let pc = usize::from_str_radix(field()?, 16).map_err(|_| "invalid pc")?;
+        let opcode = field()?.to_owned();
+        let rest = field()?;
+        // The mnemonic is quoted and has commas in it.
+        let (mnemonic, changes) = rest.strip_prefix('"')
+            .and_then(|rest| rest.split_once("\","))
+            .ok_or("unquoted mnemonic")?;
+        let changes = changes.split_whitespace()
+            .map(|change| {
+                let (name, value) = change.split_once('=')?;
+                Some((name.to_owned(), usize::from_str_radix(value, 16).ok()?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or("invalid changes")?;
+        Ok(Record { cycle, pc, opcode, mnemonic: mnemonic.to_owned(), changes })
+    }
+}
+
+/// Reads a trace file back, record by record.
+pub struct Reader {
+    lines: Lines<BufReader<File>>,
+    format: Format,
+    /// Line number of the last line read.
+    line: usize,
+}
+
+impl Reader {
+    pub fn open(path: &Path) -> Result<Reader, String> {
+        let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+        let format = Format::of(path);
+        let mut reader = Reader { lines: BufReader::new(file).lines(), format, line: 0 };
+        if format == Format::Csv {
+            reader.line += 1;
+            reader.lines.next();
+        }
+        Ok(reader)
+    }
+}
+
+impl Iterator for Reader {
+    type Item = Result<Record, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.to_string())),
+            };
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = match self.format {
+                Format::JsonLines => Record::from_json(&line),
+                Format::Csv => Record::from_csv(&line),
+            };
+            let line = self.line;
+            return Some(record.map_err(|e| format!("line {}: {}", line, e)));
+        }
+    }
+}
+
+/// The machine just before a traced instruction runs.
+pub struct Before {
+    cycle: u64,
+    pc: usize,
+    /// The opcode as hexadecimal, both words of F000 NNNN.
+    opcode: String,
+    instruction: Option<Instruction>,
+    registers: Registers,
+}
+
+impl Before {
+    pub fn of(chip: &Chip8, cycle: u64) -> Before {
+        let pc = chip.pc();
+        let memory = chip.memory();
+        let word = |at: usize| memory.get(at..at + 2).map(|op| (op[0] as u16) << 8 | op[1] as u16);
+        let (opcode, instruction) = match word(pc) {
+            Some(LONG_PREFIX) => {
+                let next = word(pc + 2).unwrap_or(0);
+                (format!("{:04X}{:04X}", LONG_PREFIX, next), decode_long(LONG_PREFIX, next))
+            },
+            Some(ops) => (format!("{:04X}", ops), decode(ops)),
+            None => (String::new(), None),
+        };
+        Before { cycle, pc, opcode, instruction, registers: Registers::of(chip) }
+    }
+
+    /// The cycle count the instruction started at.
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+}
+
+pub struct Trace {
+    out: BufWriter<File>,
+    format: Format,
+    /// Addresses to trace, all when empty.
+    ranges: Vec<RangeInclusive<usize>>,
+    max: Option<u64>,
+    records: u64,
+}
+
+impl Trace {
+    pub fn create(path: &Path, ranges: Vec<RangeInclusive<usize>>, max: Option<u64>) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("cannot create {}: {}", path.display(), e))?;
+        let format = Format::of(path);
+        let mut out = BufWriter::new(file);
+        if format == Format::Csv {
+            writeln!(out, "cycle,pc,opcode,mnemonic,changes").map_err(|e| e.to_string())?;
+        }
+        Ok(Trace { out, format, ranges, max, records: 0 })
+    }
+
+    /// Whether the instruction at `pc` is to be traced.
+    pub fn wants(&self, pc: usize) -> bool {
+        self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(&pc))
+    }
+
+    /// Writes the record of the instruction `before` was taken for, which
+    /// left the machine as `chip` is. Returns false when the trace is done,
+    /// full or failing.
+    pub fn record(&mut self, before: &Before, chip: &Chip8) -> bool {
+        let record = Record::of(before, chip);
+        let result = match self.format {
+            Format::JsonLines => writeln!(self.out, "{}", record.to_json()),
+            // Mnemonics have commas in them.
+            Format::Csv => writeln!(self.out, "{},{:04X},{},\"{}\",{}",
+                                    record.cycle, record.pc, record.opcode, record.mnemonic, record.changes_text()),
+        };
+        if let Err(e) = result {
+            warn!("trace stopped: {}", e);
+            return false;
+        }
+        self.records += 1;
+        if self.max.is_some_and(|max| self.records >= max) {
+            info!("trace stopped after {} records", self.records);
+            return false;
+        }
+        true
+    }
+}
+
+/// Parses `START-END`, two hexadecimal addresses.
+pub fn parse_range(text: &str) -> Result<RangeInclusive<usize>, String> {
+    let addr = |text: &str| usize::from_str_radix(text.trim_start_matches("0x"), 16).ok();
+    let (start, end) = text.split_once('-')
+        .and_then(|(start, end)| Some((addr(start)?, addr(end)?)))
+        .filter(|(start, end)| start <= end)
+        .ok_or_else(|| format!("invalid address range '{}', expected START-END", text))?;
+    Ok(start..=end)
+}
@@ -0,0 +1,120 @@
+//! Static checks on a ROM, without running it.
+//!
+//! `ruchip8 validate [--level chip8|schip|xochip] ROM` walks the code
+//! reachable from `PROGRAM_START` and warns about opcodes the machine does
+//! not have, jumps and calls leaving the program or landing on odd
+//! addresses, and sprites drawn from past the end of memory. Fails when
+//! there is any warning, so it can gate a build.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use ruchip8::{Instruction, Level, MEMORY_SIZE, PROGRAM_START, XO_MEMORY_SIZE};
+
+use info;
+
+const USAGE: &str = "usage: ruchip8 validate [--level chip8|schip|xochip] ROM";
+
+/// Instructions looked back through for the ANNN a DXYN draws from.
+const INDEX_LOOKBACK: usize = 16;
+
+/// The address I holds at `at`, when straight-line code right before it
+/// set it with ANNN or F000 NNNN.
+fn index_at(code: &BTreeMap<usize, Option<Instruction>>, at: usize) -> Option<usize> {
+    use ruchip8::Instruction::*;
+    let mut addr = at;
+    for _ in 0..INDEX_LOOKBACK {
+        // F000 NNNN ends 4 bytes back, anything else 2.
+        let (prev, instruction) = [2, 4].iter()
+            .filter_map(|&back| addr.checked_sub(back))
+            .filter_map(|prev| code.get(&prev).cloned().flatten().map(|i| (prev, i)))
+            .find(|&(prev, i)| prev + i.size() == addr)?;
+        match instruction {
+            LoadIndex { addr } | LongIndex { addr } => return Some(addr as usize),
+            // Anything else that moves I or can be jumped over.
+            AddIndex { .. } | Font { .. } | BigFont { .. } | Store { .. } | Load { .. } |
+            SkipEqImm { .. } | SkipNeImm { .. } | SkipEqReg { .. } | SkipNeReg { .. } |
+            SkipKey { .. } | SkipNotKey { .. } | Jump { .. } | JumpV0 { .. } | Ret | Call { .. } => return None,
+            _ => addr = prev,
+        }
+    }
+    None
+}
+
+/// Warnings for `rom` on a machine of `level`, by address.
+pub fn check(rom: &[u8], level: Level) -> Vec<(usize, String)> {
+    use ruchip8::Instruction::*;
+    let memory_size = if level == Level::XoChip { XO_MEMORY_SIZE } else { MEMORY_SIZE };
+    let end = PROGRAM_START + rom.len();
+    let code = info::code(rom);
+    // Jumps out of the program are reported where they are made.
+    let targets: BTreeSet<usize> = code.values()
+        .filter_map(|&instruction| match instruction {
+            Some(Jump { addr }) | Some(Call { addr }) => Some(addr as usize),
+            _ => None,
+        })
+        .collect();
+    let mut warnings = Vec::new();
+
+    for (&at, &instruction) in &code {
+        let instruction = match instruction {
+            Some(instruction) => instruction,
+            None if at >= end => {
+                if !targets.contains(&at) {
+                    warnings.push((at, "execution runs past the end of the ROM".to_owned()));
+                }
+                continue;
+            },
+            None => {
+                let ops = (rom[at - PROGRAM_START] as u16) << 8 | rom.get(at + 1 - PROGRAM_START).cloned().unwrap_or(0) as u16;
+                warnings.push((at, format!("unknown opcode {:04X}", ops)));
+                continue;
+            },
+        };
+        if instruction.level() > level {
+            warnings.push((at, format!("{} needs {}", instruction, instruction.level())));
+        }
+        match instruction {
+            Jump { addr } | Call { addr } => {
+                let addr = addr as usize;
+                if addr < PROGRAM_START || addr >= end {
+                    warnings.push((at, format!("{} leaves the program", instruction)));
+                }
+                if !addr.is_multiple_of(2) {
+                    warnings.push((at, format!("{} targets an odd address", instruction)));
+                }
+            },
+            JumpV0 { addr } if addr as usize + 0xFF >= memory_size => {
+                warnings.push((at, format!("{} can jump past the end of memory", instruction)));
+            },
+            Draw { n, .. } => {
+                // DXY0 is 32 bytes in 128x64, so assume the worst.
+                let len = if n == 0 { 32 } else { n as usize };
+                if let Some(i) = index_at(&code, at).filter(|&i| i + len > memory_size) {
+                    warnings.push((at, format!("{} reads {} bytes from {:04X}, past the end of memory", instruction, len, i)));
+                }
+            },
+            _ => {},
+        }
+    }
+    warnings
+}
+
+pub fn command(args: &[String]) -> Result<(), String> {
+    let (level, path) = match args {
+        [path] => (Level::XoChip, path),
+        [flag, level, path] if flag == "--level" => {
+            (Level::parse(level).ok_or_else(|| format!("unknown level '{}'\n{}", level, USAGE))?, path)
+        },
+        _ => return Err(USAGE.to_owned()),
+    };
+    let rom = info::read_rom(path)?;
+    let warnings = check(&rom, level);
+    for (addr, warning) in &warnings {
+        println!("{:04X}: warning: {}", addr, warning);
+    }
+    match warnings.len() {
+        0 => Ok(()),
+        1 => Err("1 warning".to_owned()),
+        n => Err(format!("{} warnings", n)),
+    }
+}
@@ -0,0 +1,381 @@
+//! Mirrors the machine to WebSocket clients.
+//!
+//! Every frame, or one in N with `--frame-skip N`, each connected client
+//! receives a JSON text message:
+//!
+//! ```text
+//! {"type":"frame","paused":false,"halted":false,"error":null,"pc":512,"i":0,"v":[..16..],
+//!  "stack":[..],"dt":0,"st":0,"notice":null,"notices":[],"overlay":null,"beep":440,"latency":20,
+//!  "palette":["#000000","#ffffff","#aaaaaa","#777777"],"width":64,"height":32,"screen":"<hex>"}
+//! ```
+//!
+//! `beep` is the frequency in Hz to beep at while `st` is not 0, as set with
+//! `--beep`, and `latency` how far ahead of the speaker to schedule it, in
+//! milliseconds, as set with `--audio-latency`. `palette` holds the colors for pixels of value 0 to 3, as set
+//! with `--palette`, see `ruchip8::palette`.
+//! `width` and `height` change to 128 and 64 when a SCHIP program switches
+//! modes. `screen` packs the framebuffer row by row, eight pixels per byte with the
+//! leftmost pixel in the most significant bit. `notices` are
+//! confirmations such as "saved slot 3" to draw one under the other in a
+//! corner of the screen, oldest first, each for two seconds; `notice` is
+//! the latest of them or null.
+//! `overlay`, when not null, is `{"fps":60.0,"ips":660,"speed":1.00}` to
+//! draw in a corner over the scaled screen: frames and instructions a
+//! second, and the speed next to a real machine. The `F11` hotkey turns it
+//! on and off, `--overlay` starts with it on. Clients send plain text
+//! commands back: `key <0-F> down`, `key <0-F> up`,
+//! `pause`, `resume`, `reset`, `reset hard`, which reloads the ROM into
+//! cleared memory, `save <1-10>`, `load <1-10>`, `autoresume`, which loads
+//! the autosave of the last run, `hotkey <KEY>`, which passes on a key
+//! such as `F3`, `shift+F3` or `ctrl+r` for the bindings of `hotkey`, and
+//! `slots`. That one is answered, to the asking client only,
+//! with the filled savestate slots for a load menu:
+//!
+//! ```text
+//! {"type":"slots","slots":[{"slot":1,"saved":1760000000,"thumbnail":"data:image/png;base64,..."},..]}
+//! ```
+//!
+//! `saved` is in seconds since the Unix epoch and `thumbnail` is null for
+//! states saved before thumbnails were.
+//!
+//! A hex editor can ask for memory with `memory <ADDR> <LEN>` and, while
+//! paused, write it with `poke <ADDR> <BYTE>...`, all in hex. Both are
+//! answered with the bytes as they now are:
+//!
+//! ```text
+//! {"type":"memory","addr":768,"bytes":[170,187]}
+//! ```
+//!
+//! A title message comes on connecting and whenever the title changes, for
+//! the window or tab:
+//!
+//! ```text
+//! {"type":"title","title":"pong.ch8 (CHIP-8) 100% - ruchip8"}
+//! ```
+//!
+//! It has the ROM's file name, the level its instructions need, and how
+//! fast it runs, or `paused` or `stopped` on an error or 00FD.
+//!
+//! A ROM browser asks for the ROMs in the ROM directories with `library`,
+//! see `library`, and starts one with `open <INDEX>`. The answer comes
+//! again to every client whenever the library changes:
+//!
+//! ```text
+//! {"type":"library","roms":[{"index":0,"title":"Pong","level":"CHIP-8","size":246,"sha1":"..."},..]}
+//! ```
+//!
+//! `title` is the file name without its extension where the ROM database
+//! has none.
+
+use std::io;
+use std::mem;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use ruchip8::palette;
+use tungstenite::handshake::server::{NoCallback, ServerHandshake};
+use tungstenite::handshake::{HandshakeError, MidHandshake};
+use tungstenite::{self, Message, WebSocket};
+
+use hotkey::Hotkey;
+use json::{base64, Json};
+use savestate;
+use session::{Remote, Session};
+
+type Client = WebSocket<TcpStream>;
+
+/// An upgrade still waiting on the client, from whom and since when.
+type Handshake = (MidHandshake<ServerHandshake<TcpStream, NoCallback>>, SocketAddr, Instant);
+
+/// How long a client has to finish the upgrade before it is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+enum Command {
+    Key(u8, bool),
+    Pause,
+    Resume,
+    Hotkey(Hotkey),
+    AutoResume,
+    Slots,
+    Memory(usize, usize),
+    Poke(usize, Vec<u8>),
+    Library,
+    Open(usize),
+}
+
+fn parse_slot(text: &str) -> Result<usize, String> {
+    text.parse().ok()
+        .filter(|slot| (1..=savestate::SLOTS).contains(slot))
+        .ok_or_else(|| format!("invalid slot '{}'", text))
+}
+
+fn parse_hex(text: &str) -> Result<usize, String> {
+    usize::from_str_radix(text.trim_start_matches("0x"), 16).map_err(|_| format!("invalid hex number '{}'", text))
+}
+
+fn parse_command(text: &str) -> Result<Command, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    match words.as_slice() {
+        ["pause"] => Ok(Command::Pause),
+        ["resume"] => Ok(Command::Resume),
+        ["reset"] => Ok(Command::Hotkey(Hotkey::SoftReset)),
+        ["reset", "hard"] => Ok(Command::Hotkey(Hotkey::HardReset)),
+        ["save", slot] => parse_slot(slot).map(|slot| Command::Hotkey(Hotkey::Save(slot))),
+        ["load", slot] => parse_slot(slot).map(|slot| Command::Hotkey(Hotkey::Load(slot))),
+        ["autoresume"] => Ok(Command::AutoResume),
+        ["slots"] => Ok(Command::Slots),
+        ["memory", addr, len] => Ok(Command::Memory(parse_hex(addr)?, parse_hex(len)?)),
+        ["poke", addr, bytes @ ..] if !bytes.is_empty() => {
+            let bytes = bytes.iter()
+                .map(|byte| parse_hex(byte).ok().filter(|&byte| byte <= 0xFF).map(|byte| byte as u8)
+                    .ok_or_else(|| format!("invalid byte '{}'", byte)))
+                .collect::<Result<_, String>>()?;
+            Ok(Command::Poke(parse_hex(addr)?, bytes))
+        },
+        ["library"] => Ok(Command::Library),
+        ["open", index] => index.parse().map(Command::Open).map_err(|_| format!("invalid ROM '{}'", index)),
+        ["hotkey", key] => Hotkey::parse(key).map(Command::Hotkey).ok_or_else(|| format!("no binding for '{}'", key)),
+        ["key", key, state] => {
+            let key = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|k| *k < 16)
+                .ok_or_else(|| format!("invalid key '{}'", key))?;
+            match *state {
+                "down" => Ok(Command::Key(key, true)),
+                "up" => Ok(Command::Key(key, false)),
+                _ => Err(format!("invalid key state '{}'", state)),
+            }
+        },
+        _ => Err(format!("unknown command '{}'", text)),
+    }
+}
+
+fn error_message(message: &str) -> String {
+    Json::object(vec![("type", "error".into()), ("message", message.into())]).to_string()
+}
+
+/// The answer to `memory` and `poke`.
+fn memory_message(session: &Session, addr: usize, len: usize) -> String {
+    let memory = session.chip.memory();
+    let bytes = match addr.checked_add(len).and_then(|end| memory.get(addr..end)) {
+        Some(bytes) => bytes,
+        None => return error_message("range outside of memory"),
+    };
+    let bytes: Vec<Json> = bytes.iter().map(|&byte| (byte as u64).into()).collect();
+    Json::object(vec![("type", "memory".into()), ("addr", addr.into()), ("bytes", bytes.into())]).to_string()
+}
+
+/// The answer to `library`.
+fn library_message(session: &Session) -> String {
+    let roms: Vec<Json> = session.library.entries.iter().enumerate().map(|(index, entry)| Json::object(vec![
+        ("index", index.into()),
+        ("title", entry.name().into()),
+        ("level", entry.level.to_string().into()),
+        ("size", entry.size.into()),
+        ("sha1", entry.sha1.as_str().into()),
+    ])).collect();
+    Json::object(vec![("type", "library".into()), ("roms", roms.into())]).to_string()
+}
+
+/// The answer to `slots`.
+fn slots_message(session: &Session) -> String {
+    let slots = match savestate::slots(session) {
+        Ok(slots) => slots,
+        Err(e) => return error_message(&e),
+    };
+    let slots: Vec<Json> = slots.iter().map(|slot| {
+        let saved = slot.saved.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let thumbnail = slot.thumbnail.as_ref().map(|png| format!("data:image/png;base64,{}", base64(png)));
+        Json::object(vec![("slot", slot.slot.into()), ("saved", saved.into()), ("thumbnail", thumbnail.into())])
+    }).collect();
+    Json::object(vec![("type", "slots".into()), ("slots", slots.into())]).to_string()
+}
+
+/// Reads every pending message of a client. Returns false once the
+/// connection is gone.
+fn poll_client(client: &mut Client, commands: &mut Vec<Command>) -> bool {
+    loop {
+        match client.read() {
+            Ok(Message::Text(text)) => match parse_command(text.trim()) {
+                Ok(command) => commands.push(command),
+                Err(e) => {
+                    let _ = client.send(Message::Text(error_message(&e)));
+                },
+            },
+            Ok(Message::Close(_)) => return false,
+            Ok(_) => {},
+            Err(tungstenite::Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => return true,
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Sends a message, treating a full socket buffer as success since
+/// tungstenite keeps the message queued until the next flush.
+fn send(client: &mut Client, message: &str) -> bool {
+    match client.send(Message::Text(message.to_owned())) {
+        Ok(_) => true,
+        Err(tungstenite::Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => true,
+        Err(_) => false,
+    }
+}
+
+pub struct WebSocketServer {
+    listener: TcpListener,
+    handshakes: Vec<Handshake>,
+    clients: Vec<Client>,
+    /// The title message clients were last sent.
+    title: String,
+    /// The version of the library clients were last sent.
+    library: u64,
+}
+
+impl WebSocketServer {
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        info!("listening on {}", listener.local_addr()?);
+        Ok(WebSocketServer { listener, handshakes: Vec::new(), clients: Vec::new(), title: String::new(), library: 0 })
+    }
+
+    /// Takes new connections and moves their upgrades along. The sockets
+    /// never block, so a client that stalls halfway through the upgrade
+    /// holds up nothing but itself.
+    fn accept_clients(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        warn!("cannot set up {}: {}", peer, e);
+                        continue;
+                    }
+                    self.upgrade(tungstenite::accept(stream), peer, Instant::now());
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("accept failed: {}", e);
+                    break;
+                },
+            }
+        }
+        for (handshake, peer, since) in mem::take(&mut self.handshakes) {
+            if since.elapsed() > HANDSHAKE_TIMEOUT {
+                warn!("handshake with {} timed out", peer);
+            } else {
+                self.upgrade(handshake.handshake(), peer, since);
+            }
+        }
+    }
+
+    /// Keeps a client whose upgrade finished, or its handshake until the
+    /// next poll when it is not done yet.
+    fn upgrade(&mut self, result: Result<Client, HandshakeError<ServerHandshake<TcpStream, NoCallback>>>,
+        peer: SocketAddr, since: Instant) {
+        match result {
+            Ok(mut ws) => {
+                info!("{} connected", peer);
+                if !self.title.is_empty() {
+                    send(&mut ws, &self.title);
+                }
+                self.clients.push(ws);
+            },
+            Err(HandshakeError::Interrupted(handshake)) => self.handshakes.push((handshake, peer, since)),
+            Err(HandshakeError::Failure(e)) => warn!("handshake with {} failed: {}", peer, e),
+        }
+    }
+}
+
+impl Remote for WebSocketServer {
+    fn poll(&mut self, session: &mut Session) {
+        self.accept_clients();
+
+        let mut commands = Vec::new();
+        let mut kept = 0;
+        self.clients.retain_mut(|client| {
+            let mut received = Vec::new();
+            let alive = poll_client(client, &mut received);
+            // Where the client sits once the closed ones are gone.
+            let from = if alive { Some(kept) } else { None };
+            kept += alive as usize;
+            commands.extend(received.into_iter().map(|command| (from, command)));
+            alive
+        });
+
+        for (from, command) in commands {
+            match command {
+                Command::Key(key, pressed) => session.set_key(key, pressed),
+                Command::Pause => {
+                    session.paused = true;
+                    session.show_notice("paused".to_owned());
+                },
+                Command::Resume => {
+                    session.paused = false;
+                    session.show_notice("resumed".to_owned());
+                },
+                Command::Hotkey(hotkey) => if let Err(e) = hotkey.press(session) {
+                    // Shown like a confirmation, whoever pressed it.
+                    session.show_notice(e);
+                },
+                Command::AutoResume => if let Err(e) = savestate::resume(session) {
+                    session.show_notice(e);
+                },
+                Command::Slots => if let Some(client) = from.and_then(|n| self.clients.get_mut(n)) {
+                    send(client, &slots_message(session));
+                },
+                Command::Memory(addr, len) => if let Some(client) = from.and_then(|n| self.clients.get_mut(n)) {
+                    send(client, &memory_message(session, addr, len));
+                },
+                Command::Poke(addr, bytes) => {
+                    let message = match session.poke(addr, &bytes) {
+                        Ok(()) => memory_message(session, addr, bytes.len()),
+                        Err(e) => error_message(&e),
+                    };
+                    if let Some(client) = from.and_then(|n| self.clients.get_mut(n)) {
+                        send(client, &message);
+                    }
+                },
+                Command::Library => if let Some(client) = from.and_then(|n| self.clients.get_mut(n)) {
+                    send(client, &library_message(session));
+                },
+                Command::Open(index) => if let Err(e) = session.open(index) {
+                    session.show_notice(e);
+                },
+            }
+        }
+    }
+
+    fn frame(&mut self, session: &Session) {
+        if session.drawing {
+            // The shared state object with the type and screen around it.
+            let frame = session.chip.display().frame();
+            let mut members = vec![("type".to_owned(), Json::from("frame"))];
+            if let Json::Object(state) = session.state() {
+                members.extend(state);
+            }
+            let palette: Vec<Json> = session.palette.colors.iter().map(|&color| palette::hex(color).into()).collect();
+            if let Json::Object(screen) = Json::object(vec![
+                ("beep", Json::Number(session.beep as f64)),
+                ("latency", (session.audio_latency.millis() as u64).into()),
+                ("palette", palette.into()),
+                ("width", frame.width().into()),
+                ("height", frame.height().into()),
+                ("screen", session.screen_hex().into()),
+            ]) {
+                members.extend(screen);
+            }
+            let message = Json::Object(members).to_string();
+            self.clients.retain_mut(|client| send(client, &message));
+        }
+
+        let title = Json::object(vec![("type", "title".into()), ("title", session.title().into())]).to_string();
+        if title != self.title {
+            self.clients.retain_mut(|client| send(client, &title));
+            self.title = title;
+        }
+        if session.library.version != self.library {
+            let message = library_message(session);
+            self.clients.retain_mut(|client| send(client, &message));
+            self.library = session.library.version;
+        }
+    }
+}
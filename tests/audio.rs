@@ -0,0 +1,157 @@
+extern crate ruchip8;
+
+use ruchip8::audio::{playback_rate, Latency, Synth};
+use ruchip8::Chip8;
+
+/// A machine with the sound timer running and `pattern` loaded at `pitch`.
+fn playing(pattern: Option<[u8; 16]>, pitch: u8) -> Chip8 {
+    let mut chip = Chip8::new();
+    let mut rom = vec![0x6F, pitch, 0xFF, 0x3A, 0xA2, 0x08, 0xF0, 0x02];
+    if let Some(pattern) = pattern {
+        rom.extend_from_slice(&pattern);
+    }
+    chip.load_rom(&rom).unwrap();
+    let instructions = if pattern.is_some() {4} else {2};
+    for _ in 0..instructions {
+        chip.execute_cycle().unwrap();
+    }
+    chip.set_sound_timer(10);
+    chip
+}
+
+/// A synth with the hard edges of a real machine.
+fn bare(sample_rate: u32) -> Synth {
+    let mut synth = Synth::new(sample_rate);
+    synth.ramp = 0.0;
+    synth.min_beep = 0.0;
+    synth
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.5
+}
+
+#[test]
+fn playback_rate_doubles_every_48_steps() {
+    assert!(close(playback_rate(64), 4000.0));
+    assert!(close(playback_rate(112), 8000.0));
+    assert!(close(playback_rate(16), 2000.0));
+    assert!(close(playback_rate(88), 4000.0 * 2f32.sqrt()));
+    assert!(playback_rate(255) > playback_rate(254));
+}
+
+#[test]
+fn silent_without_the_sound_timer() {
+    let mut chip = playing(None, 64);
+    chip.set_sound_timer(0);
+    let mut out = [1.0; 64];
+    Synth::new(44100).render(&chip, &mut out);
+    assert!(out.iter().all(|&s| s == 0.0));
+}
+
+#[test]
+fn beeps_without_a_pattern() {
+    let chip = playing(None, 64);
+    let mut synth = bare(44000);
+    let mut out = [0.0; 100];
+    synth.render(&chip, &mut out);
+    // 440 Hz at 44 kHz: 50 samples high, then 50 low, give or take rounding.
+    assert!(out[..49].iter().all(|&s| s == synth.volume));
+    assert!(out[51..99].iter().all(|&s| s == -synth.volume));
+}
+
+#[test]
+fn beeps_at_the_frequency_set() {
+    let chip = playing(None, 64);
+    let mut synth = bare(44000);
+    synth.frequency = 880.0;
+    let mut out = [0.0; 50];
+    synth.render(&chip, &mut out);
+    assert!(out[..24].iter().all(|&s| s == synth.volume));
+    assert!(out[26..49].iter().all(|&s| s == -synth.volume));
+}
+
+#[test]
+fn plays_the_pattern_one_bit_per_sample_at_the_sample_rate() {
+    let mut pattern = [0; 16];
+    pattern[0] = 0b1010_0000;
+    let chip = playing(Some(pattern), 64);
+    let mut synth = bare(4000);
+    synth.volume = 1.0;
+    let mut out = [0.0; 132];
+    synth.render(&chip, &mut out);
+    assert_eq!(&out[..4], &[1.0, -1.0, 1.0, -1.0]);
+    assert!(out[4..128].iter().all(|&s| s == -1.0));
+    assert_eq!(&out[128..], &[1.0, -1.0, 1.0, -1.0], "the pattern loops");
+}
+
+#[test]
+fn samples_average_the_bits_they_cover() {
+    let mut pattern = [0; 16];
+    pattern[0] = 0b1000_0000;
+    // Two bits a sample.
+    let chip = playing(Some(pattern), 112);
+    let mut synth = bare(4000);
+    synth.volume = 1.0;
+    let mut out = [0.0; 2];
+    synth.render(&chip, &mut out);
+    assert!(out[0].abs() < 0.01);
+    assert_eq!(out[1], -1.0);
+}
+
+#[test]
+fn the_waveform_continues_across_calls() {
+    let mut pattern = [0; 16];
+    pattern[0] = 0b0100_0000;
+    let chip = playing(Some(pattern), 64);
+    let mut synth = bare(4000);
+    synth.volume = 1.0;
+    let (mut first, mut second) = ([0.0; 1], [0.0; 1]);
+    synth.render(&chip, &mut first);
+    synth.render(&chip, &mut second);
+    assert_eq!((first[0], second[0]), (-1.0, 1.0));
+}
+
+#[test]
+fn parses_latencies() {
+    assert_eq!(Latency::parse("low"), Some(Latency::Low));
+    assert_eq!(Latency::parse("12"), Some(Latency::Millis(12)));
+    assert_eq!(Latency::parse("0"), None);
+    assert_eq!(Latency::parse("soon"), None);
+    assert_eq!(Latency::default().millis(), 20);
+    assert_eq!(Latency::Millis(12).millis(), 12);
+}
+
+#[test]
+fn fades_in_and_out() {
+    let mut chip = playing(None, 64);
+    let mut synth = Synth::new(44000);
+    synth.min_beep = 0.0;
+    let mut out = [0.0; 200];
+    synth.render(&chip, &mut out);
+    // 2ms at 44 kHz is 88 samples.
+    assert!(out[0] > 0.0 && out[0] < 0.01);
+    assert!(out[40] < out[44]);
+    assert_eq!(out[88].abs(), synth.volume);
+    chip.set_sound_timer(0);
+    assert!(synth.is_playing(&chip));
+    synth.render(&chip, &mut out);
+    assert!(out[0].abs() < synth.volume && out[0].abs() > 0.0);
+    assert!(out[88..].iter().all(|&s| s == 0.0));
+    assert!(!synth.is_playing(&chip));
+}
+
+#[test]
+fn short_sounds_play_for_min_beep() {
+    let mut chip = playing(None, 64);
+    let mut synth = Synth::new(1000);
+    synth.ramp = 0.0;
+    synth.min_beep = 0.05;
+    let mut out = [0.0; 10];
+    synth.render(&chip, &mut out);
+    chip.set_sound_timer(0);
+    let mut rest = [0.0; 50];
+    synth.render(&chip, &mut rest);
+    assert!(rest[..40].iter().all(|&s| s != 0.0));
+    assert!(rest[40..].iter().all(|&s| s == 0.0));
+}
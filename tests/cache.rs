@@ -0,0 +1,98 @@
+extern crate ruchip8;
+
+mod common;
+
+use common::Harness;
+use ruchip8::PROGRAM_START;
+
+/// Runs from PROGRAM_START until PC reaches `end`.
+fn run_until(h: &mut Harness, end: usize) {
+    while h.chip.pc() != end {
+        h.chip.execute_cycle().unwrap();
+    }
+}
+
+#[test]
+fn self_modifying_code_through_fx55() {
+    // Loops over a LD V2, 00 that the loop body rewrites into LD V2, 07.
+    let mut h = Harness::new().mem(PROGRAM_START, &[
+        0x62, 0x00, // 200: LD V2, 00
+        0x60, 0x62, // 202: LD V0, 62
+        0x61, 0x07, // 204: LD V1, 07
+        0xA2, 0x00, // 206: LD I, 200
+        0xF1, 0x55, // 208: LD [I], V1
+        0x12, 0x00, // 20A: JP 200
+    ]);
+    run_until(&mut h, 0x20A);
+    assert_eq!(h.chip.v(2), 0x00);
+    h.chip.execute_cycle().unwrap();
+    h.chip.execute_cycle().unwrap();
+    h.assert_reg(2, 0x07);
+}
+
+#[test]
+fn self_modifying_code_through_bcd() {
+    // BCD of 123 writes 01 02 03 from 201, over the second half of 200.
+    let mut h = Harness::new().reg(0, 123).mem(PROGRAM_START, &[
+        0x61, 0x11, // 200: LD V1, 11
+        0x12, 0x04, // 202: JP 204
+        0xA2, 0x01, // 204: LD I, 201
+        0xF0, 0x33, // 206: LD B, V0
+        0x12, 0x00, // 208: JP 200
+    ]);
+    h.chip.execute_cycle().unwrap();
+    assert_eq!(h.chip.v(1), 0x11);
+    run_until(&mut h, PROGRAM_START);
+    h.chip.execute_cycle().unwrap();
+    h.assert_reg(1, 0x01);
+}
+
+#[test]
+fn writes_through_memory_mut() {
+    let mut h = Harness::new().mem(PROGRAM_START, &[0x60, 0x01]);
+    h.chip.execute_cycle().unwrap();
+    assert_eq!(h.chip.v(0), 0x01);
+
+    h.chip.memory_mut()[PROGRAM_START + 1] = 0x02;
+    h.chip.set_pc(PROGRAM_START);
+    h.chip.execute_cycle().unwrap();
+    h.assert_reg(0, 0x02);
+}
+
+#[test]
+fn rewriting_the_address_of_f000_nnnn() {
+    // The store lands 3 bytes into the long instruction at 200.
+    let mut h = Harness::new().reg(0, 0x56).mem(PROGRAM_START, &[
+        0xF0, 0x00, 0x03, 0x00, // 200: LD I, LONG 0300
+        0xA2, 0x03,             // 204: LD I, 203
+        0xF0, 0x55,             // 206: LD [I], V0
+        0x12, 0x00,             // 208: JP 200
+    ]);
+    run_until(&mut h, 0x208);
+    h.chip.execute_cycle().unwrap();
+    h.chip.execute_cycle().unwrap();
+    h.assert_index(0x0356);
+}
+
+#[test]
+fn write_memory_drops_the_decoded_instructions_it_overlaps() {
+    let mut h = Harness::new().mem(PROGRAM_START, &[
+        0x60, 0x01, // 200: LD V0, 01
+        0x12, 0x00, // 202: JP 200
+    ]);
+    run_until(&mut h, 0x202);
+    h.chip.execute_cycle().unwrap();
+    // Patch the load while it sits decoded in the cache.
+    h.chip.write_memory(PROGRAM_START + 1, &[0x2A]).unwrap();
+    h.chip.execute_cycle().unwrap();
+    assert_eq!(h.chip.memory()[PROGRAM_START..PROGRAM_START + 2], [0x60, 0x2A]);
+    h.assert_reg(0, 0x2A);
+}
+
+#[test]
+fn write_memory_stays_inside_memory() {
+    let mut h = Harness::new();
+    let size = h.chip.memory().len();
+    assert!(h.chip.write_memory(size - 1, &[1, 2]).is_err());
+    assert!(h.chip.write_memory(size - 2, &[1, 2]).is_ok());
+}
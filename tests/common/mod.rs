@@ -0,0 +1,100 @@
+//! Shared test utilities.
+
+#![allow(dead_code)]
+
+use ruchip8::{Chip8, Error, FLAG};
+
+/// Wraps a `Chip8` so a test can set up machine state, feed it a single
+/// opcode and assert on the outcome in a few chained calls.
+pub struct Harness {
+    pub chip: Chip8,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Harness { chip: Chip8::new() }
+    }
+
+    pub fn reg(mut self, n: u8, val: u8) -> Self {
+        self.chip.set_v(n, val);
+        self
+    }
+
+    pub fn index(mut self, i: usize) -> Self {
+        self.chip.set_i(i);
+        self
+    }
+
+    pub fn pc(mut self, pc: usize) -> Self {
+        self.chip.set_pc(pc);
+        self
+    }
+
+    pub fn mem(mut self, addr: usize, bytes: &[u8]) -> Self {
+        self.chip.memory_mut()[addr..addr + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    pub fn key(mut self, key: u8, pressed: bool) -> Self {
+        self.chip.set_key(key, pressed);
+        self
+    }
+
+    /// Writes `op` at the program counter and executes one cycle.
+    pub fn run(mut self, op: u16) -> Self {
+        if let Err(e) = self.try_run(op) {
+            panic!("{:04X} failed: {}", op, e);
+        }
+        self
+    }
+
+    /// Like `run`, but hands back the error instead of panicking.
+    pub fn try_run(&mut self, op: u16) -> Result<(), Error> {
+        let pc = self.chip.pc();
+        self.chip.memory_mut()[pc..pc + 2].copy_from_slice(&[(op >> 8) as u8, op as u8]);
+        self.chip.execute_cycle()
+    }
+
+    /// Executes the given opcodes, one cycle each, starting at the program counter.
+    pub fn run_all(mut self, ops: &[u16]) -> Self {
+        for &op in ops {
+            self = self.run(op);
+        }
+        self
+    }
+
+    pub fn assert_reg(self, n: u8, expected: u8) -> Self {
+        assert_eq!(self.chip.v(n), expected, "V{:X}", n);
+        self
+    }
+
+    pub fn assert_flag(self, expected: u8) -> Self {
+        self.assert_reg(FLAG as u8, expected)
+    }
+
+    pub fn assert_pc(self, expected: usize) -> Self {
+        assert_eq!(self.chip.pc(), expected, "PC");
+        self
+    }
+
+    pub fn assert_index(self, expected: usize) -> Self {
+        assert_eq!(self.chip.i(), expected, "I");
+        self
+    }
+
+    pub fn assert_mem(self, addr: usize, expected: &[u8]) -> Self {
+        assert_eq!(&self.chip.memory()[addr..addr + expected.len()], expected,
+                   "memory at {:03X}", addr);
+        self
+    }
+
+    pub fn assert_stack(self, expected: &[u16]) -> Self {
+        assert_eq!(self.chip.stack(), expected, "stack");
+        self
+    }
+
+    pub fn assert_pixel(self, x: usize, y: usize, expected: bool) -> Self {
+        assert_eq!(self.chip.display().pixel(x, y), expected, "pixel {},{}", x, y);
+        self
+    }
+}
@@ -0,0 +1,97 @@
+extern crate ruchip8;
+
+use ruchip8::disasm::{annotated, comment, disassemble, listing, listing_with, reachable, Symbols};
+use ruchip8::{Instruction, Level};
+
+#[test]
+fn mnemonics() {
+    assert_eq!(disassemble(0x00E0).unwrap(), "CLS");
+    assert_eq!(disassemble(0x2ABC).unwrap(), "CALL ABC");
+    assert_eq!(disassemble(0x8AB4).unwrap(), "ADD VA, VB");
+    assert_eq!(disassemble(0xD125).unwrap(), "DRW V1, V2, 5");
+    assert_eq!(disassemble(0xF365).unwrap(), "LD V3, [I]");
+}
+
+#[test]
+fn unknown_opcodes() {
+    assert_eq!(disassemble(0x5121), None);
+    assert_eq!(disassemble(0xE000), None);
+}
+
+#[test]
+fn listing_shows_unknown_opcodes_as_data() {
+    let memory = [0x00, 0xE0, 0xFF, 0xFF, 0x12];
+    assert_eq!(listing(&memory, 0, 3), vec!["0000: 00E0  CLS", "0002: FFFF  DW FFFF"]);
+}
+
+#[test]
+fn listing_keeps_f000_nnnn_on_one_line() {
+    let memory = [0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0, 0xF0, 0x00];
+    assert_eq!(listing(&memory, 0, 4), vec![
+        "0000: F000 1234  LD I, LONG 1234",
+        "0004: 00E0  CLS",
+        "0006: F000  DW F000",
+    ]);
+}
+
+#[test]
+fn symbols_name_operands_and_label_lines() {
+    let symbols = Symbols::parse("# labels\n0x202 = main_loop\n206=sprite\n").unwrap();
+    assert_eq!(symbols.addr("sprite"), Some(0x206));
+
+    let mut memory = vec![0; 0x200];
+    memory.extend_from_slice(&[0x00, 0xE0, 0x12, 0x02, 0xA2, 0x06]);
+    assert_eq!(listing_with(&memory, 0x200, 3, &symbols), vec![
+        "0200: 00E0  CLS",
+        "main_loop:",
+        "0202: 1202  JP main_loop",
+        "0204: A206  LD I, sprite",
+    ]);
+}
+
+#[test]
+fn symbols_report_bad_lines() {
+    assert_eq!(Symbols::parse("200=main\nmain").unwrap_err(), "line 2: expected ADDR=NAME");
+    assert_eq!(Symbols::parse("zz=main").unwrap_err(), "line 1: invalid address 'zz'");
+    assert_eq!(Symbols::parse("200=two words").unwrap_err(), "line 1: invalid name 'two words'");
+}
+
+#[test]
+fn reachable_follows_jumps_calls_and_skips() {
+    // 0: SE V0, 0; 2: JP 8; 4: F000 0000; 8: CALL C; A: JP E; C: RET; E: data
+    let memory = [0x30, 0x00, 0x10, 0x08, 0xF0, 0x00, 0x00, 0x00, 0x20, 0x0C, 0x10, 0x0E, 0x00, 0xEE, 0xFF, 0xFF];
+    let code = reachable(&memory, 0);
+    assert_eq!(code.keys().cloned().collect::<Vec<_>>(), vec![0x0, 0x2, 0x4, 0x8, 0xA, 0xC, 0xE]);
+    assert_eq!(code[&0xE], None, "FFFF is unknown");
+    assert_eq!(code[&0x4], Some(Instruction::LongIndex { addr: 0 }));
+    assert_eq!(code[&0x4].unwrap().level(), Level::XoChip);
+}
+
+#[test]
+fn comments_explain_instructions() {
+    assert_eq!(comment(Instruction::Draw { x: 2, y: 3, n: 5 }).unwrap(), "draw 5-byte sprite at (V2,V3)");
+    assert_eq!(comment(Instruction::Bcd { x: 4 }).unwrap(), "BCD of V4 into I..I+2");
+    assert_eq!(comment(Instruction::LoadImm { x: 0, nn: 1 }), None);
+}
+
+#[test]
+fn annotated_listing_shows_data_as_pixels() {
+    let mut memory = vec![0; 0x208];
+    memory[0x200..].copy_from_slice(&[0xA2, 0x06, 0xD0, 0x11, 0x12, 0x04, 0x81, 0x3C]);
+    assert_eq!(annotated(&memory, 0x200, 0x208, &Symbols::default()), vec![
+        "0200: A206  LD I, 206",
+        "0202: D011  DRW V0, V1, 1       ; draw 1-byte sprite at (V0,V1)",
+        "0204: 1204  JP 204              ; wait here forever",
+        "; data, I points here at 0200",
+        "0206: 81    DB 81               ; #......#",
+        "0207: 3C    DB 3C               ; ..####..",
+    ]);
+}
+
+#[test]
+fn annotated_listing_marks_the_font() {
+    let mut memory = vec![0; 0x202];
+    memory[..5].copy_from_slice(&[0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    let lines = annotated(&memory, 0, 2, &Symbols::default());
+    assert_eq!(lines, vec!["0000: F0    DB F0               ; ####.... font 0", "0001: 90    DB 90               ; #..#...."]);
+}
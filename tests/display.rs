@@ -0,0 +1,60 @@
+extern crate ruchip8;
+
+use ruchip8::palette::Palette;
+use ruchip8::{Display, HIRES_HEIGHT, HIRES_WIDTH};
+
+fn drain(display: &mut Display) -> Vec<(usize, usize, bool)> {
+    display.drain_changes().collect()
+}
+
+#[test]
+fn drains_the_pixels_changed_since_the_last_call() {
+    let mut display = Display::new();
+    display.draw(2, 1, &[0xC0]);
+    assert!(drain(&mut display).is_empty(), "nothing presented yet");
+    display.present();
+    assert_eq!(drain(&mut display), vec![(2, 1, true), (3, 1, true)]);
+    assert!(drain(&mut display).is_empty());
+
+    display.draw(3, 1, &[0xC0]);
+    display.present();
+    assert_eq!(drain(&mut display), vec![(3, 1, false), (4, 1, true)]);
+}
+
+#[test]
+fn unread_changes_wait_for_the_next_call() {
+    let mut display = Display::new();
+    display.draw(0, 0, &[0xE0]);
+    display.present();
+    assert_eq!(display.drain_changes().next(), Some((0, 0, true)));
+    assert_eq!(drain(&mut display), vec![(1, 0, true), (2, 0, true)]);
+}
+
+#[test]
+fn a_mode_switch_starts_over() {
+    let mut display = Display::new();
+    display.draw(0, 0, &[0x80]);
+    display.present();
+    drain(&mut display);
+
+    display.set_hires(true);
+    display.draw(100, 50, &[0x80]);
+    display.present();
+    assert_eq!(drain(&mut display), vec![(100, 50, true)]);
+}
+
+#[test]
+fn scaled_rgba_doubles_lores_pixels() {
+    let mut display = Display::new();
+    display.draw(63, 31, &[0x80]);
+    display.present();
+    let mut rgba = vec![0; HIRES_WIDTH * HIRES_HEIGHT * 4];
+    display.frame().scaled_rgba(&Palette::HIGH_CONTRAST, &mut rgba);
+    let lit: Vec<usize> = rgba.chunks(4).enumerate()
+        .filter(|&(_, pixel)| pixel == [0xFF, 0xFF, 0xFF, 0xFF])
+        .map(|(at, _)| at)
+        .collect();
+    let at = |x: usize, y: usize| y * HIRES_WIDTH + x;
+    assert_eq!(lit, vec![at(126, 62), at(127, 62), at(126, 63), at(127, 63)]);
+    assert_eq!(&rgba[..4], &[0, 0, 0, 0xFF]);
+}
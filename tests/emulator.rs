@@ -0,0 +1,62 @@
+extern crate ruchip8;
+
+use std::time::{Duration, Instant};
+
+use ruchip8::emulator::{Controller, Event};
+use ruchip8::{Chip8, Error};
+
+/// Waits up to a second for `done` to hold.
+fn eventually<F: FnMut() -> bool>(mut done: F) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while Instant::now() < deadline {
+        if done() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    false
+}
+
+fn event(controller: &Controller) -> Option<Event> {
+    let mut event = None;
+    eventually(|| { event = controller.poll_event(); event.is_some() });
+    event
+}
+
+#[test]
+fn frames_come_from_the_emulation_thread() {
+    let mut controller = Controller::spawn(Chip8::new());
+    // Draw the 0 digit at 0,0 and spin.
+    controller.load(&[0xD0, 0x05, 0x12, 0x02]).unwrap();
+    assert!(eventually(|| controller.frames().update()));
+    assert!(controller.frames().frame().pixel(0, 0));
+}
+
+#[test]
+fn keys_reach_the_machine() {
+    let controller = Controller::spawn(Chip8::new());
+    // Wait for a key, then exit.
+    controller.load(&[0xF3, 0x0A, 0x00, 0xFD]).unwrap();
+    assert!(eventually(|| controller.save_state().unwrap().snapshot.key_wait == Some(3)));
+    controller.set_key(0xB, true);
+    assert_eq!(event(&controller), Some(Event::Halted));
+    let state = controller.save_state().unwrap();
+    assert_eq!(state.snapshot.v[3], 0xB);
+}
+
+#[test]
+fn errors_stop_the_machine_until_it_starts_over() {
+    let controller = Controller::spawn(Chip8::new());
+    assert_eq!(controller.load(&[0xFF; 0x1000]), Err(Error::RomTooLarge { size: 0x1000 }));
+    controller.load(&[0x00, 0xEE]).unwrap();
+    assert_eq!(event(&controller), Some(Event::Error(Error::StackUnderflow { pc: 0x200 })));
+
+    // LD V0, 1 then spin.
+    controller.load(&[0x60, 0x01, 0x12, 0x02]).unwrap();
+    controller.set_paused(true);
+    let mut state = controller.save_state().unwrap();
+    state.snapshot.v[5] = 0x42;
+    controller.load_state(state).unwrap();
+    assert!(eventually(|| controller.save_state().unwrap().snapshot.v[0] == 1));
+    assert_eq!(controller.save_state().unwrap().snapshot.v[5], 0x42);
+}
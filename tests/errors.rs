@@ -0,0 +1,137 @@
+extern crate ruchip8;
+
+mod common;
+
+use common::Harness;
+use ruchip8::{Chip8, Error, SysPolicy, WriteProtect, MEMORY_SIZE, PROGRAM_START, STACK_SIZE, XO_MEMORY_SIZE};
+
+#[test]
+fn unknown_opcode() {
+    let mut h = Harness::new();
+    assert_eq!(h.try_run(0x5121), Err(Error::UnknownOpcode { opcode: 0x5121, pc: PROGRAM_START }));
+    h.assert_pc(PROGRAM_START);
+}
+
+#[test]
+fn return_with_empty_stack() {
+    let mut h = Harness::new();
+    assert_eq!(h.try_run(0x00EE), Err(Error::StackUnderflow { pc: PROGRAM_START }));
+}
+
+#[test]
+fn call_with_full_stack() {
+    let mut h = Harness::new().pc(0x300);
+    for _ in 0..STACK_SIZE {
+        h = h.run(0x2300);
+    }
+    assert_eq!(h.try_run(0x2300), Err(Error::StackOverflow { pc: 0x300 }));
+    assert_eq!(h.chip.stack().len(), STACK_SIZE);
+}
+
+#[test]
+fn call_in_the_last_word_of_memory() {
+    let mut chip = Chip8::builder().memory_size(XO_MEMORY_SIZE).build().unwrap();
+    chip.memory_mut()[XO_MEMORY_SIZE - 2..].copy_from_slice(&[0x22, 0x00]);
+    chip.set_pc(XO_MEMORY_SIZE - 2);
+    assert_eq!(chip.execute_cycle(), Err(Error::MemoryOutOfBounds { addr: XO_MEMORY_SIZE, pc: XO_MEMORY_SIZE - 2 }));
+    assert!(chip.stack().is_empty());
+}
+
+#[test]
+fn memory_access_past_the_end() {
+    let i = MEMORY_SIZE - 2;
+    let mut h = Harness::new().index(i);
+    assert_eq!(h.try_run(0xF033), Err(Error::MemoryOutOfBounds { addr: i, pc: PROGRAM_START }));
+    assert_eq!(h.try_run(0xF255), Err(Error::MemoryOutOfBounds { addr: i, pc: PROGRAM_START }));
+    assert_eq!(h.try_run(0xF265), Err(Error::MemoryOutOfBounds { addr: i, pc: PROGRAM_START }));
+    assert_eq!(h.try_run(0xD003), Err(Error::MemoryOutOfBounds { addr: i, pc: PROGRAM_START }));
+    h.assert_pc(PROGRAM_START);
+}
+
+#[test]
+fn fetch_past_the_end() {
+    let mut chip = Chip8::new();
+    chip.set_pc(MEMORY_SIZE - 1);
+    assert_eq!(chip.execute_cycle(),
+               Err(Error::MemoryOutOfBounds { addr: MEMORY_SIZE - 1, pc: MEMORY_SIZE - 1 }));
+}
+
+#[test]
+fn rom_too_large() {
+    let rom = vec![0; MEMORY_SIZE - PROGRAM_START + 1];
+    assert_eq!(Chip8::new().load_rom(&rom), Err(Error::RomTooLarge { size: rom.len() }));
+    let mut xo = Chip8::builder().memory_size(XO_MEMORY_SIZE).build().unwrap();
+    assert_eq!(xo.load_rom(&rom), Ok(()));
+}
+
+#[test]
+fn memory_size_is_checked() {
+    for &size in &[0, PROGRAM_START, XO_MEMORY_SIZE + 1] {
+        assert_eq!(Chip8::builder().memory_size(size).build().err(), Some(Error::InvalidMemorySize { size }));
+    }
+}
+
+#[test]
+fn small_memory_bounds_accesses() {
+    let mut chip = Chip8::builder().memory_size(0x400).build().unwrap();
+    assert_eq!(chip.memory().len(), 0x400);
+    assert_eq!(chip.load_rom(&[0; 0x201]), Err(Error::RomTooLarge { size: 0x201 }));
+    chip.load_rom(&[0xA3, 0xFF, 0xF1, 0x55]).unwrap();
+    chip.execute_cycle().unwrap();
+    assert_eq!(chip.execute_cycle(), Err(Error::MemoryOutOfBounds { addr: 0x3FF, pc: PROGRAM_START + 2 }));
+}
+
+#[test]
+fn long_addresses_past_the_end() {
+    let mut h = Harness::new().mem(PROGRAM_START + 2, &[0xFF, 0x00]);
+    h = h.run(0xF000);
+    assert_eq!(h.try_run(0xF065), Err(Error::MemoryOutOfBounds { addr: 0xFF00, pc: PROGRAM_START + 4 }));
+}
+
+#[test]
+fn machine_call_halts_by_default() {
+    let mut h = Harness::new();
+    assert_eq!(h.try_run(0x0123), Err(Error::MachineCall { addr: 0x123, pc: PROGRAM_START }));
+    h.assert_pc(PROGRAM_START);
+}
+
+#[test]
+fn machine_call_policies() {
+    for &policy in &[SysPolicy::Ignore, SysPolicy::Warn] {
+        let mut h = Harness::new();
+        h.chip.set_sys_policy(policy);
+        h.run(0x0123).assert_pc(PROGRAM_START + 2);
+    }
+
+    fn routine(chip: &mut Chip8, addr: u16) -> Result<(), Error> {
+        chip.set_i(addr as usize);
+        Ok(())
+    }
+    let mut h = Harness::new();
+    h.chip.set_sys_policy(SysPolicy::Call(routine));
+    h.run(0x0456).assert_index(0x456).assert_pc(PROGRAM_START + 2);
+
+    assert!(SysPolicy::parse("warn").is_some());
+    assert!(SysPolicy::parse("call").is_none());
+}
+
+#[test]
+fn protected_writes_trap() {
+    let mut h = Harness::new().index(0x1FF).reg(0, 0xAA);
+    h.chip.set_write_protect(WriteProtect::Trap);
+    assert_eq!(h.try_run(0xF055), Err(Error::ProtectedWrite { addr: 0x1FF, pc: PROGRAM_START }));
+    assert_eq!(h.try_run(0xF033), Err(Error::ProtectedWrite { addr: 0x1FF, pc: PROGRAM_START }));
+    h.assert_mem(0x1FF, &[0]).assert_pc(PROGRAM_START);
+
+    let h = Harness::new().index(PROGRAM_START + 2).reg(0, 0xAA);
+    h.run(0xF055).assert_mem(PROGRAM_START + 2, &[0xAA]);
+}
+
+#[test]
+fn unprotected_writes_go_through() {
+    for &protect in &[WriteProtect::Off, WriteProtect::Warn] {
+        let mut h = Harness::new().index(0x100).reg(0, 0xAA);
+        h.chip.set_write_protect(protect);
+        h.run(0xF055).assert_mem(0x100, &[0xAA]);
+    }
+}
@@ -0,0 +1,26 @@
+extern crate ruchip8;
+
+use ruchip8::{Chip8, Font, FONT_SIZE};
+
+#[test]
+fn builder_loads_the_font_fx29_points_at() {
+    let glyphs = Font::Dream6800.glyphs();
+    let mut chip = Chip8::builder().font(glyphs).build().unwrap();
+    assert_eq!(&chip.memory()[..FONT_SIZE], &glyphs[..]);
+    chip.load_rom(&[0x61, 0x0B, 0xF1, 0x29]).unwrap();
+    chip.execute_cycle().unwrap();
+    chip.execute_cycle().unwrap();
+    assert_eq!(&chip.memory()[chip.i()..chip.i() + 5], &[0xC0, 0xA0, 0xE0, 0xA0, 0xC0]);
+}
+
+#[test]
+fn bundled_fonts_differ() {
+    let fonts = [Font::Default, Font::Vip, Font::Dream6800, Font::Eti660];
+    assert_eq!(Chip8::new().font(), Font::Default.glyphs());
+    for (n, a) in fonts.iter().enumerate() {
+        assert_eq!(Font::parse(&format!("{:?}", a).to_lowercase()), Some(*a));
+        for b in &fonts[n + 1..] {
+            assert_ne!(a.glyphs(), b.glyphs(), "{:?} and {:?}", a, b);
+        }
+    }
+}
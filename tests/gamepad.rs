@@ -0,0 +1,45 @@
+extern crate ruchip8;
+
+use ruchip8::gamepad::{Button, Mapping, BUTTON_COUNT};
+
+#[test]
+fn buttons_go_by_their_standard_index_and_name() {
+    for index in 0..BUTTON_COUNT {
+        let button = Button::from_index(index).unwrap();
+        assert_eq!(button.index(), index);
+        assert_eq!(Button::parse(button.name()), Some(button));
+    }
+    assert_eq!(Button::from_index(BUTTON_COUNT), None);
+    assert_eq!(Button::from_index(12), Some(Button::Up));
+}
+
+#[test]
+fn the_default_mapping_puts_the_dpad_where_octo_puts_wasd() {
+    let mapping = Mapping::default();
+    assert_eq!([Button::Up, Button::Left, Button::Down, Button::Right].map(|button| mapping.key(button)),
+               [Some(0x5), Some(0x7), Some(0x8), Some(0x9)]);
+    assert_eq!(mapping.key(Button::Start), None);
+}
+
+#[test]
+fn mappings_parse_over_the_default() {
+    let mapping = Mapping::parse("up=2, start=F,a=none").unwrap();
+    assert_eq!(mapping.key(Button::Up), Some(0x2));
+    assert_eq!(mapping.key(Button::Start), Some(0xF));
+    assert_eq!(mapping.key(Button::A), None);
+    assert_eq!(mapping.key(Button::Down), Some(0x8));
+    assert_eq!(Mapping::parse(""), Some(Mapping::default()));
+    for invalid in ["up", "up=10", "turbo=1", "up=g"] {
+        assert_eq!(Mapping::parse(invalid), None, "{}", invalid);
+    }
+}
+
+#[test]
+fn held_buttons_hold_their_keys() {
+    let mut mapping = Mapping::default();
+    mapping.set(Button::X, Some(0x5));
+    let buttons = 1 << Button::Up.index() | 1 << Button::X.index() | 1 << Button::A.index() | 1 << Button::Start.index();
+    assert_eq!(mapping.keys(buttons), 1 << 0x5 | 1 << 0x6);
+    assert_eq!(mapping.keys(0), 0);
+    assert_eq!(Mapping::unmapped().keys(0xFFFF), 0);
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "embedded")]
+
+extern crate embedded_graphics_core;
+extern crate ruchip8;
+
+use std::convert::Infallible;
+
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics_core::pixelcolor::BinaryColor;
+use embedded_graphics_core::Pixel;
+
+use ruchip8::graphics::Renderer;
+use ruchip8::Display;
+
+/// A 160x80 panel that remembers every pixel.
+struct Panel(Vec<BinaryColor>);
+
+impl Panel {
+    fn new() -> Self {
+        Panel(vec![BinaryColor::Off; 160 * 80])
+    }
+
+    fn at(&self, x: usize, y: usize) -> BinaryColor {
+        self.0[y * 160 + x]
+    }
+}
+
+impl OriginDimensions for Panel {
+    fn size(&self) -> Size {
+        Size::new(160, 80)
+    }
+}
+
+impl DrawTarget for Panel {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I: IntoIterator<Item = Pixel<BinaryColor>>>(&mut self, pixels: I) -> Result<(), Infallible> {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.x < 160 && point.y >= 0 && point.y < 80 {
+                self.0[point.y as usize * 160 + point.x as usize] = color;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn draws_scaled_pixels_at_the_origin() {
+    let mut display = Display::new();
+    display.draw(1, 0, &[0x80]);
+    display.present();
+    let renderer = Renderer { scale: 2, origin: Point::new(10, 5), ..Renderer::new(BinaryColor::On, BinaryColor::Off) };
+
+    let mut panel = Panel::new();
+    panel.0[0] = BinaryColor::On;
+    renderer.draw(&display, &mut panel).unwrap();
+
+    assert_eq!(panel.at(0, 0), BinaryColor::On, "outside the area is left alone");
+    assert_eq!(panel.at(10, 5), BinaryColor::Off);
+    for &(x, y) in &[(12, 5), (13, 5), (12, 6), (13, 6)] {
+        assert_eq!(panel.at(x, y), BinaryColor::On);
+    }
+    assert_eq!(panel.at(14, 5), BinaryColor::Off);
+    assert_eq!(renderer.area().size, Size::new(128, 64));
+}
+
+#[test]
+fn hires_covers_the_same_area() {
+    let mut display = Display::new();
+    display.set_hires(true);
+    display.draw(127, 63, &[0x80]);
+    display.present();
+    let renderer = Renderer { scale: 2, ..Renderer::new(BinaryColor::On, BinaryColor::Off) };
+
+    let mut panel = Panel::new();
+    renderer.draw(&display, &mut panel).unwrap();
+
+    assert_eq!(panel.at(127, 63), BinaryColor::On);
+    assert_eq!(panel.at(126, 63), BinaryColor::Off);
+    assert_eq!(panel.at(127, 62), BinaryColor::Off);
+}
@@ -0,0 +1,53 @@
+extern crate ruchip8;
+
+use std::thread;
+
+use ruchip8::handoff::FrameShare;
+use ruchip8::{Display, HIRES_HEIGHT, HIRES_WIDTH};
+
+#[test]
+fn the_reader_gets_the_newest_frame() {
+    let mut share = FrameShare::new();
+    let (mut writer, mut reader) = share.split();
+    assert!(!reader.update());
+    assert!(!reader.frame().pixel(0, 0));
+
+    let mut display = Display::new();
+    for x in 0..3 {
+        display.clear();
+        display.draw(x, 0, &[0x80]);
+        display.present();
+        writer.publish(display.frame());
+    }
+    assert!(reader.update());
+    assert!(reader.frame().pixel(2, 0) && !reader.frame().pixel(1, 0));
+    assert!(!reader.update(), "frames already taken do not come again");
+}
+
+#[test]
+fn frames_never_tear() {
+    let mut share = FrameShare::new();
+    let (mut writer, mut reader) = share.split();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut display = Display::new();
+            for n in 0..2000 {
+                // Every pixel of frame n is set or unset alike, in either mode.
+                let pixels = vec![(n % 2) as u8; HIRES_WIDTH * HIRES_HEIGHT];
+                let hires = n % 3 == 0;
+                let len = if hires { pixels.len() } else { pixels.len() / 4 };
+                display.restore(hires, &pixels[..len]);
+                writer.publish(display.frame());
+            }
+        });
+        for _ in 0..2000 {
+            if reader.update() {
+                let frame = reader.frame();
+                assert_eq!(frame.pixels().len(), frame.width() * frame.height());
+                let first = frame.pixels()[0];
+                assert!(frame.pixels().iter().all(|&px| px == first), "torn frame");
+            }
+            thread::yield_now();
+        }
+    });
+}
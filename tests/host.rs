@@ -0,0 +1,101 @@
+extern crate ruchip8;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use ruchip8::host::{Clock, Host, Keypad, Screen};
+use ruchip8::{Chip8, Display};
+
+#[derive(Default)]
+struct Frames(usize);
+
+impl Screen for Frames {
+    fn present(&mut self, _display: &Display) {
+        self.0 += 1;
+    }
+}
+
+struct Keys(u16);
+
+impl Keypad for Keys {
+    fn keys(&mut self) -> u16 {
+        self.0
+    }
+}
+
+/// A clock the test moves by hand.
+#[derive(Clone, Default)]
+struct FakeClock(Rc<Cell<u64>>);
+
+impl FakeClock {
+    fn advance(&self, micros: u64) {
+        self.0.set(self.0.get() + micros);
+    }
+}
+
+impl Clock for FakeClock {
+    fn micros(&mut self) -> u64 {
+        self.0.get()
+    }
+}
+
+/// JP 200, forever.
+const SPIN: [u8; 2] = [0x12, 0x00];
+
+fn host(keys: u16) -> (Host<Frames, Keys, FakeClock>, FakeClock) {
+    let mut chip = Chip8::new();
+    chip.load_rom(&SPIN).unwrap();
+    let clock = FakeClock::default();
+    (Host::new(chip, Frames::default(), Keys(keys), clock.clone()), clock)
+}
+
+#[test]
+fn runs_frames_when_due() {
+    let (mut host, clock) = host(0);
+    assert_eq!(host.poll(), Ok(true));
+    assert_eq!(host.poll(), Ok(false));
+    clock.advance(10_000);
+    assert_eq!(host.poll(), Ok(false));
+    clock.advance(7_000);
+    assert_eq!(host.poll(), Ok(true));
+    assert_eq!(host.screen.0, 2);
+}
+
+#[test]
+fn does_not_catch_up_after_a_stall() {
+    let (mut host, clock) = host(0);
+    clock.advance(1_000_000);
+    assert_eq!(host.poll(), Ok(true));
+    assert_eq!(host.poll(), Ok(false));
+}
+
+#[test]
+fn applies_the_keypad() {
+    let (mut host, _clock) = host(0b1000_0000_0000_0010);
+    host.frame().unwrap();
+    assert!(host.chip.is_key_down(1));
+    assert!(host.chip.is_key_down(15));
+    assert!(!host.chip.is_key_down(0));
+}
+
+#[test]
+fn presents_one_frame_in_frame_skip() {
+    let (mut host, _clock) = host(0);
+    host.frame_skip = 3;
+    for _ in 0..7 {
+        host.frame().unwrap();
+    }
+    assert_eq!(host.screen.0, 2);
+}
+
+#[test]
+fn catches_up_on_frames_skipped() {
+    let (mut host, clock) = host(0);
+    host.frame_skip = 3;
+    assert_eq!(host.poll(), Ok(true));
+    // Presenting took two frames.
+    clock.advance(40_000);
+    assert_eq!(host.poll(), Ok(true));
+    assert_eq!(host.poll(), Ok(true));
+    assert_eq!(host.poll(), Ok(false));
+}
@@ -0,0 +1,59 @@
+extern crate ruchip8;
+
+mod common;
+
+use common::Harness;
+use ruchip8::{decode, decode_match, Instruction, PROGRAM_START};
+
+#[test]
+fn encode_inverts_decode() {
+    for ops in 0..=0xFFFF {
+        if let Some(instruction) = decode(ops) {
+            assert_eq!(instruction.encode(), ops, "{}", instruction);
+        }
+    }
+}
+
+#[test]
+fn table_agrees_with_the_match() {
+    for ops in 0..=0xFFFF {
+        assert_eq!(decode(ops), decode_match(ops), "{:04X}", ops);
+    }
+}
+
+#[test]
+fn decodes_fields() {
+    assert_eq!(decode(0xD12F), Some(Instruction::Draw { x: 1, y: 2, n: 0xF }));
+    assert_eq!(decode(0x3A42), Some(Instruction::SkipEqImm { x: 0xA, nn: 0x42 }));
+    assert_eq!(decode(0xB123), Some(Instruction::JumpV0 { addr: 0x123 }));
+    assert_eq!(decode(0x5121), None);
+}
+
+#[test]
+fn mnemonics() {
+    assert_eq!(Instruction::Call { addr: 0x2AB }.to_string(), "CALL 2AB");
+    assert_eq!(Instruction::Store { x: 3 }.to_string(), "LD [I], V3");
+}
+
+#[test]
+fn patterns_match_the_opcode() {
+    for ops in 0..=0xFFFF {
+        if let Some(instruction) = decode(ops) {
+            let pattern = instruction.pattern();
+            let digits = format!("{:04X}", ops);
+            let fits = pattern.chars().zip(digits.chars()).all(|(p, d)| !p.is_ascii_hexdigit() || p == d);
+            assert!(fits, "{} for {}", pattern, digits);
+        }
+    }
+    assert_eq!(Instruction::Draw { x: 1, y: 2, n: 3 }.pattern(), "DXYN");
+}
+
+#[test]
+fn execute_matches_fetching_the_opcode() {
+    let mut fetched = Harness::new().reg(1, 0x20).reg(2, 0x30);
+    let mut executed = Harness::new().reg(1, 0x20).reg(2, 0x30);
+    fetched = fetched.run(0x8124);
+    executed.chip.execute(Instruction::Add { x: 1, y: 2 }).unwrap();
+    assert_eq!(executed.chip.v(1), fetched.chip.v(1));
+    executed.assert_pc(PROGRAM_START + 2);
+}
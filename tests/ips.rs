@@ -0,0 +1,34 @@
+extern crate ruchip8;
+
+use ruchip8::ips;
+
+#[test]
+fn writes_records_over_the_rom() {
+    let patch = b"PATCH\x00\x00\x01\x00\x02\xAA\xBBEOF";
+    assert_eq!(ips::apply(&[1, 2, 3, 4], patch).unwrap(), [1, 0xAA, 0xBB, 4]);
+}
+
+#[test]
+fn grows_the_rom_with_zeros() {
+    let patch = b"PATCH\x00\x00\x05\x00\x01\xCCEOF";
+    assert_eq!(ips::apply(&[1, 2], patch).unwrap(), [1, 2, 0, 0, 0, 0xCC]);
+}
+
+#[test]
+fn fills_runs() {
+    let patch = b"PATCH\x00\x00\x00\x00\x00\x00\x03\x7FEOF";
+    assert_eq!(ips::apply(&[1, 2, 3, 4], patch).unwrap(), [0x7F, 0x7F, 0x7F, 4]);
+}
+
+#[test]
+fn truncates_after_eof() {
+    let patch = b"PATCHEOF\x00\x00\x02";
+    assert_eq!(ips::apply(&[1, 2, 3, 4], patch).unwrap(), [1, 2]);
+}
+
+#[test]
+fn rejects_broken_patches() {
+    assert!(ips::apply(&[], b"PATCE").is_err());
+    assert!(ips::apply(&[], b"PATCH\x00\x00\x01\x00\x02\xAA").is_err());
+    assert!(ips::apply(&[], b"PATCH\x00\x00\x01\x00\x01\xAA").is_err());
+}
@@ -0,0 +1,145 @@
+#![cfg(feature = "jit")]
+
+extern crate proptest;
+extern crate ruchip8;
+
+use std::sync::{Arc, Mutex};
+
+use proptest::prelude::*;
+use ruchip8::jit::Jit;
+use ruchip8::observer::EmuObserver;
+use ruchip8::{Chip8, Instruction, PROGRAM_START};
+
+fn machine(rom: &[u8], shift_vy: bool) -> Chip8 {
+    let mut chip = Chip8::new();
+    chip.set_seed(0);
+    chip.set_shift_vy(shift_vy);
+    chip.load_rom(rom).unwrap();
+    chip
+}
+
+/// Runs `cycles` instructions through the JIT and the interpreter and
+/// checks both end up in the same state.
+fn assert_agrees(rom: &[u8], shift_vy: bool, cycles: usize) {
+    let mut interpreted = machine(rom, shift_vy);
+    let mut compiled = machine(rom, shift_vy);
+    for _ in 0..cycles {
+        interpreted.execute_cycle().unwrap();
+    }
+    Jit::new().unwrap().run(&mut compiled, cycles).unwrap();
+
+    for n in 0..16 {
+        assert_eq!(compiled.v(n), interpreted.v(n), "V{:X}", n);
+    }
+    assert_eq!(compiled.i(), interpreted.i());
+    assert_eq!(compiled.pc(), interpreted.pc());
+    assert_eq!(compiled.memory(), interpreted.memory());
+}
+
+fn bytes(ops: &[u16]) -> Vec<u8> {
+    ops.iter().flat_map(|op| vec![(op >> 8) as u8, *op as u8]).collect()
+}
+
+#[test]
+fn blocks_match_the_interpreter() {
+    // Register work, VF as an operand, a skip ending a block and a jump.
+    let rom = bytes(&[
+        0x60F0, 0x6125, 0x8014, 0x8F15, 0x8107, 0x8216, 0x831E, 0x8F0E,
+        0xA123, 0xF01E, 0xF129, 0x7FFF, 0x8126, 0x3000, 0x1200,
+    ]);
+    for &shift_vy in &[false, true] {
+        assert_agrees(&rom, shift_vy, 1000);
+    }
+}
+
+#[test]
+fn stale_blocks_are_recompiled() {
+    // The loop rewrites its own LD V2, 00 into LD V2, 07.
+    let rom = [
+        0x62, 0x00, // 200: LD V2, 00
+        0x60, 0x62, // 202: LD V0, 62
+        0x61, 0x07, // 204: LD V1, 07
+        0xA2, 0x00, // 206: LD I, 200
+        0xF1, 0x55, // 208: LD [I], V1
+        0x12, 0x00, // 20A: JP 200
+    ];
+    let mut chip = machine(&rom, false);
+    let mut jit = Jit::new().unwrap();
+    jit.run(&mut chip, 6).unwrap();
+    assert_eq!(chip.v(2), 0x00);
+    jit.run(&mut chip, 4).unwrap();
+    assert_eq!(chip.v(2), 0x07);
+    assert_eq!(chip.pc(), PROGRAM_START + 8);
+    assert_eq!(jit.compiled(), 1);
+}
+
+#[test]
+fn blocks_longer_than_the_budget_are_interpreted() {
+    let rom = bytes(&[0x7001, 0x7001, 0x7001, 0x1200]);
+    let mut chip = machine(&rom, false);
+    Jit::new().unwrap().run(&mut chip, 2).unwrap();
+    assert_eq!(chip.v(0), 2);
+    assert_eq!(chip.pc(), PROGRAM_START + 4);
+}
+
+/// Writes down the address of every instruction it is told about.
+struct Recorder(Arc<Mutex<Vec<usize>>>);
+
+impl EmuObserver for Recorder {
+    fn on_instruction(&mut self, _chip: &Chip8, pc: usize, _instruction: Instruction) {
+        self.0.lock().unwrap().push(pc);
+    }
+}
+
+#[test]
+fn observers_hear_every_instruction() {
+    let rom = bytes(&[0x7001, 0x7001, 0x7001, 0x1200]);
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut chip = machine(&rom, false);
+    chip.add_observer(Box::new(Recorder(log.clone())));
+    let mut jit = Jit::new().unwrap();
+    jit.run(&mut chip, 5).unwrap();
+    assert_eq!(*log.lock().unwrap(), [0x200, 0x202, 0x204, 0x206, 0x200]);
+    assert_eq!(jit.compiled(), 0);
+}
+
+#[test]
+fn queued_keys_arrive_between_instructions() {
+    let rom = bytes(&[0x7001, 0x7001, 0x1200]);
+    let mut compiled = machine(&rom, false);
+    let mut interpreted = machine(&rom, false);
+    compiled.push_key(5, true);
+    interpreted.push_key(5, true);
+    Jit::new().unwrap().run(&mut compiled, 2).unwrap();
+    for _ in 0..2 {
+        interpreted.execute_cycle().unwrap();
+    }
+    assert_eq!(compiled.keys(), 1 << 5);
+    assert_eq!(compiled.keys(), interpreted.keys());
+    assert_eq!(compiled.v(0), 2);
+}
+
+/// Opcodes blocks are made of.
+fn register_op() -> impl Strategy<Value = u16> {
+    let alu = prop::sample::select(vec![0, 1, 2, 3, 4, 5, 6, 7, 0xE]);
+    prop_oneof![
+        (0u16..0x1000).prop_map(|nnn| 0x6000 | nnn),
+        (0u16..0x1000).prop_map(|nnn| 0x7000 | nnn),
+        (0u16..0x100, alu).prop_map(|(xy, n)| 0x8000 | xy << 4 | n),
+        (0u16..0x1000).prop_map(|nnn| 0xA000 | nnn),
+        (0u16..0x10).prop_map(|x| 0xF01E | x << 8),
+        (0u16..0x10).prop_map(|x| 0xF029 | x << 8),
+    ]
+}
+
+proptest! {
+    // Cranelift is slow to build code without optimizations.
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn random_blocks_match_the_interpreter(ops in prop::collection::vec(register_op(), 1..80), shift_vy in any::<bool>()) {
+        let mut rom = bytes(&ops);
+        rom.extend_from_slice(&[0x12, 0x00]);
+        assert_agrees(&rom, shift_vy, ops.len() * 3);
+    }
+}
@@ -0,0 +1,71 @@
+extern crate ruchip8;
+
+use std::sync::{Arc, Mutex};
+
+use ruchip8::observer::EmuObserver;
+use ruchip8::{Chip8, KeyEvent, KEY_QUEUE_SIZE};
+
+/// LD V0, 5; SKP V0; JP 202; LD V1, 1; JP 208
+const WAIT_FOR_5: [u8; 10] = [0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02, 0x61, 0x01, 0x12, 0x08];
+
+fn chip(rom: &[u8]) -> Chip8 {
+    let mut chip = Chip8::new();
+    chip.load_rom(rom).unwrap();
+    chip
+}
+
+fn run(chip: &mut Chip8, cycles: usize) {
+    for _ in 0..cycles {
+        chip.execute_cycle().unwrap();
+    }
+}
+
+#[test]
+fn taps_shorter_than_a_read_are_seen() {
+    let mut chip = chip(&WAIT_FOR_5);
+    chip.push_key(5, true);
+    chip.push_key(5, false);
+    run(&mut chip, 4);
+    assert_eq!(chip.v(1), 1);
+    run(&mut chip, 1);
+    assert!(!chip.is_key_down(5), "the release follows once the press was seen");
+}
+
+#[test]
+fn releases_wait_at_most_a_frame() {
+    let mut chip = chip(&[0x12, 0x00]);
+    chip.push_key(3, true);
+    chip.push_key(3, false);
+    chip.push_key(4, true);
+    run(&mut chip, 10);
+    assert!(chip.is_key_down(3) && !chip.is_key_down(4), "events keep their order");
+    chip.tick_timers();
+    assert_eq!(chip.keys(), 1 << 4);
+}
+
+#[test]
+fn a_full_queue_applies_the_oldest_event() {
+    let mut chip = chip(&[0x12, 0x00]);
+    for n in 0..=KEY_QUEUE_SIZE {
+        chip.push_key((n % 16) as u8, true);
+    }
+    assert!(chip.is_key_down(0));
+    assert!(!chip.is_key_down(1));
+}
+
+#[test]
+fn applied_events_reach_observers_with_their_frame() {
+    struct Keys(Arc<Mutex<Vec<KeyEvent>>>);
+    impl EmuObserver for Keys {
+        fn on_key(&mut self, _chip: &Chip8, event: KeyEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut chip = chip(&[0x12, 0x00]);
+    chip.add_observer(Box::new(Keys(log.clone())));
+    chip.tick_timers();
+    chip.push_key(0xA, true);
+    run(&mut chip, 1);
+    assert_eq!(*log.lock().unwrap(), vec![KeyEvent { key: 0xA, pressed: true, frame: 1 }]);
+}
@@ -0,0 +1,121 @@
+extern crate ruchip8;
+
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use ruchip8::observer::EmuObserver;
+use ruchip8::{Chip8, Instruction};
+
+/// Writes down every callback.
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl EmuObserver for Recorder {
+    fn on_frame(&mut self, chip: &Chip8) {
+        self.0.lock().unwrap().push(format!("frame st={}", chip.sound_timer()));
+    }
+
+    fn on_draw(&mut self, _chip: &Chip8, x: u8, y: u8, rows: u8, collision: bool) {
+        self.0.lock().unwrap().push(format!("draw {} {} {} {}", x, y, rows, collision));
+    }
+
+    fn on_sound_start(&mut self, _chip: &Chip8) {
+        self.0.lock().unwrap().push("sound start".to_owned());
+    }
+
+    fn on_sound_stop(&mut self, _chip: &Chip8) {
+        self.0.lock().unwrap().push("sound stop".to_owned());
+    }
+
+    fn on_key_wait(&mut self, _chip: &Chip8, x: u8) {
+        self.0.lock().unwrap().push(format!("key wait V{:X}", x));
+    }
+}
+
+fn recorded(rom: &[u8], cycles: usize, frames: usize) -> Vec<String> {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut chip = Chip8::new();
+    chip.add_observer(Box::new(Recorder(log.clone())));
+    chip.load_rom(rom).unwrap();
+    for _ in 0..cycles {
+        chip.execute_cycle().unwrap();
+    }
+    for _ in 0..frames {
+        chip.tick_timers();
+    }
+    let log = log.lock().unwrap();
+    log.clone()
+}
+
+#[test]
+fn tells_about_draws_and_key_waits() {
+    // LD V0, 3; LD V1, 4; DRW V0, V1, 5; DRW V0, V1, 5; LD V2, K
+    let log = recorded(&[0x60, 0x03, 0x61, 0x04, 0xD0, 0x15, 0xD0, 0x15, 0xF2, 0x0A], 6, 0);
+    assert_eq!(log, ["draw 3 4 5 false", "draw 3 4 5 true", "key wait V2"]);
+}
+
+#[test]
+fn tells_draw_coordinates_from_before_the_flag() {
+    // LD VF, 7; LD V1, 9; DRW VF, V1, 5; DRW V1, VF, 5
+    let log = recorded(&[0x6F, 0x07, 0x61, 0x09, 0xDF, 0x15, 0xD1, 0xF5], 4, 0);
+    assert_eq!(log, ["draw 7 9 5 false", "draw 9 0 5 false"]);
+}
+
+#[test]
+fn tells_about_frames_and_sound() {
+    // LD V0, 2; LD ST, V0
+    let log = recorded(&[0x60, 0x02, 0xF0, 0x18], 2, 3);
+    assert_eq!(log, ["sound start", "frame st=1", "sound stop", "frame st=0", "frame st=0"]);
+}
+
+/// Writes down what ran, what was read and what was written.
+struct Reads(Arc<Mutex<Vec<String>>>);
+
+impl EmuObserver for Reads {
+    fn on_instruction(&mut self, _chip: &Chip8, pc: usize, instruction: Instruction) {
+        self.0.lock().unwrap().push(format!("{:03X} {}", pc, instruction));
+    }
+
+    fn on_read(&mut self, _chip: &Chip8, addrs: Range<usize>) {
+        self.0.lock().unwrap().push(format!("read {:03X}..{:03X}", addrs.start, addrs.end));
+    }
+
+    fn on_write(&mut self, _chip: &Chip8, addrs: Range<usize>) {
+        self.0.lock().unwrap().push(format!("write {:03X}..{:03X}", addrs.start, addrs.end));
+    }
+}
+
+#[test]
+fn tells_about_instructions_and_data_reads() {
+    // LD I, 0x208; DRW V0, V0, 2; LD V1, [I]; sprite
+    let rom = [0xA2, 0x08, 0xD0, 0x02, 0xF1, 0x65, 0x00, 0x00, 0xF0, 0x90];
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut chip = Chip8::new();
+    chip.add_observer(Box::new(Reads(log.clone())));
+    chip.load_rom(&rom).unwrap();
+    for _ in 0..3 {
+        chip.execute_cycle().unwrap();
+    }
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 5);
+    assert!(log[0].starts_with("200 "));
+    assert_eq!(log[1], "read 208..20A");
+    assert!(log[2].starts_with("202 "));
+    assert_eq!(log[3], "read 208..20A");
+    assert!(log[4].starts_with("204 "));
+}
+
+#[test]
+fn tells_about_memory_writes() {
+    // LD I, 0x300; LD B, V0; LD [I], V1
+    let rom = [0xA3, 0x00, 0xF0, 0x33, 0xF1, 0x55];
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut chip = Chip8::new();
+    chip.add_observer(Box::new(Reads(log.clone())));
+    chip.load_rom(&rom).unwrap();
+    for _ in 0..3 {
+        chip.execute_cycle().unwrap();
+    }
+    let log = log.lock().unwrap();
+    let writes: Vec<&String> = log.iter().filter(|line| line.starts_with("write")).collect();
+    assert_eq!(writes, ["write 300..303", "write 300..302"]);
+}
@@ -0,0 +1,138 @@
+extern crate ruchip8;
+
+use ruchip8::octo::compile;
+use ruchip8::Chip8;
+
+fn ops(source: &str) -> Vec<u16> {
+    let program = compile(source).unwrap();
+    program.rom.chunks(2).map(|op| (op[0] as u16) << 8 | op[1] as u16).collect()
+}
+
+fn error(source: &str) -> (usize, String) {
+    let e = compile(source).unwrap_err();
+    (e.line, e.message)
+}
+
+#[test]
+fn statements() {
+    assert_eq!(ops(": main
+        clear
+        v0 := 5  v1 := v0  v2 += 1  v2 -= 1  v3 += v1  v3 =- v2
+        v4 := random 0xFF  v5 := key  v6 := delay
+        i := 0x300  i += v1  i := hex v0  sprite v0 v1 5
+        delay := v0  buzzer := v1  bcd v2  save v3  load v3  save v1 - v2
+        ;"), vec![
+        0x00E0,
+        0x6005, 0x8100, 0x7201, 0x72FF, 0x8314, 0x8327,
+        0xC4FF, 0xF50A, 0xF607,
+        0xA300, 0xF11E, 0xF029, 0xD015,
+        0xF015, 0xF118, 0xF233, 0xF355, 0xF365, 0x5122,
+        0x00EE,
+    ]);
+}
+
+#[test]
+fn starts_with_a_jump_to_main_unless_main_comes_first() {
+    assert_eq!(ops(": sub ; : main sub"), vec![0x1204, 0x00EE, 0x2202]);
+    assert_eq!(ops(": main jump main"), vec![0x1200]);
+    assert_eq!(error("v0 := 1").1, "the program has no 'main' label");
+}
+
+#[test]
+fn labels_can_be_used_before_they_are_defined() {
+    let program = compile(": main i := sprite draw jump main : draw ; : sprite 0xFF").unwrap();
+    assert_eq!(program.rom, [0xA2, 0x08, 0x22, 0x06, 0x12, 0x00, 0x00, 0xEE, 0xFF]);
+    assert_eq!(program.labels["sprite"], 0x208);
+}
+
+#[test]
+fn conditions_skip_the_statement_after_then() {
+    assert_eq!(ops(": main
+        if v0 == 3 then v1 := 1
+        if v0 != v2 then v1 := 1
+        if v0 key then v1 := 1
+        if v0 < 5 then v1 := 1
+        if v0 >= v2 then v1 := 1"), vec![
+        0x4003, 0x6101,
+        0x5020, 0x6101,
+        0xE0A1, 0x6101,
+        0x6F05, 0x8F07, 0x4F00, 0x6101,
+        0x8F20, 0x8F07, 0x4F01, 0x6101,
+    ]);
+}
+
+#[test]
+fn blocks_jump_over_their_branches() {
+    assert_eq!(ops(": main
+        if v0 == 1 begin
+            v1 := 1
+        else
+            v1 := 2
+        end"), vec![0x3001, 0x1208, 0x6101, 0x120A, 0x6102]);
+}
+
+#[test]
+fn loops_run_while_their_condition_holds() {
+    assert_eq!(ops(": main
+        loop
+            v0 += 1
+            while v0 != 10
+        again"), vec![0x7001, 0x400A, 0x1208, 0x1200]);
+}
+
+#[test]
+fn macros_substitute_their_arguments() {
+    assert_eq!(ops(":macro twice reg { reg += reg reg += reg }
+        : main twice v3"), vec![0x8334, 0x8334]);
+}
+
+#[test]
+fn constants_and_expressions() {
+    let program = compile(":const speed 3
+        :calc double { speed * 2 }
+        : main v0 := double v1 := { 2 * 3 + 1 }
+        :alias x v7 x := speed
+        :byte { HERE - 0x200 } -1").unwrap();
+    assert_eq!(program.rom, [0x60, 0x06, 0x61, 0x08, 0x67, 0x03, 0x06, 0xFF]);
+}
+
+#[test]
+fn unpack_and_long_pointers() {
+    assert_eq!(ops(": main :unpack 0xA data i := long data : data"), vec![0x60A2, 0x6108, 0xF000, 0x0208]);
+}
+
+#[test]
+fn each_statement_maps_to_its_line() {
+    let program = compile(": main\nv0 := 1\n\nif v0 == 1 then\n  jump main\n").unwrap();
+    assert_eq!(program.lines.iter().map(|(&addr, &line)| (addr, line)).collect::<Vec<_>>(),
+               vec![(0x200, 2), (0x202, 4), (0x204, 5)]);
+}
+
+#[test]
+fn errors_point_at_the_line() {
+    assert_eq!(error(": main\nv0 := 300"), (2, "300 does not fit in a byte".to_owned()));
+    assert_eq!(error(": main\n\nv0 += 1\ntypo"), (4, "'typo' is not defined".to_owned()));
+    assert_eq!(error(": main\nloop\nv0 += 1"), (2, "'loop' without 'again'".to_owned()));
+    assert_eq!(error(": main\nend"), (2, "'end' without 'begin'".to_owned()));
+    assert_eq!(error(": main\n: main"), (2, "'main' is already defined".to_owned()));
+}
+
+#[test]
+fn compiled_programs_run() {
+    let program = compile(": main
+        v0 := 0
+        loop
+            v0 += 1
+            if v0 == 5 then exit
+        again").unwrap();
+    let mut chip = Chip8::new();
+    chip.load_rom(&program.rom).unwrap();
+    for _ in 0..100 {
+        if chip.is_halted() {
+            break;
+        }
+        chip.execute_cycle().unwrap();
+    }
+    assert!(chip.is_halted());
+    assert_eq!(chip.v(0), 5);
+}
@@ -0,0 +1,540 @@
+extern crate ruchip8;
+
+mod common;
+
+use common::Harness;
+use ruchip8::{Chip8, KeyWait, PROGRAM_START, XO_MEMORY_SIZE};
+
+const NEXT: usize = PROGRAM_START + 2;
+const SKIP: usize = PROGRAM_START + 4;
+
+#[test]
+fn cls_00e0_clears_screen() {
+    Harness::new()
+        .reg(0, 0).index(0)
+        .run(0xD005) // draw the '0' glyph
+        .assert_pixel(0, 0, true)
+        .run(0x00E0)
+        .assert_pixel(0, 0, false)
+        .assert_pc(SKIP);
+}
+
+#[test]
+fn call_2nnn_and_ret_00ee() {
+    Harness::new()
+        .run(0x2300)
+        .assert_pc(0x300)
+        .assert_stack(&[NEXT as u16])
+        .run(0x00EE)
+        .assert_pc(NEXT)
+        .assert_stack(&[]);
+}
+
+#[test]
+fn scd_00cn_scrolls_down() {
+    Harness::new()
+        .reg(0, 0).index(0)
+        .run(0xD001) // the top row of '0', 4 pixels
+        .run(0x00C3)
+        .assert_pixel(0, 0, false)
+        .assert_pixel(0, 3, true)
+        .assert_pixel(3, 3, true)
+        .assert_pc(PROGRAM_START + 4);
+}
+
+#[test]
+fn scu_00dn_scrolls_up() {
+    Harness::new()
+        .reg(0, 0).reg(1, 30).index(0)
+        .run(0xD011)
+        .run(0x00D2)
+        .assert_pixel(0, 30, false)
+        .assert_pixel(0, 28, true)
+        .assert_pixel(3, 28, true)
+        .run(0x00DF)
+        .assert_pixel(0, 13, true)
+        .run(0x00DF)
+        .assert_pixel(0, 0, false);
+}
+
+#[test]
+fn scr_00fb_and_scl_00fc_scroll_sideways() {
+    let h = Harness::new()
+        .reg(0, 0).index(0)
+        .run(0xD001)
+        .run(0x00FB)
+        .assert_pixel(0, 0, false)
+        .assert_pixel(4, 0, true)
+        .assert_pixel(7, 0, true)
+        .run(0x00FC)
+        .run(0x00FC)
+        .assert_pixel(0, 0, false)
+        .assert_pixel(3, 0, false);
+    assert!(h.chip.display().screen().all(|px| px == 0), "pixels scrolled off are gone");
+}
+
+#[test]
+fn scrolling_works_in_pixels_of_the_mode() {
+    Harness::new()
+        .run(0x00FF)
+        .reg(0, 100).reg(1, 50).index(0)
+        .run(0xD011)
+        .run(0x00C4)
+        .run(0x00FC)
+        .assert_pixel(96, 54, true)
+        .assert_pixel(99, 54, true)
+        .assert_pixel(100, 54, false)
+        .assert_pixel(100, 50, false);
+}
+
+#[test]
+fn high_00ff_and_low_00fe_switch_modes() {
+    let h = Harness::new()
+        .run(0x00FF)
+        .reg(0, 100).reg(1, 50).index(0)
+        .run(0xD011)
+        .assert_pixel(100, 50, true);
+    assert_eq!((h.chip.display().width(), h.chip.display().height()), (128, 64));
+    let h = h.run(0x00FE);
+    assert_eq!((h.chip.display().width(), h.chip.display().height()), (64, 32));
+    assert!(h.chip.display().screen().all(|px| px == 0), "switching clears the screen");
+}
+
+#[test]
+fn exit_00fd_halts_until_reset() {
+    let mut h = Harness::new()
+        .reg(0, 0).index(0)
+        .run(0xD005)
+        .run(0x00FD)
+        .assert_pc(PROGRAM_START + 2);
+    assert!(h.chip.is_halted());
+    h.chip.execute_cycle().unwrap();
+    h = h.assert_pc(PROGRAM_START + 2).assert_pixel(0, 0, true);
+    h.chip.soft_reset();
+    assert!(!h.chip.is_halted());
+}
+
+#[test]
+fn soft_reset_keeps_memory_and_hard_reset_clears_it() {
+    // LD I, 0x300; LD [I], V0; JP 0x204
+    let rom = [0xA3, 0x00, 0xF0, 0x55, 0x12, 0x04];
+    let mut chip = Chip8::new();
+    chip.load_rom(&rom).unwrap();
+    chip.set_v(0, 0xAB);
+    for _ in 0..2 {
+        chip.execute_cycle().unwrap();
+    }
+    chip.soft_reset();
+    assert_eq!((chip.pc(), chip.v(0)), (PROGRAM_START, 0));
+    assert_eq!(chip.memory()[0x300], 0xAB, "memory as the program left it");
+
+    chip.hard_reset(&rom).unwrap();
+    assert_eq!(chip.memory()[0x300], 0);
+    assert_eq!(&chip.memory()[PROGRAM_START..PROGRAM_START + rom.len()], &rom[..]);
+    assert_eq!(&chip.memory()[..5], &[0xF0, 0x90, 0x90, 0x90, 0xF0], "the font is back");
+}
+
+#[test]
+fn jump_1nnn() {
+    Harness::new().run(0x1ABC).assert_pc(0xABC);
+}
+
+#[test]
+fn se_3xnn() {
+    Harness::new().reg(1, 0x42).run(0x3142).assert_pc(SKIP);
+    Harness::new().reg(1, 0x42).run(0x3143).assert_pc(NEXT);
+}
+
+#[test]
+fn sne_4xnn() {
+    Harness::new().reg(1, 0x42).run(0x4142).assert_pc(NEXT);
+    Harness::new().reg(1, 0x42).run(0x4143).assert_pc(SKIP);
+}
+
+#[test]
+fn se_5xy0() {
+    Harness::new().reg(1, 7).reg(2, 7).run(0x5120).assert_pc(SKIP);
+    Harness::new().reg(1, 7).reg(2, 8).run(0x5120).assert_pc(NEXT);
+}
+
+#[test]
+fn ld_5xy2_stores_a_register_range() {
+    Harness::new()
+        .reg(2, 0x22).reg(3, 0x33).reg(4, 0x44)
+        .index(0x300)
+        .run(0x5242)
+        .assert_mem(0x300, &[0x22, 0x33, 0x44, 0x00])
+        .assert_index(0x300)
+        .run(0x5422)
+        .assert_mem(0x300, &[0x44, 0x33, 0x22, 0x00])
+        .assert_pc(PROGRAM_START + 4);
+}
+
+#[test]
+fn ld_5xy3_loads_a_register_range() {
+    Harness::new()
+        .mem(0x300, &[0x11, 0x22, 0x33])
+        .index(0x300)
+        .run(0x5683)
+        .assert_reg(5, 0).assert_reg(6, 0x11).assert_reg(7, 0x22).assert_reg(8, 0x33).assert_reg(9, 0)
+        .assert_index(0x300)
+        .run(0x5A83)
+        .assert_reg(0xA, 0x11).assert_reg(9, 0x22).assert_reg(8, 0x33)
+        .run(0x5113)
+        .assert_reg(1, 0x11);
+}
+
+#[test]
+fn ld_6xnn() {
+    Harness::new().run(0x6A55).assert_reg(0xA, 0x55).assert_pc(NEXT);
+}
+
+#[test]
+fn add_7xnn_wraps_without_touching_flag() {
+    Harness::new().reg(0, 0xFF).reg(0xF, 0x7).run(0x7002)
+        .assert_reg(0, 0x01)
+        .assert_flag(0x7)
+        .assert_pc(NEXT);
+}
+
+#[test]
+fn ld_8xy0() {
+    Harness::new().reg(2, 0x33).run(0x8120).assert_reg(1, 0x33);
+}
+
+#[test]
+fn or_and_xor_8xy1_8xy2_8xy3() {
+    Harness::new().reg(0, 0b1100).reg(1, 0b1010).run(0x8011).assert_reg(0, 0b1110);
+    Harness::new().reg(0, 0b1100).reg(1, 0b1010).run(0x8012).assert_reg(0, 0b1000);
+    Harness::new().reg(0, 0b1100).reg(1, 0b1010).run(0x8013).assert_reg(0, 0b0110);
+}
+
+#[test]
+fn add_8xy4_sets_carry() {
+    Harness::new().reg(0, 0xFF).reg(1, 0x01).run(0x8014).assert_reg(0, 0x00).assert_flag(1);
+    Harness::new().reg(0, 0xFE).reg(1, 0x01).run(0x8014).assert_reg(0, 0xFF).assert_flag(0);
+}
+
+#[test]
+fn sub_8xy5_sets_not_borrow() {
+    Harness::new().reg(0, 5).reg(1, 3).run(0x8015).assert_reg(0, 2).assert_flag(1);
+    Harness::new().reg(0, 3).reg(1, 5).run(0x8015).assert_reg(0, 0xFE).assert_flag(0);
+    // Equal operands do not borrow.
+    Harness::new().reg(0, 9).reg(1, 9).run(0x8015).assert_reg(0, 0).assert_flag(1);
+    // Operands above 0x7F used to overflow a signed cast.
+    Harness::new().reg(0, 0xC8).reg(1, 0x64).run(0x8015).assert_reg(0, 0x64).assert_flag(1);
+    Harness::new().reg(0, 0x10).reg(1, 0xF0).run(0x8015).assert_reg(0, 0x20).assert_flag(0);
+}
+
+#[test]
+fn subn_8xy7_sets_not_borrow() {
+    Harness::new().reg(0, 3).reg(1, 5).run(0x8017).assert_reg(0, 2).assert_flag(1);
+    Harness::new().reg(0, 5).reg(1, 3).run(0x8017).assert_reg(0, 0xFE).assert_flag(0);
+    Harness::new().reg(0, 0x64).reg(1, 0xC8).run(0x8017).assert_reg(0, 0x64).assert_flag(1);
+}
+
+#[test]
+fn shr_8xy6() {
+    Harness::new().reg(0, 0b0000_0101).run(0x8016).assert_reg(0, 0b10).assert_flag(1);
+    Harness::new().reg(0, 0b0000_0100).run(0x8016).assert_reg(0, 0b10).assert_flag(0);
+}
+
+#[test]
+fn shl_8xye() {
+    Harness::new().reg(0, 0b1000_0001).run(0x801E).assert_reg(0, 0b10).assert_flag(1);
+    Harness::new().reg(0, 0b0100_0001).run(0x801E).assert_reg(0, 0b1000_0010).assert_flag(0);
+}
+
+#[test]
+fn shifts_honour_shift_vy() {
+    let mut h = Harness::new().reg(0, 0).reg(1, 0b11);
+    h.chip.set_shift_vy(true);
+    h.run(0x8016).assert_reg(0, 0b1).assert_flag(1);
+}
+
+#[test]
+fn sne_9xy0() {
+    Harness::new().reg(1, 7).reg(2, 8).run(0x9120).assert_pc(SKIP);
+    Harness::new().reg(1, 7).reg(2, 7).run(0x9120).assert_pc(NEXT);
+}
+
+#[test]
+fn ld_annn() {
+    Harness::new().run(0xA123).assert_index(0x123).assert_pc(NEXT);
+}
+
+#[test]
+fn ld_f000_nnnn_reaches_all_of_xo_memory() {
+    let h = Harness { chip: Chip8::builder().memory_size(XO_MEMORY_SIZE).build().unwrap() }
+        .mem(0xFFFE, &[0xAB])
+        .mem(PROGRAM_START + 2, &[0xFF, 0xFE])
+        .run(0xF000)
+        .assert_index(0xFFFE)
+        .assert_pc(PROGRAM_START + 4)
+        .run(0xF065)
+        .assert_reg(0, 0xAB);
+    assert_eq!(h.chip.memory().len(), XO_MEMORY_SIZE);
+}
+
+#[test]
+fn skips_step_over_f000_nnnn_whole() {
+    let long = [0xF0, 0x00, 0x12, 0x34];
+    Harness::new().mem(PROGRAM_START + 2, &long).run(0x3000).assert_pc(PROGRAM_START + 6);
+    Harness::new().mem(PROGRAM_START + 2, &long).run(0x4000).assert_pc(PROGRAM_START + 2);
+    Harness::new().mem(PROGRAM_START + 2, &long).key(0, true).run(0xE09E).assert_pc(PROGRAM_START + 6);
+}
+
+#[test]
+fn jump_bnnn_adds_v0() {
+    Harness::new().reg(0, 0x10).run(0xB300).assert_pc(0x310);
+}
+
+#[test]
+fn rnd_cxnn_is_masked() {
+    for _ in 0..32 {
+        let h = Harness::new().run(0xC00F);
+        assert_eq!(h.chip.v(0) & 0xF0, 0);
+    }
+    Harness::new().reg(0, 0xAA).run(0xC000).assert_reg(0, 0).assert_pc(NEXT);
+}
+
+#[test]
+fn rnd_cxnn_is_reproducible_with_a_seed() {
+    let draw = |seed| {
+        let mut h = Harness::new();
+        h.chip.set_seed(seed);
+        let h = h.run_all(&[0xC0FF, 0xC1FF, 0xC2FF]);
+        (h.chip.v(0), h.chip.v(1), h.chip.v(2))
+    };
+    assert_eq!(draw(42), draw(42));
+    assert_ne!(draw(42), draw(43));
+}
+
+#[test]
+fn drw_dxyn_xors_and_reports_collision() {
+    Harness::new()
+        .reg(0, 2).reg(1, 3)
+        .mem(0x300, &[0b1000_0001])
+        .index(0x300)
+        .run(0xD011)
+        .assert_pixel(2, 3, true)
+        .assert_pixel(9, 3, true)
+        .assert_pixel(3, 3, false)
+        .assert_flag(0)
+        .run(0xD011)
+        .assert_pixel(2, 3, false)
+        .assert_flag(1);
+}
+
+#[test]
+fn drw_dxyn_wraps_origin_and_clips_sprite() {
+    Harness::new()
+        .reg(0, 64 + 60).reg(1, 32 + 31)
+        .mem(0x300, &[0xFF, 0xFF])
+        .index(0x300)
+        .run(0xD012)
+        .assert_pixel(60, 31, true)
+        .assert_pixel(63, 31, true)
+        .assert_pixel(0, 31, false)
+        .assert_pixel(60, 0, false);
+}
+
+#[test]
+fn draws_are_presented_at_the_timer_tick() {
+    let mut h = Harness::new()
+        .reg(0, 0).index(0)
+        .run(0xD001);
+    assert!(!h.chip.display().frame().pixel(0, 0), "the frame is still the old one");
+    assert!(!h.chip.take_frame_ready());
+    h.chip.tick_timers();
+    assert!(h.chip.take_frame_ready());
+    assert!(!h.chip.take_frame_ready(), "taking the flag clears it");
+    assert!(h.chip.display().frame().pixel(0, 0));
+
+    let h = h.run(0x00E0).run(0x00FF);
+    let frame = h.chip.display().frame();
+    assert!(frame.pixel(0, 0) && frame.width() == 64, "the frame keeps its mode until the next tick");
+}
+
+#[test]
+fn drw_dxy0_draws_16x16_in_hires() {
+    let mut sprite = [0; 32];
+    sprite[0] = 0x80;
+    sprite[1] = 0x01;
+    sprite[31] = 0x01;
+    Harness::new()
+        .run(0x00FF)
+        .reg(0, 120).reg(1, 60)
+        .mem(0x300, &sprite)
+        .index(0x300)
+        .run(0xD010)
+        .assert_pixel(120, 60, true)
+        .assert_pixel(127, 60, false)
+        .assert_pixel(121, 60, false)
+        .assert_flag(0)
+        .reg(0, 112).reg(1, 48)
+        .run(0xD010)
+        .assert_pixel(127, 48, true)
+        .assert_pixel(127, 63, true)
+        .assert_flag(0)
+        .run(0xD010)
+        .assert_pixel(127, 63, false)
+        .assert_pixel(120, 60, true)
+        .assert_flag(1);
+}
+
+#[test]
+fn skp_ex9e() {
+    Harness::new().reg(0, 0xA).key(0xA, true).run(0xE09E).assert_pc(SKIP);
+    Harness::new().reg(0, 0xA).run(0xE09E).assert_pc(NEXT);
+}
+
+#[test]
+fn sknp_exa1() {
+    Harness::new().reg(0, 0xA).key(0xA, true).run(0xE0A1).assert_pc(NEXT);
+    Harness::new().reg(0, 0xA).run(0xE0A1).assert_pc(SKIP);
+}
+
+#[test]
+fn key_tests_remember_the_key() {
+    let h = Harness::new();
+    assert_eq!(h.chip.last_polled_key(), None);
+    let h = h.reg(0, 0x1C).run(0xE0A1);
+    assert_eq!(h.chip.last_polled_key(), Some(0xC));
+}
+
+#[test]
+fn ld_fx07_reads_delay_timer() {
+    let mut h = Harness::new();
+    h.chip.set_delay_timer(0x3C);
+    h.run(0xF207).assert_reg(2, 0x3C);
+}
+
+#[test]
+fn ld_fx0a_waits_for_key() {
+    let mut h = Harness::new().run(0xF30A);
+    assert!(h.chip.is_waiting_for_key());
+    assert_eq!(h.chip.key_wait_register(), Some(3));
+    h.chip.execute_cycle().unwrap();
+    h = h.assert_pc(PROGRAM_START).key(0x7, true);
+    assert!(!h.chip.is_waiting_for_key());
+    assert_eq!(h.chip.key_wait_register(), None);
+    h.assert_reg(3, 0x7).assert_pc(NEXT);
+}
+
+#[test]
+fn ld_fx0a_can_wait_for_the_release() {
+    let mut h = Harness::new().key(0x2, true);
+    h.chip.set_key_wait(KeyWait::Release);
+    h = h.run(0xF30A).key(0x2, false).key(0x7, true).key(0x9, true).key(0x9, false);
+    assert!(h.chip.is_waiting_for_key(), "only keys pressed during the wait count, the first of them");
+    h = h.key(0x7, false);
+    assert!(!h.chip.is_waiting_for_key());
+    h.assert_reg(3, 0x7).assert_pc(NEXT);
+}
+
+#[test]
+fn ld_fx15_and_fx18_set_timers() {
+    let h = Harness::new().reg(4, 0x20).run_all(&[0xF415, 0xF418]);
+    assert_eq!(h.chip.delay_timer(), 0x20);
+    assert_eq!(h.chip.sound_timer(), 0x20);
+}
+
+#[test]
+fn add_fx1e() {
+    Harness::new().index(0x100).reg(5, 0x10).run(0xF51E).assert_index(0x110);
+}
+
+#[test]
+fn ld_fx29_points_at_font_glyph() {
+    let h = Harness::new().reg(0, 0xA).run(0xF029).assert_index(50);
+    h.assert_mem(50, &[0xF0, 0x90, 0xF0, 0x90, 0x90]);
+}
+
+#[test]
+fn ld_fx30_points_at_big_font_glyph() {
+    let h = Harness::new().reg(3, 0x1F).run(0xF330);
+    let i = h.chip.i();
+    assert!(i + 10 <= PROGRAM_START);
+    h.assert_mem(i, &[0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0]);
+}
+
+#[test]
+fn ld_fx30_sprites_draw_10_rows() {
+    Harness::new()
+        .run(0xF030)
+        .run(0xD019)
+        .assert_pixel(2, 0, true)
+        .assert_pixel(2, 8, true)
+        .assert_pixel(2, 9, false)
+        .run(0xF030)
+        .run(0xD01A)
+        .assert_flag(1)
+        .assert_pixel(2, 9, true);
+}
+
+#[test]
+fn bcd_fx33() {
+    Harness::new().reg(0, 254).index(0x300).run(0xF033).assert_mem(0x300, &[2, 5, 4]);
+    Harness::new().reg(0, 7).index(0x300).run(0xF033).assert_mem(0x300, &[0, 0, 7]);
+}
+
+#[test]
+fn ld_fx55_stores_registers() {
+    Harness::new()
+        .reg(0, 1).reg(1, 2).reg(2, 3).reg(3, 4)
+        .index(0x300)
+        .run(0xF255)
+        .assert_mem(0x300, &[1, 2, 3, 0])
+        .assert_index(0x303);
+}
+
+#[test]
+fn ld_fx65_loads_registers() {
+    Harness::new()
+        .mem(0x300, &[9, 8, 7, 6])
+        .index(0x300)
+        .run(0xF265)
+        .assert_reg(0, 9).assert_reg(1, 8).assert_reg(2, 7).assert_reg(3, 0)
+        .assert_index(0x303);
+}
+
+#[test]
+fn audio_f002_loads_the_pattern_and_fx3a_sets_pitch() {
+    let pattern: Vec<u8> = (0..16).collect();
+    let h = Harness::new()
+        .mem(0x300, &pattern)
+        .index(0x300)
+        .reg(2, 112);
+    assert_eq!(h.chip.audio_pattern(), None);
+    assert_eq!(h.chip.pitch(), 64);
+    let mut h = h.run(0xF002).run(0xF23A).assert_index(0x300);
+    assert_eq!(&h.chip.audio_pattern().unwrap()[..], &pattern[..]);
+    assert_eq!(h.chip.pitch(), 112);
+    h.chip.soft_reset();
+    assert_eq!((h.chip.audio_pattern(), h.chip.pitch()), (None, 64));
+}
+
+#[test]
+fn ld_fx75_and_fx85_keep_registers_in_rpl_flags() {
+    let mut h = Harness::new()
+        .reg(0, 1).reg(1, 2).reg(2, 3)
+        .run(0xF175);
+    assert_eq!(&h.chip.rpl_flags()[..3], &[1, 2, 0]);
+    h.chip.soft_reset();
+    h.run(0xF285)
+        .assert_reg(0, 1)
+        .assert_reg(1, 2)
+        .assert_reg(2, 0)
+        .assert_pc(NEXT);
+}
+
+#[test]
+fn timers_count_down_to_zero() {
+    let mut h = Harness::new().reg(0, 2).run_all(&[0xF015, 0xF018]);
+    for _ in 0..3 {
+        h.chip.tick_timers();
+    }
+    assert_eq!(h.chip.delay_timer(), 0);
+    assert_eq!(h.chip.sound_timer(), 0);
+}
@@ -0,0 +1,34 @@
+extern crate ruchip8;
+
+use ruchip8::palette::{self, Palette, PALETTES};
+
+#[test]
+fn built_in_palettes_are_easy_to_see() {
+    for &(name, palette) in PALETTES.iter() {
+        assert!(palette.min_contrast() >= 4.5, "{} has a contrast ratio of {}", name, palette.min_contrast());
+    }
+}
+
+#[test]
+fn contrast_ratio_goes_from_1_to_21() {
+    assert!((palette::contrast_ratio([0, 0, 0], [0xFF, 0xFF, 0xFF]) - 21.0).abs() < 0.01);
+    assert!((palette::contrast_ratio([0x12, 0x34, 0x56], [0x12, 0x34, 0x56]) - 1.0).abs() < 0.01);
+}
+
+#[test]
+fn parses_names_and_colors() {
+    assert_eq!(Palette::parse("deuteranopia"), Some(Palette::DEUTERANOPIA));
+    let palette = Palette::parse("#000000, #ff0000, 00ff00, #0000FF").unwrap();
+    assert_eq!(palette.colors, [[0, 0, 0], [0xFF, 0, 0], [0, 0xFF, 0], [0, 0, 0xFF]]);
+    assert_eq!(palette.color(5), [0xFF, 0, 0]);
+    assert_eq!(Palette::parse("#000000,#ffffff,#ffffff"), None);
+    assert_eq!(Palette::parse("#00000g,#ffffff"), None);
+    assert_eq!(Palette::parse("sepia"), None);
+}
+
+#[test]
+fn hex_round_trips() {
+    let palette = Palette::HIGH_CONTRAST;
+    let text: Vec<String> = palette.colors.iter().map(|&color| palette::hex(color)).collect();
+    assert_eq!(Palette::parse(&text.join(",")), Some(palette));
+}
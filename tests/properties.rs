@@ -0,0 +1,89 @@
+extern crate proptest;
+extern crate ruchip8;
+
+mod common;
+
+use common::Harness;
+use proptest::prelude::*;
+use ruchip8::Display;
+
+proptest! {
+    #[test]
+    fn add_8xy4_carries_iff_sum_exceeds_255(vx in any::<u8>(), vy in any::<u8>()) {
+        let sum = vx as u16 + vy as u16;
+        let h = Harness::new().reg(0, vx).reg(1, vy).run(0x8014);
+        prop_assert_eq!(h.chip.v(0), sum as u8);
+        prop_assert_eq!(h.chip.v(0xF), (sum > 0xFF) as u8);
+    }
+
+    #[test]
+    fn sub_8xy5_flag_is_not_borrow(vx in any::<u8>(), vy in any::<u8>()) {
+        let h = Harness::new().reg(0, vx).reg(1, vy).run(0x8015);
+        prop_assert_eq!(h.chip.v(0), vx.wrapping_sub(vy));
+        prop_assert_eq!(h.chip.v(0xF), (vx >= vy) as u8);
+    }
+
+    #[test]
+    fn subn_8xy7_flag_is_not_borrow(vx in any::<u8>(), vy in any::<u8>()) {
+        let h = Harness::new().reg(0, vx).reg(1, vy).run(0x8017);
+        prop_assert_eq!(h.chip.v(0), vy.wrapping_sub(vx));
+        prop_assert_eq!(h.chip.v(0xF), (vy >= vx) as u8);
+    }
+
+    #[test]
+    fn sub_and_subn_flags_agree(vx in any::<u8>(), vy in any::<u8>()) {
+        // Both directions borrow at once only when the operands differ.
+        let sub = Harness::new().reg(0, vx).reg(1, vy).run(0x8015).chip.v(0xF);
+        let subn = Harness::new().reg(0, vx).reg(1, vy).run(0x8017).chip.v(0xF);
+        prop_assert_eq!(sub == 1 && subn == 1, vx == vy);
+        prop_assert!(sub == 1 || subn == 1);
+    }
+
+    #[test]
+    fn add_then_sub_round_trips(vx in any::<u8>(), vy in any::<u8>()) {
+        let h = Harness::new().reg(0, vx).reg(1, vy).run_all(&[0x8014, 0x8015]);
+        prop_assert_eq!(h.chip.v(0), vx);
+    }
+
+    #[test]
+    fn shifts_move_the_dropped_bit_into_vf(vx in any::<u8>()) {
+        let shr = Harness::new().reg(0, vx).run(0x8016);
+        prop_assert_eq!(shr.chip.v(0), vx >> 1);
+        prop_assert_eq!(shr.chip.v(0xF), vx & 1);
+
+        let shl = Harness::new().reg(0, vx).run(0x801E);
+        prop_assert_eq!(shl.chip.v(0), vx << 1);
+        prop_assert_eq!(shl.chip.v(0xF), vx >> 7);
+    }
+
+    #[test]
+    fn bcd_fx33_digits_recompose(vx in any::<u8>()) {
+        let h = Harness::new().reg(0, vx).index(0x300).run(0xF033);
+        let d = &h.chip.memory()[0x300..0x303];
+        prop_assert_eq!(d[0] as u16 * 100 + d[1] as u16 * 10 + d[2] as u16, vx as u16);
+    }
+
+    #[test]
+    fn draws_match_pixel_by_pixel_xor(
+        hires in any::<bool>(),
+        sprites in prop::collection::vec((0..200usize, 0..100usize, prop::collection::vec(any::<u8>(), 0..16)), 1..6),
+    ) {
+        let mut display = Display::new();
+        display.set_hires(hires);
+        let (width, height) = (display.width(), display.height());
+        let mut model = vec![false; width * height];
+        for (x, y, sprite) in sprites {
+            let (x, y) = (x % width, y % height);
+            let mut collision = false;
+            for (row, byte) in sprite.iter().enumerate().filter(|&(row, _)| y + row < height) {
+                for col in (0..8).filter(|col| x + col < width && byte & 0x80 >> col != 0) {
+                    let px = &mut model[(y + row) * width + x + col];
+                    collision |= *px;
+                    *px = !*px;
+                }
+            }
+            prop_assert_eq!(display.draw(x, y, &sprite), collision);
+        }
+        prop_assert_eq!(display.screen().map(|px| px != 0).collect::<Vec<_>>(), model);
+    }
+}
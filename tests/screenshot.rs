@@ -0,0 +1,68 @@
+extern crate ruchip8;
+
+use ruchip8::palette::Palette;
+use ruchip8::{screenshot, Chip8};
+
+/// A machine showing the font's 0 in the top left corner.
+fn zero() -> Chip8 {
+    let mut chip = Chip8::new();
+    // I := 0, sprite V0 V0 5
+    chip.load_rom(&[0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04]).unwrap();
+    chip.run_frame(0).unwrap();
+    chip
+}
+
+#[test]
+fn pbm_marks_lit_pixels_with_1() {
+    let pbm = screenshot::to_pbm(zero().display(), 1);
+    let lines: Vec<&str> = pbm.lines().collect();
+    assert_eq!(lines[..2], ["P1", "64 32"]);
+    assert_eq!(lines.len(), 2 + 32);
+    assert_eq!(&lines[2][..8], "11110000");
+    assert_eq!(&lines[3][..8], "10010000");
+    assert!(lines[7].chars().all(|c| c == '0'));
+}
+
+#[test]
+fn pbm_keeps_lines_short() {
+    let pbm = screenshot::to_pbm(zero().display(), 3);
+    assert!(pbm.starts_with("P1\n192 96\n"));
+    assert!(pbm.lines().all(|line| line.len() <= 70));
+    assert_eq!(pbm.lines().skip(2).map(str::len).sum::<usize>(), 192 * 96);
+}
+
+#[test]
+fn xbm_packs_rows_lowest_bit_first() {
+    let xbm = screenshot::to_xbm(zero().display(), 1, "zero");
+    assert!(xbm.starts_with("#define zero_width 64\n#define zero_height 32\nstatic unsigned char zero_bits[] = {\n"));
+    assert!(xbm.ends_with("};\n"));
+    let bytes: Vec<&str> = xbm.split([' ', ',', '\n']).filter(|word| word.starts_with("0x")).collect();
+    assert_eq!(bytes.len(), 8 * 32);
+    assert_eq!(bytes[0], "0x0f");
+    assert_eq!(bytes[8], "0x09");
+}
+
+#[test]
+fn sixel_draws_each_band_in_both_colors() {
+    let sixel = screenshot::to_sixel(zero().display(), 1, &Palette::DEFAULT);
+    assert!(sixel.starts_with("\x1bP0;1;0q\"1;1;64;32#0;2;0;0;0#1;2;100;100;100"));
+    assert!(sixel.ends_with("-\x1b\\"));
+    // 32 rows are six bands, the last of two.
+    assert_eq!(sixel.matches('-').count(), 6);
+    // The first band has rows 0 to 4 of the 0's outer columns lit and
+    // rows 0 and 4 of the inner ones, the lowest bit on top.
+    let sixel = |bits: u8| (63 + bits) as char;
+    let top = format!("#1{}{}{}{}!60?$", sixel(31), sixel(17), sixel(17), sixel(31));
+    assert!(screenshot::to_sixel(zero().display(), 1, &Palette::DEFAULT).contains(&top));
+}
+
+#[test]
+fn braille_packs_2x4_pixels_a_character() {
+    let braille = screenshot::to_braille(zero().display());
+    let lines: Vec<&str> = braille.lines().collect();
+    assert_eq!(lines.len(), 8);
+    assert!(lines.iter().all(|line| line.chars().count() == 32));
+    // The 0 is 1111, 1001, 1001, 1001, 1111: rows 0 to 3, then row 4.
+    assert_eq!(lines[0].chars().take(3).collect::<String>(), "\u{284F}\u{28B9}\u{2800}");
+    assert_eq!(lines[1].chars().take(3).collect::<String>(), "\u{2809}\u{2809}\u{2800}");
+}
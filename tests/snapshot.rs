@@ -0,0 +1,127 @@
+extern crate ruchip8;
+
+use ruchip8::{rom_hash, Chip8, Error, Profile, Savestate, Snapshot, SAVESTATE_MAGIC, SAVESTATE_VERSION, XO_MEMORY_SIZE};
+
+/// Counts V0 up, draws a random sprite and keeps a subroutine on the stack.
+const PROGRAM: [u8; 12] = [
+    0x22, 0x04, // CALL 204
+    0x12, 0x00, // JP 200
+    0x70, 0x01, // ADD V0, 1
+    0xC1, 0xFF, // RND V1, FF
+    0xD0, 0x15, // DRW V0, V1, 5
+    0x12, 0x04, // JP 204
+];
+
+fn running() -> Chip8 {
+    let mut chip = Chip8::new();
+    chip.set_seed(7);
+    chip.load_rom(&PROGRAM).unwrap();
+    for _ in 0..20 {
+        chip.execute_cycle().unwrap();
+    }
+    chip.set_delay_timer(30);
+    chip
+}
+
+#[test]
+fn restoring_replays_the_same_run() {
+    let mut chip = running();
+    let snapshot = chip.snapshot();
+    let run = |chip: &mut Chip8| {
+        for _ in 0..40 {
+            chip.execute_cycle().unwrap();
+        }
+        chip.tick_timers();
+        (chip.v(0), chip.v(1), chip.delay_timer(), chip.display().frame().pixels().to_vec())
+    };
+    let first = run(&mut chip);
+
+    chip.restore(&snapshot).unwrap();
+    assert_eq!(chip.pc(), snapshot.pc);
+    assert_eq!(chip.stack(), &[0x202]);
+    assert_eq!(chip.display().screen().collect::<Vec<_>>(), snapshot.screen);
+    assert_eq!(run(&mut chip), first);
+}
+
+#[test]
+fn bytes_round_trip() {
+    let snapshot = running().snapshot();
+    assert_eq!(Snapshot::from_bytes(&snapshot.to_bytes()), Ok(snapshot));
+}
+
+#[test]
+fn malformed_bytes_are_refused() {
+    let bytes = running().snapshot().to_bytes();
+    assert_eq!(Snapshot::from_bytes(&bytes[..bytes.len() - 1]), Err(Error::InvalidSnapshot));
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert_eq!(Snapshot::from_bytes(&longer), Err(Error::InvalidSnapshot));
+}
+
+#[test]
+fn snapshots_only_fit_the_same_memory_size() {
+    let snapshot = running().snapshot();
+    let mut chip = Chip8::builder().memory_size(XO_MEMORY_SIZE).build().unwrap();
+    assert_eq!(chip.restore(&snapshot), Err(Error::InvalidSnapshot));
+    assert_eq!(chip.pc(), 0x200);
+}
+
+#[test]
+fn savestates_round_trip() {
+    let state = Savestate::of(&running(), Some(0x1234));
+    let bytes = state.to_bytes();
+    assert!(bytes.starts_with(&SAVESTATE_MAGIC));
+    assert!(state.thumbnail.as_ref().unwrap().starts_with(b"\x89PNG"));
+    assert_eq!(Savestate::from_bytes(&bytes), Ok(state));
+}
+
+#[test]
+fn version_1_states_migrate_without_a_thumbnail() {
+    let state = Savestate::of(&running(), Some(0x1234));
+    // Magic, version, memory size, quirk and hash, then the thumbnail.
+    let header = SAVESTATE_MAGIC.len() + 2 + 4 + 1 + 9;
+    let thumbnail = state.thumbnail.as_ref().unwrap().len();
+    let bytes = state.to_bytes();
+    let mut old = bytes[..header].to_vec();
+    old[SAVESTATE_MAGIC.len()..SAVESTATE_MAGIC.len() + 2].copy_from_slice(&1u16.to_le_bytes());
+    old.extend_from_slice(&bytes[header + 4 + thumbnail..]);
+
+    let migrated = Savestate::from_bytes(&old).unwrap();
+    assert_eq!(migrated.thumbnail, None);
+    assert_eq!(migrated.rom_hash, Some(0x1234));
+    assert_eq!(migrated.snapshot, state.snapshot);
+}
+
+#[test]
+fn bare_snapshots_migrate_from_version_0() {
+    let chip = running();
+    let state = Savestate::from_bytes(&chip.snapshot().to_bytes()).unwrap();
+    assert_eq!(state.profile, Profile { memory_size: 4096, shift_vy: false });
+    assert_eq!(state.rom_hash, None);
+    assert_eq!(state.snapshot, chip.snapshot());
+}
+
+#[test]
+fn newer_savestates_are_refused() {
+    let mut bytes = Savestate::of(&running(), None).to_bytes();
+    let version = SAVESTATE_VERSION + 1;
+    bytes[SAVESTATE_MAGIC.len()..SAVESTATE_MAGIC.len() + 2].copy_from_slice(&version.to_le_bytes());
+    assert_eq!(Savestate::from_bytes(&bytes), Err(Error::NewerSavestate { version }));
+}
+
+#[test]
+fn savestates_only_restore_on_the_same_profile() {
+    let state = Savestate::of(&running(), None);
+    let mut chip = Chip8::new();
+    chip.set_shift_vy(true);
+    assert_eq!(state.restore(&mut chip), Err(Error::OtherMachine { memory_size: 4096, shift_vy: false }));
+    chip.set_shift_vy(false);
+    assert_eq!(state.restore(&mut chip), Ok(()));
+}
+
+#[test]
+fn rom_hashes_are_fnv_1a() {
+    assert_eq!(rom_hash(b""), 0xCBF2_9CE4_8422_2325);
+    assert_eq!(rom_hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+    assert_ne!(rom_hash(&PROGRAM), rom_hash(&PROGRAM[..10]));
+}
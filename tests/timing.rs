@@ -0,0 +1,66 @@
+extern crate ruchip8;
+
+use ruchip8::{cycles_for, decode, Chip8, Timing, CPU_CLOCK, TIMERS_CLOCK};
+
+fn cycles(op: u16) -> u32 {
+    cycles_for(decode(op).unwrap())
+}
+
+#[test]
+fn vip_costs_grow_with_the_work_done() {
+    assert!(cycles(0xD011) < cycles(0xD01F));
+    assert!(cycles(0xF055) < cycles(0xFF55));
+    assert!(cycles(0x6000) < cycles(0x8014));
+}
+
+#[test]
+fn timings_parse() {
+    assert_eq!(Timing::parse("vip"), Some(Timing::Vip));
+    assert_eq!(Timing::parse("schip"), Some(Timing::Schip));
+    assert_eq!(Timing::parse("20"), Some(Timing::Fixed(20)));
+    assert_eq!(Timing::parse("0"), None);
+    assert_eq!(Timing::parse("fast"), None);
+    assert_eq!(Timing::default().budget(), CPU_CLOCK / TIMERS_CLOCK);
+}
+
+#[test]
+fn execute_timed_reports_the_cost() {
+    let mut chip = Chip8::new();
+    chip.load_rom(&[0x60, 0x01, 0xD0, 0x15, 0xF0, 0x0A]).unwrap();
+    assert_eq!(chip.execute_timed(Timing::Vip).unwrap(), cycles(0x6001));
+    assert_eq!(chip.execute_timed(Timing::Vip).unwrap(), cycles(0xD015));
+    assert_eq!(chip.execute_timed(Timing::Fixed(10)).unwrap(), 1);
+
+    // Waiting on FX0A keeps costing the same.
+    assert!(chip.is_waiting_for_key());
+    assert_eq!(chip.execute_timed(Timing::Vip).unwrap(), cycles(0xF00A));
+}
+
+#[test]
+fn run_frame_spends_the_budget_and_ticks_the_timers() {
+    // LD V0, 3; LD ST, V0; JP 204
+    let mut chip = Chip8::builder().timing(Timing::Fixed(10)).build().unwrap();
+    chip.load_rom(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+    let output = chip.run_frame(0).unwrap();
+    assert_eq!((output.instructions, output.cycles), (10, 10));
+    assert!(output.sound);
+    assert_eq!(chip.sound_timer(), 2);
+}
+
+#[test]
+fn run_frame_stops_early_on_key_waits_and_exit() {
+    // CLS; LD V1, K; 00FD
+    let mut chip = Chip8::new();
+    chip.set_timing(Timing::Vip);
+    chip.load_rom(&[0x00, 0xE0, 0xF1, 0x0A, 0x00, 0xFD]).unwrap();
+    let output = chip.run_frame(0).unwrap();
+    assert!(output.waiting_for_key);
+    assert!(output.frame_ready);
+    assert_eq!(output.instructions, 2);
+    assert_eq!(output.cycles, cycles(0x00E0) + cycles(0xF10A));
+
+    let output = chip.run_frame(1 << 7).unwrap();
+    assert!(!output.waiting_for_key && output.halted);
+    assert_eq!(output.instructions, 1);
+    assert_eq!(chip.v(1), 7);
+}
@@ -0,0 +1,113 @@
+//! Sound through WebAudio.
+//!
+//! Every frame the machine's sound is rendered with the same `Synth` the
+//! native backends use, beep and XO-CHIP patterns alike, into a buffer
+//! scheduled to play right after the previous one. The schedule runs a
+//! latency ahead of the context clock, so a late frame does not gap.
+//!
+//! Browsers keep audio suspended until the user interacts with the page.
+//! `WebAudio` resumes it on the first click, touch or key press.
+
+use ruchip8::audio::{Latency, Synth, MIN_BEEP, RAMP};
+use ruchip8::{Chip8, TIMERS_CLOCK};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{AudioContext, AudioContextOptions, AudioContextState};
+
+/// Events that count as the gesture unlocking audio.
+const GESTURES: [&str; 3] = ["pointerdown", "touchstart", "keydown"];
+
+pub struct WebAudio {
+    context: AudioContext,
+    synth: Synth,
+    /// How far ahead of the context clock buffers are scheduled, in seconds.
+    lead: f64,
+    /// When the next buffer starts, on the context clock.
+    next: f64,
+    samples: Vec<f32>,
+    /// Kept alive for as long as the listeners may call it.
+    unlock: Closure<dyn FnMut()>,
+}
+
+impl WebAudio {
+    pub fn new(latency: Latency) -> Result<WebAudio, JsValue> {
+        let lead = latency.millis() as f64 / 1000.0;
+        let options = AudioContextOptions::new();
+        options.set_latency_hint(&JsValue::from_f64(lead));
+        let context = AudioContext::new_with_context_options(&options)?;
+        let synth = Synth::new(context.sample_rate() as u32);
+
+        let unlock = {
+            let context = context.clone();
+            Closure::wrap(Box::new(move || {
+                if context.state() == AudioContextState::Suspended {
+                    let _ = context.resume();
+                }
+            }) as Box<dyn FnMut()>)
+        };
+        let document = web_sys::window().and_then(|window| window.document()).ok_or("no document")?;
+        for gesture in GESTURES {
+            document.add_event_listener_with_callback(gesture, unlock.as_ref().unchecked_ref())?;
+        }
+        Ok(WebAudio { context, synth, lead, next: 0.0, samples: Vec::new(), unlock })
+    }
+
+    /// The beep's volume, between 0 and 1.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.synth.volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// The beep's frequency, in Hz.
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.synth.frequency = frequency;
+    }
+
+    /// Whether sounds fade in and out and last at least two frames, see
+    /// `Synth::ramp`, or start, stop and last as the sound timer says.
+    pub fn set_envelope(&mut self, on: bool) {
+        let (ramp, min_beep) = if on { (RAMP, MIN_BEEP) } else { (0.0, 0.0) };
+        self.synth.ramp = ramp;
+        self.synth.min_beep = min_beep;
+    }
+
+    /// Queues the sound of the frame `chip` just ran. Call once a frame.
+    pub fn frame(&mut self, chip: &Chip8) -> Result<(), JsValue> {
+        if !self.synth.is_playing(chip) || self.context.state() != AudioContextState::Running {
+            // Lets the next sound start at the beginning of its waveform.
+            self.synth.render(chip, &mut []);
+            return Ok(());
+        }
+        let rate = self.context.sample_rate();
+        let now = self.context.current_time();
+        if self.next < now {
+            // Silence or a tab in the background let the schedule fall behind.
+            self.next = now + self.lead;
+        } else if self.next > now + 4.0 * self.lead {
+            // Fast forward runs frames faster than they play, drop some.
+            return Ok(());
+        }
+        let len = (rate / TIMERS_CLOCK as f32).round() as usize;
+        self.samples.resize(len, 0.0);
+        self.synth.render(chip, &mut self.samples);
+
+        let buffer = self.context.create_buffer(1, len as u32, rate)?;
+        buffer.copy_to_channel(&self.samples, 0)?;
+        let source = self.context.create_buffer_source()?;
+        source.set_buffer(Some(&buffer));
+        source.connect_with_audio_node(&self.context.destination())?;
+        source.start_with_when(self.next)?;
+        self.next += len as f64 / rate as f64;
+        Ok(())
+    }
+}
+
+impl Drop for WebAudio {
+    fn drop(&mut self) {
+        if let Some(document) = web_sys::window().and_then(|window| window.document()) {
+            for gesture in GESTURES {
+                let _ = document.remove_event_listener_with_callback(gesture, self.unlock.as_ref().unchecked_ref());
+            }
+        }
+        let _ = self.context.close();
+    }
+}
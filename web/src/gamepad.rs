@@ -0,0 +1,52 @@
+//! Controllers through the Gamepad API, mapped with `ruchip8::gamepad`
+//! like the native build maps them.
+
+use ruchip8::gamepad::{Mapping, BUTTON_COUNT};
+use ruchip8::{Chip8, KEY_COUNT};
+use wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton};
+
+/// Polls every connected gamepad. Browsers only list one once a button on
+/// it was pressed.
+pub struct Gamepads {
+    pub mapping: Mapping,
+    /// The keys the gamepads held at the last poll.
+    held: u16,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Gamepads { mapping: Mapping::default(), held: 0 }
+    }
+
+    /// The buttons held on any gamepad, bit n for button n of the standard
+    /// layout.
+    fn buttons() -> u16 {
+        let pads = match web_sys::window().and_then(|window| window.navigator().get_gamepads().ok()) {
+            Some(pads) => pads,
+            None => return 0,
+        };
+        let mut buttons = 0;
+        // Empty slots are null.
+        for pad in pads.iter().filter_map(|pad| pad.dyn_into::<Gamepad>().ok()) {
+            for (n, button) in pad.buttons().iter().take(BUTTON_COUNT).enumerate() {
+                if button.dyn_into::<GamepadButton>().is_ok_and(|button| button.pressed()) {
+                    buttons |= 1 << n;
+                }
+            }
+        }
+        buttons
+    }
+
+    /// Presses and releases the keys whose buttons changed since the last
+    /// poll. Call once a frame, before running it.
+    pub fn poll(&mut self, chip: &mut Chip8) {
+        let keys = self.mapping.keys(Gamepads::buttons());
+        for key in 0..KEY_COUNT as u8 {
+            if (self.held ^ keys) & (1 << key) != 0 {
+                chip.push_key(key, keys & (1 << key) != 0);
+            }
+        }
+        self.held = keys;
+    }
+}
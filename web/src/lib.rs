@@ -0,0 +1,227 @@
+//! ruchip8 in the browser, built for `wasm32-unknown-unknown` with
+//! wasm-bindgen.
+//!
+//! From JavaScript the emulator is one class:
+//!
+//! ```js
+//! import init, { Chip8 } from "ruchip8";
+//!
+//! await init();
+//! const chip = new Chip8(new Uint8Array(await (await fetch("pong.ch8")).arrayBuffer()));
+//! document.addEventListener("keydown", e => chip.keyDown(keymap[e.key]));
+//! function loop() {
+//!     if (chip.frame()) draw(chip.framebuffer(), chip.width, chip.height);
+//!     requestAnimationFrame(loop);
+//! }
+//! ```
+//!
+//! `frame()` runs one frame, a sixtieth of a second of emulated time, so
+//! call it at 60Hz.
+
+extern crate js_sys;
+extern crate rand;
+extern crate ruchip8;
+extern crate wasm_bindgen;
+extern crate web_sys;
+
+mod audio;
+mod gamepad;
+mod storage;
+
+use js_sys::Uint8Array;
+use ruchip8::audio::Latency;
+use ruchip8::gamepad::Mapping;
+use ruchip8::palette::Palette;
+use ruchip8::{rom_hash, Savestate, KEY_COUNT};
+use wasm_bindgen::prelude::*;
+
+pub use audio::WebAudio;
+use gamepad::Gamepads;
+use storage::Storage;
+
+/// A CHIP-8 machine with a ROM loaded.
+#[wasm_bindgen(js_name = Chip8)]
+pub struct Emulator {
+    chip: ruchip8::Chip8,
+    rom: Vec<u8>,
+    audio: Option<WebAudio>,
+    gamepads: Gamepads,
+    palette: Palette,
+    storage: Storage,
+    /// The RPL flags as last stored.
+    rpl_saved: Vec<u8>,
+}
+
+fn js_error(e: ruchip8::Error) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+fn key(k: u8) -> Result<u8, JsError> {
+    if (k as usize) < KEY_COUNT {
+        Ok(k)
+    } else {
+        Err(JsError::new(&format!("no key {}, keys go from 0 to 15", k)))
+    }
+}
+
+#[wasm_bindgen(js_class = Chip8)]
+impl Emulator {
+    /// Loads `rom` into a new machine, with the RPL flags it left in this
+    /// browser. Throws if it does not fit.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Result<Emulator, JsError> {
+        let mut chip = ruchip8::Chip8::new();
+        chip.load_rom(rom).map_err(js_error)?;
+        let storage = Storage::new(rom_hash(rom));
+        if let Some(flags) = storage.rpl_flags() {
+            chip.set_rpl_flags(&flags);
+        }
+        let rpl_saved = chip.rpl_flags().to_vec();
+        Ok(Emulator { chip, rom: rom.to_vec(), audio: None, gamepads: Gamepads::new(), palette: Palette::default(), storage, rpl_saved })
+    }
+
+    /// Runs a frame and returns whether the screen changed. Throws if the
+    /// program faults. Gamepads are read first.
+    pub fn frame(&mut self) -> Result<bool, JsError> {
+        self.gamepads.poll(&mut self.chip);
+        let keys = self.chip.keys();
+        let frame_ready = self.chip.run_frame(keys).map_err(js_error)?.frame_ready;
+        if let Some(audio) = &mut self.audio {
+            audio.frame(&self.chip).map_err(|_| JsError::new("audio failed"))?;
+        }
+        if self.chip.rpl_flags() != &self.rpl_saved[..] {
+            self.rpl_saved = self.chip.rpl_flags().to_vec();
+            self.storage.save_rpl_flags(&self.rpl_saved);
+        }
+        Ok(frame_ready)
+    }
+
+    /// Presses keypad key `k`, 0 to 15. Presses and releases are queued,
+    /// so a tap between two frames still counts.
+    #[wasm_bindgen(js_name = keyDown)]
+    pub fn key_down(&mut self, k: u8) -> Result<(), JsError> {
+        self.chip.push_key(key(k)?, true);
+        Ok(())
+    }
+
+    /// Releases keypad key `k`.
+    #[wasm_bindgen(js_name = keyUp)]
+    pub fn key_up(&mut self, k: u8) -> Result<(), JsError> {
+        self.chip.push_key(key(k)?, false);
+        Ok(())
+    }
+
+    /// Maps gamepad buttons onto keys as `BUTTON=KEY` pairs, `up=2,a=5` say,
+    /// see `ruchip8::gamepad`. Buttons not named keep the default. Throws
+    /// on a button or key it does not know.
+    #[wasm_bindgen(js_name = setGamepadMapping)]
+    pub fn set_gamepad_mapping(&mut self, mapping: &str) -> Result<(), JsError> {
+        self.gamepads.mapping = Mapping::parse(mapping)
+            .ok_or_else(|| JsError::new(&format!("invalid gamepad mapping '{}'", mapping)))?;
+        Ok(())
+    }
+
+    /// The screen, `width` by `height` bytes row by row, 0 for an unlit
+    /// pixel. XO-CHIP programs light pixels up to 3, one bit per plane.
+    pub fn framebuffer(&self) -> Uint8Array {
+        Uint8Array::from(self.chip.display().frame().pixels())
+    }
+
+    /// The screen in the colors of the palette, 4 bytes of RGBA a pixel,
+    /// ready for an `ImageData`.
+    pub fn rgba(&self) -> Uint8Array {
+        let pixels = self.chip.display().frame().pixels();
+        let rgba: Vec<u8> = pixels.iter().flat_map(|&pixel| {
+            let [r, g, b] = self.palette.color(pixel);
+            [r, g, b, 0xFF]
+        }).collect();
+        Uint8Array::from(&rgba[..])
+    }
+
+    /// Picks the colors `rgba` uses, a palette name such as
+    /// `high-contrast` or `deuteranopia` or colors like `#000000,#ffffff`.
+    #[wasm_bindgen(js_name = setPalette)]
+    pub fn set_palette(&mut self, palette: &str) -> Result<(), JsError> {
+        self.palette = Palette::parse(palette).ok_or_else(|| JsError::new(&format!("invalid palette '{}'", palette)))?;
+        Ok(())
+    }
+
+    /// 64, or 128 in SUPER-CHIP high resolution.
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.chip.display().frame().width()
+    }
+
+    /// 32, or 64 in SUPER-CHIP high resolution.
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.chip.display().frame().height()
+    }
+
+    /// Whether the machine is beeping.
+    #[wasm_bindgen(getter)]
+    pub fn sound(&self) -> bool {
+        self.chip.sound_timer() > 0
+    }
+
+    /// Whether the program has exited, with SUPER-CHIP 00FD.
+    #[wasm_bindgen(getter)]
+    pub fn halted(&self) -> bool {
+        self.chip.is_halted()
+    }
+
+    /// Starts the ROM over on cleared memory.
+    pub fn reset(&mut self) {
+        self.chip.hard_reset(&self.rom).expect("the ROM fitted before");
+    }
+
+    /// Starts the program over, keeping memory as it left it.
+    #[wasm_bindgen(js_name = softReset)]
+    pub fn soft_reset(&mut self) {
+        self.chip.soft_reset();
+    }
+
+    /// Saves the machine in `slot`, 0 unless given, in the browser's
+    /// storage for this ROM. Throws when the storage is unavailable or full.
+    #[wasm_bindgen(js_name = saveState)]
+    pub fn save_state(&self, slot: Option<u32>) -> Result<(), JsValue> {
+        let state = Savestate::of(&self.chip, Some(rom_hash(&self.rom)));
+        self.storage.save_state(slot.unwrap_or(0), &state.to_bytes())
+    }
+
+    /// Restores the machine from `slot`, 0 unless given. Returns false when
+    /// nothing was saved there, throws when what is there does not decode
+    /// or comes from another machine.
+    #[wasm_bindgen(js_name = loadState)]
+    pub fn load_state(&mut self, slot: Option<u32>) -> Result<bool, JsError> {
+        let bytes = match self.storage.state(slot.unwrap_or(0)) {
+            Some(bytes) => bytes,
+            None => return Ok(false),
+        };
+        Savestate::from_bytes(&bytes).and_then(|state| state.restore(&mut self.chip)).map_err(js_error)?;
+        Ok(true)
+    }
+
+    /// Plays the machine's sound through WebAudio from now on. It starts
+    /// once the user has clicked, touched or typed on the page.
+    /// `latency_ms` is how far ahead of the speaker sound is scheduled,
+    /// 20ms when not given: less answers keys sooner, more crackles less
+    /// on a slow machine.
+    #[wasm_bindgen(js_name = enableAudio)]
+    pub fn enable_audio(&mut self, latency_ms: Option<u32>) -> Result<(), JsValue> {
+        if self.audio.is_none() {
+            let latency = latency_ms.filter(|&ms| ms > 0).map_or_else(Latency::default, Latency::Millis);
+            self.audio = Some(WebAudio::new(latency)?);
+        }
+        Ok(())
+    }
+
+    /// Turns the fades and minimum length of sounds on, the default, or
+    /// off for the bare sound of a real machine. Call after `enableAudio`.
+    #[wasm_bindgen(js_name = setAudioEnvelope)]
+    pub fn set_audio_envelope(&mut self, on: bool) {
+        if let Some(audio) = &mut self.audio {
+            audio.set_envelope(on);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Savestates and RPL flags kept in `localStorage`.
+//!
+//! Entries are named after the ROM's hash, like the files of the native
+//! build: `ruchip8/state/HASH/SLOT` holds a savestate in the usual format
+//! and `ruchip8/rpl/HASH` the flags, both as hexadecimal since storage
+//! only takes strings.
+
+use std::fmt::Write;
+
+use wasm_bindgen::JsValue;
+
+/// The page's `localStorage`, which private browsing or a sandboxed frame
+/// may deny.
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|at| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok()).collect()
+}
+
+/// What is stored for one ROM.
+pub struct Storage {
+    rom: u64,
+}
+
+impl Storage {
+    pub fn new(rom: u64) -> Self {
+        Storage { rom }
+    }
+
+    fn state_key(&self, slot: u32) -> String {
+        format!("ruchip8/state/{:016x}/{}", self.rom, slot)
+    }
+
+    fn rpl_key(&self) -> String {
+        format!("ruchip8/rpl/{:016x}", self.rom)
+    }
+
+    fn get(key: &str) -> Option<Vec<u8>> {
+        let hex = local_storage()?.get_item(key).ok().flatten()?;
+        let bytes = from_hex(&hex);
+        if bytes.is_none() {
+            warn(&format!("ignoring {}, it is not hexadecimal", key));
+        }
+        bytes
+    }
+
+    fn set(key: &str, bytes: &[u8]) -> Result<(), JsValue> {
+        let storage = local_storage().ok_or("localStorage is not available")?;
+        storage.set_item(key, &to_hex(bytes))
+    }
+
+    /// The savestate in `slot`, encoded, if there is one.
+    pub fn state(&self, slot: u32) -> Option<Vec<u8>> {
+        Storage::get(&self.state_key(slot))
+    }
+
+    /// Fails when storage is unavailable or full.
+    pub fn save_state(&self, slot: u32, state: &[u8]) -> Result<(), JsValue> {
+        Storage::set(&self.state_key(slot), state)
+    }
+
+    pub fn rpl_flags(&self) -> Option<Vec<u8>> {
+        Storage::get(&self.rpl_key())
+    }
+
+    /// Saves the flags, with a warning on the console when it fails: the
+    /// game goes on either way.
+    pub fn save_rpl_flags(&self, flags: &[u8]) {
+        if Storage::set(&self.rpl_key(), flags).is_err() {
+            warn("cannot save RPL flags");
+        }
+    }
+}
+
+fn warn(message: &str) {
+    web_sys::console::warn_1(&JsValue::from_str(message));
+}